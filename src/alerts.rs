@@ -5,6 +5,7 @@
 
 use crate::error::{HyperVError, Result};
 use crate::task::{Task, TaskStatus};
+use crate::watchdog::WatchdogBreach;
 use chrono::{DateTime, Duration, Utc};
 use std::collections::HashMap;
 use std::time::Duration as StdDuration;
@@ -18,8 +19,20 @@ const TELEGRAM_ERROR_BODY_LIMIT_CHARS: usize = 300;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AlertReason {
-    CrashLoop { crashes: usize, window_minutes: i64 },
-    RestartExhausted { attempts: u32 },
+    CrashLoop {
+        crashes: usize,
+        window_minutes: i64,
+    },
+    RestartExhausted {
+        attempts: u32,
+    },
+    Unhealthy {
+        failures: u32,
+    },
+    /// A watchdog threshold was crossed, e.g. `memory 612 MB is above the limit of 512 MB`
+    Watchdog {
+        breach: String,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -113,6 +126,60 @@ impl AlertTracker {
         })
     }
 
+    /// Record a task that was found unhealthy. The health-triggered restart counts
+    /// towards crash-loop detection like any other crash.
+    pub fn record_unhealthy(&mut self, task: &Task, now: DateTime<Utc>) -> Option<Alert> {
+        let crash_loop = self.record_crash(task, now);
+        if crash_loop.is_some() {
+            return crash_loop;
+        }
+
+        let cooldown_key = format!("unhealthy:{}", task.id);
+        if self.cooldown_active(&cooldown_key, now) {
+            return None;
+        }
+        self.sent_at_by_key.insert(cooldown_key, now);
+
+        Some(Alert {
+            task_id: task.id.clone(),
+            task_name: task.name.clone(),
+            restart_count: task.restart_count,
+            last_exit_code: task.last_exit_code,
+            reason: AlertReason::Unhealthy {
+                failures: task
+                    .health
+                    .as_ref()
+                    .map_or(0, |health| health.consecutive_failures),
+            },
+            detected_at: now,
+        })
+    }
+
+    /// Record a task that went over a watchdog threshold with the `alert` action
+    pub fn record_watchdog(
+        &mut self,
+        task: &Task,
+        breach: &WatchdogBreach,
+        now: DateTime<Utc>,
+    ) -> Option<Alert> {
+        let cooldown_key = format!("watchdog:{}", task.id);
+        if self.cooldown_active(&cooldown_key, now) {
+            return None;
+        }
+        self.sent_at_by_key.insert(cooldown_key, now);
+
+        Some(Alert {
+            task_id: task.id.clone(),
+            task_name: task.name.clone(),
+            restart_count: task.restart_count,
+            last_exit_code: task.last_exit_code,
+            reason: AlertReason::Watchdog {
+                breach: breach.kind.to_string(),
+            },
+            detected_at: now,
+        })
+    }
+
     fn cooldown_active(&self, key: &str, now: DateTime<Utc>) -> bool {
         self.sent_at_by_key
             .get(key)
@@ -237,7 +304,7 @@ fn truncate_error_body(body: &str) -> String {
 }
 
 pub fn format_alert_message(alert: &Alert) -> String {
-    let reason = match alert.reason {
+    let reason = match &alert.reason {
         AlertReason::CrashLoop {
             crashes,
             window_minutes,
@@ -245,6 +312,10 @@ pub fn format_alert_message(alert: &Alert) -> String {
         AlertReason::RestartExhausted { attempts } => {
             format!("restart attempts exhausted: {attempts} attempts")
         }
+        AlertReason::Unhealthy { failures } => {
+            format!("health check failing: {failures} consecutive failures")
+        }
+        AlertReason::Watchdog { breach } => format!("watchdog: {breach}"),
     };
     let exit_code = alert
         .last_exit_code
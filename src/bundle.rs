@@ -0,0 +1,239 @@
+//! Support bundles
+//!
+//! `hyperV bundle <task>` collects what is needed to troubleshoot a task into one
+//! tar.gz: its definition, restart and exit history, binary diagnosis, log summary,
+//! recent log lines and the processes it runs. Environment values with sensitive
+//! names and `key=value` secrets in arguments, commands and log lines are replaced
+//! by `[REDACTED]`, so the bundle can be attached to a bug report.
+
+use crate::error::{HyperVError, Result};
+use crate::logs::redact_sensitive_values;
+use crate::secrets::is_secret_ref;
+use crate::task::Task;
+use crate::usage;
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::Path;
+
+/// Files gathered for one bundle, written under a single top-level directory
+#[derive(Debug, Clone)]
+pub struct SupportBundle {
+    root: String,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl SupportBundle {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: root.to_string(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Add a file at `name`, relative to the bundle directory
+    pub fn add(&mut self, name: &str, contents: impl Into<Vec<u8>>) {
+        self.files.push((name.to_string(), contents.into()));
+    }
+
+    /// Names of the files added so far
+    pub fn file_names(&self) -> Vec<&str> {
+        self.files.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Write the bundle as a gzip-compressed tar archive
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).map_err(HyperVError::Io)?;
+        let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        let mtime = Utc::now().timestamp().max(0) as u64;
+        for (name, contents) in &self.files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            header.set_cksum();
+            archive
+                .append_data(
+                    &mut header,
+                    format!("{}/{}", self.root, name),
+                    contents.as_slice(),
+                )
+                .map_err(HyperVError::Io)?;
+        }
+        archive
+            .into_inner()
+            .and_then(|encoder| encoder.finish())
+            .map_err(HyperVError::Io)?;
+        Ok(())
+    }
+}
+
+/// The task definition as JSON, with secrets redacted; secret references are kept
+pub fn redacted_task_json(task: &Task) -> Result<String> {
+    let mut value = serde_json::to_value(task).map_err(HyperVError::Json)?;
+    if let Some(Value::Object(env)) = value.get_mut("env") {
+        for (name, value) in env.iter_mut() {
+            if task.is_secret_env(name) && !value.as_str().is_some_and(is_secret_ref) {
+                *value = Value::String("[REDACTED]".to_string());
+            }
+        }
+    }
+    let json = serde_json::to_string_pretty(&value).map_err(HyperVError::Json)?;
+    Ok(redact_sensitive_values(&json))
+}
+
+/// Current state, restart and exit history of a task
+pub fn task_history(task: &Task) -> String {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let mut out = String::new();
+    out.push_str(&format!("Status: {}\n", task.status));
+    out.push_str(&format!(
+        "PID: {}\n",
+        optional(task.pid.map(|p| p.to_string()))
+    ));
+    out.push_str(&format!("Created: {}\n", task.created_at));
+    out.push_str(&format!(
+        "Last started: {}\n",
+        optional(task.last_started.clone())
+    ));
+    out.push_str(&format!(
+        "Last exited: {}\n",
+        optional(task.last_exit_at.clone())
+    ));
+    out.push_str(&format!(
+        "Last exit code: {}\n",
+        optional(task.last_exit_code.map(|c| c.to_string()))
+    ));
+    out.push_str(&format!(
+        "Last exit signal: {}\n",
+        optional(task.exit_signal_display())
+    ));
+    if !task.limits.is_empty() {
+        out.push_str(&format!("Resource limits: {}\n", task.limits));
+    }
+    out.push_str(&format!("Restart policy: {}\n", task.restart_policy));
+    out.push_str(&format!("Restart count: {}\n", task.restart_count));
+    let backoff = &task.restart_backoff;
+    out.push_str(&format!(
+        "Restart backoff: {}s x{} up to {}s, max {} attempts, stable after {}s\n",
+        backoff.initial_delay_secs,
+        backoff.multiplier,
+        backoff.max_delay_secs,
+        backoff.max_attempts,
+        backoff.stable_after_secs
+    ));
+    if let Some(next_restart) = task.next_restart_at()
+        && task.last_exit_at.is_some()
+        && task.wants_restart()
+    {
+        out.push_str(&format!("Next restart: {}\n", next_restart.to_rfc3339()));
+    }
+    if task.suppress_restart {
+        out.push_str("Restarts suppressed: stopped by the user\n");
+    }
+    if let Some(health) = &task.health {
+        out.push_str(&format!(
+            "Health: {} (consecutive failures: {})\n",
+            health.status, health.consecutive_failures
+        ));
+        if let Some(error) = &health.last_error {
+            out.push_str(&format!(
+                "Last health check error: {}\n",
+                redact_sensitive_values(error)
+            ));
+        }
+    }
+    if !task.events.is_empty() {
+        out.push_str("Recent events:\n");
+        for event in &task.events {
+            out.push_str(&format!(
+                "  {} {}\n",
+                event.at,
+                redact_sensitive_values(&event.message)
+            ));
+        }
+    }
+    out
+}
+
+/// The processes of a running task, one per line and indented by depth: every member
+/// of its cgroup, or else its PID and all of its descendants
+pub fn process_tree(task: &Task, running: bool) -> String {
+    use sysinfo::{Pid, ProcessRefreshKind, System};
+
+    let Some(pid) = task.pid.filter(|_| running) else {
+        return "Task is not running.\n".to_string();
+    };
+    let mut system = System::new();
+    system.refresh_processes_specifics(ProcessRefreshKind::everything());
+    let members: HashSet<Pid> = usage::task_tree_pids(&system, task)
+        .into_iter()
+        .map(Pid::from_u32)
+        .filter(|pid| system.process(*pid).is_some())
+        .collect();
+    if members.is_empty() {
+        return format!("Process {} is not running.\n", pid);
+    }
+    let children_of = |parent: Option<Pid>| {
+        let mut children: Vec<Pid> = members
+            .iter()
+            .copied()
+            .filter(|pid| {
+                let ppid = system.process(*pid).and_then(|process| process.parent());
+                match parent {
+                    Some(parent) => ppid == Some(parent),
+                    // Members whose parent is outside the task start a tree of their own
+                    None => ppid.is_none_or(|ppid| !members.contains(&ppid)),
+                }
+            })
+            .collect();
+        // Popped from the end, so the lowest PID comes first
+        children.sort_by_key(|child| std::cmp::Reverse(child.as_u32()));
+        children
+    };
+
+    let mut out = format!(
+        "{:<8} {:<8} {:<10} {:>10} {:<20} COMMAND\n",
+        "PID", "PPID", "STATUS", "MEMORY", "STARTED"
+    );
+    let mut stack: Vec<(Pid, usize)> = children_of(None)
+        .into_iter()
+        .map(|root| (root, 0))
+        .collect();
+    while let Some((pid, depth)) = stack.pop() {
+        let Some(process) = system.process(pid) else {
+            continue;
+        };
+        let command = if process.cmd().is_empty() {
+            process.name().to_string()
+        } else {
+            process.cmd().join(" ")
+        };
+        let started = DateTime::<Utc>::from_timestamp(process.start_time() as i64, 0)
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "{:<8} {:<8} {:<10} {:>10} {:<20} {}{}\n",
+            pid.as_u32(),
+            process
+                .parent()
+                .map(|p| p.as_u32().to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            process.status().to_string(),
+            format!("{:.1} MB", process.memory() as f64 / (1024.0 * 1024.0)),
+            started,
+            "  ".repeat(depth),
+            redact_sensitive_values(&command)
+        ));
+
+        stack.extend(
+            children_of(Some(pid))
+                .into_iter()
+                .map(|child| (child, depth + 1)),
+        );
+    }
+    out
+}
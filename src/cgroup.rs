@@ -0,0 +1,404 @@
+//! cgroup v2 placement and resource control
+//!
+//! When a cgroup v2 hierarchy is writable, every task runs in a cgroup of its own,
+//! `<root>/<task id>`. The child joins it between fork and exec, so the cgroup holds
+//! the whole process tree, including double-forked daemons that leave the process
+//! group. Stop signals every member and finishes with `cgroup.kill`, `list` counts
+//! the members, and `memory.max`, `cpu.max` and `pids.max` are set from the task's
+//! `cgroup_limits`. Without a writable hierarchy tasks are tracked by process group.
+
+use crate::logs::{deserialize_size, format_bytes};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Period of `cpu.max` in microseconds
+const CPU_PERIOD_USEC: u64 = 100_000;
+
+/// Whether this process may move itself into a leaf cgroup, see [`leave_cgroup`]
+static MAY_LEAVE_OWN_CGROUP: AtomicBool = AtomicBool::new(false);
+
+/// cgroup settings in `daemon.yaml`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CgroupConfig {
+    /// Place tasks in cgroups when the hierarchy is writable
+    pub enabled: bool,
+    /// Directory that holds the task cgroups; defaults to `hyperv` below hyperV's own
+    /// cgroup when that is writable, else at the top of the cgroup v2 hierarchy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<PathBuf>,
+}
+
+impl Default for CgroupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            root: None,
+        }
+    }
+}
+
+impl CgroupConfig {
+    /// The directory task cgroups are created in; `None` when cgroups are disabled
+    /// or there is no cgroup v2 hierarchy. By default it is `hyperv` below the cgroup
+    /// this process was started in, so a delegated service or user cgroup is used
+    /// when there is one; otherwise it is at the top of the hierarchy.
+    pub fn task_root(&self) -> Option<PathBuf> {
+        if !self.enabled {
+            return None;
+        }
+        if let Some(root) = &self.root {
+            return Some(root.clone());
+        }
+        // Worked out once: the daemon may move into a leaf cgroup later on
+        static DEFAULT_ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
+        DEFAULT_ROOT
+            .get_or_init(|| {
+                let mount = cgroup2_mount()?;
+                let base = own_cgroup().filter(|own| is_writable(own)).unwrap_or(mount);
+                Some(base.join("hyperv"))
+            })
+            .clone()
+    }
+}
+
+/// Let this process move itself into a leaf cgroup when that is needed to enable
+/// controllers for the task cgroups. Only the daemon does this; a CLI command keeps
+/// the cgroup it was started in.
+pub fn allow_leaving_own_cgroup() {
+    MAY_LEAVE_OWN_CGROUP.store(true, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+fn is_writable(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_writable(_dir: &Path) -> bool {
+    false
+}
+
+/// Where the cgroup v2 hierarchy is mounted, from `/proc/self/mountinfo`
+fn cgroup2_mount() -> Option<PathBuf> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").ok()?;
+    mountinfo.lines().find_map(|line| {
+        let (mount, fs) = line.split_once(" - ")?;
+        if fs.split_whitespace().next()? != "cgroup2" {
+            return None;
+        }
+        mount.split_whitespace().nth(4).map(PathBuf::from)
+    })
+}
+
+/// The cgroup v2 directory of this process, from `/proc/self/cgroup`
+fn own_cgroup() -> Option<PathBuf> {
+    let cgroups = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let own = cgroups.lines().find_map(|line| line.strip_prefix("0::"))?;
+    Some(cgroup2_mount()?.join(own.trim_start_matches('/')))
+}
+
+/// Move this process out of `dir` into the leaf cgroup `dir/supervisor`, when it runs
+/// in `dir` and is allowed to (see [`allow_leaving_own_cgroup`]). A cgroup with
+/// processes of its own cannot enable controllers for its children, which is the case
+/// when the task root is below hyperV's own cgroup.
+fn leave_cgroup(dir: &Path) -> io::Result<bool> {
+    if !MAY_LEAVE_OWN_CGROUP.load(Ordering::Relaxed) {
+        return Ok(false);
+    }
+    let Some(own) = own_cgroup() else {
+        return Ok(false);
+    };
+    if fs::canonicalize(&own).ok() != fs::canonicalize(dir).ok() {
+        return Ok(false);
+    }
+    let leaf = dir.join("supervisor");
+    ensure_dir(&leaf)?;
+    fs::write(leaf.join("cgroup.procs"), std::process::id().to_string())?;
+    Ok(true)
+}
+
+/// cgroup limits of a task; unset limits are `max`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CgroupLimits {
+    /// Memory of the whole cgroup in bytes (`memory.max`); sizes like `512MB` are accepted
+    #[serde(
+        deserialize_with = "deserialize_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub memory_max: Option<u64>,
+    /// CPUs the cgroup may use, e.g. `0.5` (`cpu.max`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_max: Option<f64>,
+    /// Processes and threads in the cgroup (`pids.max`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pids_max: Option<u64>,
+}
+
+impl CgroupLimits {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.memory_max == Some(0) {
+            return Err("memory_max must be greater than 0".to_string());
+        }
+        if let Some(cpus) = self.cpu_max
+            && !(cpus.is_finite() && cpus * CPU_PERIOD_USEC as f64 >= 1000.0)
+        {
+            return Err(format!("cpu_max must be at least 0.01, got {}", cpus));
+        }
+        if self.pids_max == Some(0) {
+            return Err("pids_max must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// `(controller, file, value)` for each interface file; `None` where unset
+    fn files(&self) -> [(&'static str, &'static str, Option<String>); 3] {
+        [
+            (
+                "memory",
+                "memory.max",
+                self.memory_max.map(|bytes| bytes.to_string()),
+            ),
+            (
+                "cpu",
+                "cpu.max",
+                self.cpu_max.map(|cpus| {
+                    format!(
+                        "{} {}",
+                        (cpus * CPU_PERIOD_USEC as f64).round() as u64,
+                        CPU_PERIOD_USEC
+                    )
+                }),
+            ),
+            ("pids", "pids.max", self.pids_max.map(|n| n.to_string())),
+        ]
+    }
+}
+
+impl std::fmt::Display for CgroupLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(bytes) = self.memory_max {
+            parts.push(format!("memory {}", format_bytes(bytes)));
+        }
+        if let Some(cpus) = self.cpu_max {
+            parts.push(format!("CPUs {}", cpus));
+        }
+        if let Some(n) = self.pids_max {
+            parts.push(format!("pids {}", n));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// The cgroup of one task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// The cgroup at `path`, which may not exist (any more)
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Create the cgroup of task `task_id` below `root`, and `root` itself if needed
+    pub fn create(root: &Path, task_id: &str) -> io::Result<Self> {
+        // Only cgroup v2 directories have `cgroup.procs`; never create plain directories
+        let is_cgroup = |dir: &Path| dir.join("cgroup.procs").exists();
+        if !is_cgroup(root) {
+            if !root.parent().is_some_and(is_cgroup) {
+                return Err(io::Error::other(format!(
+                    "{} is not in a cgroup v2 hierarchy",
+                    root.display()
+                )));
+            }
+            ensure_dir(root)?;
+        }
+        let path = root.join(task_id);
+        ensure_dir(&path)?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.join("cgroup.procs").exists()
+    }
+
+    /// Set `memory.max`, `cpu.max` and `pids.max`, enabling the controllers they need
+    /// in the parent first. Unset limits are reset to `max` where the controller is
+    /// available; setting a limit whose controller is not available fails.
+    pub fn apply_limits(&self, limits: &CgroupLimits) -> io::Result<()> {
+        for (controller, file, value) in limits.files() {
+            let interface = self.path.join(file);
+            if value.is_some() && !interface.exists() {
+                self.enable_controller(controller);
+            }
+            if !interface.exists() {
+                if value.is_some() {
+                    return Err(io::Error::other(format!(
+                        "the {} controller is not available in {}",
+                        controller,
+                        self.path.parent().unwrap_or(&self.path).display()
+                    )));
+                }
+                continue;
+            }
+            let value = value.unwrap_or_else(|| match controller {
+                "cpu" => format!("max {}", CPU_PERIOD_USEC),
+                _ => "max".to_string(),
+            });
+            fs::write(&interface, &value)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", file, e)))?;
+        }
+        Ok(())
+    }
+
+    /// Best effort: turn `controller` on for the task cgroups and for their root. When
+    /// the daemon itself runs in the parent of the root, it first moves into a leaf.
+    fn enable_controller(&self, controller: &str) {
+        let Some(root) = self.path.parent() else {
+            return;
+        };
+        let line = format!("+{}", controller);
+        if let Some(parent) = root.parent() {
+            let subtree_control = parent.join("cgroup.subtree_control");
+            if fs::write(&subtree_control, &line).is_err() && leave_cgroup(parent).unwrap_or(false)
+            {
+                let _ = fs::write(&subtree_control, &line);
+            }
+        }
+        let _ = fs::write(root.join("cgroup.subtree_control"), &line);
+    }
+
+    /// PIDs of every process in this cgroup and the cgroups below it
+    pub fn members(&self) -> Vec<u32> {
+        let mut pids = Vec::new();
+        let mut dirs = vec![self.path.clone()];
+        while let Some(dir) = dirs.pop() {
+            if let Ok(procs) = fs::read_to_string(dir.join("cgroup.procs")) {
+                pids.extend(
+                    procs
+                        .lines()
+                        .filter_map(|line| line.trim().parse::<u32>().ok()),
+                );
+            }
+            if let Ok(entries) = fs::read_dir(&dir) {
+                dirs.extend(
+                    entries
+                        .flatten()
+                        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                        .map(|entry| entry.path()),
+                );
+            }
+        }
+        pids
+    }
+
+    /// Whether any process is left in the cgroup or below it
+    pub fn is_populated(&self) -> bool {
+        match fs::read_to_string(self.path.join("cgroup.events")) {
+            Ok(events) => events.lines().any(|line| line.trim() == "populated 1"),
+            Err(_) => !self.members().is_empty(),
+        }
+    }
+
+    /// Send `signal` to every member; false when there was none to signal
+    #[cfg(unix)]
+    pub fn signal(&self, signal: i32) -> bool {
+        let mut sent = false;
+        for pid in self.members() {
+            if unsafe { libc::kill(pid as i32, signal) } == 0 {
+                sent = true;
+            }
+        }
+        sent
+    }
+
+    /// Kill every member at once with `cgroup.kill`, or with `SIGKILL` one by one on
+    /// kernels older than 5.14
+    #[cfg(unix)]
+    pub fn kill(&self) -> io::Result<()> {
+        match fs::write(self.path.join("cgroup.kill"), "1") {
+            Err(e) if e.kind() == io::ErrorKind::NotFound && self.exists() => {
+                self.signal(libc::SIGKILL);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Delete the cgroup, and any cgroups below it; it must be empty
+    pub fn remove(&self) -> io::Result<()> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+        if let Ok(entries) = fs::read_dir(&self.path) {
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                    Cgroup::at(entry.path()).remove()?;
+                }
+            }
+        }
+        fs::remove_dir(&self.path)
+    }
+
+    /// Make the child of `cmd` join this cgroup before exec, so everything it starts
+    /// is a member too
+    #[cfg(unix)]
+    pub fn add_to(&self, cmd: &mut Command) -> io::Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::process::CommandExt;
+
+        let procs = std::ffi::CString::new(self.path.join("cgroup.procs").as_os_str().as_bytes())
+            .map_err(io::Error::other)?;
+        // Only async-signal-safe calls between fork and exec; "0" moves the caller
+        unsafe {
+            cmd.pre_exec(move || {
+                let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let written = libc::write(fd, b"0".as_ptr() as *const libc::c_void, 1);
+                let error = io::Error::last_os_error();
+                libc::close(fd);
+                if written != 1 {
+                    return Err(error);
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn add_to(&self, _cmd: &mut Command) -> io::Result<()> {
+        Err(io::Error::other("cgroups are only supported on Linux"))
+    }
+}
+
+fn ensure_dir(path: &Path) -> io::Result<()> {
+    match fs::create_dir(path) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        result => result,
+    }
+}
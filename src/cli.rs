@@ -1,5 +1,14 @@
-use crate::logs::LogType;
-use clap::{Parser, Subcommand};
+use crate::cgroup::CgroupLimits;
+use crate::constants::{MAX_LOG_ARCHIVES, MAX_LOG_SIZE};
+use crate::error::Result;
+use crate::forward::ForwardTarget;
+use crate::health::{HealthCheck, HealthProbe};
+use crate::limits::ResourceLimits;
+use crate::logs::{JsonFieldMap, LogSettings, LogStream, LogType, parse_size};
+use crate::task::RestartPolicy;
+use crate::watchdog::{Watchdog, WatchdogAction};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
 
 /// hyperV CLI application
 #[derive(Parser)]
@@ -11,8 +20,33 @@ pub struct Cli {
     pub command: Commands,
 }
 
+/// `hyperV secret` subcommands
+#[derive(Subcommand)]
+pub enum SecretCommands {
+    /// Encrypt and store a secret
+    Set {
+        /// Secret name (letters, digits, '_', '-' and '.')
+        name: String,
+        /// Value to store; read from stdin when omitted, keeping it out of shell history
+        value: Option<String>,
+    },
+    /// Print the value of a secret
+    Get {
+        /// Secret name
+        name: String,
+    },
+    /// List stored secrets and the tasks that use them
+    List,
+    /// Delete a secret
+    Rm {
+        /// Secret name
+        name: String,
+    },
+}
+
 /// Available CLI commands
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 pub enum Commands {
     /// Create a new task
     New {
@@ -25,18 +59,84 @@ pub enum Commands {
         /// Environment variables (format: KEY=VALUE)
         #[arg(short, long)]
         env: Vec<String>,
+        /// File of KEY=VALUE lines read on every start (repeatable; default: .env in the workdir)
+        #[arg(long = "env-file", value_name = "PATH")]
+        env_files: Vec<String>,
+        /// Mask this variable in `status` and `diagnose` even if its name does not look
+        /// secret (repeatable)
+        #[arg(long = "secret-env", value_name = "NAME")]
+        secret_env: Vec<String>,
+        /// Run as this user (name or UID); requires hyperV to run as root
+        #[arg(long, value_name = "USER")]
+        user: Option<String>,
+        /// Run with this primary group (name or GID; default: the user's group)
+        #[arg(long, value_name = "GROUP")]
+        group: Option<String>,
+        /// Supplementary group of the process (name or GID, repeatable)
+        #[arg(long = "supplementary-group", value_name = "GROUP")]
+        supplementary_groups: Vec<String>,
         /// Working directory
         #[arg(short, long)]
         workdir: Option<String>,
-        /// Auto-restart on failure
+        /// Auto-restart on failure (same as `--restart on-failure`)
         #[arg(long)]
         auto_restart: bool,
+        /// Restart policy: always, on-failure, unless-stopped or never
+        #[arg(long, value_name = "POLICY")]
+        restart: Option<RestartPolicy>,
+        /// Exit code that counts as success besides 0 (repeatable)
+        #[arg(
+            long = "success-exit-code",
+            value_name = "CODE",
+            allow_hyphen_values = true
+        )]
+        success_exit_codes: Vec<i32>,
+        /// Seconds to wait before the first restart attempt (default: 1)
+        #[arg(long, value_name = "SECS")]
+        restart_delay: Option<u64>,
+        /// Factor applied to the restart delay after each attempt (default: 2)
+        #[arg(long, value_name = "FACTOR")]
+        restart_multiplier: Option<f64>,
+        /// Upper bound for the restart delay in seconds (default: 60)
+        #[arg(long, value_name = "SECS")]
+        restart_max_delay: Option<u64>,
+        /// Give up after this many consecutive restarts (default: 5)
+        #[arg(long, value_name = "N")]
+        max_restarts: Option<u32>,
+        /// Reset the restart count once a run lasts this many seconds (default: 60)
+        #[arg(long, value_name = "SECS")]
+        stable_after: Option<u64>,
+        #[command(flatten)]
+        health: HealthArgs,
+        #[command(flatten)]
+        limits: LimitArgs,
+        #[command(flatten)]
+        watchdog: WatchdogArgs,
+        #[command(flatten)]
+        logs: LogArgs,
         /// Arguments for the binary (must be the last option)
         #[arg(short, long, num_args = 1.., allow_hyphen_values = true)]
         args: Vec<String>,
     },
     /// List all tasks
-    List,
+    List {
+        /// Only show tasks created by this compose project
+        #[arg(short, long)]
+        project: Option<String>,
+    },
+    /// Show the memory, CPU, threads, open files and uptime of every task's
+    /// process tree, refreshed until interrupted
+    Top {
+        /// Only show tasks created by this compose project
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Seconds between refreshes
+        #[arg(short, long, default_value = "2", value_name = "SECS")]
+        interval: u64,
+        /// Exit after this many refreshes
+        #[arg(short = 'n', long, value_name = "N")]
+        iterations: Option<usize>,
+    },
     /// Start a task
     Start {
         /// Task name or ID
@@ -61,11 +161,21 @@ pub enum Commands {
     Status {
         /// Task name or ID (optional, shows all if not specified)
         task: Option<String>,
+        /// Show secret environment values and arguments instead of masking them
+        #[arg(long)]
+        show_secrets: bool,
     },
     /// Show task logs
     Logs {
-        /// Task name or ID
-        task: String,
+        /// Task names or IDs; with more than one, lines are prefixed with the task name
+        #[arg(value_name = "TASK", required_unless_present_any = ["all", "project", "prune"])]
+        tasks: Vec<String>,
+        /// Show the logs of every task
+        #[arg(long, conflicts_with_all = ["tasks", "project"])]
+        all: bool,
+        /// Show the logs of every task created by this compose project
+        #[arg(long, conflicts_with = "tasks")]
+        project: Option<String>,
         /// Number of lines to show
         #[arg(short, long, default_value = "50")]
         lines: usize,
@@ -76,16 +186,57 @@ pub enum Commands {
         #[arg(short, long)]
         follow: bool,
         /// Show a compact diagnostic summary instead of raw log lines
-        #[arg(long)]
+        #[arg(long, conflicts_with_all = ["since", "until", "grep", "level", "json_field", "all", "project"])]
         summary: bool,
+        /// Only show lines received at or after this time (RFC3339, or a duration ago like 15m)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only show lines received at or before this time (RFC3339, or a duration ago like 15m)
+        #[arg(long)]
+        until: Option<String>,
+        /// Only show lines matching this regular expression
+        #[arg(long)]
+        grep: Option<String>,
+        /// Only show lines at this level or more severe (error, warn, info)
+        #[arg(long)]
+        level: Option<String>,
+        /// Only show JSON lines whose field has this value, e.g. request_id=abc or
+        /// logger=db (can be repeated; all must match)
+        #[arg(long, value_name = "KEY=VALUE")]
+        json_field: Vec<String>,
+        /// Delete rotated logs past their retention (of every task unless tasks are given)
+        #[arg(long, conflicts_with_all = ["follow", "summary", "since", "until", "grep", "level", "json_field"])]
+        prune: bool,
     },
     /// Diagnose binary file issues
     Diagnose {
         /// Task name or ID
         task: String,
+        /// Show secret environment values and arguments instead of masking them
+        #[arg(long)]
+        show_secrets: bool,
+    },
+    /// Collect a task's definition, history, diagnosis and recent logs into a
+    /// tar.gz for troubleshooting, with secrets redacted
+    Bundle {
+        /// Task name or ID
+        task: String,
+        /// Where to write the bundle (default: hyperv-bundle-<task>-<time>.tar.gz)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Number of recent lines to include from each log stream
+        #[arg(short, long, default_value = "500")]
+        lines: usize,
+    },
+    /// Manage the encrypted secret store that `secret:NAME` env values refer to
+    Secret {
+        #[command(subcommand)]
+        command: SecretCommands,
     },
     /// Run in daemon mode (monitor and auto-restart tasks)
     Daemon,
+    /// Ask the running daemon to reload task definitions from disk
+    Reload,
     /// Apply services from a compose YAML file (create/update tasks)
     Up {
         /// Path to compose YAML file
@@ -94,6 +245,15 @@ pub enum Commands {
         /// Start services after applying
         #[arg(long)]
         start: bool,
+        /// Show what would be created, updated or orphaned without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Stop and remove tasks created from this file whose service was removed
+        #[arg(long)]
+        remove_orphans: bool,
+        /// Print the file with variables and env files resolved, then exit
+        #[arg(long)]
+        print_config: bool,
     },
     /// Remove services defined in a compose YAML file
     Down {
@@ -101,4 +261,235 @@ pub enum Commands {
         #[arg(short, long, default_value = "hyperv.yaml")]
         file: String,
     },
+    /// Copy a task's output from stdin into a rotating log file (started by hyperV)
+    #[command(hide = true)]
+    CaptureLog {
+        /// Log file to write
+        path: PathBuf,
+        /// Stream recorded with every line: stdout or stderr
+        #[arg(long, default_value = "stdout")]
+        stream: LogStream,
+        /// Rotate the file once it grows past this many bytes
+        #[arg(long, default_value_t = MAX_LOG_SIZE)]
+        max_size: u64,
+        /// Rotated files to keep
+        #[arg(long, default_value_t = MAX_LOG_ARCHIVES)]
+        max_archives: usize,
+        /// Delete rotated files older than this many days
+        #[arg(long)]
+        max_age_days: Option<u64>,
+        /// Keep rotated files uncompressed
+        #[arg(long)]
+        no_compress: bool,
+    },
+}
+
+/// Health check options for `hyperV new`
+#[derive(Args, Debug, Default)]
+pub struct HealthArgs {
+    /// Health check: GET this URL and expect --health-status
+    #[arg(long, value_name = "URL", conflicts_with_all = ["health_tcp", "health_cmd"])]
+    pub health_http: Option<String>,
+    /// Health check: connect to this host:port
+    #[arg(long, value_name = "ADDR", conflicts_with = "health_cmd")]
+    pub health_tcp: Option<String>,
+    /// Health check: run this shell command and expect exit code 0
+    #[arg(long, value_name = "CMD")]
+    pub health_cmd: Option<String>,
+    /// Expected HTTP status for --health-http (default: 200)
+    #[arg(long, value_name = "STATUS", requires = "health_http")]
+    pub health_status: Option<u16>,
+    /// Seconds between health checks (default: 10)
+    #[arg(long, value_name = "SECS")]
+    pub health_interval: Option<u64>,
+    /// Seconds before a health check counts as failed (default: 5)
+    #[arg(long, value_name = "SECS")]
+    pub health_timeout: Option<u64>,
+    /// Consecutive failures before the task is unhealthy (default: 3)
+    #[arg(long, value_name = "N")]
+    pub health_retries: Option<u32>,
+    /// Seconds after start during which failures are not counted (default: 0)
+    #[arg(long, value_name = "SECS")]
+    pub health_start_period: Option<u64>,
+}
+
+impl HealthArgs {
+    /// The configured health check, if a probe was given
+    pub fn to_health_check(&self) -> Option<HealthCheck> {
+        let probe = if let Some(url) = &self.health_http {
+            HealthProbe::Http {
+                url: url.clone(),
+                expected_status: self.health_status.unwrap_or(200),
+            }
+        } else if let Some(address) = &self.health_tcp {
+            HealthProbe::Tcp {
+                address: address.clone(),
+            }
+        } else {
+            HealthProbe::Exec {
+                command: vec![
+                    "/bin/sh".to_string(),
+                    "-c".to_string(),
+                    self.health_cmd.clone()?,
+                ],
+                expected_exit_code: 0,
+            }
+        };
+
+        let mut check = HealthCheck::new(probe);
+        if let Some(interval) = self.health_interval {
+            check.interval_secs = interval;
+        }
+        if let Some(timeout) = self.health_timeout {
+            check.timeout_secs = timeout;
+        }
+        if let Some(retries) = self.health_retries {
+            check.retries = retries;
+        }
+        if let Some(start_period) = self.health_start_period {
+            check.start_period_secs = start_period;
+        }
+        Some(check)
+    }
+}
+
+/// Resource limits of `hyperV new`
+#[derive(Args, Debug, Default)]
+pub struct LimitArgs {
+    /// Most file descriptors the process may open
+    #[arg(long, value_name = "N")]
+    pub limit_open_files: Option<u64>,
+    /// Most virtual memory the process may map, e.g. 2GB
+    #[arg(long, value_name = "SIZE", value_parser = |s: &str| parse_size(s).map_err(|e| e.to_string()))]
+    pub limit_address_space: Option<u64>,
+    /// Largest core dump written, e.g. 0 to turn core dumps off
+    #[arg(long, value_name = "SIZE", value_parser = |s: &str| parse_size(s).map_err(|e| e.to_string()))]
+    pub limit_core_size: Option<u64>,
+    /// Most processes and threads the task's user may run
+    #[arg(long, value_name = "N")]
+    pub limit_processes: Option<u64>,
+    /// CPU seconds after which the process is sent SIGXCPU
+    #[arg(long, value_name = "SECS")]
+    pub limit_cpu_time: Option<u64>,
+    /// Memory of the whole process tree (cgroup memory.max), e.g. 512MB
+    #[arg(long, value_name = "SIZE", value_parser = |s: &str| parse_size(s).map_err(|e| e.to_string()))]
+    pub memory_max: Option<u64>,
+    /// CPUs the process tree may use (cgroup cpu.max), e.g. 0.5
+    #[arg(long, value_name = "CPUS")]
+    pub cpu_max: Option<f64>,
+    /// Processes and threads in the process tree (cgroup pids.max)
+    #[arg(long, value_name = "N")]
+    pub pids_max: Option<u64>,
+}
+
+impl LimitArgs {
+    pub fn to_resource_limits(&self) -> ResourceLimits {
+        ResourceLimits {
+            open_files: self.limit_open_files,
+            address_space: self.limit_address_space,
+            core_size: self.limit_core_size,
+            processes: self.limit_processes,
+            cpu_time: self.limit_cpu_time,
+        }
+    }
+
+    pub fn to_cgroup_limits(&self) -> CgroupLimits {
+        CgroupLimits {
+            memory_max: self.memory_max,
+            cpu_max: self.cpu_max,
+            pids_max: self.pids_max,
+        }
+    }
+}
+
+/// Watchdog options for `hyperV new`
+#[derive(Args, Debug, Default)]
+pub struct WatchdogArgs {
+    /// Act when the process tree uses more than this many MB of memory
+    #[arg(long, value_name = "MB")]
+    pub watchdog_memory: Option<u64>,
+    /// Act when the process tree uses more CPU than this, in percent of one core
+    #[arg(long, value_name = "PERCENT")]
+    pub watchdog_cpu: Option<f64>,
+    /// Seconds the CPU usage is averaged over (default: 60)
+    #[arg(long, value_name = "SECS")]
+    pub watchdog_cpu_window: Option<u64>,
+    /// What to do when a limit is crossed: warn, alert or restart (default: warn)
+    #[arg(long, value_name = "ACTION")]
+    pub watchdog_action: Option<WatchdogAction>,
+}
+
+impl WatchdogArgs {
+    /// The configured watchdog, if any watchdog option was given
+    pub fn to_watchdog(&self) -> Option<Watchdog> {
+        if self.watchdog_memory.is_none()
+            && self.watchdog_cpu.is_none()
+            && self.watchdog_cpu_window.is_none()
+            && self.watchdog_action.is_none()
+        {
+            return None;
+        }
+        let mut watchdog = Watchdog {
+            max_memory_mb: self.watchdog_memory,
+            max_cpu_percent: self.watchdog_cpu,
+            ..Watchdog::default()
+        };
+        if let Some(window) = self.watchdog_cpu_window {
+            watchdog.cpu_window_secs = window;
+        }
+        if let Some(action) = self.watchdog_action {
+            watchdog.action = action;
+        }
+        Some(watchdog)
+    }
+}
+
+/// Log rotation options for `hyperV new`; unset options use `daemon.yaml`
+#[derive(Args, Debug, Default)]
+pub struct LogArgs {
+    /// Rotate logs once they grow past this size, e.g. 50MB (default: 10MB)
+    #[arg(long, value_name = "SIZE", value_parser = |s: &str| parse_size(s).map_err(|e| e.to_string()))]
+    pub log_max_size: Option<u64>,
+    /// Rotated logs kept per stream (default: 5)
+    #[arg(long, value_name = "N")]
+    pub log_max_archives: Option<usize>,
+    /// Delete rotated logs older than this many days
+    #[arg(long, value_name = "DAYS")]
+    pub log_max_age_days: Option<u64>,
+    /// Keep rotated logs uncompressed
+    #[arg(long)]
+    pub log_no_compress: bool,
+    /// Where a field is found in JSON log lines, e.g. level=severity or
+    /// trace_id=span.trace (fields: timestamp, level, message, logger, trace_id,
+    /// error_type; can be repeated)
+    #[arg(long, value_name = "FIELD=PATH")]
+    pub log_json: Vec<String>,
+    /// Forward captured lines to syslog (udp://host:514, tcp://host:514,
+    /// unix:///dev/log) or as JSON lines to an http(s) URL (can be repeated)
+    #[arg(long, value_name = "TARGET")]
+    pub log_forward: Vec<ForwardTarget>,
+    /// Do not forward this task's logs, even if daemon.yaml forwards all logs
+    #[arg(long, conflicts_with = "log_forward")]
+    pub no_log_forward: bool,
+}
+
+impl LogArgs {
+    pub fn to_log_settings(&self) -> Result<LogSettings> {
+        let mut json = JsonFieldMap::default();
+        for mapping in &self.log_json {
+            json.set(mapping)?;
+        }
+        Ok(LogSettings {
+            max_size: self.log_max_size,
+            max_archives: self.log_max_archives,
+            max_age_days: self.log_max_age_days,
+            compress: self.log_no_compress.then_some(false),
+            json,
+            forward: if self.no_log_forward {
+                Some(Vec::new())
+            } else {
+                (!self.log_forward.is_empty()).then(|| self.log_forward.clone())
+            },
+        })
+    }
 }
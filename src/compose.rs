@@ -1,85 +1,883 @@
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use crate::cgroup::CgroupLimits;
+use crate::dotenv;
 use crate::error::{HyperVError, Result};
+use crate::health::{HealthCheck, HealthStatus};
+use crate::interpolation;
+use crate::limits::ResourceLimits;
+use crate::logs::LogSettings;
 use crate::manager::TaskManager;
+use crate::secrets::SecretRef;
+use crate::task::{RestartBackoff, RestartPolicy, Task, TaskOptions, TaskStatus};
+use crate::watchdog::Watchdog;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ComposeFile {
-    pub services: HashMap<String, Service>,
+    /// Project name; defaults to the name of the directory containing the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub services: BTreeMap<String, Service>,
+    /// Where the file was loaded from; recorded on the tasks it creates
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Service {
     pub binary: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub args: Vec<String>,
-    #[serde(default)]
+    /// Files of `KEY=VALUE` lines, relative to the compose file; `env` takes precedence
+    #[serde(
+        default,
+        deserialize_with = "deserialize_env_file",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub env_file: Vec<String>,
+    #[serde(
+        default,
+        serialize_with = "serialize_sorted",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
     pub env: HashMap<String, String>,
-    #[serde(default)]
+    /// Variables masked in `status` and `diagnose` besides those whose names look secret
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secret_env: Vec<String>,
+    /// User (name or UID) the service runs as; requires hyperV to run as root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Primary group (name or GID); defaults to the user's primary group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Supplementary groups (names or GIDs)
+    #[serde(default, alias = "group_add", skip_serializing_if = "Vec::is_empty")]
+    pub supplementary_groups: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub workdir: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub auto_restart: bool,
+    /// Restart policy; takes precedence over `auto_restart` when set
+    #[serde(default, alias = "restart", skip_serializing_if = "Option::is_none")]
+    pub restart_policy: Option<RestartPolicy>,
+    /// Exit codes besides 0 that count as a successful exit
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub success_exit_codes: Vec<i32>,
+    /// Restart delays and limits; omitted keys use the defaults
+    #[serde(default)]
+    pub restart_backoff: RestartBackoff,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheck>,
+    /// Memory and CPU thresholds, e.g. `max_memory_mb: 512` and `action: restart`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watchdog: Option<Watchdog>,
+    /// Resource limits of the process, e.g. `open_files: 1024` or `address_space: 2GB`
+    #[serde(default, skip_serializing_if = "ResourceLimits::is_empty")]
+    pub limits: ResourceLimits,
+    /// cgroup limits of the whole process tree, e.g. `memory_max: 512MB` or `cpu_max: 0.5`
+    #[serde(default, skip_serializing_if = "CgroupLimits::is_empty")]
+    pub cgroup: CgroupLimits,
+    /// Log rotation and retention; omitted keys use the defaults in `daemon.yaml`
+    #[serde(default, skip_serializing_if = "LogSettings::is_empty")]
+    pub logs: LogSettings,
+    /// Services that must reach a condition before this one is started
+    #[serde(
+        default,
+        deserialize_with = "deserialize_depends_on",
+        serialize_with = "serialize_depends_on",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub depends_on: BTreeMap<String, DependencyCondition>,
+}
+
+/// What a dependency must reach before its dependents are started
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyCondition {
+    /// The dependency has been started
+    #[default]
+    #[serde(alias = "service_started")]
+    Started,
+    /// The dependency's health check passed
+    #[serde(alias = "service_healthy")]
+    Healthy,
+    /// The dependency ran to completion with a success exit code
+    #[serde(alias = "service_completed_successfully")]
+    CompletedSuccessfully,
+}
+
+impl std::fmt::Display for DependencyCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DependencyCondition::Started => "started",
+            DependencyCondition::Healthy => "healthy",
+            DependencyCondition::CompletedSuccessfully => "completed_successfully",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl DependencyCondition {
+    /// Whether `task` satisfies this condition. Errors when it never will, e.g. a job
+    /// that was expected to complete successfully failed instead.
+    pub fn is_satisfied_by(&self, task: &Task) -> Result<bool> {
+        match self {
+            DependencyCondition::Started => match task.status {
+                TaskStatus::Running | TaskStatus::Exited => Ok(true),
+                TaskStatus::Failed => Err(HyperVError::ProcessError(format!(
+                    "Dependency \"{}\" failed to start",
+                    task.name
+                ))),
+                TaskStatus::Stopped => Ok(false),
+            },
+            DependencyCondition::Healthy => {
+                if task.health_check.is_none() {
+                    return Err(HyperVError::InvalidInput(format!(
+                        "Dependency \"{}\" has no health_check, so it can never become healthy",
+                        task.name
+                    )));
+                }
+                match task.status {
+                    TaskStatus::Running => Ok(task
+                        .health
+                        .as_ref()
+                        .is_some_and(|health| health.status == HealthStatus::Healthy)),
+                    _ if task.last_started.is_none() => Ok(false),
+                    _ => Err(HyperVError::ProcessError(format!(
+                        "Dependency \"{}\" stopped before becoming healthy",
+                        task.name
+                    ))),
+                }
+            }
+            DependencyCondition::CompletedSuccessfully => match task.status {
+                TaskStatus::Exited => Ok(true),
+                TaskStatus::Failed => Err(HyperVError::ProcessError(format!(
+                    "Dependency \"{}\" did not complete successfully (exit code: {})",
+                    task.name,
+                    task.last_exit_code
+                        .map_or_else(|| "unknown".to_string(), |code| code.to_string())
+                ))),
+                TaskStatus::Running | TaskStatus::Stopped => Ok(false),
+            },
+        }
+    }
+}
+
+/// Accept both `depends_on: [db]` and `depends_on: {db: {condition: healthy}}`
+fn deserialize_depends_on<'de, D>(
+    deserializer: D,
+) -> std::result::Result<BTreeMap<String, DependencyCondition>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Dependency {
+        #[serde(default)]
+        condition: DependencyCondition,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum DependsOn {
+        List(Vec<String>),
+        Map(BTreeMap<String, Dependency>),
+    }
+
+    Ok(match DependsOn::deserialize(deserializer)? {
+        DependsOn::List(names) => names
+            .into_iter()
+            .map(|name| (name, DependencyCondition::Started))
+            .collect(),
+        DependsOn::Map(map) => map
+            .into_iter()
+            .map(|(name, dependency)| (name, dependency.condition))
+            .collect(),
+    })
+}
+
+/// Write dependencies in the long form so the output can be read back
+fn serialize_depends_on<S>(
+    depends_on: &BTreeMap<String, DependencyCondition>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    #[derive(Serialize)]
+    struct Dependency {
+        condition: DependencyCondition,
+    }
+
+    serializer.collect_map(
+        depends_on
+            .iter()
+            .map(|(name, &condition)| (name, Dependency { condition })),
+    )
+}
+
+/// Accept both `env_file: .env.api` and `env_file: [.env.common, .env.api]`
+fn deserialize_env_file<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum EnvFile {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match EnvFile::deserialize(deserializer)? {
+        EnvFile::One(path) => vec![path],
+        EnvFile::Many(paths) => paths,
+    })
+}
+
+fn serialize_sorted<S>(
+    map: &HashMap<String, String>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(map.iter().collect::<BTreeMap<_, _>>())
+}
+
+impl Service {
+    /// Task settings described by this service
+    pub fn task_options(&self) -> TaskOptions {
+        let restart_policy = self.restart_policy.unwrap_or(if self.auto_restart {
+            RestartPolicy::OnFailure
+        } else {
+            RestartPolicy::Never
+        });
+        TaskOptions {
+            restart_policy,
+            success_exit_codes: self.success_exit_codes.clone(),
+            restart_backoff: self.restart_backoff.clone(),
+            health_check: self.health_check.clone(),
+            watchdog: self.watchdog.clone(),
+            // `env_file` entries are merged into `env` when the file is loaded
+            env_files: dotenv::workdir_env_file(self.workdir.as_deref())
+                .into_iter()
+                .collect(),
+            secret_env: self.secret_env.clone(),
+            user: self.user.clone(),
+            group: self.group.clone(),
+            supplementary_groups: self.supplementary_groups.clone(),
+            limits: self.limits.clone(),
+            cgroup_limits: self.cgroup.clone(),
+            log_settings: self.logs.clone(),
+        }
+    }
+}
+
+/// A single field that `up` would change on an existing task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// What `up` would do with one service or task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanAction {
+    Create,
+    Update(Vec<FieldChange>),
+    Unchanged,
+    /// A task created from this file whose service has since been removed
+    Orphaned,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanEntry {
+    pub name: String,
+    pub action: PlanAction,
+}
+
+/// The changes `up` would make, in startup order followed by orphans
+#[derive(Debug, Clone, Default)]
+pub struct ComposePlan {
+    pub entries: Vec<PlanEntry>,
+}
+
+impl ComposePlan {
+    pub fn orphans(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .filter(|e| e.action == PlanAction::Orphaned)
+            .map(|e| e.name.as_str())
+    }
+
+    pub fn updated(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e.action, PlanAction::Update(_)))
+            .map(|e| e.name.as_str())
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|e| e.action != PlanAction::Unchanged)
+    }
+
+    pub fn print(&self) {
+        for entry in &self.entries {
+            match &entry.action {
+                PlanAction::Create => println!("  + create     {}", entry.name),
+                PlanAction::Update(changes) => {
+                    println!("  ~ update     {}", entry.name);
+                    for change in changes {
+                        println!("      {}: {} -> {}", change.field, change.old, change.new);
+                    }
+                }
+                PlanAction::Unchanged => println!("  = unchanged  {}", entry.name),
+                PlanAction::Orphaned => println!(
+                    "  - orphaned   {} (not in the file any more; use --remove-orphans to remove)",
+                    entry.name
+                ),
+            }
+        }
+        if !self.has_changes() {
+            println!("  No changes.");
+        }
+    }
+}
+
+/// Compare the configuration `svc` would give a task with what `task` has now
+fn diff_service(task: &Task, svc: &Service) -> Vec<FieldChange> {
+    fn change<T: std::fmt::Debug + PartialEq>(
+        changes: &mut Vec<FieldChange>,
+        field: &str,
+        old: &T,
+        new: &T,
+    ) {
+        if old != new {
+            changes.push(FieldChange {
+                field: field.to_string(),
+                old: format!("{:?}", old),
+                new: format!("{:?}", new),
+            });
+        }
+    }
+
+    let mut changes = Vec::new();
+    let options = svc.task_options();
+    change(&mut changes, "binary", &task.binary, &svc.binary);
+    change(&mut changes, "args", &task.args, &svc.args);
+    change(&mut changes, "workdir", &task.workdir, &svc.workdir);
+    change(&mut changes, "user", &task.user, &options.user);
+    change(&mut changes, "group", &task.group, &options.group);
+    change(
+        &mut changes,
+        "supplementary_groups",
+        &task.supplementary_groups,
+        &options.supplementary_groups,
+    );
+    change(&mut changes, "limits", &task.limits, &options.limits);
+    change(
+        &mut changes,
+        "cgroup",
+        &task.cgroup_limits,
+        &options.cgroup_limits,
+    );
+
+    // Compare the environment the process would actually get
+    change(
+        &mut changes,
+        "env_files",
+        &task.env_files,
+        &options.env_files,
+    );
+    let old_env = TaskManager::effective_env(&task.env, &task.env_files);
+    let new_env = TaskManager::effective_env(&svc.env, &options.env_files);
+    let keys: std::collections::BTreeSet<&String> = old_env.keys().chain(new_env.keys()).collect();
+    for key in keys {
+        let (old, new) = (old_env.get(key), new_env.get(key));
+        if old != new && (task.is_secret_env(key) || svc.secret_env.contains(key)) {
+            let mask = |value: Option<&String>| value.map(|_| "[REDACTED]");
+            changes.push(FieldChange {
+                field: format!("env.{}", key),
+                old: format!("{:?}", mask(old)),
+                new: format!("{:?}", mask(new)),
+            });
+        } else {
+            change(&mut changes, &format!("env.{}", key), &old, &new);
+        }
+    }
+    change(
+        &mut changes,
+        "secret_env",
+        &task.secret_env,
+        &options.secret_env,
+    );
+
+    change(
+        &mut changes,
+        "restart_policy",
+        &task.restart_policy,
+        &options.restart_policy,
+    );
+    change(
+        &mut changes,
+        "success_exit_codes",
+        &task.success_exit_codes,
+        &options.success_exit_codes,
+    );
+    change(
+        &mut changes,
+        "restart_backoff",
+        &task.restart_backoff,
+        &options.restart_backoff,
+    );
+    change(
+        &mut changes,
+        "health_check",
+        &task.health_check,
+        &options.health_check,
+    );
+    change(&mut changes, "watchdog", &task.watchdog, &options.watchdog);
+    change(
+        &mut changes,
+        "logs",
+        &task.log_settings,
+        &options.log_settings,
+    );
+    changes
 }
 
 impl ComposeFile {
+    /// Load a compose file, interpolating variables from the environment of this
+    /// process and the `.env` file next to the compose file
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(&path).map_err(HyperVError::Io)?;
-        let compose: ComposeFile = serde_yml::from_str(&content)
+        Self::from_path_with_vars(path, std::env::vars().collect())
+    }
+
+    /// Load a compose file, interpolating variables from `vars` and the `.env` file
+    /// next to the compose file. Values in `vars` take precedence over `.env`.
+    pub fn from_path_with_vars<P: AsRef<Path>>(
+        path: P,
+        vars: HashMap<String, String>,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(HyperVError::Io)?;
+        let source = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let dir = source.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+        let project_env = dir.join(".env");
+        let mut all_vars: HashMap<String, String> = if project_env.is_file() {
+            let file = dotenv::load(&project_env).map_err(HyperVError::Io)?;
+            for warning in &file.warnings {
+                eprintln!("⚠️  {}: {}", project_env.display(), warning);
+            }
+            file.to_map()
+        } else {
+            HashMap::new()
+        };
+        all_vars.extend(vars);
+
+        let (document, unset) = interpolation::interpolate_yaml(&content, &all_vars)?;
+        for unset in unset {
+            eprintln!(
+                "⚠️  Variable {} is not set; using an empty string for {}",
+                unset.name, unset.location
+            );
+        }
+        // Deserialize from text so unquoted numbers and booleans are still accepted
+        // where a string is expected, e.g. `PORT: 8080` under `env`
+        let document = serde_yml::to_string(&document)
             .map_err(|e| HyperVError::InvalidInput(format!("Failed to parse YAML: {}", e)))?;
+        let mut compose: ComposeFile = serde_yml::from_str(&document)
+            .map_err(|e| HyperVError::InvalidInput(format!("Failed to parse YAML: {}", e)))?;
+
+        for (name, svc) in compose.services.iter_mut() {
+            svc.logs.validate().map_err(|e| {
+                HyperVError::InvalidInput(format!("Service \"{}\", key \"logs\": {}", name, e))
+            })?;
+            svc.limits.validate().map_err(|e| {
+                HyperVError::InvalidInput(format!("Service \"{}\", key \"limits\": {}", name, e))
+            })?;
+            svc.cgroup.validate().map_err(|e| {
+                HyperVError::InvalidInput(format!("Service \"{}\", key \"cgroup\": {}", name, e))
+            })?;
+            svc.restart_backoff.validate().map_err(|e| {
+                HyperVError::InvalidInput(format!(
+                    "Service \"{}\", key \"restart_backoff\": {}",
+                    name, e
+                ))
+            })?;
+            if let Some(watchdog) = &svc.watchdog {
+                watchdog.validate().map_err(|e| {
+                    HyperVError::InvalidInput(format!(
+                        "Service \"{}\", key \"watchdog\": {}",
+                        name, e
+                    ))
+                })?;
+            }
+
+            // Later files override earlier ones; `env` overrides them all
+            let mut from_files: HashMap<String, String> = HashMap::new();
+            for file in &svc.env_file {
+                let file_path = dir.join(file);
+                let content = fs::read_to_string(&file_path).map_err(|e| {
+                    HyperVError::InvalidInput(format!(
+                        "Service \"{}\", key \"env_file\": cannot read {}: {}",
+                        name,
+                        file_path.display(),
+                        e
+                    ))
+                })?;
+                let parsed = dotenv::parse_with(&content, &from_files);
+                for warning in &parsed.warnings {
+                    eprintln!("⚠️  {}: {}", file_path.display(), warning);
+                }
+                from_files.extend(parsed.entries);
+            }
+            for (key, value) in from_files {
+                svc.env.entry(key).or_insert(value);
+            }
+            for (key, value) in &svc.env {
+                SecretRef::parse(value).map_err(|e| match e {
+                    HyperVError::InvalidInput(msg) => HyperVError::InvalidInput(format!(
+                        "Service \"{}\", key \"env.{}\": {}",
+                        name, key, msg
+                    )),
+                    other => other,
+                })?;
+            }
+        }
+
+        compose.source = Some(source);
         Ok(compose)
     }
+
+    /// The file with variables resolved and `env_file` entries merged into `env`, as YAML
+    pub fn to_yaml(&self) -> Result<String> {
+        serde_yml::to_string(self)
+            .map_err(|e| HyperVError::InvalidInput(format!("Failed to render YAML: {}", e)))
+    }
+
+    /// Project that owns the tasks created from this file: the `name:` key, or the
+    /// directory the file lives in
+    pub fn project_name(&self) -> String {
+        if let Some(name) = self.name.as_deref().map(str::trim)
+            && !name.is_empty()
+        {
+            return name.to_string();
+        }
+        let dir_name = self
+            .source
+            .as_deref()
+            .and_then(Path::parent)
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let project: String = dir_name
+            .to_lowercase()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let project = project.trim_matches('-');
+        if project.is_empty() {
+            "default".to_string()
+        } else {
+            project.to_string()
+        }
+    }
+
+    /// Name of the task that runs `service`, namespaced by project so two projects
+    /// can both define a `worker`
+    pub fn task_name(&self, service: &str) -> String {
+        format!("{}_{}", self.project_name(), service)
+    }
+
+    fn source_string(&self) -> Option<String> {
+        self.source
+            .as_ref()
+            .map(|path| path.to_string_lossy().to_string())
+    }
+
+    /// Whether applying this file needs a daemon beyond what restart policies ask for:
+    /// health checks (and therefore `healthy` dependencies) only run inside the daemon.
+    pub fn needs_daemon(&self) -> bool {
+        self.services.values().any(|svc| svc.health_check.is_some())
+    }
+
+    /// Service names in an order where every service comes after its dependencies.
+    /// Independent services are ordered by name so the result is stable.
+    pub fn startup_order(&self) -> Result<Vec<String>> {
+        for (name, service) in &self.services {
+            if let Some(missing) = service
+                .depends_on
+                .keys()
+                .find(|dep| !self.services.contains_key(*dep))
+            {
+                return Err(HyperVError::InvalidInput(format!(
+                    "Service \"{}\" depends on unknown service \"{}\"",
+                    name, missing
+                )));
+            }
+        }
+
+        let mut remaining: BTreeMap<&str, usize> = self
+            .services
+            .iter()
+            .map(|(name, service)| (name.as_str(), service.depends_on.len()))
+            .collect();
+        let mut order = Vec::with_capacity(self.services.len());
+
+        while let Some(next) = remaining
+            .iter()
+            .find(|(_, pending)| **pending == 0)
+            .map(|(name, _)| *name)
+        {
+            remaining.remove(next);
+            for (name, service) in &self.services {
+                if service.depends_on.contains_key(next)
+                    && let Some(pending) = remaining.get_mut(name.as_str())
+                {
+                    *pending -= 1;
+                }
+            }
+            order.push(next.to_string());
+        }
+
+        if !remaining.is_empty() {
+            let cycle = self.find_cycle(&remaining);
+            return Err(HyperVError::InvalidInput(format!(
+                "Dependency cycle between services: {}",
+                cycle.join(" -> ")
+            )));
+        }
+        Ok(order)
+    }
+
+    /// Every service left over by the topological sort still waits on another left-over
+    /// service, so following those edges from any of them must run into a cycle.
+    fn find_cycle(&self, unresolved: &BTreeMap<&str, usize>) -> Vec<String> {
+        let Some(mut current) = unresolved.keys().next().copied() else {
+            return Vec::new();
+        };
+        let mut path: Vec<&str> = Vec::new();
+        loop {
+            if let Some(pos) = path.iter().position(|seen| *seen == current) {
+                let mut cycle: Vec<String> = path[pos..].iter().map(|s| s.to_string()).collect();
+                cycle.push(current.to_string());
+                return cycle;
+            }
+            path.push(current);
+            match self.services[current]
+                .depends_on
+                .keys()
+                .find(|dep| unresolved.contains_key(dep.as_str()))
+            {
+                Some(next) => current = next.as_str(),
+                None => return path.iter().map(|s| s.to_string()).collect(),
+            }
+        }
+    }
 }
 
 impl TaskManager {
-    /// Apply services from a compose file: create or update tasks to match the file
-    pub fn up_from_compose(&mut self, compose: &ComposeFile) -> Result<()> {
-        // Create or update tasks for each service
-        for (name, svc) in &compose.services {
-            // Convert env map to vec of KEY=VALUE like CLI create expects
-            let env_vars: Vec<String> = svc
-                .env
-                .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect();
-
-            // If task exists, replace its configuration; otherwise create
-            if let Some(task) = self.find_task_mut(name) {
-                task.binary = svc.binary.clone();
-                task.args = svc.args.clone();
-                task.env = svc.env.clone();
-                task.workdir = svc.workdir.clone();
-                task.auto_restart = svc.auto_restart;
-            } else {
-                self.create_task(
-                    name.clone(),
+    /// The task running `service` of this compose project, or else a task `up` adopts
+    /// from before task names were namespaced. Errors when a task with the project's
+    /// name for it exists but was created by someone else.
+    fn compose_task(&self, compose: &ComposeFile, service: &str) -> Result<Option<&Task>> {
+        let project = compose.project_name();
+        let task_name = compose.task_name(service);
+        match self.tasks().iter().find(|t| t.name == task_name) {
+            Some(task) if !task.belongs_to_project(&project) => {
+                Err(HyperVError::InvalidInput(format!(
+                    "Task \"{}\" already exists and does not belong to compose project \"{}\"",
+                    task_name, project
+                )))
+            }
+            Some(task) => Ok(Some(task)),
+            None => Ok(self.legacy_compose_task(compose, service)),
+        }
+    }
+
+    /// A task created from this file before tasks recorded their project: it is named
+    /// after the service instead of `{project}_{service}`. The oldest versions of `up`
+    /// did not record the compose file either, so such a task is recognised by running
+    /// the service's binary.
+    fn legacy_compose_task(&self, compose: &ComposeFile, service: &str) -> Option<&Task> {
+        let source = compose.source_string();
+        let binary = &compose.services[service].binary;
+        self.tasks().iter().find(|t| {
+            t.name == service
+                && t.project.is_none()
+                && match t.compose_file.as_deref() {
+                    Some(file) => source.as_deref() == Some(file),
+                    None => t.binary == *binary,
+                }
+        })
+    }
+
+    /// Work out what `up` would change without touching any task
+    pub fn plan_compose(&self, compose: &ComposeFile) -> Result<ComposePlan> {
+        let order = compose.startup_order()?;
+        let project = compose.project_name();
+        let mut plan = ComposePlan::default();
+        let mut adopted = HashSet::new();
+
+        for service in order {
+            let svc = &compose.services[&service];
+            let action = match self.compose_task(compose, &service)? {
+                None => PlanAction::Create,
+                Some(task) => {
+                    let mut changes = diff_service(task, svc);
+                    let task_name = compose.task_name(&service);
+                    if task.name != task_name {
+                        adopted.insert(task.id.clone());
+                        changes.insert(
+                            0,
+                            FieldChange {
+                                field: "name".to_string(),
+                                old: format!("{:?}", task.name),
+                                new: format!("{:?}", task_name),
+                            },
+                        );
+                    }
+                    if changes.is_empty() {
+                        PlanAction::Unchanged
+                    } else {
+                        PlanAction::Update(changes)
+                    }
+                }
+            };
+            plan.entries.push(PlanEntry {
+                name: compose.task_name(&service),
+                action,
+            });
+        }
+
+        // Tasks of this project whose service is gone, plus tasks created from the same
+        // file before tasks recorded their project that are not adopted above
+        let source = compose.source_string();
+        let mut orphans: Vec<&Task> = self
+            .tasks()
+            .iter()
+            .filter(|t| {
+                if t.belongs_to_project(&project) {
+                    t.service
+                        .as_ref()
+                        .is_none_or(|service| !compose.services.contains_key(service))
+                } else {
+                    t.project.is_none()
+                        && source.is_some()
+                        && t.compose_file.as_deref() == source.as_deref()
+                        && !adopted.contains(&t.id)
+                }
+            })
+            .collect();
+        orphans.sort_by(|a, b| a.name.cmp(&b.name));
+        plan.entries
+            .extend(orphans.into_iter().map(|task| PlanEntry {
+                name: task.name.clone(),
+                action: PlanAction::Orphaned,
+            }));
+
+        Ok(plan)
+    }
+
+    /// Apply services from a compose file: create or update tasks to match the file.
+    /// Returns the plan that was applied.
+    pub fn up_from_compose(&mut self, compose: &ComposeFile) -> Result<ComposePlan> {
+        // Validate dependencies before touching any task
+        let order = compose.startup_order()?;
+
+        let _lock_file = self.lock_tasks_for_update()?;
+        let plan = self.plan_compose(compose)?;
+        let source = compose.source_string();
+        let project = compose.project_name();
+
+        for service in &order {
+            let svc = &compose.services[service];
+            let task_name = compose.task_name(service);
+
+            // If task exists, replace its configuration; otherwise create. A task from
+            // before names were namespaced is renamed and keeps its ID and logs.
+            let existing = self
+                .compose_task(compose, service)?
+                .map(|task| task.id.clone());
+            let task = match existing.and_then(|id| self.find_task_mut(&id)) {
+                Some(task) => {
+                    task.name = task_name;
+                    task.binary = svc.binary.clone();
+                    task.args = svc.args.clone();
+                    task.env = svc.env.clone();
+                    task.workdir = svc.workdir.clone();
+                    svc.task_options().apply_to(task);
+                    task
+                }
+                None => self.add_task_unlocked(
+                    task_name,
                     svc.binary.clone(),
                     svc.args.clone(),
-                    env_vars,
+                    svc.env.clone(),
                     svc.workdir.clone(),
-                    svc.auto_restart,
-                )?;
-            }
+                    svc.task_options(),
+                )?,
+            };
+            task.compose_file = source.clone();
+            task.project = Some(project.clone());
+            task.service = Some(service.clone());
         }
 
-        // Remove tasks that are not in the compose file? For safety, we won't automatically remove.
-        // Users can run `down` to remove only compose-defined tasks.
-        self.save()?;
-        Ok(())
+        self.save_unlocked()?;
+        Ok(plan)
     }
 
-    /// Remove tasks that are defined in the compose file
+    /// Remove this project's tasks for the services in the compose file, stopping
+    /// dependents before the services they depend on. Tasks of other projects and
+    /// hand-made tasks are never touched.
     pub fn down_from_compose(&mut self, compose: &ComposeFile) -> Result<()> {
-        let names: Vec<String> = compose.services.keys().cloned().collect();
+        let project = compose.project_name();
+        let mut services = compose.startup_order()?;
+        services.reverse();
+        let names: Vec<String> = services
+            .iter()
+            .map(|service| compose.task_name(service))
+            .filter(|name| {
+                self.tasks()
+                    .iter()
+                    .any(|t| t.name == *name && t.belongs_to_project(&project))
+            })
+            .collect();
+        self.stop_and_remove(&names);
+        Ok(())
+    }
+
+    /// Stop and remove tasks created from this compose file whose service no longer exists
+    pub fn remove_compose_orphans(&mut self, plan: &ComposePlan) -> Result<()> {
+        let names: Vec<String> = plan.orphans().map(str::to_string).collect();
+        self.stop_and_remove(&names);
+        Ok(())
+    }
+
+    /// Stop all named tasks first, then remove them
+    fn stop_and_remove(&mut self, names: &[String]) {
         for name in names {
-            if self.find_task(&name).is_some() {
-                // Stop if running, then remove
-                let _ = self.stop_task(&name);
-                let _ = self.remove_task(&name);
+            if self.find_task(name).is_some() {
+                let _ = self.stop_task(name);
+            }
+        }
+        for name in names {
+            if self.find_task(name).is_some() {
+                let _ = self.remove_task(name);
             }
         }
-        Ok(())
     }
 }
@@ -2,9 +2,23 @@
 //!
 //! Handles configuration directory setup, file paths, and persistent storage.
 
+use crate::cgroup::CgroupConfig;
 use crate::error::{HyperVError, Result};
+use crate::logs::LogSettings;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Settings from `daemon.yaml` in the configuration directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// Log settings for tasks that do not set their own
+    pub logs: LogSettings,
+    /// Whether and where tasks are placed in cgroups
+    pub cgroup: CgroupConfig,
+}
 
 /// Configuration manager for hyperV
 pub struct Config {
@@ -73,6 +87,62 @@ impl Config {
     pub fn daemon_pid_path(&self) -> PathBuf {
         self.config_dir.join("daemon.pid")
     }
+
+    /// Path to the daemon control socket
+    pub fn control_socket_path(&self) -> PathBuf {
+        self.config_dir.join("daemon.sock")
+    }
+
+    /// Path to the daemon configuration file
+    pub fn daemon_config_path(&self) -> PathBuf {
+        self.config_dir.join("daemon.yaml")
+    }
+
+    /// Path to the encrypted secret store
+    pub fn secrets_path(&self) -> PathBuf {
+        self.config_dir.join("secrets.json")
+    }
+
+    /// Path to the key that encrypts the secret store
+    pub fn secret_key_path(&self) -> PathBuf {
+        self.config_dir.join("secrets.key")
+    }
+
+    /// Read `daemon.yaml`; without one every setting has its default
+    pub fn load_daemon_config(&self) -> Result<DaemonConfig> {
+        let path = self.daemon_config_path();
+        if !path.exists() {
+            return Ok(DaemonConfig::default());
+        }
+        let content = fs::read_to_string(&path).map_err(HyperVError::Io)?;
+        let config: DaemonConfig = serde_yml::from_str(&content)
+            .map_err(|e| HyperVError::Config(format!("{}: {}", path.display(), e)))?;
+        config
+            .logs
+            .validate()
+            .map_err(|e| HyperVError::Config(format!("{}: logs: {}", path.display(), e)))?;
+        Ok(config)
+    }
+}
+
+/// Write `contents` to `path`, readable and writable by its owner only (0600)
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(HyperVError::Io)?;
+    // The mode only applies to new files; tighten files left by older versions too
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))
+            .map_err(HyperVError::Io)?;
+    }
+    file.write_all(contents).map_err(HyperVError::Io)
 }
 
 impl Default for Config {
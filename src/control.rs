@@ -0,0 +1,255 @@
+//! Control channel between the CLI and the daemon
+//!
+//! The daemon listens on a Unix domain socket under the config directory and
+//! executes task lifecycle requests on behalf of short-lived CLI invocations,
+//! so that every task process is spawned (and later reaped) by the daemon and
+//! concurrent commands are applied one at a time.
+//!
+//! The wire format is one JSON object per line in each direction. Every message
+//! carries a `version` field; the daemon rejects requests from a different
+//! protocol version instead of guessing at their meaning.
+
+use crate::config::Config;
+use crate::error::{HyperVError, Result};
+use crate::task::Task;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Version of the request/response protocol spoken over the control socket
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How long the CLI waits for the daemon to answer a request
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the daemon waits for a connected client to send its request
+#[cfg(unix)]
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Request sent by the CLI to the daemon
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlRequest {
+    Start { task: String },
+    Stop { task: String },
+    Restart { task: String },
+    Status { task: Option<String> },
+    List,
+    Reload,
+}
+
+/// A request together with the protocol version it was written for
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ControlEnvelope {
+    pub version: u32,
+    #[serde(flatten)]
+    pub request: ControlRequest,
+}
+
+/// Response sent by the daemon for a single request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ControlResponse {
+    pub version: u32,
+    pub ok: bool,
+    pub message: String,
+    /// Task snapshots for `status` and `list`, refreshed by the daemon
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tasks: Vec<Task>,
+}
+
+impl ControlResponse {
+    pub fn success(message: impl Into<String>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            ok: true,
+            message: message.into(),
+            tasks: Vec::new(),
+        }
+    }
+
+    pub fn failure(message: impl Into<String>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            ok: false,
+            message: message.into(),
+            tasks: Vec::new(),
+        }
+    }
+
+    pub fn with_tasks(mut self, tasks: Vec<Task>) -> Self {
+        self.tasks = tasks;
+        self
+    }
+
+    /// Fail if the response was not a success, keeping the daemon's message
+    pub fn into_result(self) -> Result<Self> {
+        if self.ok {
+            Ok(self)
+        } else {
+            Err(HyperVError::Daemon(self.message))
+        }
+    }
+}
+
+/// Send a request to the running daemon.
+///
+/// Returns `None` when no daemon is listening on the control socket, so the caller
+/// can fall back to acting directly.
+#[cfg(unix)]
+pub fn send_request(config: &Config, request: &ControlRequest) -> Option<Result<ControlResponse>> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let stream = UnixStream::connect(config.control_socket_path()).ok()?;
+
+    let exchange = || -> Result<ControlResponse> {
+        stream
+            .set_read_timeout(Some(CONTROL_TIMEOUT))
+            .map_err(HyperVError::Io)?;
+        let envelope = ControlEnvelope {
+            version: PROTOCOL_VERSION,
+            request: request.clone(),
+        };
+        let mut payload = serde_json::to_string(&envelope)?;
+        payload.push('\n');
+        (&stream)
+            .write_all(payload.as_bytes())
+            .map_err(HyperVError::Io)?;
+
+        let mut line = String::new();
+        BufReader::new(&stream)
+            .read_line(&mut line)
+            .map_err(HyperVError::Io)?;
+        if line.trim().is_empty() {
+            return Err(HyperVError::Daemon(
+                "Daemon closed the control connection without a response".to_string(),
+            ));
+        }
+        let response: ControlResponse = serde_json::from_str(&line)?;
+        if response.version != PROTOCOL_VERSION {
+            return Err(HyperVError::Daemon(format!(
+                "Daemon speaks control protocol version {} but this CLI expects {}; restart the daemon",
+                response.version, PROTOCOL_VERSION
+            )));
+        }
+        Ok(response)
+    };
+
+    Some(exchange())
+}
+
+#[cfg(not(unix))]
+pub fn send_request(
+    _config: &Config,
+    _request: &ControlRequest,
+) -> Option<Result<ControlResponse>> {
+    None
+}
+
+/// Wait until the daemon accepts connections on the control socket
+pub fn wait_for_daemon(config: &Config, timeout: Duration) -> bool {
+    let start = std::time::Instant::now();
+    while start.elapsed() < timeout {
+        if is_daemon_listening(config) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    is_daemon_listening(config)
+}
+
+#[cfg(unix)]
+fn is_daemon_listening(config: &Config) -> bool {
+    std::os::unix::net::UnixStream::connect(config.control_socket_path()).is_ok()
+}
+
+#[cfg(not(unix))]
+fn is_daemon_listening(_config: &Config) -> bool {
+    false
+}
+
+/// Decode a request line, checking the protocol version before the command itself
+pub fn parse_request(line: &str) -> Result<ControlRequest> {
+    let value: serde_json::Value = serde_json::from_str(line)?;
+    let version = value.get("version").and_then(serde_json::Value::as_u64);
+    if version != Some(u64::from(PROTOCOL_VERSION)) {
+        return Err(HyperVError::Daemon(format!(
+            "Unsupported control protocol version {} (daemon speaks {})",
+            version.map_or_else(|| "<missing>".to_string(), |v| v.to_string()),
+            PROTOCOL_VERSION
+        )));
+    }
+    let envelope: ControlEnvelope = serde_json::from_value(value)?;
+    Ok(envelope.request)
+}
+
+/// Daemon side of the control socket
+#[cfg(unix)]
+pub struct ControlServer {
+    listener: tokio::net::UnixListener,
+    socket_path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl ControlServer {
+    /// Bind the control socket, replacing a stale socket file left by a previous daemon.
+    /// The caller must already hold the daemon PID lock.
+    pub fn bind(config: &Config) -> Result<Self> {
+        let socket_path = config.control_socket_path();
+        if socket_path.exists() {
+            std::fs::remove_file(&socket_path).map_err(HyperVError::Io)?;
+        }
+        let listener = tokio::net::UnixListener::bind(&socket_path).map_err(HyperVError::Io)?;
+        Ok(Self {
+            listener,
+            socket_path,
+        })
+    }
+
+    /// Accept the next client. The request itself is read with [`ControlConnection::read_request`]
+    /// so that a slow client can never be cut off halfway by other daemon work.
+    pub async fn accept(&self) -> Result<ControlConnection> {
+        let (stream, _) = self.listener.accept().await.map_err(HyperVError::Io)?;
+        Ok(ControlConnection { stream })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
+
+/// A client connection waiting for its request to be read and answered
+#[cfg(unix)]
+pub struct ControlConnection {
+    stream: tokio::net::UnixStream,
+}
+
+#[cfg(unix)]
+impl ControlConnection {
+    /// Read and decode the client's single request line
+    pub async fn read_request(&mut self) -> Result<ControlRequest> {
+        use tokio::io::AsyncBufReadExt;
+
+        let mut reader = tokio::io::BufReader::new(&mut self.stream);
+        let mut line = String::new();
+        tokio::time::timeout(REQUEST_READ_TIMEOUT, reader.read_line(&mut line))
+            .await
+            .map_err(|_| HyperVError::Daemon("Timed out reading control request".to_string()))?
+            .map_err(HyperVError::Io)?;
+        parse_request(&line)
+    }
+
+    pub async fn respond(mut self, response: &ControlResponse) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut payload = serde_json::to_string(response)?;
+        payload.push('\n');
+        self.stream
+            .write_all(payload.as_bytes())
+            .await
+            .map_err(HyperVError::Io)?;
+        self.stream.shutdown().await.map_err(HyperVError::Io)
+    }
+}
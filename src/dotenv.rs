@@ -0,0 +1,231 @@
+//! `.env` files
+//!
+//! One `KEY=VALUE` per line, with an optional `export ` prefix. Blank lines and lines
+//! starting with `#` are skipped.
+//!
+//! - Unquoted values are trimmed and end at an inline ` #` comment.
+//! - Single-quoted values are taken literally.
+//! - Double-quoted values understand `\n`, `\t`, `\r`, `\"`, `\\` and `\$`.
+//! - Quoted values may span several lines.
+//!
+//! Unquoted and double-quoted values expand `${KEY}` references to keys defined
+//! earlier (with the same syntax as compose files). Problems are reported as
+//! warnings with line numbers instead of failing the whole file.
+
+use crate::interpolation::interpolate_str;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A problem found while parsing, tied to the line it starts on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// A parsed `.env` file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvFile {
+    /// Entries in file order; a key set twice appears twice and the later value wins
+    pub entries: Vec<(String, String)>,
+    pub warnings: Vec<Warning>,
+}
+
+impl EnvFile {
+    pub fn to_map(&self) -> HashMap<String, String> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+/// Parse the contents of a `.env` file
+pub fn parse(content: &str) -> EnvFile {
+    parse_with(content, &HashMap::new())
+}
+
+/// Parse the contents of a `.env` file; `defined` is visible to `${KEY}` references
+/// as if it had been set above the first line
+pub fn parse_with(content: &str, defined: &HashMap<String, String>) -> EnvFile {
+    let mut vars = defined.clone();
+    let mut file = EnvFile::default();
+    let mut first_line: HashMap<String, usize> = HashMap::new();
+    let lines: Vec<&str> = content.lines().collect();
+    let mut next = 0;
+
+    while next < lines.len() {
+        let line_no = next + 1;
+        let line = lines[next].trim();
+        next += 1;
+        let mut warn = |message: String| {
+            file.warnings.push(Warning {
+                line: line_no,
+                message,
+            })
+        };
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line
+            .strip_prefix("export ")
+            .map(str::trim_start)
+            .unwrap_or(line);
+        let Some((key, rest)) = line.split_once('=') else {
+            warn(format!("expected KEY=VALUE, found {:?}", line));
+            continue;
+        };
+        let key = key.trim();
+        if !is_valid_key(key) {
+            warn(format!("invalid variable name {:?}", key));
+            continue;
+        }
+        let rest = rest.trim_start();
+
+        let (raw, expand) = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let mut quoted = rest[1..].to_string();
+                let closed = loop {
+                    if let Some(end) = find_closing_quote(&quoted, quote) {
+                        let trailing = quoted[end + 1..].trim();
+                        if !trailing.is_empty() && !trailing.starts_with('#') {
+                            warn(format!(
+                                "ignoring {:?} after the closing quote of {}",
+                                trailing, key
+                            ));
+                        }
+                        quoted.truncate(end);
+                        break true;
+                    }
+                    if next >= lines.len() {
+                        break false;
+                    }
+                    quoted.push('\n');
+                    quoted.push_str(lines[next]);
+                    next += 1;
+                };
+                if !closed {
+                    warn(format!(
+                        "unterminated {} quote in the value of {}",
+                        quote, key
+                    ));
+                    continue;
+                }
+                if quote == '"' {
+                    (unescape(&quoted), true)
+                } else {
+                    (quoted, false)
+                }
+            }
+            _ => {
+                let value = if rest.starts_with('#') {
+                    ""
+                } else {
+                    rest.find(" #")
+                        .or_else(|| rest.find("\t#"))
+                        .map_or(rest, |index| &rest[..index])
+                };
+                (value.trim_end().to_string(), true)
+            }
+        };
+
+        let value = if expand {
+            match interpolate_str(&raw, &vars) {
+                Ok((value, unset)) => {
+                    for name in unset {
+                        warn(format!(
+                            "{} references {}, which is not defined above it",
+                            key, name
+                        ));
+                    }
+                    value
+                }
+                Err(e) => {
+                    warn(format!("{} (value of {} kept as written)", e, key));
+                    raw
+                }
+            }
+        } else {
+            raw
+        };
+
+        if let Some(first) = first_line.get(key) {
+            warn(format!(
+                "{} is already set on line {}; this value wins",
+                key, first
+            ));
+        } else {
+            first_line.insert(key.to_string(), line_no);
+        }
+        vars.insert(key.to_string(), value.clone());
+        file.entries.push((key.to_string(), value));
+    }
+    file
+}
+
+fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c == '.' || c.is_ascii_alphanumeric())
+}
+
+/// Byte index of the quote that closes a value, skipping escaped double quotes
+fn find_closing_quote(value: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote == '"' {
+            escaped = true;
+        } else if c == quote {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// Resolve backslash escapes in a double-quoted value. `\$` becomes `$$` so that
+/// it survives expansion as a literal dollar sign.
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('$') => out.push_str("$$"),
+            Some(other @ ('"' | '\\')) => out.push(other),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Read and parse a `.env` file
+pub fn load(path: &Path) -> io::Result<EnvFile> {
+    Ok(parse(&fs::read_to_string(path)?))
+}
+
+/// The `.env` file in `workdir`, if there is one. Tasks created with a working
+/// directory use it unless other env files are given.
+pub fn workdir_env_file(workdir: Option<&str>) -> Option<String> {
+    let path = PathBuf::from(workdir?).join(".env");
+    path.is_file().then(|| path.to_string_lossy().to_string())
+}
@@ -30,6 +30,8 @@ pub enum HyperVError {
     InvalidInput(String),
     /// Working directory not found
     WorkdirNotFound(String),
+    /// Env file not found
+    EnvFileNotFound(String),
     /// Invalid environment variable format
     InvalidEnvVar(String),
     /// Invalid log type
@@ -48,6 +50,18 @@ pub enum HyperVError {
     InterpreterNotFound(String),
     /// Invalid binary
     InvalidBinary(String),
+    /// Request rejected or failed inside the daemon
+    Daemon(String),
+    /// Secret reference or secret store error
+    Secret(String),
+    /// User to run a task as does not exist
+    UserNotFound(String),
+    /// Group to run a task as does not exist
+    GroupNotFound(String),
+    /// Switching a task to its user and group failed
+    PrivilegeDrop(String),
+    /// Resource limits of a task cannot be applied
+    ResourceLimit(String),
 }
 
 impl fmt::Display for HyperVError {
@@ -68,6 +82,7 @@ impl fmt::Display for HyperVError {
             HyperVError::LogError(msg) => write!(f, "Log error: {}", msg),
             HyperVError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
             HyperVError::WorkdirNotFound(dir) => write!(f, "Working directory not found: {}", dir),
+            HyperVError::EnvFileNotFound(path) => write!(f, "Env file not found: {}", path),
             HyperVError::InvalidEnvVar(var) => {
                 write!(f, "Invalid environment variable format: {}", var)
             }
@@ -85,6 +100,14 @@ impl fmt::Display for HyperVError {
                 write!(f, "Interpreter not found: {}", interpreter)
             }
             HyperVError::InvalidBinary(msg) => write!(f, "Invalid binary: {}", msg),
+            HyperVError::Daemon(msg) => write!(f, "{}", msg),
+            HyperVError::Secret(msg) => write!(f, "Secret error: {}", msg),
+            HyperVError::UserNotFound(user) => write!(f, "User not found: {}", user),
+            HyperVError::GroupNotFound(group) => write!(f, "Group not found: {}", group),
+            HyperVError::PrivilegeDrop(msg) => {
+                write!(f, "Failed to switch user and group: {}", msg)
+            }
+            HyperVError::ResourceLimit(msg) => write!(f, "Resource limit error: {}", msg),
         }
     }
 }
@@ -0,0 +1,698 @@
+//! Forwarding captured log lines to syslog and HTTP collectors
+//!
+//! The daemon follows the log files of every task that has forwarding targets and
+//! queues each new line for those targets. Every target has a bounded queue and a
+//! thread of its own that sends lines in batches, so a slow or unreachable
+//! collector neither blocks the daemon nor holds up other targets. While a target
+//! is down its lines are kept (up to [`FORWARD_BUFFER_LINES`], dropping the oldest)
+//! and sent again with an increasing delay.
+//!
+//! Targets are written as URLs:
+//!
+//! - `udp://host:514`, `tcp://host:514` and `unix:///dev/log` send RFC 5424 syslog
+//!   messages (octet-counted on TCP). `?facility=local0` picks the facility, `user`
+//!   by default.
+//! - `http://...` and `https://...` receive newline-delimited JSON, one object per
+//!   line, POSTed a batch at a time.
+//!
+//! Lines written while no daemon is running are not forwarded.
+
+use crate::error::{HyperVError, Result};
+use crate::logs::{FollowedLog, JsonFieldMap, LogRecord, LogStream, level_name};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Lines sent to a target at once
+pub const FORWARD_BATCH_LINES: usize = 100;
+
+/// Lines kept per target while it cannot be reached
+pub const FORWARD_BUFFER_LINES: usize = 10_000;
+
+/// Longest a line waits for a batch to fill up
+const FORWARD_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Delay before the first retry; doubled after every failure up to the maximum
+const FORWARD_RETRY_DELAY: Duration = Duration::from_secs(1);
+const FORWARD_MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Connect, read and write timeout for remote targets
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the followed log files are checked for new lines
+const FORWARD_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Longest syslog message sent over UDP
+const MAX_UDP_MESSAGE: usize = 60_000;
+
+/// Transport of a forwarding target
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ForwardTransport {
+    SyslogUdp(String),
+    SyslogTcp(String),
+    SyslogUnix(PathBuf),
+    Http(String),
+}
+
+/// Where captured lines are forwarded, written as a URL
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ForwardTarget {
+    url: String,
+    pub transport: ForwardTransport,
+    /// Syslog facility code
+    pub facility: u8,
+}
+
+impl std::str::FromStr for ForwardTarget {
+    type Err = HyperVError;
+
+    fn from_str(url: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            HyperVError::InvalidInput(format!(
+                "invalid log forwarding target {:?}: {}",
+                url, reason
+            ))
+        };
+        let (scheme, rest) = url.split_once("://").ok_or_else(|| {
+            invalid("expected udp://host:port, tcp://host:port, unix:///path or an http(s) URL")
+        })?;
+        if matches!(scheme, "http" | "https") {
+            if rest.is_empty() {
+                return Err(invalid("missing host"));
+            }
+            return Ok(Self {
+                url: url.to_string(),
+                transport: ForwardTransport::Http(url.to_string()),
+                facility: 1,
+            });
+        }
+
+        let (location, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let mut facility = 1;
+        for parameter in query.split('&').filter(|p| !p.is_empty()) {
+            match parameter.split_once('=') {
+                Some(("facility", name)) => {
+                    facility = syslog_facility(name)
+                        .ok_or_else(|| invalid(&format!("unknown syslog facility {:?}", name)))?;
+                }
+                _ => return Err(invalid(&format!("unknown parameter {:?}", parameter))),
+            }
+        }
+        let address = |location: &str| -> Result<String> {
+            if location.is_empty() {
+                return Err(invalid("missing host"));
+            }
+            // Syslog listens on 514 unless told otherwise
+            let has_port = location
+                .rsplit_once(':')
+                .is_some_and(|(host, port)| !host.ends_with(':') && port.parse::<u16>().is_ok());
+            Ok(if has_port {
+                location.to_string()
+            } else {
+                format!("{}:514", location)
+            })
+        };
+        let transport = match scheme {
+            "udp" => ForwardTransport::SyslogUdp(address(location)?),
+            "tcp" => ForwardTransport::SyslogTcp(address(location)?),
+            "unix" if location.starts_with('/') => {
+                ForwardTransport::SyslogUnix(PathBuf::from(location))
+            }
+            "unix" => {
+                return Err(invalid(
+                    "expected an absolute socket path like unix:///dev/log",
+                ));
+            }
+            _ => return Err(invalid(&format!("unsupported scheme {:?}", scheme))),
+        };
+        Ok(Self {
+            url: url.to_string(),
+            transport,
+            facility,
+        })
+    }
+}
+
+impl TryFrom<String> for ForwardTarget {
+    type Error = HyperVError;
+
+    fn try_from(url: String) -> Result<Self> {
+        url.parse()
+    }
+}
+
+impl From<ForwardTarget> for String {
+    fn from(target: ForwardTarget) -> String {
+        target.url
+    }
+}
+
+impl std::fmt::Display for ForwardTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
+
+fn syslog_facility(name: &str) -> Option<u8> {
+    let code = match name {
+        "kern" => 0,
+        "user" => 1,
+        "mail" => 2,
+        "daemon" => 3,
+        "auth" => 4,
+        "syslog" => 5,
+        "lpr" => 6,
+        "news" => 7,
+        "uucp" => 8,
+        "cron" => 9,
+        "authpriv" => 10,
+        "ftp" => 11,
+        _ => {
+            let local: u8 = name.strip_prefix("local")?.parse().ok()?;
+            return (local <= 7).then_some(16 + local);
+        }
+    };
+    Some(code)
+}
+
+/// One captured line on its way to a target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardedLine {
+    pub time: DateTime<Utc>,
+    pub host: String,
+    pub task: String,
+    pub stream: LogStream,
+    /// `error`, `warn` or `info` when the line has a recognizable level
+    pub level: Option<&'static str>,
+    pub message: String,
+}
+
+impl ForwardedLine {
+    pub fn new(host: &str, task: &str, record: LogRecord, field_map: &JsonFieldMap) -> Self {
+        Self {
+            time: record.time.unwrap_or_else(Utc::now),
+            host: host.to_string(),
+            task: task.to_string(),
+            stream: record.stream,
+            level: level_name(&record.message, field_map),
+            message: record.message,
+        }
+    }
+
+    /// Syslog severity: the line's own level, otherwise informational for stdout
+    /// and error for stderr
+    pub fn severity(&self) -> u8 {
+        match (self.level, self.stream) {
+            (Some("error"), _) => 3,
+            (Some("warn"), _) => 4,
+            (Some(_), _) | (None, LogStream::Stdout) => 6,
+            (None, LogStream::Stderr) => 3,
+        }
+    }
+
+    /// RFC 5424 message: `<PRI>1 TIMESTAMP HOST APP-NAME PROCID MSGID SD MSG`, with
+    /// the task as app name and the stream as message id
+    pub fn to_syslog(&self, facility: u8) -> String {
+        format!(
+            "<{}>1 {} {} {} - {} - {}",
+            u16::from(facility) * 8 + u16::from(self.severity()),
+            self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            syslog_name(&self.host, 255),
+            syslog_name(&self.task, 48),
+            self.stream.as_str(),
+            self.message
+        )
+    }
+
+    /// One line of newline-delimited JSON
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "time": self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "host": self.host,
+            "task": self.task,
+            "stream": self.stream.as_str(),
+            "level": self.level,
+            "message": self.message,
+        })
+        .to_string()
+    }
+}
+
+/// A syslog header field: printable ASCII without spaces, `-` when empty
+fn syslog_name(value: &str, max_len: usize) -> String {
+    let name: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max_len)
+        .collect();
+    if name.is_empty() {
+        "-".to_string()
+    } else {
+        name
+    }
+}
+
+/// A task whose logs are forwarded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardedTask {
+    pub id: String,
+    pub name: String,
+    pub stdout_path: PathBuf,
+    pub stderr_path: PathBuf,
+    pub targets: Vec<ForwardTarget>,
+    pub field_map: JsonFieldMap,
+}
+
+/// Follows the logs of forwarded tasks on a thread of its own
+pub struct LogForwarder {
+    updates: Sender<Vec<Followed>>,
+    current: Vec<ForwardedTask>,
+}
+
+impl LogForwarder {
+    pub fn start() -> Self {
+        let (updates, receiver) = mpsc::channel();
+        thread::spawn(move || follow_tasks(receiver));
+        Self {
+            updates,
+            current: Vec::new(),
+        }
+    }
+
+    /// Forward the logs of `tasks` from now on. Returns whether anything changed.
+    pub fn update(&mut self, tasks: Vec<ForwardedTask>) -> bool {
+        if tasks == self.current {
+            return false;
+        }
+        // New log files are opened here, so that lines written once this returns
+        // are forwarded
+        let followed = tasks
+            .iter()
+            .map(|task| {
+                let unchanged = self.current.iter().any(|current| {
+                    current.id == task.id
+                        && current.stdout_path == task.stdout_path
+                        && current.stderr_path == task.stderr_path
+                });
+                let logs = (!unchanged).then(|| {
+                    [
+                        (&task.stdout_path, LogStream::Stdout),
+                        (&task.stderr_path, LogStream::Stderr),
+                    ]
+                    .into_iter()
+                    .filter_map(|(path, stream)| match FollowedLog::open(path, stream) {
+                        Ok(log) => Some(log),
+                        Err(e) => {
+                            eprintln!("Cannot follow {} for forwarding: {}", path.display(), e);
+                            None
+                        }
+                    })
+                    .collect()
+                });
+                Followed {
+                    task: task.clone(),
+                    logs,
+                }
+            })
+            .collect();
+        self.current = tasks;
+        let _ = self.updates.send(followed);
+        true
+    }
+
+    pub fn tasks(&self) -> &[ForwardedTask] {
+        &self.current
+    }
+}
+
+/// Log files of one task being followed; `None` in an update keeps following the
+/// files the task had
+struct Followed {
+    task: ForwardedTask,
+    logs: Option<Vec<FollowedLog>>,
+}
+
+fn follow_tasks(updates: Receiver<Vec<Followed>>) {
+    let host = sysinfo::System::host_name().unwrap_or_else(|| "localhost".to_string());
+    let mut followed: Vec<Followed> = Vec::new();
+    let mut senders: HashMap<ForwardTarget, Arc<TargetQueue>> = HashMap::new();
+
+    loop {
+        let mut latest = None;
+        loop {
+            match updates.try_recv() {
+                Ok(tasks) => latest = Some(tasks),
+                Err(mpsc::TryRecvError::Empty) => break,
+                // The daemon is gone; stop sending once the queues are drained
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    for queue in senders.values() {
+                        queue.close();
+                    }
+                    return;
+                }
+            }
+        }
+        if let Some(tasks) = latest {
+            // Tasks whose log files did not change keep their read position
+            let mut previous: Vec<Followed> = std::mem::take(&mut followed);
+            for update in tasks {
+                let logs = match update.logs {
+                    Some(logs) => logs,
+                    None => previous
+                        .iter()
+                        .position(|f| f.task.id == update.task.id)
+                        .and_then(|index| previous.swap_remove(index).logs)
+                        .unwrap_or_default(),
+                };
+                followed.push(Followed {
+                    task: update.task,
+                    logs: Some(logs),
+                });
+            }
+
+            senders.retain(|target, queue| {
+                let used = followed.iter().any(|f| f.task.targets.contains(target));
+                if !used {
+                    queue.close();
+                }
+                used
+            });
+            for target in followed.iter().flat_map(|f| &f.task.targets) {
+                senders
+                    .entry(target.clone())
+                    .or_insert_with(|| TargetQueue::spawn(target.clone()));
+            }
+        }
+
+        for entry in &mut followed {
+            for log in entry.logs.iter_mut().flatten() {
+                for record in log.read_available() {
+                    let line =
+                        ForwardedLine::new(&host, &entry.task.name, record, &entry.task.field_map);
+                    for target in &entry.task.targets {
+                        if let Some(queue) = senders.get(target) {
+                            queue.push(line.clone());
+                        }
+                    }
+                }
+            }
+        }
+        thread::sleep(FORWARD_POLL_INTERVAL);
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    lines: VecDeque<ForwardedLine>,
+    /// Lines dropped because the queue was full, not reported yet
+    dropped: u64,
+    closed: bool,
+}
+
+/// Lines waiting to be sent to one target
+struct TargetQueue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+impl TargetQueue {
+    fn spawn(target: ForwardTarget) -> Arc<Self> {
+        let queue = Arc::new(Self {
+            state: Mutex::new(QueueState::default()),
+            ready: Condvar::new(),
+        });
+        let sender_queue = Arc::clone(&queue);
+        thread::spawn(move || sender_queue.send_loop(Connection::new(target)));
+        queue
+    }
+
+    fn push(&self, line: ForwardedLine) {
+        let mut state = self.state.lock().unwrap();
+        if state.lines.len() == FORWARD_BUFFER_LINES {
+            state.lines.pop_front();
+            state.dropped += 1;
+        }
+        state.lines.push_back(line);
+        // Wake the sender to start the flush timer, and again once a batch is full
+        if state.lines.len() == 1 || state.lines.len() == FORWARD_BATCH_LINES {
+            self.ready.notify_one();
+        }
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.ready.notify_one();
+    }
+
+    /// Take the next batch, once it is full or its first line has waited long
+    /// enough; `None` when the queue is closed and empty
+    fn next_batch(&self) -> Option<Vec<ForwardedLine>> {
+        let mut state = self.state.lock().unwrap();
+        let mut waiting_since: Option<Instant> = None;
+        loop {
+            if state.lines.is_empty() {
+                if state.closed {
+                    return None;
+                }
+                waiting_since = None;
+                state = self.ready.wait(state).unwrap();
+                continue;
+            }
+            let since = *waiting_since.get_or_insert_with(Instant::now);
+            let waited = since.elapsed();
+            if state.closed
+                || state.lines.len() >= FORWARD_BATCH_LINES
+                || waited >= FORWARD_FLUSH_INTERVAL
+            {
+                let count = state.lines.len().min(FORWARD_BATCH_LINES);
+                return Some(state.lines.drain(..count).collect());
+            }
+            state = self
+                .ready
+                .wait_timeout(state, FORWARD_FLUSH_INTERVAL - waited)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Send batches until the queue is closed. A batch that fails is held here and
+    /// sent again; lines arriving meanwhile wait in the bounded queue.
+    fn send_loop(&self, mut connection: Connection) {
+        let mut retry_delay = FORWARD_RETRY_DELAY;
+        let mut failing = false;
+        let mut batch = Vec::new();
+        loop {
+            if batch.is_empty() {
+                match self.next_batch() {
+                    Some(next) => batch = next,
+                    None => return,
+                }
+            }
+            match connection.send(&batch) {
+                Err(SendError::Rejected(e)) => {
+                    eprintln!(
+                        "⚠️  {} rejected {} log line(s), dropping them: {}",
+                        connection.target,
+                        batch.len(),
+                        e
+                    );
+                    batch.clear();
+                }
+                Ok(()) => {
+                    batch.clear();
+                    let dropped = std::mem::take(&mut self.state.lock().unwrap().dropped);
+                    if failing {
+                        println!("📡 Forwarding to {} resumed", connection.target);
+                        failing = false;
+                    }
+                    if dropped > 0 {
+                        eprintln!(
+                            "⚠️  Dropped {} log line(s) for {} while it was unreachable",
+                            dropped, connection.target
+                        );
+                    }
+                    retry_delay = FORWARD_RETRY_DELAY;
+                }
+                Err(SendError::Retry { sent, error: e }) => {
+                    // Datagrams that went out are not sent twice
+                    batch.drain(..sent);
+                    if !failing {
+                        eprintln!(
+                            "⚠️  Forwarding to {} failed, retrying: {}",
+                            connection.target, e
+                        );
+                        failing = true;
+                    }
+                    if self.state.lock().unwrap().closed {
+                        return;
+                    }
+                    thread::sleep(retry_delay);
+                    retry_delay = (retry_delay * 2).min(FORWARD_MAX_RETRY_DELAY);
+                }
+            }
+        }
+    }
+}
+
+/// Why a batch was not delivered
+#[derive(Debug)]
+enum SendError {
+    /// The first `sent` lines were delivered; the rest is sent again later
+    Retry { sent: usize, error: String },
+    /// The target refused the batch, and sending it again would not help
+    Rejected(String),
+}
+
+impl SendError {
+    fn retry(error: impl ToString) -> Self {
+        SendError::Retry {
+            sent: 0,
+            error: error.to_string(),
+        }
+    }
+}
+
+/// Sends batches to one target, reconnecting as needed
+struct Connection {
+    target: ForwardTarget,
+    tcp: Option<TcpStream>,
+    agent: Option<ureq::Agent>,
+}
+
+impl Connection {
+    fn new(target: ForwardTarget) -> Self {
+        Self {
+            target,
+            tcp: None,
+            agent: None,
+        }
+    }
+
+    fn send(&mut self, batch: &[ForwardedLine]) -> std::result::Result<(), SendError> {
+        let facility = self.target.facility;
+        match &self.target.transport {
+            ForwardTransport::SyslogUdp(address) => {
+                let address = address
+                    .to_socket_addrs()
+                    .map_err(SendError::retry)?
+                    .next()
+                    .ok_or_else(|| SendError::retry(format!("{} does not resolve", address)))?;
+                let bind = if address.is_ipv4() {
+                    "0.0.0.0:0"
+                } else {
+                    "[::]:0"
+                };
+                let socket = UdpSocket::bind(bind).map_err(SendError::retry)?;
+                for (sent, line) in batch.iter().enumerate() {
+                    let mut message = line.to_syslog(facility);
+                    message.truncate(message.floor_char_boundary(MAX_UDP_MESSAGE));
+                    socket
+                        .send_to(message.as_bytes(), address)
+                        .map_err(|e| SendError::Retry {
+                            sent,
+                            error: e.to_string(),
+                        })?;
+                }
+                Ok(())
+            }
+            ForwardTransport::SyslogTcp(address) => {
+                if self.tcp.is_none() {
+                    let address = address
+                        .to_socket_addrs()
+                        .map_err(SendError::retry)?
+                        .next()
+                        .ok_or_else(|| SendError::retry(format!("{} does not resolve", address)))?;
+                    let stream = TcpStream::connect_timeout(&address, FORWARD_TIMEOUT)
+                        .map_err(SendError::retry)?;
+                    stream
+                        .set_write_timeout(Some(FORWARD_TIMEOUT))
+                        .map_err(SendError::retry)?;
+                    self.tcp = Some(stream);
+                }
+                // RFC 6587 octet counting: the length of each message, a space, the message
+                let mut frames = Vec::new();
+                for line in batch {
+                    let message = line.to_syslog(facility);
+                    frames.extend_from_slice(format!("{} {}", message.len(), message).as_bytes());
+                }
+                let stream = self.tcp.as_mut().expect("connected above");
+                let written = stream.write_all(&frames).and_then(|()| stream.flush());
+                if let Err(e) = written {
+                    self.tcp = None;
+                    return Err(SendError::retry(e));
+                }
+                Ok(())
+            }
+            ForwardTransport::SyslogUnix(path) => send_unix(path, batch, facility),
+            ForwardTransport::Http(url) => {
+                let agent = self.agent.get_or_insert_with(|| {
+                    ureq::AgentBuilder::new()
+                        .timeout_connect(FORWARD_TIMEOUT)
+                        .timeout_read(FORWARD_TIMEOUT)
+                        .timeout_write(FORWARD_TIMEOUT)
+                        .build()
+                });
+                let mut body = String::new();
+                for line in batch {
+                    body.push_str(&line.to_json());
+                    body.push('\n');
+                }
+                agent
+                    .post(url)
+                    .set("Content-Type", "application/x-ndjson")
+                    .send_string(&body)
+                    .map(|_| ())
+                    .map_err(|e| match e {
+                        // A client error other than a timeout or rate limit comes back
+                        // the same however often the batch is sent
+                        ureq::Error::Status(status, _)
+                            if (400..500).contains(&status) && status != 408 && status != 429 =>
+                        {
+                            SendError::Rejected(format!("HTTP status {}", status))
+                        }
+                        ureq::Error::Status(status, _) => {
+                            SendError::retry(format!("HTTP status {}", status))
+                        }
+                        ureq::Error::Transport(e) => SendError::retry(e),
+                    })
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send_unix(
+    path: &std::path::Path,
+    batch: &[ForwardedLine],
+    facility: u8,
+) -> std::result::Result<(), SendError> {
+    let socket = std::os::unix::net::UnixDatagram::unbound().map_err(SendError::retry)?;
+    for (sent, line) in batch.iter().enumerate() {
+        socket
+            .send_to(line.to_syslog(facility).as_bytes(), path)
+            .map_err(|e| SendError::Retry {
+                sent,
+                error: e.to_string(),
+            })?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_unix(
+    _path: &std::path::Path,
+    _batch: &[ForwardedLine],
+    _facility: u8,
+) -> std::result::Result<(), SendError> {
+    Err(SendError::Rejected(
+        "Unix sockets are not supported on this platform".to_string(),
+    ))
+}
@@ -0,0 +1,477 @@
+//! Health checks for running tasks
+//!
+//! A live PID only proves that a process exists. Health checks probe the service
+//! itself (an HTTP endpoint, a TCP port, or a command) on a fixed interval; a task
+//! that fails `retries` probes in a row is marked unhealthy and restarted by the
+//! daemon. Probes run off the daemon loop, and their schedule is process-local
+//! like the alert state: restarting the daemon starts a fresh schedule.
+
+use crate::manager::TaskManager;
+use crate::secrets::{self, SecretStore};
+use crate::task::{Task, TaskStatus};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const DEFAULT_INTERVAL_SECS: u64 = 10;
+const DEFAULT_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_HTTP_STATUS: u16 = 200;
+const EXEC_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+fn default_interval_secs() -> u64 {
+    DEFAULT_INTERVAL_SECS
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+fn default_retries() -> u32 {
+    DEFAULT_RETRIES
+}
+
+fn default_http_status() -> u16 {
+    DEFAULT_HTTP_STATUS
+}
+
+/// What a health check probes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum HealthProbe {
+    /// GET `url` and expect `expected_status`
+    Http {
+        url: String,
+        #[serde(default = "default_http_status")]
+        expected_status: u16,
+    },
+    /// Open a TCP connection to `address` (`host:port`)
+    Tcp { address: String },
+    /// Run `command` in the task's working directory and environment and expect `expected_exit_code`
+    Exec {
+        command: Vec<String>,
+        #[serde(default)]
+        expected_exit_code: i32,
+    },
+}
+
+impl std::fmt::Display for HealthProbe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HealthProbe::Http {
+                url,
+                expected_status,
+            } => write!(f, "http GET {} (expect {})", url, expected_status),
+            HealthProbe::Tcp { address } => write!(f, "tcp {}", address),
+            HealthProbe::Exec {
+                command,
+                expected_exit_code,
+            } => write!(f, "exec {:?} (expect exit {})", command, expected_exit_code),
+        }
+    }
+}
+
+/// Health check configuration for a task
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HealthCheck {
+    #[serde(flatten)]
+    pub probe: HealthProbe,
+    /// Seconds between probes
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    /// Seconds before a single probe counts as failed
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Consecutive failures before the task is unhealthy
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Grace period after start during which failures are not counted
+    #[serde(default)]
+    pub start_period_secs: u64,
+}
+
+impl HealthCheck {
+    pub fn new(probe: HealthProbe) -> Self {
+        Self {
+            probe,
+            interval_secs: DEFAULT_INTERVAL_SECS,
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+            retries: DEFAULT_RETRIES,
+            start_period_secs: 0,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs.max(1))
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.max(1))
+    }
+}
+
+/// Health of a running task
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// No probe has passed yet
+    Starting,
+    Healthy,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    pub fn display_with_icon(&self) -> &'static str {
+        match self {
+            HealthStatus::Starting => "⏳ starting",
+            HealthStatus::Healthy => "💚 healthy",
+            HealthStatus::Unhealthy => "💔 unhealthy",
+        }
+    }
+}
+
+impl std::fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display_with_icon())
+    }
+}
+
+/// Latest health check results for the current run of a task
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HealthState {
+    pub status: HealthStatus,
+    pub consecutive_failures: u32,
+    #[serde(default)]
+    pub last_checked_at: Option<String>,
+    /// Why the last probe failed
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+impl HealthState {
+    pub fn starting() -> Self {
+        Self {
+            status: HealthStatus::Starting,
+            consecutive_failures: 0,
+            last_checked_at: None,
+            last_error: None,
+        }
+    }
+
+    /// Apply one probe result. Failures inside the start period are reported but not
+    /// counted. Returns `true` when this result made the task unhealthy.
+    pub fn record(
+        &mut self,
+        check: &HealthCheck,
+        result: &std::result::Result<(), String>,
+        in_start_period: bool,
+        now: DateTime<Utc>,
+    ) -> bool {
+        self.last_checked_at = Some(now.to_rfc3339());
+        match result {
+            Ok(()) => {
+                self.status = HealthStatus::Healthy;
+                self.consecutive_failures = 0;
+                self.last_error = None;
+                false
+            }
+            Err(error) => {
+                self.last_error = Some(error.clone());
+                if in_start_period {
+                    return false;
+                }
+                self.consecutive_failures += 1;
+                let was_unhealthy = self.status == HealthStatus::Unhealthy;
+                if self.consecutive_failures >= check.retries.max(1) {
+                    self.status = HealthStatus::Unhealthy;
+                }
+                !was_unhealthy && self.status == HealthStatus::Unhealthy
+            }
+        }
+    }
+}
+
+/// A probe to run for one task, detached from the task list so it can run on another thread
+#[derive(Debug, Clone)]
+pub struct HealthProbeJob {
+    pub task_id: String,
+    pub pid: u32,
+    pub check: HealthCheck,
+    pub workdir: Option<String>,
+    pub env: HashMap<String, String>,
+    pub env_files: Vec<String>,
+    /// Store the `secret:` references in `env` are resolved from
+    pub secret_store: SecretStore,
+    /// Identity exec probes run as, the same as the task's
+    #[cfg(unix)]
+    pub run_as: std::result::Result<Option<crate::privileges::RunAs>, String>,
+}
+
+/// Outcome of a finished probe
+#[derive(Debug, Clone)]
+pub struct HealthProbeResult {
+    pub task_id: String,
+    pub pid: u32,
+    pub result: std::result::Result<(), String>,
+}
+
+impl HealthProbeJob {
+    pub fn for_task(task: &Task, secret_store: &SecretStore) -> Option<Self> {
+        if task.status != TaskStatus::Running {
+            return None;
+        }
+        Some(Self {
+            task_id: task.id.clone(),
+            pid: task.pid?,
+            check: task.health_check.clone()?,
+            workdir: task.workdir.clone(),
+            env: task.env.clone(),
+            env_files: task.env_files.clone(),
+            secret_store: secret_store.clone(),
+            #[cfg(unix)]
+            run_as: crate::privileges::RunAs::for_task(task).map_err(|e| e.to_string()),
+        })
+    }
+
+    /// Run the probe, blocking for at most the check timeout
+    pub fn run(self) -> HealthProbeResult {
+        let result = self.probe_env().and_then(|env| self.run_with_env(&env));
+        HealthProbeResult {
+            task_id: self.task_id,
+            pid: self.pid,
+            result,
+        }
+    }
+
+    /// The environment the task itself was started with: env files, `env`, and
+    /// resolved secrets
+    fn probe_env(&self) -> std::result::Result<HashMap<String, String>, String> {
+        secrets::resolve_env(
+            &TaskManager::effective_env(&self.env, &self.env_files),
+            &self.secret_store,
+        )
+        .map_err(|e| format!("cannot resolve the task environment: {}", e))
+    }
+
+    #[cfg(unix)]
+    fn run_with_env(&self, env: &HashMap<String, String>) -> std::result::Result<(), String> {
+        let run_as = self.run_as.clone()?;
+        run_probe_with(
+            &self.check.probe,
+            self.check.timeout(),
+            self.workdir.as_deref(),
+            env,
+            &|cmd| {
+                if let Some(run_as) = &run_as {
+                    run_as.apply_to(cmd, env);
+                }
+            },
+        )
+    }
+
+    #[cfg(not(unix))]
+    fn run_with_env(&self, env: &HashMap<String, String>) -> std::result::Result<(), String> {
+        run_probe(
+            &self.check.probe,
+            self.check.timeout(),
+            self.workdir.as_deref(),
+            env,
+        )
+    }
+}
+
+/// Decides which tasks are due for a probe
+#[derive(Debug)]
+pub struct HealthScheduler {
+    next_due: HashMap<String, (u32, Instant)>,
+    in_flight: HashSet<String>,
+    secret_store: SecretStore,
+}
+
+impl HealthScheduler {
+    pub fn new(secret_store: SecretStore) -> Self {
+        Self {
+            next_due: HashMap::new(),
+            in_flight: HashSet::new(),
+            secret_store,
+        }
+    }
+
+    /// Probes that should start now. A task is probed again one interval after its
+    /// previous probe was scheduled, never while one is still in flight, and its
+    /// schedule restarts whenever its PID changes.
+    pub fn due_jobs(&mut self, tasks: &[Task], now: Instant) -> Vec<HealthProbeJob> {
+        let mut jobs = Vec::new();
+        for job in tasks
+            .iter()
+            .filter_map(|task| HealthProbeJob::for_task(task, &self.secret_store))
+        {
+            if self.in_flight.contains(&job.task_id) {
+                continue;
+            }
+            let due = match self.next_due.get(&job.task_id) {
+                Some((pid, due)) if *pid == job.pid => *due <= now,
+                _ => true,
+            };
+            if due {
+                self.next_due
+                    .insert(job.task_id.clone(), (job.pid, now + job.check.interval()));
+                self.in_flight.insert(job.task_id.clone());
+                jobs.push(job);
+            }
+        }
+        let active: HashSet<&String> = tasks.iter().map(|t| &t.id).collect();
+        self.next_due.retain(|task_id, _| active.contains(task_id));
+        jobs
+    }
+
+    pub fn finished(&mut self, task_id: &str) {
+        self.in_flight.remove(task_id);
+    }
+}
+
+/// Run a single probe
+pub fn run_probe(
+    probe: &HealthProbe,
+    timeout: Duration,
+    workdir: Option<&str>,
+    env: &HashMap<String, String>,
+) -> std::result::Result<(), String> {
+    run_probe_with(probe, timeout, workdir, env, &|_| {})
+}
+
+/// [`run_probe`], letting `prepare` adjust the command of an exec probe before it is
+/// spawned (e.g. to run it as the task's user)
+pub fn run_probe_with(
+    probe: &HealthProbe,
+    timeout: Duration,
+    workdir: Option<&str>,
+    env: &HashMap<String, String>,
+    prepare: &dyn Fn(&mut Command),
+) -> std::result::Result<(), String> {
+    match probe {
+        HealthProbe::Http {
+            url,
+            expected_status,
+        } => probe_http(url, *expected_status, timeout),
+        HealthProbe::Tcp { address } => probe_tcp(address, timeout),
+        HealthProbe::Exec {
+            command,
+            expected_exit_code,
+        } => probe_exec(command, *expected_exit_code, timeout, workdir, env, prepare),
+    }
+}
+
+fn probe_http(
+    url: &str,
+    expected_status: u16,
+    timeout: Duration,
+) -> std::result::Result<(), String> {
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    let status = match agent.get(url).call() {
+        Ok(response) => response.status(),
+        Err(ureq::Error::Status(status, _)) => status,
+        Err(ureq::Error::Transport(err)) => return Err(err.to_string()),
+    };
+    if status == expected_status {
+        Ok(())
+    } else {
+        Err(format!(
+            "HTTP status {} (expected {})",
+            status, expected_status
+        ))
+    }
+}
+
+fn probe_tcp(address: &str, timeout: Duration) -> std::result::Result<(), String> {
+    use std::net::{TcpStream, ToSocketAddrs};
+    use std::sync::mpsc;
+
+    // Name resolution has no timeout of its own, so it runs on a thread that the probe
+    // stops waiting for at the deadline; the thread ends when the lookup does
+    let deadline = Instant::now() + timeout;
+    let (sender, receiver) = mpsc::channel();
+    let lookup = address.to_string();
+    std::thread::spawn(move || {
+        let _ = sender.send(lookup.to_socket_addrs().map(Vec::from_iter));
+    });
+    let addrs = match receiver.recv_timeout(timeout) {
+        Ok(addrs) => addrs.map_err(|e| format!("cannot resolve {}: {}", address, e))?,
+        Err(_) => {
+            return Err(format!(
+                "resolving {} timed out after {}s",
+                address,
+                timeout.as_secs()
+            ));
+        }
+    };
+
+    let mut last_error = format!("{} did not resolve to any address", address);
+    for addr in addrs {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(format!(
+                "connect to {} timed out after {}s",
+                address,
+                timeout.as_secs()
+            ));
+        }
+        match TcpStream::connect_timeout(&addr, remaining) {
+            Ok(_) => return Ok(()),
+            Err(e) => last_error = format!("connect to {} failed: {}", addr, e),
+        }
+    }
+    Err(last_error)
+}
+
+fn probe_exec(
+    command: &[String],
+    expected_exit_code: i32,
+    timeout: Duration,
+    workdir: Option<&str>,
+    env: &HashMap<String, String>,
+    prepare: &dyn Fn(&mut Command),
+) -> std::result::Result<(), String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| "health check command is empty".to_string())?;
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(workdir) = workdir {
+        cmd.current_dir(PathBuf::from(workdir));
+    }
+    prepare(&mut cmd);
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("failed to run {}: {}", program, e))?;
+
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                return match status.code() {
+                    Some(code) if code == expected_exit_code => Ok(()),
+                    Some(code) => Err(format!(
+                        "command exited with code {} (expected {})",
+                        code, expected_exit_code
+                    )),
+                    None => Err("command was killed by a signal".to_string()),
+                };
+            }
+            Ok(None) if start.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("command timed out after {}s", timeout.as_secs()));
+            }
+            Ok(None) => std::thread::sleep(EXEC_POLL_INTERVAL),
+            Err(e) => return Err(format!("failed to wait for command: {}", e)),
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Variable interpolation in compose files
+//!
+//! Every string value in a compose file may reference variables as `$VAR`,
+//! `${VAR}`, `${VAR:-default}` (also used when `VAR` is empty), `${VAR-default}`,
+//! `${VAR:?message}` or `${VAR?message}` (fail when unset). `$$` is a literal `$`.
+//! An unquoted value that is a single substitution is read as YAML once resolved,
+//! so `auto_restart: ${AUTO_RESTART:-true}` is a boolean; quoted values stay strings.
+
+use crate::error::{HyperVError, Result};
+use serde_yml::Value;
+use serde_yml::libyml::parser::{Event, Parser, ScalarStyle};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+/// A variable that was referenced without a default but is not set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsetVariable {
+    pub name: String,
+    /// Where it was referenced, e.g. `services.api.env.TOKEN`
+    pub location: String,
+}
+
+/// Interpolate one string. Returns the result and the unset variables it referenced.
+pub fn interpolate_str(
+    input: &str,
+    vars: &HashMap<String, String>,
+) -> std::result::Result<(String, Vec<String>), String> {
+    let mut output = String::with_capacity(input.len());
+    let mut unset = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((_, c)) = chars.next() {
+        if c != '$' {
+            output.push(c);
+            continue;
+        }
+        match chars.peek().map(|&(_, c)| c) {
+            Some('$') => {
+                chars.next();
+                output.push('$');
+            }
+            Some('{') => {
+                let (start, _) = chars.next().unwrap();
+                let mut depth = 1;
+                let mut end = None;
+                for (index, c) in chars.by_ref() {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                end = Some(index);
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                let end = end.ok_or_else(|| format!("unterminated \"${{\" in {:?}", input))?;
+                let expression = &input[start + 1..end];
+                output.push_str(&expand(expression, vars, &mut unset)?);
+            }
+            Some(c) if c == '_' || c.is_ascii_alphabetic() => {
+                let mut name = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c == '_' || c.is_ascii_alphanumeric() {
+                        name.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                match vars.get(&name) {
+                    Some(value) => output.push_str(value),
+                    None => unset.push(name),
+                }
+            }
+            _ => output.push('$'),
+        }
+    }
+    Ok((output, unset))
+}
+
+/// Expand the inside of a `${...}` expression
+fn expand(
+    expression: &str,
+    vars: &HashMap<String, String>,
+    unset: &mut Vec<String>,
+) -> std::result::Result<String, String> {
+    let name_len = expression
+        .find(|c: char| c != '_' && !c.is_ascii_alphanumeric())
+        .unwrap_or(expression.len());
+    let (name, modifier) = expression.split_at(name_len);
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(format!("invalid variable name in \"${{{}}}\"", expression));
+    }
+    let value = vars.get(name);
+    let is_empty = value.is_none_or(|v| v.is_empty());
+
+    let (operator, argument) = if let Some(rest) = modifier.strip_prefix(":-") {
+        (":-", rest)
+    } else if let Some(rest) = modifier.strip_prefix(":?") {
+        (":?", rest)
+    } else if let Some(rest) = modifier.strip_prefix('-') {
+        ("-", rest)
+    } else if let Some(rest) = modifier.strip_prefix('?') {
+        ("?", rest)
+    } else if modifier.is_empty() {
+        ("", "")
+    } else {
+        return Err(format!("invalid substitution \"${{{}}}\"", expression));
+    };
+
+    let use_fallback = match operator {
+        ":-" | ":?" => is_empty,
+        "-" | "?" => value.is_none(),
+        _ => false,
+    };
+    if !use_fallback {
+        return match value {
+            Some(value) => Ok(value.clone()),
+            None => {
+                unset.push(name.to_string());
+                Ok(String::new())
+            }
+        };
+    }
+    if operator.ends_with('?') {
+        let state = if value.is_none() { "not set" } else { "empty" };
+        return Err(if argument.is_empty() {
+            format!("required variable {} is {}", name, state)
+        } else {
+            format!("required variable {} is {}: {}", name, state, argument)
+        });
+    }
+    // Defaults may themselves reference variables
+    let (default, nested_unset) = interpolate_str(argument, vars)?;
+    unset.extend(nested_unset);
+    Ok(default)
+}
+
+/// Parse a YAML document and interpolate every string in it
+pub fn interpolate_yaml(
+    content: &str,
+    vars: &HashMap<String, String>,
+) -> Result<(Value, Vec<UnsetVariable>)> {
+    let mut document: Value = serde_yml::from_str(content)
+        .map_err(|e| HyperVError::InvalidInput(format!("Failed to parse YAML: {}", e)))?;
+    let retyped = plain_substitutions(content);
+    let mut unset = Vec::new();
+    walk(&mut document, vars, &retyped, &mut Vec::new(), &mut unset)?;
+    Ok((document, unset))
+}
+
+fn walk(
+    value: &mut Value,
+    vars: &HashMap<String, String>,
+    retyped: &HashSet<String>,
+    path: &mut Vec<String>,
+    unset: &mut Vec<UnsetVariable>,
+) -> Result<()> {
+    match value {
+        Value::String(s) => {
+            let (resolved, names) = interpolate_str(s, vars)
+                .map_err(|e| HyperVError::InvalidInput(format!("{}: {}", describe(path), e)))?;
+            unset.extend(names.into_iter().map(|name| UnsetVariable {
+                name,
+                location: location(path),
+            }));
+            *value = if retyped.contains(&location(path)) {
+                scalar(resolved)
+            } else {
+                Value::String(resolved)
+            };
+        }
+        Value::Sequence(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                path.push(format!("[{}]", index));
+                walk(item, vars, retyped, path, unset)?;
+                path.pop();
+            }
+        }
+        Value::Mapping(map) => {
+            for (key, item) in map.iter_mut() {
+                let key = match key {
+                    Value::String(s) => s.clone(),
+                    other => serde_yml::to_string(other)
+                        .map(|s| s.trim().to_string())
+                        .unwrap_or_default(),
+                };
+                path.push(key);
+                walk(item, vars, retyped, path, unset)?;
+                path.pop();
+            }
+        }
+        Value::Tagged(tagged) => walk(&mut tagged.value, vars, retyped, path, unset)?,
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+    Ok(())
+}
+
+/// A resolved substitution read as a plain YAML scalar: a boolean or a number if it
+/// looks like one, else the string itself
+fn scalar(resolved: String) -> Value {
+    match serde_yml::from_str::<Value>(&resolved) {
+        Ok(value @ (Value::Bool(_) | Value::Number(_))) => value,
+        _ => Value::String(resolved),
+    }
+}
+
+/// Position of a node in its parent collection while scanning YAML events
+enum Frame {
+    Mapping { key: Option<String> },
+    Sequence { next: usize },
+}
+
+/// Locations of the unquoted values in `content` that are a single substitution,
+/// e.g. `auto_restart: ${AUTO_RESTART}`. Only the scanner knows how a scalar was
+/// quoted; the parsed document does not.
+fn plain_substitutions(content: &str) -> HashSet<String> {
+    let mut found = HashSet::new();
+    let mut parser = Parser::new(Cow::Borrowed(content.as_bytes()));
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    while let Ok((event, _)) = parser.parse_next_event() {
+        match event {
+            Event::StreamEnd => break,
+            Event::Scalar(scalar) => {
+                let text = String::from_utf8_lossy(&scalar.value).into_owned();
+                if let Some(Frame::Mapping { key: key @ None }) = frames.last_mut() {
+                    *key = Some(text);
+                    continue;
+                }
+                let segment = next_segment(&mut frames);
+                if scalar.style == ScalarStyle::Plain && is_single_substitution(&text) {
+                    path.extend(segment);
+                    found.insert(location(&path));
+                    if !frames.is_empty() {
+                        path.pop();
+                    }
+                }
+            }
+            Event::Alias(_) => {
+                if let Some(Frame::Mapping { key: key @ None }) = frames.last_mut() {
+                    *key = Some(String::new());
+                } else {
+                    next_segment(&mut frames);
+                }
+            }
+            Event::SequenceStart(_) | Event::MappingStart(_) => {
+                // A collection used as a mapping key gets a placeholder segment
+                let segment = match frames.last_mut() {
+                    Some(Frame::Mapping { key: key @ None }) => {
+                        *key = Some("?".to_string());
+                        Some("?".to_string())
+                    }
+                    _ => next_segment(&mut frames),
+                };
+                path.extend(segment);
+                frames.push(match event {
+                    Event::SequenceStart(_) => Frame::Sequence { next: 0 },
+                    _ => Frame::Mapping { key: None },
+                });
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                frames.pop();
+                if !frames.is_empty() {
+                    path.pop();
+                }
+            }
+            Event::StreamStart | Event::DocumentStart | Event::DocumentEnd => {}
+        }
+    }
+    found
+}
+
+/// Path segment of the next value in the innermost collection; `None` at the top level
+fn next_segment(frames: &mut [Frame]) -> Option<String> {
+    match frames.last_mut()? {
+        Frame::Mapping { key } => key.take(),
+        Frame::Sequence { next } => {
+            *next += 1;
+            Some(format!("[{}]", *next - 1))
+        }
+    }
+}
+
+/// `${...}` or `$NAME` with nothing around it
+fn is_single_substitution(s: &str) -> bool {
+    if let Some(inner) = s.strip_prefix("${") {
+        let mut depth = 1;
+        for (index, c) in inner.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return index + 1 == inner.len();
+                    }
+                }
+                _ => {}
+            }
+        }
+        false
+    } else if let Some(name) = s.strip_prefix('$') {
+        name.starts_with(|c: char| c == '_' || c.is_ascii_alphabetic())
+            && name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric())
+    } else {
+        false
+    }
+}
+
+/// `services.api.args[0]`
+fn location(path: &[String]) -> String {
+    let mut out = String::new();
+    for segment in path {
+        if !out.is_empty() && !segment.starts_with('[') {
+            out.push('.');
+        }
+        out.push_str(segment);
+    }
+    out
+}
+
+/// Name the service and key an error is about
+fn describe(path: &[String]) -> String {
+    match path {
+        [services, service, rest @ ..] if services == "services" && !rest.is_empty() => {
+            format!("Service \"{}\", key \"{}\"", service, location(rest))
+        }
+        _ => format!("Key \"{}\"", location(path)),
+    }
+}
@@ -6,14 +6,27 @@
 #![allow(non_snake_case)]
 
 pub mod alerts;
+pub mod bundle;
+pub mod cgroup;
 pub mod cli;
 pub mod compose;
 pub mod config;
+pub mod control;
+pub mod dotenv;
 pub mod error;
+pub mod forward;
+pub mod health;
+pub mod interpolation;
+pub mod limits;
 pub mod logs;
 pub mod manager;
+#[cfg(unix)]
+pub mod privileges;
 pub mod process;
+pub mod secrets;
 pub mod task;
+pub mod usage;
+pub mod watchdog;
 
 pub use error::{HyperVError, Result};
 pub use manager::TaskManager;
@@ -23,18 +36,27 @@ pub use task::{Task, TaskStatus};
 pub mod constants {
     use std::time::Duration;
 
-    /// Maximum log file size before rotation (10MB)
+    /// Default log file size before rotation (10MB); see `logs::LogSettings`
     pub const MAX_LOG_SIZE: u64 = 10 * 1024 * 1024;
 
-    /// Number of compressed rotated log archives to retain per log file
+    /// Default number of rotated log archives to retain per log file
     pub const MAX_LOG_ARCHIVES: usize = 5;
 
-    /// Maximum number of automatic restart attempts
+    /// Default maximum number of automatic restart attempts
     pub const MAX_RESTART_ATTEMPTS: u32 = 5;
 
-    /// Delay between restart attempts
+    /// Default delay before the first restart attempt
     pub const RESTART_DELAY: Duration = Duration::from_secs(1);
 
+    /// Default factor applied to the restart delay after each attempt
+    pub const RESTART_BACKOFF_MULTIPLIER: f64 = 2.0;
+
+    /// Default upper bound for the restart delay
+    pub const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+    /// Default run time after which a task counts as stable and its restart count is reset
+    pub const RESTART_STABLE_AFTER: Duration = Duration::from_secs(60);
+
     /// Log follow polling interval
     pub const LOG_FOLLOW_INTERVAL: Duration = Duration::from_millis(100);
 
@@ -44,6 +66,9 @@ pub mod constants {
     /// Main loop interval for checking and restarting tasks
     pub const MAIN_LOOP_INTERVAL: Duration = Duration::from_secs(5);
 
+    /// Events kept in the history of each task
+    pub const MAX_TASK_EVENTS: usize = 20;
+
     /// Default number of log lines to show
     pub const DEFAULT_LOG_LINES: usize = 50;
 }
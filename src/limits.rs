@@ -0,0 +1,211 @@
+//! Per-task resource limits
+//!
+//! Limits are applied with `setrlimit` in the child process, after fork and before
+//! exec, so they bind the task and everything it spawns but never hyperV itself.
+
+use crate::error::{HyperVError, Result};
+use crate::logs::{deserialize_size, format_bytes};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::time::Duration;
+
+/// Resource limits of a task; unset limits are inherited from hyperV
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceLimits {
+    /// Open file descriptors (`RLIMIT_NOFILE`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_files: Option<u64>,
+    /// Virtual memory in bytes (`RLIMIT_AS`); sizes like `2GB` are accepted
+    #[serde(
+        deserialize_with = "deserialize_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub address_space: Option<u64>,
+    /// Largest core dump in bytes (`RLIMIT_CORE`); 0 turns core dumps off
+    #[serde(
+        deserialize_with = "deserialize_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub core_size: Option<u64>,
+    /// Processes and threads of the task's user (`RLIMIT_NPROC`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processes: Option<u64>,
+    /// CPU seconds (`RLIMIT_CPU`); the process gets `SIGXCPU` once it used them,
+    /// and `SIGKILL` a second later
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_time: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Reject limits that would keep the task from starting at all
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let invalid = |field: &str| Err(format!("{} must be greater than 0", field));
+        if self.open_files == Some(0) {
+            return invalid("open_files");
+        }
+        if self.address_space == Some(0) {
+            return invalid("address_space");
+        }
+        if self.processes == Some(0) {
+            return invalid("processes");
+        }
+        if self.cpu_time == Some(0) {
+            return invalid("cpu_time");
+        }
+        Ok(())
+    }
+
+    /// Why a process under these limits was killed by `signal`, when a limit explains it.
+    /// `cpu_used` is the CPU time the process had used, when known.
+    pub fn violation(&self, signal: i32, cpu_used: Option<Duration>) -> Option<String> {
+        #[cfg(unix)]
+        {
+            if signal == libc::SIGXCPU
+                && let Some(cpu_time) = self.cpu_time
+            {
+                return Some(format!("CPU time limit of {}s reached", cpu_time));
+            }
+            // A process that ignores SIGXCPU gets SIGKILL at the hard limit
+            if signal == libc::SIGKILL
+                && let Some(cpu_time) = self.cpu_time
+                && cpu_used.is_some_and(|used| used >= Duration::from_secs(cpu_time))
+            {
+                return Some(format!(
+                    "CPU time limit of {}s likely reached; SIGXCPU was ignored",
+                    cpu_time
+                ));
+            }
+            if [libc::SIGSEGV, libc::SIGBUS, libc::SIGABRT].contains(&signal)
+                && let Some(address_space) = self.address_space
+            {
+                return Some(format!(
+                    "possibly out of memory under the address space limit of {}",
+                    format_bytes(address_space)
+                ));
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = (signal, cpu_used);
+        None
+    }
+
+    /// `(field, resource, soft, hard)` for each limit that is set
+    #[cfg(unix)]
+    fn rlimits(&self) -> Vec<(&'static str, RlimitResource, u64, u64)> {
+        let mut limits = Vec::new();
+        if let Some(n) = self.open_files {
+            limits.push(("open_files", libc::RLIMIT_NOFILE, n, n));
+        }
+        if let Some(bytes) = self.address_space {
+            limits.push(("address_space", libc::RLIMIT_AS, bytes, bytes));
+        }
+        if let Some(bytes) = self.core_size {
+            limits.push(("core_size", libc::RLIMIT_CORE, bytes, bytes));
+        }
+        if let Some(n) = self.processes {
+            limits.push(("processes", libc::RLIMIT_NPROC, n, n));
+        }
+        if let Some(secs) = self.cpu_time {
+            // The hard limit one second later kills a process that ignores SIGXCPU
+            limits.push(("cpu_time", libc::RLIMIT_CPU, secs, secs.saturating_add(1)));
+        }
+        limits
+    }
+
+    /// Fail unless hyperV may set these limits: raising a hard limit takes root
+    #[cfg(unix)]
+    pub fn check_permitted(&self) -> Result<()> {
+        if unsafe { libc::geteuid() } == 0 {
+            return Ok(());
+        }
+        for (name, resource, _, hard) in self.rlimits() {
+            let mut current = libc::rlimit {
+                rlim_cur: 0,
+                rlim_max: 0,
+            };
+            if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+                continue;
+            }
+            let max = current.rlim_max;
+            if max != libc::RLIM_INFINITY && hard > max {
+                return Err(HyperVError::ResourceLimit(format!(
+                    "{} limit {} is above the hard limit {} of hyperV; only root can raise it",
+                    name, hard, max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Make `cmd` apply these limits before exec
+    #[cfg(unix)]
+    pub fn apply_to(&self, cmd: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        let limits = self.rlimits();
+        if limits.is_empty() {
+            return;
+        }
+        // Only async-signal-safe calls between fork and exec
+        unsafe {
+            cmd.pre_exec(move || {
+                for (_, resource, soft, hard) in &limits {
+                    let limit = libc::rlimit {
+                        rlim_cur: *soft as libc::rlim_t,
+                        rlim_max: *hard as libc::rlim_t,
+                    };
+                    if libc::setrlimit(*resource, &limit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Limits cannot be applied on this platform
+    #[cfg(not(unix))]
+    pub fn check_permitted(&self) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        Err(HyperVError::ResourceLimit(
+            "resource limits are only supported on Unix".to_string(),
+        ))
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply_to(&self, _cmd: &mut Command) {}
+}
+
+#[cfg(all(unix, target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type RlimitResource = libc::c_int;
+
+impl std::fmt::Display for ResourceLimits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(n) = self.open_files {
+            parts.push(format!("open files {}", n));
+        }
+        if let Some(bytes) = self.address_space {
+            parts.push(format!("address space {}", format_bytes(bytes)));
+        }
+        if let Some(bytes) = self.core_size {
+            parts.push(format!("core size {}", format_bytes(bytes)));
+        }
+        if let Some(n) = self.processes {
+            parts.push(format!("processes {}", n));
+        }
+        if let Some(secs) = self.cpu_time {
+            parts.push(format!("CPU time {}s", secs));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
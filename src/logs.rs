@@ -1,18 +1,33 @@
 //! Log management for hyperV
 //!
 //! Handles log file rotation, reading, and real-time following functionality.
+//!
+//! Captured output is stored one record per line, prefixed with the time hyperV
+//! received it and the stream it came from:
+//!
+//! ```text
+//! 2026-05-21T07:00:00.123Z stdout service ready
+//! ```
+//!
+//! Lines without that prefix (written before it existed, or by a task writing to its
+//! log file directly) are still read; they just have no time of their own.
 
 use crate::constants::{LOG_FOLLOW_INTERVAL, MAX_LOG_ARCHIVES, MAX_LOG_SIZE};
 use crate::error::{HyperVError, Result};
+use crate::forward::ForwardTarget;
+use chrono::{DateTime, Local, SecondsFormat, Utc};
 use flate2::Compression;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::thread;
+use std::time::{Duration, SystemTime};
 
 /// Types of logs that can be viewed
 #[derive(Debug, Clone, PartialEq)]
@@ -35,23 +50,848 @@ impl std::str::FromStr for LogType {
     }
 }
 
+/// Log rotation and retention settings of a task, or the defaults for all tasks in
+/// `daemon.yaml`. Unset fields fall back to `daemon.yaml`, then to the built-in defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogSettings {
+    /// Rotate a log once it grows past this many bytes; sizes like `50MB` are accepted
+    #[serde(
+        deserialize_with = "deserialize_size",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_size: Option<u64>,
+    /// Rotated logs kept per stream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_archives: Option<usize>,
+    /// Rotated logs older than this many days are deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u64>,
+    /// Gzip rotated logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress: Option<bool>,
+    /// Where to find well-known fields in JSON log lines
+    #[serde(skip_serializing_if = "JsonFieldMap::is_empty")]
+    pub json: JsonFieldMap,
+    /// Collectors that receive every captured line; an empty list turns off
+    /// forwarding set up in `daemon.yaml`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forward: Option<Vec<ForwardTarget>>,
+}
+
+impl LogSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Reject settings that would keep no logs at all
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let invalid = |field: &str| Err(format!("{} must be greater than 0", field));
+        if self.max_size == Some(0) {
+            return invalid("max_size");
+        }
+        if self.max_archives == Some(0) {
+            return invalid("max_archives");
+        }
+        if self.max_age_days == Some(0) {
+            return invalid("max_age_days");
+        }
+        Ok(())
+    }
+
+    /// Forwarding targets, from these settings or else from `defaults`
+    pub fn forward_targets(&self, defaults: &LogSettings) -> Vec<ForwardTarget> {
+        self.forward
+            .as_ref()
+            .or(defaults.forward.as_ref())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// These settings with unset fields taken from `defaults`, then the built-in defaults
+    pub fn resolve(&self, defaults: &LogSettings) -> LogRetention {
+        let builtin = LogRetention::default();
+        LogRetention {
+            max_size: self
+                .max_size
+                .or(defaults.max_size)
+                .unwrap_or(builtin.max_size),
+            max_archives: self
+                .max_archives
+                .or(defaults.max_archives)
+                .unwrap_or(builtin.max_archives),
+            max_age_days: self.max_age_days.or(defaults.max_age_days),
+            compress: self
+                .compress
+                .or(defaults.compress)
+                .unwrap_or(builtin.compress),
+        }
+    }
+}
+
+impl std::fmt::Display for LogSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(max_size) = self.max_size {
+            parts.push(format!("max size {}", format_bytes(max_size)));
+        }
+        if let Some(max_archives) = self.max_archives {
+            parts.push(format!("{} archives", max_archives));
+        }
+        if let Some(max_age_days) = self.max_age_days {
+            parts.push(format!("kept {} days", max_age_days));
+        }
+        if let Some(compress) = self.compress {
+            parts.push(
+                if compress {
+                    "compressed"
+                } else {
+                    "uncompressed"
+                }
+                .to_string(),
+            );
+        }
+        if !self.json.is_empty() {
+            parts.push(format!("JSON fields {}", self.json));
+        }
+        match self.forward.as_deref() {
+            Some([]) => parts.push("not forwarded".to_string()),
+            Some(targets) => {
+                let targets: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+                parts.push(format!("forwarded to {}", targets.join(", ")));
+            }
+            None => {}
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Where to find well-known fields in JSON log lines, as dotted paths such as
+/// `fields.message`. Fields left unset are looked up under the names used by
+/// common loggers: Go zap, Rust tracing-subscriber, Serilog compact JSON, pino,
+/// logrus and Elastic Common Schema.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsonFieldMap {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logger: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_type: Option<String>,
+}
+
+/// Names tried, in order, for fields a [`JsonFieldMap`] leaves unset
+const TIMESTAMP_FIELDS: &[&str] = &["timestamp", "time", "ts", "@t", "@timestamp"];
+const LEVEL_FIELDS: &[&str] = &["level", "lvl", "severity", "@l", "log.level", "levelname"];
+const MESSAGE_FIELDS: &[&str] = &["msg", "message", "@m", "fields.message", "@mt"];
+const LOGGER_FIELDS: &[&str] = &[
+    "logger",
+    "target",
+    "SourceContext",
+    "logger_name",
+    "log.logger",
+    "name",
+];
+const TRACE_ID_FIELDS: &[&str] = &[
+    "trace_id",
+    "traceId",
+    "TraceId",
+    "@tr",
+    "trace.id",
+    "span.trace_id",
+    "fields.trace_id",
+];
+const ERROR_TYPE_FIELDS: &[&str] = &[
+    "error_type",
+    "error.type",
+    "errorType",
+    "exception.type",
+    "ExceptionType",
+    "err.type",
+    "fields.error_type",
+];
+/// Error text whose leading `Type: message` names the error type
+const ERROR_TEXT_FIELDS: &[&str] = &["@x", "exception", "error", "err", "fields.error"];
+
+impl JsonFieldMap {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// These paths, with unset fields taken from `defaults`
+    pub fn or(&self, defaults: &JsonFieldMap) -> JsonFieldMap {
+        let pick = |own: &Option<String>, default: &Option<String>| own.clone().or(default.clone());
+        JsonFieldMap {
+            timestamp: pick(&self.timestamp, &defaults.timestamp),
+            level: pick(&self.level, &defaults.level),
+            message: pick(&self.message, &defaults.message),
+            logger: pick(&self.logger, &defaults.logger),
+            trace_id: pick(&self.trace_id, &defaults.trace_id),
+            error_type: pick(&self.error_type, &defaults.error_type),
+        }
+    }
+
+    /// Set one field from a `FIELD=PATH` command-line value
+    pub fn set(&mut self, assignment: &str) -> Result<()> {
+        let invalid = || {
+            HyperVError::InvalidInput(format!(
+                "invalid JSON field mapping {:?} (expected FIELD=PATH, where FIELD is timestamp, level, message, logger, trace_id or error_type)",
+                assignment
+            ))
+        };
+        let (field, path) = assignment.split_once('=').ok_or_else(invalid)?;
+        let slot = match field {
+            "timestamp" => &mut self.timestamp,
+            "level" => &mut self.level,
+            "message" => &mut self.message,
+            "logger" => &mut self.logger,
+            "trace_id" => &mut self.trace_id,
+            "error_type" => &mut self.error_type,
+            _ => return Err(invalid()),
+        };
+        if path.is_empty() {
+            return Err(invalid());
+        }
+        *slot = Some(path.to_string());
+        Ok(())
+    }
+
+    /// The well-known fields of a line that is a JSON object
+    fn parse(&self, line: &str) -> Option<StructuredLine> {
+        let trimmed = line.trim();
+        if !trimmed.starts_with('{') {
+            return None;
+        }
+        let value: Value = serde_json::from_str(trimmed).ok()?;
+        let find = |mapped: &Option<String>, names: &[&str]| -> Option<&Value> {
+            match mapped {
+                Some(path) => json_path(&value, path),
+                None => names.iter().find_map(|name| json_path(&value, name)),
+            }
+        };
+
+        let time = find(&self.timestamp, TIMESTAMP_FIELDS).and_then(json_time);
+        let level = match find(&self.level, LEVEL_FIELDS) {
+            Some(Value::String(level)) => Some(parse_level(level)),
+            Some(Value::Number(level)) => level.as_f64().map(numeric_level),
+            // Serilog compact JSON leaves out the level of Information events
+            None if self.level.is_none() && value.get("@t").is_some() => Some(LogLevel::Info),
+            _ => None,
+        };
+        let error_type = find(&self.error_type, ERROR_TYPE_FIELDS)
+            .and_then(json_text)
+            .or_else(|| {
+                ERROR_TEXT_FIELDS
+                    .iter()
+                    .filter_map(|name| json_path(&value, name).and_then(json_text))
+                    .find_map(|text| error_type_from_text(&text))
+            });
+        Some(StructuredLine {
+            time,
+            level,
+            message: find(&self.message, MESSAGE_FIELDS).and_then(json_text),
+            logger: find(&self.logger, LOGGER_FIELDS).and_then(json_text),
+            trace_id: find(&self.trace_id, TRACE_ID_FIELDS).and_then(json_text),
+            error_type,
+            value,
+        })
+    }
+}
+
+impl std::fmt::Display for JsonFieldMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = [
+            ("timestamp", &self.timestamp),
+            ("level", &self.level),
+            ("message", &self.message),
+            ("logger", &self.logger),
+            ("trace_id", &self.trace_id),
+            ("error_type", &self.error_type),
+        ];
+        let set: Vec<String> = fields
+            .iter()
+            .filter_map(|(field, path)| path.as_ref().map(|path| format!("{}={}", field, path)))
+            .collect();
+        write!(f, "{}", set.join(" "))
+    }
+}
+
+/// Well-known fields of a JSON log line
+#[derive(Debug, Clone)]
+struct StructuredLine {
+    value: Value,
+    time: Option<DateTime<Utc>>,
+    level: Option<LogLevel>,
+    message: Option<String>,
+    logger: Option<String>,
+    trace_id: Option<String>,
+    error_type: Option<String>,
+}
+
+impl StructuredLine {
+    /// A field by dotted path, or by its well-known name (`level`, `trace_id`, ...)
+    fn field(&self, key: &str) -> Option<String> {
+        if let Some(value) = json_path(&self.value, key) {
+            return json_text(value);
+        }
+        match key {
+            "timestamp" => self
+                .time
+                .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true)),
+            "level" => self.level.map(|level| level.to_string().to_lowercase()),
+            "message" => self.message.clone(),
+            "logger" => self.logger.clone(),
+            "trace_id" => self.trace_id.clone(),
+            "error_type" => self.error_type.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// The key of `value` named `path`, or the nested key it spells out (`fields.message`)
+fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    if let Some(found) = value.get(path) {
+        return Some(found);
+    }
+    if !path.contains('.') {
+        return None;
+    }
+    path.split('.')
+        .try_fold(value, |current, segment| current.get(segment))
+}
+
+/// Strings as they are, numbers and booleans as written in JSON
+fn json_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// RFC3339 strings, or Unix times in seconds (zap) or milliseconds (pino)
+fn json_time(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(text) => DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|time| time.with_timezone(&Utc)),
+        Value::Number(number) => {
+            let number = number.as_f64()?;
+            let millis = if number > 1e12 {
+                number
+            } else {
+                number * 1000.0
+            };
+            DateTime::from_timestamp_millis(millis as i64)
+        }
+        _ => None,
+    }
+}
+
+/// pino and bunyan levels: 50 and up are errors, 40 warnings, 30 info
+fn numeric_level(level: f64) -> LogLevel {
+    if level >= 50.0 {
+        LogLevel::Error
+    } else if level >= 40.0 {
+        LogLevel::Warn
+    } else if level >= 30.0 {
+        LogLevel::Info
+    } else {
+        LogLevel::Other
+    }
+}
+
+/// `System.TimeoutException` from `System.TimeoutException: The operation timed out`
+fn error_type_from_text(text: &str) -> Option<String> {
+    let first_line = text.lines().next()?;
+    let name = first_line
+        .split_once(": ")
+        .map_or(first_line, |(name, _)| name)
+        .trim();
+    let is_type_name = name.len() <= 120
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '$'));
+    is_type_name.then(|| name.to_string())
+}
+
+/// Accept both a byte count and a size with a unit
+pub(crate) fn deserialize_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+    match Option::<Size>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Size::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(Size::Text(text)) => parse_size(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// Parse a size like `512K`, `50MB` or `1GiB` (binary units) or a plain byte count
+pub fn parse_size(input: &str) -> Result<u64> {
+    let trimmed = input.trim();
+    let digits_end = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(digits_end);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+        _ => 0,
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .filter(|_| multiplier > 0)
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| {
+            HyperVError::InvalidInput(format!(
+                "invalid size {:?} (expected bytes or a size like 512K, 50MB or 1GB)",
+                input
+            ))
+        })
+}
+
+/// Resolved log rotation and retention for one task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRetention {
+    pub max_size: u64,
+    pub max_archives: usize,
+    pub max_age_days: Option<u64>,
+    pub compress: bool,
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        Self {
+            max_size: MAX_LOG_SIZE,
+            max_archives: MAX_LOG_ARCHIVES,
+            max_age_days: None,
+            compress: true,
+        }
+    }
+}
+
+/// Output stream a log record was captured from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl LogStream {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+        }
+    }
+
+    /// Short label used when streams are shown together
+    fn tag(&self) -> &'static str {
+        match self {
+            LogStream::Stdout => "OUT",
+            LogStream::Stderr => "ERR",
+        }
+    }
+}
+
+impl std::str::FromStr for LogStream {
+    type Err = HyperVError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "stdout" => Ok(LogStream::Stdout),
+            "stderr" => Ok(LogStream::Stderr),
+            _ => Err(HyperVError::InvalidLogType(s.to_string())),
+        }
+    }
+}
+
+/// One line of captured output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// When hyperV received the line; unknown for lines without a prefix
+    pub time: Option<DateTime<Utc>>,
+    pub stream: LogStream,
+    pub message: String,
+}
+
+impl LogRecord {
+    /// Parse a line read from the log of `stream`
+    pub fn parse(line: &str, stream: LogStream) -> Self {
+        let mut parts = line.splitn(3, ' ');
+        if let (Some(time), Some(tag)) = (parts.next(), parts.next())
+            && let Ok(time) = DateTime::parse_from_rfc3339(time)
+            && let Ok(tag) = tag.parse::<LogStream>()
+        {
+            return Self {
+                time: Some(time.with_timezone(&Utc)),
+                stream: tag,
+                message: parts.next().unwrap_or_default().to_string(),
+            };
+        }
+        Self {
+            time: None,
+            stream,
+            message: line.to_string(),
+        }
+    }
+
+    /// Prefix written in front of a line received at `time`
+    pub fn prefix(time: DateTime<Utc>, stream: LogStream) -> String {
+        format!(
+            "{} {} ",
+            time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            stream.as_str()
+        )
+    }
+
+    /// Human-readable form: local receive time, optionally the stream, then the text
+    pub fn display(&self, with_stream: bool) -> String {
+        let mut out = String::new();
+        if let Some(time) = self.time {
+            out.push_str(&format!(
+                "[{}] ",
+                time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S%.3f")
+            ));
+        }
+        if with_stream {
+            out.push_str(&format!("[{}] ", self.stream.tag()));
+        }
+        out.push_str(&self.message);
+        out
+    }
+}
+
+/// Merge two streams into one chronological list. Records without a time stay
+/// right after the record before them in their own stream.
+pub fn merge_records(left: Vec<LogRecord>, right: Vec<LogRecord>) -> Vec<LogRecord> {
+    merge_by_time(left, right, |record| record)
+}
+
+/// [`merge_records`] for records tagged with the task they belong to
+fn merge_tagged(
+    left: Vec<(usize, LogRecord)>,
+    right: Vec<(usize, LogRecord)>,
+) -> Vec<(usize, LogRecord)> {
+    merge_by_time(left, right, |(_, record)| record)
+}
+
+fn merge_by_time<T>(left: Vec<T>, right: Vec<T>, record: impl Fn(&T) -> &LogRecord) -> Vec<T> {
+    let effective_times = |items: &[T]| -> Vec<DateTime<Utc>> {
+        let mut last = DateTime::<Utc>::MIN_UTC;
+        items
+            .iter()
+            .map(|item| {
+                last = record(item).time.unwrap_or(last);
+                last
+            })
+            .collect()
+    };
+
+    let left_times = effective_times(&left);
+    let right_times = effective_times(&right);
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().zip(left_times).peekable();
+    let mut right = right.into_iter().zip(right_times).peekable();
+    loop {
+        let take_left = match (left.peek(), right.peek()) {
+            (Some((_, l)), Some((_, r))) => l <= r,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => break,
+        };
+        let next = if take_left { left.next() } else { right.next() };
+        merged.extend(next.map(|(item, _)| item));
+    }
+    merged
+}
+
+/// Which records `hyperV logs` shows
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Matched against the message, without the time and stream prefix
+    pub pattern: Option<Regex>,
+    /// Least severe level shown; lines with no recognizable level are hidden
+    level: Option<LogLevel>,
+    /// `--json-field KEY=VALUE` conditions; lines that are not JSON are hidden
+    json_fields: Vec<(String, String)>,
+    /// How JSON lines are read for `level` and `json_fields`
+    pub field_map: JsonFieldMap,
+}
+
+impl LogFilter {
+    /// Build a filter from command-line values. Times are RFC3339 or relative to
+    /// now (`90s`, `15m`, `2h`, `1d`, `1h30m`), levels are `error`, `warn` or `info`.
+    pub fn new(
+        since: Option<&str>,
+        until: Option<&str>,
+        grep: Option<&str>,
+        level: Option<&str>,
+    ) -> Result<Self> {
+        let now = Utc::now();
+        let pattern = grep
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|e| {
+                    HyperVError::InvalidInput(format!("invalid --grep pattern: {}", e))
+                })
+            })
+            .transpose()?;
+        let level = level
+            .map(|level| match parse_level(level) {
+                LogLevel::Other => Err(HyperVError::InvalidInput(format!(
+                    "unknown log level {:?} (expected error, warn or info)",
+                    level
+                ))),
+                level => Ok(level),
+            })
+            .transpose()?;
+        Ok(Self {
+            since: since.map(|s| parse_time_bound(s, now)).transpose()?,
+            until: until.map(|s| parse_time_bound(s, now)).transpose()?,
+            pattern,
+            level,
+            ..Self::default()
+        })
+    }
+
+    /// Also require JSON lines with these `KEY=VALUE` fields. Keys are dotted paths
+    /// or the well-known names `level`, `message`, `logger`, `trace_id`, `error_type`
+    /// and `timestamp`.
+    pub fn with_json_fields(mut self, fields: &[String]) -> Result<Self> {
+        for field in fields {
+            let (key, value) = field
+                .split_once('=')
+                .filter(|(key, _)| !key.is_empty())
+                .ok_or_else(|| {
+                    HyperVError::InvalidInput(format!(
+                        "invalid --json-field {:?} (expected KEY=VALUE)",
+                        field
+                    ))
+                })?;
+            self.json_fields.push((key.to_string(), value.to_string()));
+        }
+        Ok(self)
+    }
+
+    /// Read JSON lines with `field_map`
+    pub fn with_field_map(mut self, field_map: JsonFieldMap) -> Self {
+        self.field_map = field_map;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none()
+            && self.until.is_none()
+            && self.pattern.is_none()
+            && self.level.is_none()
+            && self.json_fields.is_empty()
+    }
+
+    /// Whether `record` passes. `time` is the record's own time, or that of the
+    /// record before it when it has none.
+    pub fn matches(&self, record: &LogRecord, time: Option<DateTime<Utc>>) -> bool {
+        if let Some(since) = self.since
+            && time.is_none_or(|time| time < since)
+        {
+            return false;
+        }
+        if let Some(until) = self.until
+            && time.is_none_or(|time| time > until)
+        {
+            return false;
+        }
+        if let Some(pattern) = &self.pattern
+            && !pattern.is_match(&record.message)
+        {
+            return false;
+        }
+        if self.level.is_none() && self.json_fields.is_empty() {
+            return true;
+        }
+        let structured = self.field_map.parse(&record.message);
+        if let Some(level) = self.level {
+            let record_level = match &structured {
+                Some(line) => line.level.unwrap_or(LogLevel::Other),
+                None => parse_level(&record.message),
+            };
+            if record_level > level {
+                return false;
+            }
+        }
+        self.json_fields.iter().all(|(key, value)| {
+            structured
+                .as_ref()
+                .and_then(|line| line.field(key))
+                .is_some_and(|found| found == *value)
+        })
+    }
+}
+
+/// Parse a `--since`/`--until` value: an RFC3339 time, or a duration before `now`
+pub fn parse_time_bound(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let invalid = || {
+        HyperVError::InvalidInput(format!(
+            "invalid time {:?} (expected RFC3339 like 2026-05-21T07:00:00Z, or a duration like 15m)",
+            input
+        ))
+    };
+    let mut seconds: i64 = 0;
+    let mut digits = String::new();
+    for c in input.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let value: i64 = digits.parse().map_err(|_| invalid())?;
+        seconds = value
+            .checked_mul(unit)
+            .and_then(|v| seconds.checked_add(v))
+            .ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if input.is_empty() || !digits.is_empty() {
+        return Err(invalid());
+    }
+    chrono::Duration::try_seconds(seconds)
+        .and_then(|duration| now.checked_sub_signed(duration))
+        .ok_or_else(invalid)
+}
+
+/// Log files of one task, for views that show several tasks together
+#[derive(Debug, Clone)]
+pub struct TaskLogs {
+    pub name: String,
+    pub stdout_path: PathBuf,
+    pub stderr_path: PathBuf,
+    /// How this task's JSON lines are read
+    pub json_fields: JsonFieldMap,
+}
+
+impl TaskLogs {
+    /// The files selected by `log_type`, with their streams
+    fn selected(&self, log_type: &LogType) -> Vec<(&Path, LogStream)> {
+        let stdout = (self.stdout_path.as_path(), LogStream::Stdout);
+        let stderr = (self.stderr_path.as_path(), LogStream::Stderr);
+        match log_type {
+            LogType::Stdout => vec![stdout],
+            LogType::Stderr => vec![stderr],
+            LogType::Both => vec![stdout, stderr],
+        }
+    }
+}
+
+/// Colors cycled through for task name prefixes
+const PREFIX_COLORS: [u8; 6] = [36, 33, 32, 35, 34, 96];
+
+/// `name | ` prefixes padded to the longest task name, colored when writing to a
+/// terminal (unless `NO_COLOR` is set)
+fn task_prefixes(tasks: &[TaskLogs]) -> Vec<String> {
+    use std::io::IsTerminal;
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let width = tasks
+        .iter()
+        .map(|t| t.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    tasks
+        .iter()
+        .enumerate()
+        .map(|(index, task)| {
+            let prefix = format!("{:<width$} |", task.name, width = width);
+            if color {
+                let code = PREFIX_COLORS[index % PREFIX_COLORS.len()];
+                format!("\x1b[{}m{}\x1b[0m ", code, prefix)
+            } else {
+                format!("{} ", prefix)
+            }
+        })
+        .collect()
+}
+
+/// Prefixes each line of captured output with the time it was received
+struct LineStamper {
+    stream: LogStream,
+    at_line_start: bool,
+}
+
+impl LineStamper {
+    fn stamp(&mut self, data: &[u8], now: DateTime<Utc>) -> Vec<u8> {
+        let prefix = LogRecord::prefix(now, self.stream);
+        let mut out = Vec::with_capacity(data.len() + prefix.len());
+        for line in data.split_inclusive(|&b| b == b'\n') {
+            if self.at_line_start {
+                out.extend_from_slice(prefix.as_bytes());
+            }
+            out.extend_from_slice(line);
+            self.at_line_start = line.ends_with(b"\n");
+        }
+        out
+    }
+}
+
 /// Log manager for handling log files
 pub struct LogManager;
 
 impl LogManager {
     /// Rotate a log file if it exceeds the maximum size
     pub fn rotate_log_if_needed(log_path: &Path) -> Result<()> {
+        Self::rotate_log_with(log_path, &LogRetention::default())
+    }
+
+    /// Rotate a log file if it exceeds `retention.max_size`, then drop archives that
+    /// are too many or too old
+    pub fn rotate_log_with(log_path: &Path, retention: &LogRetention) -> Result<()> {
         if !log_path.exists() {
             return Ok(());
         }
 
         let metadata = fs::metadata(log_path).map_err(HyperVError::Io)?;
 
-        if metadata.len() > MAX_LOG_SIZE {
-            Self::rotate_archives(log_path)?;
-            let archive_path = Self::archive_path(log_path, 1)?;
-            Self::compress_log_to_archive(log_path, &archive_path)?;
-            fs::remove_file(log_path).map_err(HyperVError::Io)?;
+        if metadata.len() > retention.max_size {
+            Self::rotate_archives_keeping(log_path, retention.max_archives)?;
+            let archive_path = if retention.compress {
+                let archive_path = Self::archive_path(log_path, 1)?;
+                Self::compress_log_to_archive(log_path, &archive_path)?;
+                fs::remove_file(log_path).map_err(HyperVError::Io)?;
+                archive_path
+            } else {
+                let archive_path = Self::plain_archive_path(log_path, 1)?;
+                fs::rename(log_path, &archive_path).map_err(HyperVError::Io)?;
+                archive_path
+            };
 
             println!(
                 "📦 Rotated log file: {} -> {}",
@@ -60,33 +900,132 @@ impl LogManager {
             );
         }
 
+        Self::prune_archives(log_path, retention)?;
         Ok(())
     }
 
-    fn rotate_archives(log_path: &Path) -> Result<()> {
-        let oldest_archive = Self::archive_path(log_path, MAX_LOG_ARCHIVES)?;
-        if oldest_archive.exists() {
-            fs::remove_file(&oldest_archive).map_err(HyperVError::Io)?;
-        }
-
-        for archive_index in (1..MAX_LOG_ARCHIVES).rev() {
-            let source = Self::archive_path(log_path, archive_index)?;
-            if source.exists() {
-                let destination = Self::archive_path(log_path, archive_index + 1)?;
-                fs::rename(source, destination).map_err(HyperVError::Io)?;
+    /// Shift `name.N.gz` (or `name.N`) to `name.N+1.gz`, dropping the oldest so that
+    /// at most `max_archives` remain once a new `name.1.gz` is written
+    fn rotate_archives_keeping(log_path: &Path, max_archives: usize) -> Result<()> {
+        let max_archives = max_archives.max(1);
+        for (archive_index, source) in Self::archives(log_path)?.into_iter().rev() {
+            if archive_index >= max_archives {
+                fs::remove_file(&source).map_err(HyperVError::Io)?;
+                continue;
             }
+            let destination = if Self::is_compressed(&source) {
+                Self::archive_path(log_path, archive_index + 1)?
+            } else {
+                Self::plain_archive_path(log_path, archive_index + 1)?
+            };
+            fs::rename(source, destination).map_err(HyperVError::Io)?;
         }
 
         Ok(())
     }
 
+    /// Delete archives beyond `retention.max_archives` and, with `max_age_days`, those
+    /// last written longer ago than that. Returns the removed files and their sizes.
+    pub fn prune_archives(
+        log_path: &Path,
+        retention: &LogRetention,
+    ) -> Result<Vec<(PathBuf, u64)>> {
+        let cutoff = retention.max_age_days.and_then(|days| {
+            SystemTime::now().checked_sub(Duration::from_secs(days.saturating_mul(24 * 60 * 60)))
+        });
+        let mut removed = Vec::new();
+        for (archive_index, archive) in Self::archives(log_path)? {
+            let archive = Self::existing_archive(&archive);
+            let metadata = fs::metadata(&archive).map_err(HyperVError::Io)?;
+            let too_many = archive_index > retention.max_archives.max(1);
+            let too_old = cutoff
+                .is_some_and(|cutoff| metadata.modified().is_ok_and(|modified| modified < cutoff));
+            if too_many || too_old {
+                fs::remove_file(&archive).map_err(HyperVError::Io)?;
+                removed.push((archive, metadata.len()));
+            }
+        }
+        Ok(removed)
+    }
+
     fn archive_path(log_path: &Path, archive_index: usize) -> Result<PathBuf> {
+        Ok(Self::plain_archive_path(log_path, archive_index)?.with_added_extension("gz"))
+    }
+
+    /// Archive written when compression is turned off
+    fn plain_archive_path(log_path: &Path, archive_index: usize) -> Result<PathBuf> {
         let file_name = log_path
             .file_name()
             .ok_or_else(|| HyperVError::LogError("Log path has no file name".to_string()))?
             .to_string_lossy();
 
-        Ok(log_path.with_file_name(format!("{file_name}.{archive_index}.gz")))
+        Ok(log_path.with_file_name(format!("{file_name}.{archive_index}")))
+    }
+
+    fn is_compressed(archive: &Path) -> bool {
+        archive
+            .extension()
+            .is_some_and(|extension| extension == "gz")
+    }
+
+    /// Archives of a log with their numbers, newest (`name.1.gz`) first
+    fn archives(log_path: &Path) -> Result<Vec<(usize, PathBuf)>> {
+        let file_name = log_path
+            .file_name()
+            .ok_or_else(|| HyperVError::LogError("Log path has no file name".to_string()))?
+            .to_string_lossy()
+            .to_string();
+        let Some(dir) = log_path.parent().filter(|dir| dir.exists()) else {
+            return Ok(Vec::new());
+        };
+
+        let mut archives = Vec::new();
+        for entry in fs::read_dir(dir).map_err(HyperVError::Io)? {
+            let entry = entry.map_err(HyperVError::Io)?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(suffix) = name
+                .strip_prefix(&file_name)
+                .and_then(|s| s.strip_prefix('.'))
+            else {
+                continue;
+            };
+            let number = suffix.strip_suffix(".gz").unwrap_or(suffix);
+            if let Ok(archive_index) = number.parse::<usize>()
+                && archive_index > 0
+            {
+                archives.push((archive_index, entry.path()));
+            }
+        }
+        archives.sort();
+        // While `name.N` is being compressed both files exist; the finished gzip wins
+        archives.dedup_by(|later, earlier| {
+            if later.0 == earlier.0 {
+                *earlier = later.clone();
+                true
+            } else {
+                false
+            }
+        });
+        Ok(archives)
+    }
+
+    /// `archive`, or the gzip that replaced it when it was compressed after being listed
+    fn existing_archive(archive: &Path) -> PathBuf {
+        if Self::is_compressed(archive) || archive.exists() {
+            archive.to_path_buf()
+        } else {
+            archive.with_added_extension("gz")
+        }
+    }
+
+    fn open_archive(archive: &Path) -> Result<Box<dyn BufRead>> {
+        let archive = Self::existing_archive(archive);
+        let file = File::open(&archive).map_err(HyperVError::Io)?;
+        Ok(if Self::is_compressed(&archive) {
+            Box::new(BufReader::new(GzDecoder::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        })
     }
 
     fn compress_log_to_archive(log_path: &Path, archive_path: &Path) -> Result<()> {
@@ -102,6 +1041,37 @@ impl LogManager {
         Ok(())
     }
 
+    /// Copy captured output from `input` into `log_path` until the writing end is
+    /// closed, stamping every line and rotating the file as soon as it grows past
+    /// `retention.max_size`
+    pub fn capture<R: Read>(
+        mut input: R,
+        log_path: &Path,
+        stream: LogStream,
+        retention: LogRetention,
+    ) -> Result<()> {
+        let mut writer = RotatingLogWriter::open(log_path, retention)?;
+        let mut stamper = LineStamper {
+            stream,
+            at_line_start: true,
+        };
+        let mut buffer = vec![0; CAPTURE_BUFFER_SIZE];
+        loop {
+            let read = match input.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(HyperVError::Io(e)),
+            };
+            // Keep draining the pipe even if a write fails, so the task never blocks on us
+            let stamped = stamper.stamp(&buffer[..read], Utc::now());
+            if let Err(e) = writer.write(&stamped) {
+                eprintln!("Failed to write {}: {}", log_path.display(), e);
+            }
+        }
+        writer.finish()
+    }
+
     /// Read the last N lines from a log file
     pub fn read_log_lines(log_path: &Path, lines: usize) -> Result<Vec<String>> {
         if !log_path.exists() {
@@ -155,44 +1125,124 @@ impl LogManager {
         Ok(result_lines)
     }
 
+    /// The last `count` records of a log, continuing into its gzip archives when the
+    /// current file holds fewer lines
+    pub fn read_records(
+        log_path: &Path,
+        stream: LogStream,
+        count: usize,
+    ) -> Result<Vec<LogRecord>> {
+        let mut lines = if log_path.exists() {
+            Self::read_log_lines(log_path, count)?
+        } else {
+            Vec::new()
+        };
+
+        for (_, archive_path) in Self::archives(log_path)? {
+            if lines.len() >= count {
+                break;
+            }
+            let archived: Vec<String> = Self::open_archive(&archive_path)?
+                .lines()
+                .collect::<std::io::Result<_>>()
+                .map_err(HyperVError::Io)?;
+            let wanted = count - lines.len();
+            let start = archived.len().saturating_sub(wanted);
+            lines.splice(0..0, archived.into_iter().skip(start));
+        }
+
+        Ok(lines
+            .iter()
+            .map(|line| LogRecord::parse(line, stream))
+            .collect())
+    }
+
+    /// The last `count` records of a log that pass `filter`, searching the gzip
+    /// archives (oldest first) as well as the current file
+    pub fn search_records(
+        log_path: &Path,
+        stream: LogStream,
+        filter: &LogFilter,
+        count: usize,
+    ) -> Result<Vec<LogRecord>> {
+        if filter.is_empty() {
+            return Self::read_records(log_path, stream, count);
+        }
+
+        let mut readers: Vec<Box<dyn BufRead>> = Vec::new();
+        for (_, archive_path) in Self::archives(log_path)?.iter().rev() {
+            readers.push(Self::open_archive(archive_path)?);
+        }
+        if log_path.exists() {
+            let file = File::open(log_path).map_err(HyperVError::Io)?;
+            readers.push(Box::new(BufReader::new(file)));
+        }
+
+        let mut kept = VecDeque::with_capacity(count.min(4096));
+        let mut last_time = None;
+        for reader in readers {
+            for line in reader.lines() {
+                let record = LogRecord::parse(&line.map_err(HyperVError::Io)?, stream);
+                last_time = record.time.or(last_time);
+                // Records are written in order, so nothing later can be before --until
+                if let (Some(until), Some(time)) = (filter.until, last_time)
+                    && time > until
+                {
+                    return Ok(kept.into());
+                }
+                if count > 0 && filter.matches(&record, last_time) {
+                    if kept.len() == count {
+                        kept.pop_front();
+                    }
+                    kept.push_back(record);
+                }
+            }
+        }
+        Ok(kept.into())
+    }
+
     /// Show logs for a task
     pub fn show_logs(
         stdout_path: &Path,
         stderr_path: &Path,
         log_type: LogType,
         lines: usize,
+        filter: &LogFilter,
         follow: bool,
         summary: bool,
     ) -> Result<()> {
         if summary {
-            let summary = Self::summarize_logs(stdout_path, stderr_path, log_type)?;
+            let summary =
+                Self::summarize_logs_with(stdout_path, stderr_path, log_type, &filter.field_map)?;
             print!("{}", summary.format());
             return Ok(());
         }
 
         match log_type {
             LogType::Stdout => {
-                Self::show_single_log(stdout_path, "STDOUT", lines, follow)?;
+                Self::show_single_log(stdout_path, LogStream::Stdout, lines, filter, follow)?;
             }
             LogType::Stderr => {
-                Self::show_single_log(stderr_path, "STDERR", lines, follow)?;
+                Self::show_single_log(stderr_path, LogStream::Stderr, lines, filter, follow)?;
             }
             LogType::Both => {
-                println!("=== STDOUT ===");
-                let stdout_lines = Self::read_log_lines(stdout_path, lines / 2)?;
-                for line in stdout_lines {
-                    println!("{}", line);
+                println!("=== STDOUT + STDERR ===");
+                // The newest `lines` records overall are among the newest `lines` of each stream
+                let merged = merge_records(
+                    Self::search_records(stdout_path, LogStream::Stdout, filter, lines)?,
+                    Self::search_records(stderr_path, LogStream::Stderr, filter, lines)?,
+                );
+                if merged.is_empty() && !filter.is_empty() {
+                    println!("No matching log lines.");
                 }
-
-                println!("\n=== STDERR ===");
-                let stderr_lines = Self::read_log_lines(stderr_path, lines / 2)?;
-                for line in stderr_lines {
-                    println!("{}", line);
+                let start = merged.len().saturating_sub(lines);
+                for record in &merged[start..] {
+                    println!("{}", record.display(true));
                 }
 
                 if follow {
                     println!("\n=== Following logs (Ctrl+C to stop) ===");
-                    Self::follow_both_logs(stdout_path, stderr_path)?;
+                    Self::follow_both_logs(stdout_path, stderr_path, filter)?;
                 }
             }
         }
@@ -200,20 +1250,97 @@ impl LogManager {
         Ok(())
     }
 
+    /// Show the logs of several tasks as one chronological stream, each line prefixed
+    /// with its task name
+    pub fn show_task_logs(
+        tasks: &[TaskLogs],
+        log_type: LogType,
+        lines: usize,
+        filter: &LogFilter,
+        follow: bool,
+    ) -> Result<()> {
+        let prefixes = task_prefixes(tasks);
+        let with_stream = log_type == LogType::Both;
+
+        // Each task reads its JSON lines its own way
+        let filters: Vec<LogFilter> = tasks
+            .iter()
+            .map(|task| filter.clone().with_field_map(task.json_fields.clone()))
+            .collect();
+
+        // Records are tagged with the index of their task so they survive merging
+        let mut merged: Vec<(usize, LogRecord)> = Vec::new();
+        for (index, task) in tasks.iter().enumerate() {
+            for (path, stream) in task.selected(&log_type) {
+                let records = Self::search_records(path, stream, &filters[index], lines)?;
+                merged = merge_tagged(merged, records.into_iter().map(|r| (index, r)).collect());
+            }
+        }
+        if merged.is_empty() {
+            println!("No matching log lines.");
+        }
+        let start = merged.len().saturating_sub(lines);
+        for (index, record) in &merged[start..] {
+            println!("{}{}", prefixes[*index], record.display(with_stream));
+        }
+
+        if !follow {
+            return Ok(());
+        }
+        let names: Vec<&str> = tasks.iter().map(|t| t.name.as_str()).collect();
+        println!("\n📖 Following {} (Press Ctrl+C to stop)", names.join(", "));
+        let mut followed = Vec::new();
+        for (index, task) in tasks.iter().enumerate() {
+            for (path, stream) in task.selected(&log_type) {
+                followed.push((index, FollowedLog::open(path, stream)?));
+            }
+        }
+        loop {
+            let mut merged = Vec::new();
+            for (index, log) in followed.iter_mut() {
+                let records = log.read_available();
+                merged = merge_tagged(merged, records.into_iter().map(|r| (*index, r)).collect());
+            }
+            if merged.is_empty() {
+                thread::sleep(LOG_FOLLOW_INTERVAL);
+            }
+            for (index, record) in merged {
+                if filters[index].matches(&record, record.time) {
+                    println!("{}{}", prefixes[index], record.display(with_stream));
+                }
+            }
+        }
+    }
+
     /// Summarize selected logs without dumping full log content.
     pub fn summarize_logs(
         stdout_path: &Path,
         stderr_path: &Path,
         log_type: LogType,
+    ) -> Result<LogSummary> {
+        Self::summarize_logs_with(stdout_path, stderr_path, log_type, &JsonFieldMap::default())
+    }
+
+    /// [`Self::summarize_logs`] reading JSON lines with `field_map`
+    pub fn summarize_logs_with(
+        stdout_path: &Path,
+        stderr_path: &Path,
+        log_type: LogType,
+        field_map: &JsonFieldMap,
     ) -> Result<LogSummary> {
         let mut summary = LogSummary::default();
 
-        match log_type {
-            LogType::Stdout => Self::summarize_log_family(stdout_path, "STDOUT", &mut summary)?,
-            LogType::Stderr => Self::summarize_log_family(stderr_path, "STDERR", &mut summary)?,
-            LogType::Both => {
-                Self::summarize_log_family(stdout_path, "STDOUT", &mut summary)?;
-                Self::summarize_log_family(stderr_path, "STDERR", &mut summary)?;
+        for (path, stream) in [
+            (stdout_path, LogStream::Stdout),
+            (stderr_path, LogStream::Stderr),
+        ] {
+            let selected = match log_type {
+                LogType::Stdout => stream == LogStream::Stdout,
+                LogType::Stderr => stream == LogStream::Stderr,
+                LogType::Both => true,
+            };
+            if selected {
+                Self::summarize_log_family(path, stream, field_map, &mut summary)?;
             }
         }
 
@@ -223,9 +1350,11 @@ impl LogManager {
 
     fn summarize_log_family(
         log_path: &Path,
-        log_name: &str,
+        stream: LogStream,
+        field_map: &JsonFieldMap,
         summary: &mut LogSummary,
     ) -> Result<()> {
+        let log_name = stream.as_str().to_uppercase();
         let before_lines = summary.total_lines;
         let before_bytes = summary.total_bytes;
 
@@ -233,26 +1362,26 @@ impl LogManager {
             let metadata = fs::metadata(log_path).map_err(HyperVError::Io)?;
             summary.total_bytes += metadata.len();
             let file = File::open(log_path).map_err(HyperVError::Io)?;
-            Self::summarize_reader(BufReader::new(file), log_name, false, summary)?;
+            Self::summarize_reader(BufReader::new(file), stream, false, field_map, summary)?;
         }
 
-        for archive_index in 1..=MAX_LOG_ARCHIVES {
-            let archive_path = Self::archive_path(log_path, archive_index)?;
-            if !archive_path.exists() {
-                continue;
-            }
-
+        for (_, archive_path) in Self::archives(log_path)? {
+            let archive_path = Self::existing_archive(&archive_path);
             let metadata = fs::metadata(&archive_path).map_err(HyperVError::Io)?;
             summary.total_bytes += metadata.len();
             summary.archive_count += 1;
 
-            let file = File::open(&archive_path).map_err(HyperVError::Io)?;
-            let decoder = GzDecoder::new(file);
-            Self::summarize_reader(BufReader::new(decoder), log_name, true, summary)?;
+            Self::summarize_reader(
+                Self::open_archive(&archive_path)?,
+                stream,
+                true,
+                field_map,
+                summary,
+            )?;
         }
 
         summary.files.push(LogFileSummary {
-            name: log_name.to_string(),
+            name: log_name,
             path: log_path.to_string_lossy().to_string(),
             exists: log_path.exists(),
             lines: summary.total_lines - before_lines,
@@ -264,21 +1393,31 @@ impl LogManager {
 
     fn summarize_reader<R: BufRead>(
         reader: R,
-        log_name: &str,
+        stream: LogStream,
         archived: bool,
+        field_map: &JsonFieldMap,
         summary: &mut LogSummary,
     ) -> Result<()> {
         for line_result in reader.lines() {
             let line = line_result.map_err(HyperVError::Io)?;
             summary.total_lines += 1;
 
-            let event = LogEvent::from_line(log_name, archived, &line);
+            let event = LogEvent::from_record(LogRecord::parse(&line, stream), archived, field_map);
             let message = event.message.clone();
             *summary.message_counts.entry(message).or_insert(0) += 1;
 
             match event.level {
                 LogLevel::Error => {
                     summary.error_count += 1;
+                    if let Some(logger) = &event.logger {
+                        *summary.errors_by_logger.entry(logger.clone()).or_insert(0) += 1;
+                    }
+                    if let Some(error_type) = &event.error_type {
+                        *summary
+                            .errors_by_type
+                            .entry(error_type.clone())
+                            .or_insert(0) += 1;
+                    }
                     summary.recent_events.push(event);
                 }
                 LogLevel::Warn => {
@@ -296,14 +1435,24 @@ impl LogManager {
     }
 
     /// Show logs from a single file
-    fn show_single_log(log_path: &Path, log_name: &str, lines: usize, follow: bool) -> Result<()> {
+    fn show_single_log(
+        log_path: &Path,
+        stream: LogStream,
+        lines: usize,
+        filter: &LogFilter,
+        follow: bool,
+    ) -> Result<()> {
+        let log_name = stream.as_str().to_uppercase();
         println!("=== {} ===", log_name);
 
         // Always show the most recent content
         if log_path.exists() {
-            let log_lines = Self::read_log_lines(log_path, lines)?;
-            for line in log_lines {
-                println!("{}", line);
+            let records = Self::search_records(log_path, stream, filter, lines)?;
+            if records.is_empty() && !filter.is_empty() {
+                println!("No matching log lines.");
+            }
+            for record in records {
+                println!("{}", record.display(false));
             }
         } else {
             println!(
@@ -316,14 +1465,14 @@ impl LogManager {
 
         if follow {
             println!("\n=== Following {} (Ctrl+C to stop) ===", log_name);
-            Self::follow_single_log(log_path)?;
+            Self::follow_single_log(log_path, stream, filter)?;
         }
 
         Ok(())
     }
 
     /// Follow a single log file in real-time
-    fn follow_single_log(log_path: &Path) -> Result<()> {
+    fn follow_single_log(log_path: &Path, stream: LogStream, filter: &LogFilter) -> Result<()> {
         if !log_path.exists() {
             println!("Log file not found: {}", log_path.display());
             return Ok(());
@@ -365,9 +1514,12 @@ impl LogManager {
                     continue;
                 }
                 Ok(_) => {
-                    // Print with timestamp
-                    let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
-                    print!("[{}] {}", timestamp, line);
+                    // Lines without a recorded time are shown with the time they were read
+                    let mut record = LogRecord::parse(line.trim_end_matches('\n'), stream);
+                    record.time.get_or_insert_with(Utc::now);
+                    if filter.matches(&record, record.time) {
+                        println!("{}", record.display(false));
+                    }
                 }
                 Err(e) => {
                     eprintln!("Error reading log: {}", e);
@@ -379,95 +1531,25 @@ impl LogManager {
         Ok(())
     }
 
-    /// Follow both stdout and stderr logs in real-time
-    /// Follow both stdout and stderr logs in real-time
-    fn follow_both_logs(stdout_path: &Path, stderr_path: &Path) -> Result<()> {
-        let mut stdout_file = if stdout_path.exists() {
-            let mut f = File::open(stdout_path).map_err(HyperVError::Io)?;
-            f.seek(SeekFrom::End(0)).map_err(HyperVError::Io)?;
-            Some(BufReader::new(f))
-        } else {
-            None
-        };
-
-        let mut stderr_file = if stderr_path.exists() {
-            let mut f = File::open(stderr_path).map_err(HyperVError::Io)?;
-            f.seek(SeekFrom::End(0)).map_err(HyperVError::Io)?;
-            Some(BufReader::new(f))
-        } else {
-            None
-        };
-
-        let mut stdout_line = String::new();
-        let mut stderr_line = String::new();
+    /// Follow both stdout and stderr logs in real-time, printing what arrives in
+    /// each poll in the order it was received
+    fn follow_both_logs(stdout_path: &Path, stderr_path: &Path, filter: &LogFilter) -> Result<()> {
+        let mut stdout_log = FollowedLog::open(stdout_path, LogStream::Stdout)?;
+        let mut stderr_log = FollowedLog::open(stderr_path, LogStream::Stderr)?;
 
         println!("📖 Following logs (Press Ctrl+C to stop)");
         println!("OUT: {}", stdout_path.display());
         println!("ERR: {}", stderr_path.display());
 
         loop {
-            let mut has_output = false;
-
-            // Check stdout
-            if let Some(ref mut reader) = stdout_file {
-                stdout_line.clear();
-                match reader.read_line(&mut stdout_line) {
-                    Ok(n) if n > 0 => {
-                        print!("[OUT] {}", stdout_line);
-                        has_output = true;
-                    }
-                    Ok(_) => {
-                        // Check rotation
-                        if let Ok(new_file) = File::open(stdout_path)
-                            && let Ok(new_meta) = new_file.metadata()
-                            && let Ok(curr_meta) = reader.get_ref().metadata()
-                            && new_meta.len() < curr_meta.len()
-                        {
-                            println!("🔄 Stdout log rotated, reopening...");
-                            *reader = BufReader::new(new_file);
-                        }
-                    }
-                    Err(_) => {}
-                }
-            } else if stdout_path.exists() {
-                // File appeared
-                if let Ok(f) = File::open(stdout_path) {
-                    // Start from beginning or end? Tail usually starts from end if following,
-                    // but if it just appeared we might want start. Let's start from beginning.
-                    stdout_file = Some(BufReader::new(f));
-                }
+            let merged = merge_records(stdout_log.read_available(), stderr_log.read_available());
+            if merged.is_empty() {
+                thread::sleep(LOG_FOLLOW_INTERVAL);
             }
-
-            // Check stderr
-            if let Some(ref mut reader) = stderr_file {
-                stderr_line.clear();
-                match reader.read_line(&mut stderr_line) {
-                    Ok(n) if n > 0 => {
-                        print!("[ERR] {}", stderr_line);
-                        has_output = true;
-                    }
-                    Ok(_) => {
-                        // Check rotation
-                        if let Ok(new_file) = File::open(stderr_path)
-                            && let Ok(new_meta) = new_file.metadata()
-                            && let Ok(curr_meta) = reader.get_ref().metadata()
-                            && new_meta.len() < curr_meta.len()
-                        {
-                            println!("🔄 Stderr log rotated, reopening...");
-                            *reader = BufReader::new(new_file);
-                        }
-                    }
-                    Err(_) => {}
+            for record in merged {
+                if filter.matches(&record, record.time) {
+                    println!("{}", record.display(true));
                 }
-            } else if stderr_path.exists() {
-                // File appeared
-                if let Ok(f) = File::open(stderr_path) {
-                    stderr_file = Some(BufReader::new(f));
-                }
-            }
-
-            if !has_output {
-                thread::sleep(LOG_FOLLOW_INTERVAL);
             }
         }
     }
@@ -497,6 +1579,191 @@ impl LogManager {
     }
 }
 
+/// A log file followed from its end, across rotations
+pub(crate) struct FollowedLog {
+    path: PathBuf,
+    stream: LogStream,
+    reader: Option<BufReader<File>>,
+    /// Start of a line whose end has not been written yet
+    partial: String,
+}
+
+impl FollowedLog {
+    pub(crate) fn open(path: &Path, stream: LogStream) -> Result<Self> {
+        let reader = if path.exists() {
+            let mut file = File::open(path).map_err(HyperVError::Io)?;
+            file.seek(SeekFrom::End(0)).map_err(HyperVError::Io)?;
+            Some(BufReader::new(file))
+        } else {
+            None
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            stream,
+            reader,
+            partial: String::new(),
+        })
+    }
+
+    /// Complete lines written since the last call
+    pub(crate) fn read_available(&mut self) -> Vec<LogRecord> {
+        let mut records = Vec::new();
+        let Some(reader) = self.reader.as_mut() else {
+            // A file that appears later is read from its beginning
+            if let Ok(file) = File::open(&self.path) {
+                self.reader = Some(BufReader::new(file));
+            }
+            return records;
+        };
+
+        while let Ok(read) = reader.read_line(&mut self.partial) {
+            if read == 0 || !self.partial.ends_with('\n') {
+                break;
+            }
+            let mut record = LogRecord::parse(self.partial.trim_end_matches('\n'), self.stream);
+            record.time.get_or_insert_with(Utc::now);
+            records.push(record);
+            self.partial.clear();
+        }
+
+        // Once the old file is drained, switch to the new one if the log was rotated
+        if records.is_empty()
+            && let Ok(new_file) = File::open(&self.path)
+            && let Ok(new_meta) = new_file.metadata()
+            && let Ok(current_meta) = reader.get_ref().metadata()
+            && new_meta.len() < current_meta.len()
+        {
+            println!("🔄 {} log rotated, reopening...", self.stream.as_str());
+            self.reader = Some(BufReader::new(new_file));
+            self.partial.clear();
+        }
+        records
+    }
+}
+
+/// Size of the reads from a capture pipe
+const CAPTURE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Appends captured output to a log file and rotates it into the `name.N.gz` archives
+/// as soon as it crosses the size limit. Each file is filled up to the last line break
+/// that fits, and compression runs on a background thread so the pipe keeps draining
+/// while an archive is written.
+pub struct RotatingLogWriter {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    /// Whether the file ends with a complete line
+    at_line_start: bool,
+    retention: LogRetention,
+    compressing: Option<thread::JoinHandle<Result<()>>>,
+}
+
+impl RotatingLogWriter {
+    pub fn open(path: &Path, retention: LogRetention) -> Result<Self> {
+        let mut file = Self::open_file(path)?;
+        let size = file.metadata().map_err(HyperVError::Io)?.len();
+        let mut last = [b'\n'];
+        if size > 0 {
+            file.seek(SeekFrom::End(-1)).map_err(HyperVError::Io)?;
+            file.read_exact(&mut last).map_err(HyperVError::Io)?;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size,
+            at_line_start: last[0] == b'\n',
+            retention,
+            compressing: None,
+        })
+    }
+
+    fn open_file(path: &Path) -> Result<File> {
+        fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(path)
+            .map_err(HyperVError::Io)
+    }
+
+    pub fn write(&mut self, mut data: &[u8]) -> Result<()> {
+        let max_size = self.retention.max_size;
+        while self.size + data.len() as u64 > max_size {
+            let room = max_size.saturating_sub(self.size).min(data.len() as u64) as usize;
+            if let Some(line_end) = data[..room].iter().rposition(|&b| b == b'\n') {
+                // Fill the file up to the last line break that fits
+                self.append(&data[..=line_end])?;
+                data = &data[line_end + 1..];
+                self.rotate()?;
+            } else if self.size > 0 && self.at_line_start {
+                // The next line does not fit; start it in a new file
+                self.rotate()?;
+            } else if let Some(line_end) = data.iter().position(|&b| b == b'\n') {
+                // A line longer than the room left is kept whole
+                self.append(&data[..=line_end])?;
+                data = &data[line_end + 1..];
+                self.rotate()?;
+            } else if self.size >= max_size {
+                // No line break in sight; only split a line once the file is already full
+                self.rotate()?;
+                return self.append(data);
+            } else {
+                break;
+            }
+        }
+        self.append(data)
+    }
+
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.file.write_all(data).map_err(HyperVError::Io)?;
+        self.size += data.len() as u64;
+        self.at_line_start = data.ends_with(b"\n");
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        // Archives are shifted one at a time, so let the previous one finish first
+        self.wait_for_compression();
+        LogManager::rotate_archives_keeping(&self.path, self.retention.max_archives)?;
+
+        let archive = LogManager::plain_archive_path(&self.path, 1)?;
+        fs::rename(&self.path, &archive).map_err(HyperVError::Io)?;
+        if self.retention.compress {
+            // Readers see the plain archive until the compressed one replaces it
+            let compressed = LogManager::archive_path(&self.path, 1)?;
+            self.compressing = Some(thread::spawn(move || {
+                LogManager::compress_log_to_archive(&archive, &compressed)?;
+                fs::remove_file(&archive).map_err(HyperVError::Io)
+            }));
+        }
+        self.file = Self::open_file(&self.path)?;
+        self.size = 0;
+        self.at_line_start = true;
+
+        LogManager::prune_archives(&self.path, &self.retention)?;
+        Ok(())
+    }
+
+    fn wait_for_compression(&mut self) {
+        if let Some(handle) = self.compressing.take() {
+            match handle.join() {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("Failed to compress {}: {}", self.path.display(), e),
+                Err(_) => eprintln!("Log compression for {} panicked", self.path.display()),
+            }
+        }
+    }
+
+    /// Wait for any archive still being written
+    pub fn finish(mut self) -> Result<()> {
+        self.wait_for_compression();
+        self.file.flush().map_err(HyperVError::Io)
+    }
+}
+
 /// Compact diagnostic summary for one `hyperV logs --summary` invocation.
 #[derive(Debug, Default)]
 pub struct LogSummary {
@@ -510,19 +1777,17 @@ pub struct LogSummary {
     top_messages: Vec<(usize, String)>,
     recent_events: Vec<LogEvent>,
     message_counts: HashMap<String, usize>,
+    /// Errors of JSON lines per logger and per error type
+    errors_by_logger: HashMap<String, usize>,
+    errors_by_type: HashMap<String, usize>,
 }
 
 impl LogSummary {
     fn finalize(&mut self) {
-        let mut top_messages: Vec<(usize, String)> = self
-            .message_counts
-            .iter()
-            .map(|(message, count)| (*count, message.clone()))
-            .collect();
-        top_messages.sort_by(|left, right| right.0.cmp(&left.0).then_with(|| left.1.cmp(&right.1)));
-        top_messages.truncate(8);
-        self.top_messages = top_messages;
+        self.top_messages = top_counts(&self.message_counts, 8);
 
+        // Archives are read after the current file, so order events by when they happened
+        self.recent_events.sort_by_key(|event| event.time);
         let recent_start = self.recent_events.len().saturating_sub(10);
         self.recent_events = self.recent_events.split_off(recent_start);
     }
@@ -565,15 +1830,37 @@ impl LogSummary {
             }
         }
 
+        for (title, counts) in [
+            ("Errors by logger", &self.errors_by_logger),
+            ("Errors by type", &self.errors_by_type),
+        ] {
+            if counts.is_empty() {
+                continue;
+            }
+            output.push_str(&format!("\n{}:\n", title));
+            for (count, name) in top_counts(counts, 5) {
+                output.push_str(&format!("- {}x {}\n", count, name));
+            }
+        }
+
         output.push_str("\nRecent warnings/errors:\n");
         if self.recent_events.is_empty() {
             output.push_str("- none\n");
         } else {
             for event in &self.recent_events {
                 let archive_marker = if event.archived { " archived" } else { "" };
+                let time = event
+                    .time
+                    .map(|time| {
+                        format!(
+                            "{} ",
+                            time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+                        )
+                    })
+                    .unwrap_or_default();
                 output.push_str(&format!(
-                    "- [{}{}] {} {}\n",
-                    event.source, archive_marker, event.level, event.message
+                    "- {}[{}{}] {} {}\n",
+                    time, event.source, archive_marker, event.level, event.message
                 ));
             }
         }
@@ -582,6 +1869,17 @@ impl LogSummary {
     }
 }
 
+/// The `limit` largest counts, most frequent first
+fn top_counts(counts: &HashMap<String, usize>, limit: usize) -> Vec<(usize, String)> {
+    let mut top: Vec<(usize, String)> = counts
+        .iter()
+        .map(|(name, count)| (*count, name.clone()))
+        .collect();
+    top.sort_by(|left, right| right.0.cmp(&left.0).then_with(|| left.1.cmp(&right.1)));
+    top.truncate(limit);
+    top
+}
+
 #[derive(Debug)]
 pub struct LogFileSummary {
     pub name: String,
@@ -591,7 +1889,8 @@ pub struct LogFileSummary {
     pub bytes: u64,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Ordered from most to least severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum LogLevel {
     Error,
     Warn,
@@ -614,44 +1913,67 @@ impl std::fmt::Display for LogLevel {
 struct LogEvent {
     source: String,
     archived: bool,
+    /// When the line was received, or the time the application logged in it
+    time: Option<DateTime<Utc>>,
     level: LogLevel,
     message: String,
+    /// Logger and error type of JSON lines
+    logger: Option<String>,
+    error_type: Option<String>,
 }
 
 impl LogEvent {
-    fn from_line(source: &str, archived: bool, line: &str) -> Self {
-        if let Ok(value) = serde_json::from_str::<Value>(line) {
-            let level = value
-                .get("level")
-                .and_then(Value::as_str)
-                .map(parse_level)
-                .unwrap_or(LogLevel::Other);
-            let message = value
-                .get("msg")
-                .or_else(|| value.get("message"))
-                .and_then(Value::as_str)
-                .unwrap_or(line);
+    fn from_record(record: LogRecord, archived: bool, field_map: &JsonFieldMap) -> Self {
+        let source = record.stream.as_str().to_uppercase();
+        let mut event = Self::from_line(&source, archived, &record.message, field_map);
+        event.time = record.time.or(event.time);
+        event
+    }
 
+    fn from_line(source: &str, archived: bool, line: &str, field_map: &JsonFieldMap) -> Self {
+        if let Some(structured) = field_map.parse(line) {
+            let message = structured.message.as_deref().unwrap_or(line);
             return Self {
                 source: source.to_string(),
                 archived,
-                level,
+                time: structured.time,
+                level: structured.level.unwrap_or(LogLevel::Other),
                 message: sanitize_message(message),
+                logger: structured.logger,
+                error_type: structured.error_type,
             };
         }
 
         Self {
             source: source.to_string(),
             archived,
+            time: None,
             level: parse_level(line),
             message: sanitize_message(line),
+            logger: None,
+            error_type: None,
         }
     }
 }
 
+/// `error`, `warn` or `info` for a line with a recognizable level: the level field
+/// of a JSON line, otherwise guessed from the text
+pub(crate) fn level_name(message: &str, field_map: &JsonFieldMap) -> Option<&'static str> {
+    let level = match field_map.parse(message) {
+        Some(line) => line.level.unwrap_or(LogLevel::Other),
+        None => parse_level(message),
+    };
+    match level {
+        LogLevel::Error => Some("error"),
+        LogLevel::Warn => Some("warn"),
+        LogLevel::Info => Some("info"),
+        LogLevel::Other => None,
+    }
+}
+
 fn parse_level(input: &str) -> LogLevel {
     match input.to_ascii_uppercase().as_str() {
-        "ERROR" => LogLevel::Error,
+        "ERROR" | "ERR" | "FATAL" | "CRITICAL" | "CRIT" | "DPANIC" => LogLevel::Error,
         "WARN" | "WARNING" => LogLevel::Warn,
         "INFO" => LogLevel::Info,
         value if value.contains("ERROR") || value.contains("PANIC") || value.contains("FAIL") => {
@@ -686,7 +2008,9 @@ const SENSITIVE_REDACTION_KEYS: [&str; 11] = [
     "key",
 ];
 
-fn redact_sensitive_values(input: &str) -> String {
+/// Replace the values of `key=value` and `key: value` pairs with sensitive keys
+/// (passwords, tokens, secrets, API keys) by `[REDACTED]`
+pub fn redact_sensitive_values(input: &str) -> String {
     let mut output = input.to_string();
     for key in SENSITIVE_REDACTION_KEYS {
         output = redact_key_assignments(&output, key);
@@ -694,6 +2018,28 @@ fn redact_sensitive_values(input: &str) -> String {
     output
 }
 
+/// Whether a variable name such as `DATABASE_PASSWORD`, `api-key` or `Gemini__ApiKey`
+/// names a secret: one of its `_`, `-`, `.` or camelCase separated words is a
+/// sensitive key
+pub fn is_sensitive_name(name: &str) -> bool {
+    let mut split = String::with_capacity(name.len() + 4);
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && previous.is_some_and(|p| p.is_ascii_lowercase()) {
+            split.push('_');
+        }
+        split.push(c.to_ascii_lowercase());
+        previous = Some(c);
+    }
+    let words: Vec<&str> = split.split(['_', '-', '.']).collect();
+    SENSITIVE_REDACTION_KEYS.iter().any(|key| {
+        let key_words = key.split('_').count();
+        words
+            .windows(key_words)
+            .any(|window| window.join("_") == *key)
+    })
+}
+
 fn redact_key_assignments(input: &str, key: &str) -> String {
     let mut output = String::with_capacity(input.len());
     let lower = input.to_ascii_lowercase();
@@ -771,7 +2117,7 @@ mod tests {
     }
 }
 
-fn format_bytes(bytes: u64) -> String {
+pub(crate) fn format_bytes(bytes: u64) -> String {
     if bytes < 1024 {
         format!("{} B", bytes)
     } else if bytes < 1024 * 1024 {
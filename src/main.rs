@@ -8,20 +8,61 @@ use clap::Parser;
 use hyperV::alerts::{Alert, AlertTracker, TelegramNotifier, format_alert_message};
 use hyperV::compose::ComposeFile;
 use hyperV::config::Config;
-use hyperV::constants::MAX_RESTART_ATTEMPTS;
+use hyperV::control::{self, ControlRequest, ControlResponse};
+use hyperV::forward::LogForwarder;
+use hyperV::health::{HealthProbeResult, HealthScheduler};
+use hyperV::logs::{LogFilter, LogManager, LogRetention};
+use hyperV::process::StopInProgress;
+use hyperV::usage::UsageSampler;
+use hyperV::watchdog::{WatchdogAction, WatchdogBreach, WatchdogMonitor};
 use hyperV::{
-    Result,
-    cli::{Cli, Commands},
+    HyperVError, Result,
+    cli::{Cli, Commands, SecretCommands},
     manager::TaskManager,
-    task::TaskStatus,
+    task::{RestartBackoff, RestartPolicy, TaskOptions, TaskStatus},
 };
 use std::fs;
 use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// How long the CLI waits for a freshly spawned daemon to accept control requests
+const DAEMON_STARTUP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long `up --start` waits for a dependency to reach its `depends_on` condition
+const DEPENDENCY_WAIT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How often `up --start` re-checks a dependency it is waiting for
+const DEPENDENCY_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the daemon looks for tasks whose health check is due
+const HEALTH_CHECK_TICK: Duration = Duration::from_secs(1);
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    if let Commands::CaptureLog {
+        path,
+        stream,
+        max_size,
+        max_archives,
+        max_age_days,
+        no_compress,
+    } = &cli.command
+    {
+        let retention = LogRetention {
+            max_size: *max_size,
+            max_archives: *max_archives,
+            max_age_days: *max_age_days,
+            compress: !no_compress,
+        };
+        return LogManager::capture(std::io::stdin().lock(), path, *stream, retention);
+    }
+
     let mut task_manager = TaskManager::new()?;
+    if let Ok(executable) = std::env::current_exe() {
+        task_manager.set_log_writer(executable);
+    }
 
     match cli.command {
         Commands::New {
@@ -29,42 +70,218 @@ async fn main() -> Result<()> {
             binary,
             args,
             env,
+            env_files,
+            secret_env,
+            user,
+            group,
+            supplementary_groups,
             workdir,
             auto_restart,
+            restart,
+            success_exit_codes,
+            restart_delay,
+            restart_multiplier,
+            restart_max_delay,
+            max_restarts,
+            stable_after,
+            health,
+            limits,
+            watchdog,
+            logs,
         } => {
-            task_manager.create_task(name, binary, args, env, workdir, auto_restart)?;
-        }
-        Commands::List => {
-            task_manager.list_tasks();
+            let log_settings = logs.to_log_settings()?;
+            log_settings.validate().map_err(HyperVError::InvalidInput)?;
+            let restart_policy = restart.unwrap_or(if auto_restart {
+                RestartPolicy::OnFailure
+            } else {
+                RestartPolicy::Never
+            });
+            let defaults = RestartBackoff::default();
+            let options = TaskOptions {
+                restart_policy,
+                success_exit_codes,
+                restart_backoff: RestartBackoff {
+                    initial_delay_secs: restart_delay.unwrap_or(defaults.initial_delay_secs),
+                    multiplier: restart_multiplier.unwrap_or(defaults.multiplier),
+                    max_delay_secs: restart_max_delay.unwrap_or(defaults.max_delay_secs),
+                    max_attempts: max_restarts.unwrap_or(defaults.max_attempts),
+                    stable_after_secs: stable_after.unwrap_or(defaults.stable_after_secs),
+                },
+                health_check: health.to_health_check(),
+                watchdog: watchdog.to_watchdog(),
+                env_files: env_files
+                    .iter()
+                    .map(|path| {
+                        std::path::absolute(path)
+                            .map(|p| p.to_string_lossy().to_string())
+                            .map_err(HyperVError::Io)
+                    })
+                    .collect::<Result<_>>()?,
+                secret_env,
+                user,
+                group,
+                supplementary_groups,
+                limits: limits.to_resource_limits(),
+                cgroup_limits: limits.to_cgroup_limits(),
+                log_settings,
+            };
+            task_manager.create_task_with_options(name, binary, args, env, workdir, options)?;
         }
+        Commands::List { project } => match daemon_request(ControlRequest::List)? {
+            Some(response) => {
+                let tasks: Vec<_> = response
+                    .tasks
+                    .into_iter()
+                    .filter(|t| project.as_deref().is_none_or(|p| t.belongs_to_project(p)))
+                    .collect();
+                TaskManager::print_task_list(&tasks);
+            }
+            None => task_manager.list_tasks(project.as_deref()),
+        },
+        Commands::Top {
+            project,
+            interval,
+            iterations,
+        } => run_top(&mut task_manager, project.as_deref(), interval, iterations)?,
         Commands::Start { task } => {
-            task_manager.start_task(&task)?;
-            maybe_spawn_daemon(&mut task_manager)?;
+            maybe_spawn_daemon(&mut task_manager, false)?;
+            match daemon_request(ControlRequest::Start { task: task.clone() })? {
+                Some(response) => println!("{}", response.message),
+                None => task_manager.start_task(&task)?,
+            }
         }
         Commands::Restart { task } => {
-            task_manager.restart_task(&task)?;
-            maybe_spawn_daemon(&mut task_manager)?;
+            maybe_spawn_daemon(&mut task_manager, false)?;
+            match daemon_request(ControlRequest::Restart { task: task.clone() })? {
+                Some(response) => println!("{}", response.message),
+                None => task_manager.restart_task(&task)?,
+            }
         }
         Commands::Stop { task } => {
-            task_manager.stop_task(&task)?;
+            match daemon_request(ControlRequest::Stop { task: task.clone() })? {
+                Some(response) => println!("{}", response.message),
+                None => task_manager.stop_task(&task)?,
+            }
         }
         Commands::Remove { task } => {
             task_manager.remove_task(&task)?;
         }
-        Commands::Status { task } => {
-            task_manager.show_status(task.as_deref())?;
+        Commands::Status { task, show_secrets } => {
+            match daemon_request(ControlRequest::Status { task: task.clone() })? {
+                Some(response) => {
+                    TaskManager::print_task_status(&response.tasks, task.as_deref(), show_secrets)
+                }
+                None => task_manager.show_status(task.as_deref(), show_secrets)?,
+            }
         }
+        Commands::Reload => match daemon_request(ControlRequest::Reload)? {
+            Some(response) => println!("{}", response.message),
+            None => println!("ℹ️  No daemon is running; nothing to reload"),
+        },
         Commands::Logs {
-            task,
+            tasks,
+            all,
+            project,
             lines,
             log_type,
             follow,
             summary,
+            since,
+            until,
+            grep,
+            level,
+            json_field,
+            prune,
         } => {
-            task_manager.show_logs(&task, lines, log_type, follow, summary)?;
+            if prune {
+                let all = all || (tasks.is_empty() && project.is_none());
+                let selected = task_manager.select_tasks(&tasks, all, project.as_deref())?;
+                task_manager.prune_logs(&selected)?;
+                return Ok(());
+            }
+            let filter = LogFilter::new(
+                since.as_deref(),
+                until.as_deref(),
+                grep.as_deref(),
+                level.as_deref(),
+            )?
+            .with_json_fields(&json_field)?;
+            if let [task] = tasks.as_slice()
+                && project.is_none()
+            {
+                task_manager.show_logs(task, lines, log_type, &filter, follow, summary)?;
+            } else if summary {
+                return Err(HyperVError::InvalidInput(
+                    "--summary works with one task at a time".to_string(),
+                ));
+            } else {
+                let selected = task_manager.select_tasks(&tasks, all, project.as_deref())?;
+                task_manager.show_task_logs(&selected, lines, log_type, &filter, follow)?;
+            }
         }
-        Commands::Diagnose { task } => {
-            task_manager.diagnose_task(&task)?;
+        Commands::Diagnose { task, show_secrets } => {
+            task_manager.diagnose_task(&task, show_secrets)?;
+        }
+        Commands::Bundle {
+            task,
+            output,
+            lines,
+        } => {
+            let path = task_manager.bundle_task(&task, lines, output.as_deref())?;
+            println!(
+                "📦 Support bundle for \"{}\" written to {}",
+                task,
+                path.display()
+            );
+        }
+        Commands::Secret { command } => {
+            let store = task_manager.secret_store();
+            match command {
+                SecretCommands::Set { name, value } => {
+                    let value = match value {
+                        Some(value) => value,
+                        None => {
+                            let mut value = String::new();
+                            std::io::Read::read_to_string(&mut std::io::stdin(), &mut value)
+                                .map_err(HyperVError::Io)?;
+                            value.trim_end_matches(['\r', '\n']).to_string()
+                        }
+                    };
+                    store.set(&name, &value)?;
+                    println!("🔐 Secret \"{}\" stored", name);
+                }
+                SecretCommands::Get { name } => println!("{}", store.get(&name)?),
+                SecretCommands::List => {
+                    let names = store.names()?;
+                    if names.is_empty() {
+                        println!("No secrets stored.");
+                    }
+                    for name in names {
+                        let users = task_manager.tasks_using_secret(&name);
+                        if users.is_empty() {
+                            println!("{}", name);
+                        } else {
+                            println!("{} (used by: {})", name, users.join(", "));
+                        }
+                    }
+                }
+                SecretCommands::Rm { name } => {
+                    if !store.remove(&name)? {
+                        return Err(HyperVError::Secret(format!(
+                            "secret \"{}\" is not in the store",
+                            name
+                        )));
+                    }
+                    println!("🗑️  Secret \"{}\" removed", name);
+                    let users = task_manager.tasks_using_secret(&name);
+                    if !users.is_empty() {
+                        println!(
+                            "⚠️  Still referenced by: {}; they will fail to start",
+                            users.join(", ")
+                        );
+                    }
+                }
+            }
         }
         Commands::Daemon => {
             // Run in daemon mode - monitoring and auto-restarting tasks
@@ -75,15 +292,48 @@ async fn main() -> Result<()> {
             let _ = remove_daemon_pid();
             result?;
         }
-        Commands::Up { file, start } => {
+        Commands::Up {
+            file,
+            start,
+            dry_run,
+            remove_orphans,
+            print_config,
+        } => {
             let compose = ComposeFile::from_path(&file)?;
-            task_manager.up_from_compose(&compose)?;
-            if start {
-                for name in compose.services.keys() {
-                    let _ = task_manager.start_task(name);
+            if print_config {
+                print!("{}", compose.to_yaml()?);
+                return Ok(());
+            }
+            if dry_run {
+                let plan = task_manager.plan_compose(&compose)?;
+                println!("📋 Plan for {} (dry run, nothing was changed):", file);
+                plan.print();
+                return Ok(());
+            }
+
+            let plan = task_manager.up_from_compose(&compose)?;
+            println!("📋 Applying {}:", file);
+            plan.print();
+            if remove_orphans {
+                task_manager.remove_compose_orphans(&plan)?;
+            }
+            maybe_spawn_daemon(&mut task_manager, compose.needs_daemon())?;
+
+            task_manager.refresh_task_statuses()?;
+            let changed: Vec<&str> = plan.updated().collect();
+            for service in compose.startup_order()? {
+                let name = compose.task_name(&service);
+                let running = task_manager
+                    .find_task(&name)
+                    .is_some_and(|t| t.status == TaskStatus::Running);
+                if running && changed.contains(&name.as_str()) {
+                    println!("🔄 Configuration of \"{}\" changed; restarting", name);
+                    restart_service(&mut task_manager, &name);
+                } else if start {
+                    wait_for_dependencies(&mut task_manager, &compose, &service)?;
+                    start_service(&mut task_manager, &name);
                 }
             }
-            maybe_spawn_daemon(&mut task_manager)?;
             println!("✅ Applied services from {}", file);
         }
         Commands::Down { file } => {
@@ -91,6 +341,8 @@ async fn main() -> Result<()> {
             task_manager.down_from_compose(&compose)?;
             println!("✅ Removed services from {}", file);
         }
+        // Handled before the task list is loaded
+        Commands::CaptureLog { .. } => unreachable!(),
     }
 
     Ok(())
@@ -99,9 +351,10 @@ async fn main() -> Result<()> {
 async fn run_daemon_mode(mut task_manager: TaskManager) -> Result<()> {
     use hyperV::constants::MAIN_LOOP_INTERVAL;
     use tokio::signal;
-    use tokio::time::sleep;
+    use tokio::time::{MissedTickBehavior, interval};
 
     println!("🚀 Starting hyperV daemon mode...");
+    hyperV::cgroup::allow_leaving_own_cgroup();
     println!(
         "📋 Monitoring {} tasks ({} with auto-restart)",
         task_manager.task_count(),
@@ -119,18 +372,117 @@ async fn run_daemon_mode(mut task_manager: TaskManager) -> Result<()> {
         );
     }
     let mut alert_tracker = AlertTracker::new();
+    let mut health_scheduler = HealthScheduler::new(task_manager.secret_store());
+    let mut health_probes: JoinSet<HealthProbeResult> = JoinSet::new();
+    let mut health_ticker = interval(HEALTH_CHECK_TICK);
+    let mut watchdog = WatchdogMonitor::new();
+    health_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Graceful stops wait for the task off the loop; see `wait_in_background`
+    let mut stops: JoinSet<FinishedStop> = JoinSet::new();
+
+    // Bring back `always` tasks before accepting requests so CLI commands see the result
+    if let Err(e) = task_manager.start_always_tasks() {
+        eprintln!("Error starting tasks with restart policy 'always': {}", e);
+    }
+
+    let mut log_forwarder = LogForwarder::start();
+    update_log_forwarding(&task_manager, &mut log_forwarder);
+
+    #[cfg(unix)]
+    let control_server = control::ControlServer::bind(&Config::new()?)?;
 
     // Set up signal handler for graceful shutdown
     let ctrl_c = signal::ctrl_c();
     tokio::pin!(ctrl_c);
 
+    // A ticker (rather than a fresh sleep per iteration) keeps maintenance on schedule
+    // even while control requests keep arriving.
+    let mut ticker = interval(MAIN_LOOP_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
     loop {
+        #[cfg(unix)]
+        let next_request = control_server.accept();
+        #[cfg(not(unix))]
+        let next_request = std::future::pending::<Result<()>>();
+
+        // Wake up when the earliest backoff expires rather than blocking the loop on it
+        let restart_wait = task_manager.next_restart_in();
+
         tokio::select! {
             _ = &mut ctrl_c => {
                 println!("\n🛑 Received shutdown signal, stopping daemon...");
+                // Let requested stops finish so their clients get an answer
+                while let Some(joined) = stops.join_next().await {
+                    if let Ok(finished) = joined {
+                        finish_background_stop(&mut task_manager, finished).await;
+                    }
+                }
                 break;
             }
-            _ = sleep(MAIN_LOOP_INTERVAL) => {
+            connection = next_request => {
+                #[cfg(unix)]
+                match connection {
+                    Ok(mut connection) => {
+                        let handled = match connection.read_request().await {
+                            Ok(request) => match handle_control_request(&mut task_manager, &request) {
+                                Handled::Respond(response) => response,
+                                Handled::Stopping(stop) => {
+                                    wait_in_background(
+                                        &mut stops,
+                                        stop,
+                                        AfterStop::Reply(connection, request),
+                                    );
+                                    continue;
+                                }
+                            },
+                            Err(e) => ControlResponse::failure(format!("Invalid request: {}", e)),
+                        };
+                        respond(connection, &handled).await;
+                    }
+                    Err(e) => eprintln!("Control socket error: {}", e),
+                }
+                #[cfg(not(unix))]
+                let _ = connection;
+            }
+            _ = health_ticker.tick() => {
+                for job in health_scheduler.due_jobs(task_manager.tasks(), Instant::now()) {
+                    health_probes.spawn_blocking(move || job.run());
+                }
+            }
+            Some(joined) = health_probes.join_next(), if !health_probes.is_empty() => {
+                let Ok(probe) = joined else { continue };
+                health_scheduler.finished(&probe.task_id);
+                match task_manager.record_health_result(&probe) {
+                    Ok(Some(task)) => {
+                        println!("💔 Task \"{}\" is unhealthy", task.name);
+                        if let Some(alert) = alert_tracker.record_unhealthy(&task, Utc::now()) {
+                            deliver_alert(notifier.as_ref(), &alert);
+                        }
+                        let restarted = task_manager
+                            .claim_unhealthy_restart(&task.id)
+                            .and_then(|claimed| match claimed {
+                                Some(name) => restart_in_background(&mut task_manager, &mut stops, &name),
+                                None => Ok(()),
+                            });
+                        if let Err(e) = restarted {
+                            eprintln!("Failed to restart unhealthy task \"{}\": {}", task.name, e);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Error recording health check: {}", e),
+                }
+            }
+            Some(joined) = stops.join_next(), if !stops.is_empty() => {
+                let Ok(finished) = joined else { continue };
+                finish_background_stop(&mut task_manager, finished).await;
+            }
+            _ = tokio::time::sleep(restart_wait.unwrap_or(MAIN_LOOP_INTERVAL)), if restart_wait.is_some() => {
+                if let Err(e) = task_manager.check_and_restart_tasks() {
+                    eprintln!("Error during task restart check: {}", e);
+                }
+            }
+            _ = ticker.tick() => {
                 match task_manager.cleanup_with_events() {
                     Ok(failed_tasks) => {
                         let now = Utc::now();
@@ -147,20 +499,28 @@ async fn run_daemon_mode(mut task_manager: TaskManager) -> Result<()> {
                 if let Err(e) = task_manager.check_and_restart_tasks() {
                     eprintln!("Error during task restart check: {}", e);
                 }
+                update_log_forwarding(&task_manager, &mut log_forwarder);
                 let now = Utc::now();
                 for task in task_manager.tasks() {
-                    if task.auto_restart
-                        && !task.suppress_restart
-                        && task.status == TaskStatus::Failed
+                    if task.wants_restart()
                         && let Some(alert) = alert_tracker.check_restart_exhausted(
                             task,
-                            MAX_RESTART_ATTEMPTS,
+                            task.restart_backoff.max_attempts,
                             now,
                         )
                     {
                         deliver_alert(notifier.as_ref(), &alert);
                     }
                 }
+                for breach in watchdog.check(task_manager.tasks(), Instant::now()) {
+                    handle_watchdog_breach(
+                        &mut task_manager,
+                        &mut stops,
+                        &mut alert_tracker,
+                        notifier.as_ref(),
+                        &breach,
+                    );
+                }
             }
         }
     }
@@ -169,6 +529,316 @@ async fn run_daemon_mode(mut task_manager: TaskManager) -> Result<()> {
     Ok(())
 }
 
+/// Record a watchdog breach and take the task's configured action
+fn handle_watchdog_breach(
+    task_manager: &mut TaskManager,
+    stops: &mut JoinSet<FinishedStop>,
+    alert_tracker: &mut AlertTracker,
+    notifier: Option<&TelegramNotifier>,
+    breach: &WatchdogBreach,
+) {
+    let task = match task_manager.record_watchdog_breach(breach) {
+        Ok(Some(task)) => task,
+        Ok(None) => return,
+        Err(e) => {
+            eprintln!("Error recording watchdog breach: {}", e);
+            return;
+        }
+    };
+    eprintln!("⚠️  Task \"{}\": {}", task.name, breach.kind);
+    match breach.action {
+        WatchdogAction::Warn => {}
+        WatchdogAction::Alert => {
+            if let Some(alert) = alert_tracker.record_watchdog(&task, breach, Utc::now()) {
+                deliver_alert(notifier, &alert);
+            }
+        }
+        WatchdogAction::Restart => {
+            let restarted = task_manager
+                .claim_runaway_restart(&task.id)
+                .and_then(|claimed| match claimed {
+                    Some(name) => restart_in_background(task_manager, stops, &name),
+                    None => Ok(()),
+                });
+            if let Err(e) = restarted {
+                eprintln!("Failed to restart runaway task \"{}\": {}", task.name, e);
+            }
+        }
+    }
+}
+
+/// What the daemon does once a stop that waited off the loop has finished
+enum AfterStop {
+    /// Answer the `Stop` or `Restart` request that asked for it
+    #[cfg(unix)]
+    Reply(control::ControlConnection, ControlRequest),
+    /// Start the task again (health check and watchdog restarts)
+    Start(String),
+}
+
+/// A stop whose wait has ended, with the outcome of that wait
+type FinishedStop = (StopInProgress, Result<()>, AfterStop);
+
+/// Wait for a task that was sent SIGTERM on a blocking thread, so health checks,
+/// restarts and other requests keep being served while it shuts down
+fn wait_in_background(
+    stops: &mut JoinSet<FinishedStop>,
+    mut stop: StopInProgress,
+    after: AfterStop,
+) {
+    stops.spawn_blocking(move || {
+        let result = stop.wait();
+        (stop, result, after)
+    });
+}
+
+/// Restart a task, waiting for the old run off the loop when it has to be stopped
+fn restart_in_background(
+    task_manager: &mut TaskManager,
+    stops: &mut JoinSet<FinishedStop>,
+    task_name: &str,
+) -> Result<()> {
+    let running = task_manager
+        .find_task(task_name)
+        .is_some_and(|t| t.status == TaskStatus::Running);
+    if running && let Some(stop) = task_manager.begin_stop_task(task_name)? {
+        wait_in_background(stops, stop, AfterStop::Start(task_name.to_string()));
+        return Ok(());
+    }
+    task_manager.start_task(task_name)
+}
+
+/// Record the end of a stop and carry out what was waiting for it
+async fn finish_background_stop(
+    task_manager: &mut TaskManager,
+    (stop, result, after): FinishedStop,
+) {
+    // Other hyperV processes may have changed the task files during the wait
+    if let Err(e) = task_manager.reload() {
+        eprintln!("⚠️  Failed to load tasks: {}", e);
+    }
+    let finished = task_manager.finish_stop(stop, result);
+    match after {
+        #[cfg(unix)]
+        AfterStop::Reply(connection, request) => {
+            let response = finished
+                .and_then(|()| finish_control_request(task_manager, &request))
+                .unwrap_or_else(|e| ControlResponse::failure(e.to_string()));
+            respond(connection, &response).await;
+        }
+        AfterStop::Start(task_name) => {
+            if let Err(e) = finished.and_then(|()| task_manager.start_task(&task_name)) {
+                eprintln!("Failed to restart task \"{}\": {}", task_name, e);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn respond(connection: control::ControlConnection, response: &ControlResponse) {
+    if let Err(e) = connection.respond(response).await {
+        eprintln!("Failed to answer control request: {}", e);
+    }
+}
+
+/// Follow the logs of tasks that have forwarding targets, picking up tasks and
+/// `daemon.yaml` changes
+fn update_log_forwarding(task_manager: &TaskManager, log_forwarder: &mut LogForwarder) {
+    match task_manager.log_forwarding() {
+        Ok(tasks) => {
+            if log_forwarder.update(tasks) && !log_forwarder.tasks().is_empty() {
+                let names: Vec<&str> = log_forwarder
+                    .tasks()
+                    .iter()
+                    .map(|task| task.name.as_str())
+                    .collect();
+                println!("📡 Forwarding logs of {}", names.join(", "));
+            }
+        }
+        Err(e) => eprintln!("Error reading log forwarding settings: {}", e),
+    }
+}
+
+/// Result of handling a control request on the daemon loop
+enum Handled {
+    /// Answer the client right away
+    Respond(ControlResponse),
+    /// The task was sent SIGTERM; answer once it has exited
+    Stopping(StopInProgress),
+}
+
+/// Execute a CLI request inside the daemon. Requests are handled one at a time, so the
+/// daemon is the single place where concurrent lifecycle commands are ordered, and every
+/// process it spawns is owned (and later reaped) here.
+///
+/// A graceful `Stop` or `Restart` only sends SIGTERM here. The wait for the task to
+/// exit runs off the loop, and [`finish_control_request`] answers once it is over.
+fn handle_control_request(task_manager: &mut TaskManager, request: &ControlRequest) -> Handled {
+    if let Err(e) = task_manager.reload() {
+        return Handled::Respond(ControlResponse::failure(format!(
+            "Failed to load tasks: {}",
+            e
+        )));
+    }
+
+    let result = match request {
+        ControlRequest::Start { task } => task_manager
+            .start_task(task)
+            .map(|()| ControlResponse::success(started_message(task_manager, task))),
+        ControlRequest::Restart { task } => {
+            let running = task_manager
+                .find_task(task)
+                .is_some_and(|t| t.status == TaskStatus::Running);
+            let stop = if running {
+                task_manager.begin_stop_task(task)
+            } else {
+                Ok(None)
+            };
+            match stop {
+                Ok(Some(stop)) => return Handled::Stopping(stop),
+                Ok(None) => finish_control_request(task_manager, request),
+                Err(e) => Err(e),
+            }
+        }
+        ControlRequest::Stop { task } => {
+            let was_running = task_manager
+                .find_task(task)
+                .map(|t| (t.name.clone(), t.status == TaskStatus::Running));
+            match task_manager.begin_stop_task(task) {
+                Ok(Some(stop)) => return Handled::Stopping(stop),
+                Ok(None) => Ok(match was_running {
+                    Some((name, true)) => {
+                        ControlResponse::success(format!("✅ Task \"{}\" stopped", name))
+                    }
+                    Some((name, false)) => ControlResponse::success(format!(
+                        "ℹ️  Task \"{}\" is already stopped",
+                        name
+                    )),
+                    None => ControlResponse::success("✅ Task stopped"),
+                }),
+                Err(e) => Err(e),
+            }
+        }
+        ControlRequest::Status { task } => task_manager.refresh_task_statuses().map(|()| {
+            let tasks = task_manager
+                .tasks()
+                .iter()
+                .filter(|t| task.as_deref().is_none_or(|id| t.matches(id)))
+                .cloned()
+                .collect();
+            ControlResponse::success("").with_tasks(tasks)
+        }),
+        ControlRequest::List => task_manager
+            .refresh_task_statuses()
+            .map(|()| ControlResponse::success("").with_tasks(task_manager.tasks().to_vec())),
+        ControlRequest::Reload => task_manager.refresh_task_statuses().map(|()| {
+            ControlResponse::success(format!(
+                "🔄 Daemon reloaded {} tasks",
+                task_manager.task_count()
+            ))
+        }),
+    };
+
+    Handled::Respond(result.unwrap_or_else(|e| ControlResponse::failure(e.to_string())))
+}
+
+/// The rest of a `Stop` or `Restart` request once the task has been stopped
+fn finish_control_request(
+    task_manager: &mut TaskManager,
+    request: &ControlRequest,
+) -> Result<ControlResponse> {
+    match request {
+        ControlRequest::Restart { task } => task_manager
+            .start_task(task)
+            .map(|()| ControlResponse::success(started_message(task_manager, task))),
+        ControlRequest::Stop { task } => Ok(ControlResponse::success(
+            match task_manager.find_task(task) {
+                Some(t) => format!("✅ Task \"{}\" stopped", t.name),
+                None => "✅ Task stopped".to_string(),
+            },
+        )),
+        _ => Err(HyperVError::Daemon(format!(
+            "Request {:?} does not stop a task",
+            request
+        ))),
+    }
+}
+
+/// The live usage view of `hyperV top`
+fn run_top(
+    task_manager: &mut TaskManager,
+    project: Option<&str>,
+    interval: u64,
+    iterations: Option<usize>,
+) -> Result<()> {
+    use std::io::{IsTerminal, Write};
+
+    let interval = Duration::from_secs(interval.max(1));
+    let redraw = std::io::stdout().is_terminal();
+    let mut sampler = UsageSampler::new();
+    let mut shown = 0;
+    loop {
+        let tasks: Vec<_> = match daemon_request(ControlRequest::List)? {
+            Some(response) => response.tasks,
+            None => {
+                task_manager.reload()?;
+                task_manager.refresh_task_statuses()?;
+                task_manager.tasks().to_vec()
+            }
+        }
+        .into_iter()
+        .filter(|t| project.is_none_or(|p| t.belongs_to_project(p)))
+        .collect();
+
+        // CPU usage is measured between refreshes; the first view needs two
+        if shown == 0 {
+            sampler.refresh_with_cpu();
+        } else {
+            sampler.refresh();
+        }
+        if redraw {
+            print!("\x1b[2J\x1b[H");
+        } else if shown > 0 {
+            println!();
+        }
+        println!(
+            "hyperV top - {} (every {}s, Ctrl+C to quit)\n",
+            chrono::Local::now().format("%H:%M:%S"),
+            interval.as_secs()
+        );
+        TaskManager::print_task_usage(&tasks, &sampler);
+        std::io::stdout().flush()?;
+
+        shown += 1;
+        if iterations.is_some_and(|n| shown >= n) {
+            return Ok(());
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+fn started_message(task_manager: &TaskManager, identifier: &str) -> String {
+    match task_manager.find_task(identifier) {
+        Some(task) => format!(
+            "✅ Task \"{}\" started successfully with PID {} (supervised by daemon)",
+            task.name,
+            task.pid
+                .map(|pid| pid.to_string())
+                .unwrap_or_else(|| "?".to_string())
+        ),
+        None => "✅ Task started".to_string(),
+    }
+}
+
+/// Send a request to the running daemon. `None` means no daemon is reachable and the
+/// caller should act on the task files directly.
+fn daemon_request(request: ControlRequest) -> Result<Option<ControlResponse>> {
+    match control::send_request(&Config::new()?, &request) {
+        Some(response) => Ok(Some(response?.into_result()?)),
+        None => Ok(None),
+    }
+}
+
 fn deliver_alert(notifier: Option<&TelegramNotifier>, alert: &Alert) {
     let local_message = format_alert_message(alert).replace('\n', " | ");
     eprintln!("⚠️  {}", local_message);
@@ -242,8 +912,99 @@ fn is_daemon_running() -> bool {
     }
 }
 
-fn maybe_spawn_daemon(task_manager: &mut TaskManager) -> Result<()> {
-    if task_manager.any_autorestart_enabled() && !is_daemon_running() {
+/// Start one compose service, through the daemon when one is running. Services that
+/// are already running are left alone.
+fn start_service(task_manager: &mut TaskManager, name: &str) {
+    let result = match daemon_request(ControlRequest::Start {
+        task: name.to_string(),
+    }) {
+        Ok(Some(response)) => {
+            println!("{}", response.message);
+            Ok(())
+        }
+        Ok(None) => task_manager.start_task(name),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) | Err(hyperV::HyperVError::TaskAlreadyRunning(_)) => {}
+        Err(e) => println!("⚠️  Failed to start service \"{}\": {}", name, e),
+    }
+}
+
+/// Restart one compose service, through the daemon when one is running
+fn restart_service(task_manager: &mut TaskManager, name: &str) {
+    let result = match daemon_request(ControlRequest::Restart {
+        task: name.to_string(),
+    }) {
+        Ok(Some(response)) => {
+            println!("{}", response.message);
+            Ok(())
+        }
+        Ok(None) => task_manager.restart_task(name),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        println!("⚠️  Failed to restart service \"{}\": {}", name, e);
+    }
+}
+
+/// Block until every dependency of `service` reaches its `depends_on` condition
+fn wait_for_dependencies(
+    task_manager: &mut TaskManager,
+    compose: &ComposeFile,
+    service: &str,
+) -> Result<()> {
+    let name = compose.task_name(service);
+    for (dependency, condition) in &compose.services[service].depends_on {
+        let dependency = compose.task_name(dependency);
+        let deadline = Instant::now() + DEPENDENCY_WAIT_TIMEOUT;
+        let mut announced = false;
+        loop {
+            let task = current_task(task_manager, &dependency)?;
+            if condition.is_satisfied_by(&task)? {
+                break;
+            }
+            if Instant::now() >= deadline {
+                return Err(hyperV::HyperVError::ProcessError(format!(
+                    "Timed out after {}s waiting for \"{}\" to be {} before starting \"{}\"",
+                    DEPENDENCY_WAIT_TIMEOUT.as_secs(),
+                    dependency,
+                    condition,
+                    name
+                )));
+            }
+            if !announced {
+                println!(
+                    "⏳ Waiting for \"{}\" to be {} before starting \"{}\"...",
+                    dependency, condition, name
+                );
+                announced = true;
+            }
+            std::thread::sleep(DEPENDENCY_POLL_INTERVAL);
+        }
+    }
+    Ok(())
+}
+
+/// Latest state of a task, as seen by the daemon when one is running
+fn current_task(task_manager: &mut TaskManager, identifier: &str) -> Result<hyperV::Task> {
+    if let Some(response) = daemon_request(ControlRequest::Status {
+        task: Some(identifier.to_string()),
+    })? && let Some(task) = response.tasks.into_iter().next()
+    {
+        return Ok(task);
+    }
+    task_manager.reload()?;
+    // Collects exit codes of tasks this process started itself
+    task_manager.cleanup()?;
+    task_manager
+        .find_task(identifier)
+        .cloned()
+        .ok_or_else(|| hyperV::HyperVError::TaskNotFound(identifier.to_string()))
+}
+
+fn maybe_spawn_daemon(task_manager: &mut TaskManager, required: bool) -> Result<()> {
+    if (required || task_manager.any_autorestart_enabled()) && !is_daemon_running() {
         // Spawn a background daemon
         if let Ok(current_exe) = std::env::current_exe() {
             let _child = Command::new(current_exe)
@@ -253,6 +1014,9 @@ fn maybe_spawn_daemon(task_manager: &mut TaskManager) -> Result<()> {
                 .stderr(Stdio::null())
                 .spawn()
                 .map_err(|e| hyperV::HyperVError::ProcessStart("daemon".into(), e.to_string()))?;
+
+            // Give the daemon a moment to open its control socket so it can own the task process.
+            control::wait_for_daemon(&Config::new()?, DAEMON_STARTUP_TIMEOUT);
         }
     }
     Ok(())
@@ -3,16 +3,30 @@
 //! High-level task management operations including CRUD operations,
 //! process lifecycle management, and coordination between modules.
 
-use crate::config::Config;
+use crate::bundle::{SupportBundle, process_tree, redacted_task_json, task_history};
+use crate::cgroup::Cgroup;
+use crate::config::{Config, write_private};
+use crate::dotenv;
 use crate::error::{HyperVError, Result};
-use crate::logs::{LogManager, LogType};
-use crate::process::{ProcessManager, diagnose_binary};
-use crate::task::{Task, TaskStatus};
+use crate::forward::ForwardedTask;
+use crate::health::{HealthProbeResult, HealthStatus};
+use crate::logs::{
+    JsonFieldMap, LogFilter, LogManager, LogRecord, LogRetention, LogStream, LogType, TaskLogs,
+    format_bytes, redact_sensitive_values,
+};
+use crate::process::{
+    ProcessExit, ProcessManager, StopInProgress, diagnose_binary, diagnose_binary_with,
+};
+use crate::secrets::{self, SecretRef, SecretStore};
+use crate::task::{RestartPolicy, Task, TaskOptions, TaskStatus};
+use crate::usage::{TaskUsage, UsageSampler, format_uptime};
+use crate::watchdog::WatchdogBreach;
+use chrono::Utc;
 use fs2::FileExt;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use sysinfo::{Pid, System};
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -33,19 +47,14 @@ pub struct TaskManager {
     config: Config,
     /// Process manager
     process_manager: ProcessManager,
+    /// Exits reaped from owned children whose task has not been marked as exited yet
+    /// (e.g. the leader exited while other members of its process group keep running)
+    pending_exits: HashMap<String, ProcessExit>,
+    /// Tasks that were sent SIGTERM and are being given time to exit
+    stopping: HashSet<String>,
 }
 
 impl TaskManager {
-    fn get_process_memory_mb(sys: &mut System, pid: u32) -> u64 {
-        let pid = Pid::from_u32(pid);
-        if let Some(proc_) = sys.process(pid) {
-            // memory() returns bytes in sysinfo 0.30
-            let bytes = proc_.memory();
-            return bytes / (1024 * 1024); // Convert bytes to MB
-        }
-        0
-    }
-
     fn tasks_lock_file(&self) -> Result<fs::File> {
         let lock_path = self.config.tasks_file.with_extension("lock");
         fs::OpenOptions::new()
@@ -57,7 +66,7 @@ impl TaskManager {
             .map_err(HyperVError::Io)
     }
 
-    fn lock_tasks_for_update(&mut self) -> Result<fs::File> {
+    pub(crate) fn lock_tasks_for_update(&mut self) -> Result<fs::File> {
         let lock_file = self.tasks_lock_file()?;
         lock_file.lock_exclusive().map_err(HyperVError::Io)?;
         self.load_unlocked()?;
@@ -73,6 +82,8 @@ impl TaskManager {
             tasks: Vec::new(),
             config,
             process_manager,
+            pending_exits: HashMap::new(),
+            stopping: HashSet::new(),
         };
 
         // Load existing tasks (with locking)
@@ -118,6 +129,11 @@ impl TaskManager {
         Ok(manager)
     }
 
+    /// Re-read tasks from disk to pick up changes made by other hyperV processes
+    pub fn reload(&mut self) -> Result<()> {
+        self.load()
+    }
+
     /// Load tasks from configuration file
     pub(crate) fn load(&mut self) -> Result<()> {
         let lock_file = self.tasks_lock_file()?;
@@ -131,13 +147,37 @@ impl TaskManager {
             return Ok(());
         }
 
-        let file = fs::File::open(&self.config.tasks_file).map_err(HyperVError::Io)?;
+        self.tasks = Self::read_tasks_file(&self.config.tasks_file)?;
+        Ok(())
+    }
+
+    /// Parse a tasks file, upgrading entries written by older versions
+    fn read_tasks_file(path: &std::path::Path) -> Result<Vec<Task>> {
+        let file = fs::File::open(path).map_err(HyperVError::Io)?;
         let reader = std::io::BufReader::new(&file);
-        let tasks: Vec<Task> = serde_json::from_reader(reader)
+        let entries: Vec<serde_json::Value> = serde_json::from_reader(reader)
             .map_err(|e| HyperVError::Serialization(e.to_string()))?;
+        let mut tasks = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let legacy_env = entry.get("env_files").is_none();
+            let mut task: Task = serde_json::from_value(entry)
+                .map_err(|e| HyperVError::Serialization(e.to_string()))?;
+            task.migrate_legacy_restart();
+            if legacy_env {
+                task.migrate_legacy_env_file();
+            }
+            tasks.push(task);
+        }
+        Ok(tasks)
+    }
 
-        self.tasks = tasks;
-        Ok(())
+    /// Status for a task whose process has just been seen to exit
+    fn status_after_exit(task: &Task) -> TaskStatus {
+        if task.exit_succeeded() {
+            TaskStatus::Exited
+        } else {
+            TaskStatus::Failed
+        }
     }
 
     /// Save tasks to configuration file
@@ -147,18 +187,18 @@ impl TaskManager {
         self.save_unlocked()
     }
 
-    fn save_unlocked(&self) -> Result<()> {
+    pub(crate) fn save_unlocked(&self) -> Result<()> {
         let json = serde_json::to_string_pretty(&self.tasks)
             .map_err(|e| HyperVError::Serialization(e.to_string()))?;
 
         // Write atomically: write to temp file then rename over the original.
         let tmp_path = self.config.tasks_file.with_extension("json.tmp");
-        fs::write(&tmp_path, json.as_bytes()).map_err(HyperVError::Io)?;
+        write_private(&tmp_path, json.as_bytes())?;
 
         // Backup previous file if exists
-        if self.config.tasks_file.exists() {
+        if let Ok(previous) = fs::read(&self.config.tasks_file) {
             let backup_path = self.config.tasks_file.with_extension("json.prev");
-            let _ = fs::copy(&self.config.tasks_file, &backup_path);
+            let _ = write_private(&backup_path, &previous);
         }
 
         fs::rename(&tmp_path, &self.config.tasks_file).map_err(HyperVError::Io)?;
@@ -190,7 +230,7 @@ impl TaskManager {
 
         // Write atomically: write to temp file then rename
         let tmp_path = self.config.running_tasks_file.with_extension("json.tmp");
-        fs::write(&tmp_path, json.as_bytes()).map_err(HyperVError::Io)?;
+        write_private(&tmp_path, json.as_bytes())?;
         fs::rename(&tmp_path, &self.config.running_tasks_file).map_err(HyperVError::Io)?;
 
         Ok(())
@@ -205,6 +245,27 @@ impl TaskManager {
         env_vars: Vec<String>,
         workdir: Option<String>,
         auto_restart: bool,
+    ) -> Result<()> {
+        let options = TaskOptions {
+            restart_policy: if auto_restart {
+                RestartPolicy::OnFailure
+            } else {
+                RestartPolicy::Never
+            },
+            ..TaskOptions::default()
+        };
+        self.create_task_with_options(name, binary, args, env_vars, workdir, options)
+    }
+
+    /// Create a new task with explicit restart settings
+    pub fn create_task_with_options(
+        &mut self,
+        name: String,
+        binary: String,
+        args: Vec<String>,
+        env_vars: Vec<String>,
+        workdir: Option<String>,
+        mut options: TaskOptions,
     ) -> Result<()> {
         let _lock_file = self.lock_tasks_for_update()?;
 
@@ -222,22 +283,59 @@ impl TaskManager {
                 return Err(HyperVError::InvalidEnvVar(env_var));
             }
         }
+        secrets::validate_env(&env)?;
+        #[cfg(unix)]
+        crate::privileges::RunAs::resolve(
+            options.user.as_deref(),
+            options.group.as_deref(),
+            &options.supplementary_groups,
+        )?;
+        options
+            .limits
+            .validate()
+            .map_err(HyperVError::InvalidInput)?;
+        options
+            .cgroup_limits
+            .validate()
+            .map_err(HyperVError::InvalidInput)?;
+        if let Some(watchdog) = &options.watchdog {
+            watchdog.validate().map_err(HyperVError::InvalidInput)?;
+        }
+        options
+            .restart_backoff
+            .validate()
+            .map_err(HyperVError::InvalidInput)?;
 
-        // Load environment variables from .env file in workdir
-        if let Some(ref workdir) = workdir {
-            let env_file_path = std::path::Path::new(workdir).join(".env");
-            if let Ok(lines) = std::fs::read_to_string(&env_file_path) {
-                for line in lines.lines() {
-                    if let Some((key, value)) = line.split_once('=') {
-                        // Command-line env vars take precedence
-                        if !env.contains_key(key) {
-                            env.insert(key.to_string(), value.to_string());
-                        }
-                    }
-                }
-            }
+        if let Some(missing) = options
+            .env_files
+            .iter()
+            .find(|path| !std::path::Path::new(path).is_file())
+        {
+            return Err(HyperVError::EnvFileNotFound(missing.clone()));
         }
+        // Without explicit env files, a .env in the working directory is used
+        if options.env_files.is_empty() {
+            options.env_files = dotenv::workdir_env_file(workdir.as_deref())
+                .into_iter()
+                .collect();
+        }
+
+        self.add_task_unlocked(name, binary, args, env, workdir, options)?;
+        self.save_unlocked()?;
+        println!("✅ Task created successfully!");
+        Ok(())
+    }
 
+    /// Add a new task to the in-memory list. The caller holds the tasks lock and saves.
+    pub(crate) fn add_task_unlocked(
+        &mut self,
+        name: String,
+        binary: String,
+        args: Vec<String>,
+        env: HashMap<String, String>,
+        workdir: Option<String>,
+        options: TaskOptions,
+    ) -> Result<&mut Task> {
         let id = Uuid::new_v4().to_string();
 
         // Ensure log directory exists
@@ -246,77 +344,170 @@ impl TaskManager {
         let stdout_log_path = self.config.stdout_log_path(&id);
         let stderr_log_path = self.config.stderr_log_path(&id);
 
-        let task = Task::new(
+        let mut task = Task::new(
             id,
             name,
             binary,
             args,
             env,
             workdir,
-            auto_restart,
+            false,
             Some(stdout_log_path.to_string_lossy().to_string()),
             Some(stderr_log_path.to_string_lossy().to_string()),
         );
+        options.apply_to(&mut task);
 
         self.tasks.push(task);
-        self.save_unlocked()?;
-        println!("✅ Task created successfully!");
-        Ok(())
+        Ok(self.tasks.last_mut().expect("task was just added"))
+    }
+
+    /// Environment a task runs with: the variables from its env files, in order, with
+    /// the task's own values taking precedence. Unreadable files are skipped.
+    pub fn effective_env(
+        env: &HashMap<String, String>,
+        env_files: &[String],
+    ) -> HashMap<String, String> {
+        let mut effective = HashMap::new();
+        for path in env_files {
+            if let Ok(content) = fs::read_to_string(path) {
+                let file = dotenv::parse_with(&content, &effective);
+                effective.extend(file.entries);
+            }
+        }
+        effective.extend(env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        effective
+    }
+
+    /// Capture task output through pipes so logs rotate while tasks run; see
+    /// [`ProcessManager::set_log_writer`]
+    pub fn set_log_writer(&mut self, executable: std::path::PathBuf) {
+        self.process_manager.set_log_writer(executable);
     }
 
-    /// List all tasks
-    pub fn list_tasks(&mut self) {
+    /// List all tasks, or only those of one compose project
+    pub fn list_tasks(&mut self, project: Option<&str>) {
         // Refresh task statuses before listing
         let _ = self.refresh_task_statuses();
+        let tasks: Vec<Task> = self
+            .tasks
+            .iter()
+            .filter(|t| project.is_none_or(|p| t.belongs_to_project(p)))
+            .cloned()
+            .collect();
+        Self::print_task_list(&tasks);
+    }
 
-        if self.tasks.is_empty() {
+    /// Print the task table shown by `hyperV list`
+    pub fn print_task_list(tasks: &[Task]) {
+        if tasks.is_empty() {
             println!("No tasks configured.");
             return;
         }
 
         println!(
-            "{:<36} {:<18} {:<15} {:<11} {:<20} {:<30}",
-            "ID", "NAME", "STATUS", "MEM(MB)", "STARTED", "BINARY"
+            "{:<36} {:<18} {:<15} {:<14} {:<9} {:<7} {:<8} {:<6} {:<9} {:<20} {:<30}",
+            "ID",
+            "NAME",
+            "STATUS",
+            "HEALTH",
+            "MEM(MB)",
+            "CPU%",
+            "THREADS",
+            "FDS",
+            "UPTIME",
+            "STARTED",
+            "BINARY"
         );
-        println!("{}", "-".repeat(140));
+        println!("{}", "-".repeat(192));
 
-        let mut sys = System::new();
-        sys.refresh_processes();
+        let mut sampler = UsageSampler::new();
+        if tasks.iter().any(|t| t.status == TaskStatus::Running) {
+            sampler.refresh_with_cpu();
+        }
 
-        for task in &self.tasks {
+        for task in tasks {
             let status_display = task.status.display_with_icon();
-            // Memory usage in MB if running
-            let mem_mb = if let (TaskStatus::Running, Some(pid)) = (&task.status, task.pid) {
-                Self::get_process_memory_mb(&mut sys, pid)
-            } else {
-                0
-            };
+            let [mem_mb, cpu, threads, fds, uptime] = Self::usage_columns(sampler.measure(task));
 
+            let health = task
+                .health
+                .as_ref()
+                .map_or("-", |health| health.status.display_with_icon());
             let started = task.last_started.as_deref().unwrap_or("-");
             println!(
-                "{:<36} {:<18} {:<15} {:<11} {:<20} {:<30}",
+                "{:<36} {:<18} {:<15} {:<14} {:<9} {:<7} {:<8} {:<6} {:<9} {:<20} {:<30}",
                 &task.id[..8],
                 task.name,
                 status_display,
+                health,
                 mem_mb,
+                cpu,
+                threads,
+                fds,
+                uptime,
                 started,
                 task.binary
             );
         }
     }
 
+    /// Print the usage table shown by `hyperV top`, measured by `sampler`
+    pub fn print_task_usage(tasks: &[Task], sampler: &UsageSampler) {
+        if tasks.is_empty() {
+            println!("No tasks configured.");
+            return;
+        }
+
+        println!(
+            "{:<18} {:<15} {:<6} {:<9} {:<7} {:<8} {:<6} {:<9}",
+            "NAME", "STATUS", "PROCS", "MEM(MB)", "CPU%", "THREADS", "FDS", "UPTIME"
+        );
+        println!("{}", "-".repeat(84));
+
+        for task in tasks {
+            let usage = sampler.measure(task);
+            let processes = usage
+                .as_ref()
+                .map_or_else(|| "-".to_string(), |usage| usage.processes.to_string());
+            let [mem_mb, cpu, threads, fds, uptime] = Self::usage_columns(usage);
+            println!(
+                "{:<18} {:<15} {:<6} {:<9} {:<7} {:<8} {:<6} {:<9}",
+                task.name,
+                task.status.display_with_icon(),
+                processes,
+                mem_mb,
+                cpu,
+                threads,
+                fds,
+                uptime
+            );
+        }
+    }
+
+    /// Memory, CPU, threads, open files and uptime as table cells; `-` when unknown
+    fn usage_columns(usage: Option<TaskUsage>) -> [String; 5] {
+        let Some(usage) = usage else {
+            return std::array::from_fn(|_| "-".to_string());
+        };
+        [
+            usage.memory_mb().to_string(),
+            format!("{:.1}", usage.cpu_percent),
+            usage.threads.to_string(),
+            usage
+                .open_fds
+                .map_or_else(|| "-".to_string(), |fds| fds.to_string()),
+            format_uptime(usage.uptime),
+        ]
+    }
+
     /// Find a task by identifier (name, ID, or partial ID)
-    pub(crate) fn find_task(&self, identifier: &str) -> Option<&Task> {
-        self.tasks
-            .iter()
-            .find(|t| t.name == identifier || t.id == identifier || t.id.starts_with(identifier))
+    pub fn find_task(&self, identifier: &str) -> Option<&Task> {
+        self.tasks.iter().find(|t| t.matches(identifier))
     }
 
     /// Find a mutable task by identifier
     pub(crate) fn find_task_mut(&mut self, identifier: &str) -> Option<&mut Task> {
-        self.tasks
-            .iter_mut()
-            .find(|t| t.name == identifier || t.id == identifier || t.id.starts_with(identifier))
+        self.tasks.iter_mut().find(|t| t.matches(identifier))
     }
 
     /// Start a task
@@ -326,12 +517,21 @@ impl TaskManager {
             .ok_or_else(|| HyperVError::TaskNotFound(identifier.to_string()))?
             .clone();
 
+        if self.stopping.contains(&task.id) {
+            return Err(HyperVError::ProcessError(format!(
+                "Task \"{}\" is still being stopped",
+                task.name
+            )));
+        }
+
         // Check if task is already running
         if task.status == TaskStatus::Running
             && let Some(pid) = task.pid
         {
             let pid_running = self.process_manager.is_process_running(pid);
-            let group_running = self.process_manager.is_process_group_running(pid);
+            let group_running = self
+                .process_manager
+                .is_task_group_running(pid, task.cgroup.as_deref());
             if (pid_running || group_running)
                 && (!pid_running
                     || self.process_manager.pid_matches_identity(
@@ -345,6 +545,7 @@ impl TaskManager {
                 // Process died, update status
                 if let Some(task_mut) = self.find_task_mut(identifier) {
                     task_mut.set_status(TaskStatus::Failed);
+                    Self::release_cgroup(task_mut);
                     task_mut.clear_pid();
                 }
                 self.save()?;
@@ -358,13 +559,22 @@ impl TaskManager {
             return Err(HyperVError::WorkdirNotFound(workdir.clone()));
         }
 
+        if let Some(missing) = task
+            .env_files
+            .iter()
+            .find(|path| !std::path::Path::new(path).is_file())
+        {
+            return Err(HyperVError::EnvFileNotFound(missing.clone()));
+        }
+
         // Get log paths
         let stdout_path = self.config.stdout_log_path(&task.id);
         let stderr_path = self.config.stderr_log_path(&task.id);
 
         // Rotate logs if needed
-        LogManager::rotate_log_if_needed(&stdout_path)?;
-        LogManager::rotate_log_if_needed(&stderr_path)?;
+        let retention = self.log_retention(&task)?;
+        LogManager::rotate_log_with(&stdout_path, &retention)?;
+        LogManager::rotate_log_with(&stderr_path, &retention)?;
 
         println!(
             "🚀 Starting task \"{}\" with binary: {}",
@@ -380,34 +590,34 @@ impl TaskManager {
             println!("   Working directory: {}", workdir);
         }
 
-        // Clone the task's env and load from .env file
-        let mut task_env = task.env.clone();
-        if let Some(ref workdir) = task.workdir {
-            let env_file_path = std::path::Path::new(workdir).join(".env");
-            if let Ok(lines) = std::fs::read_to_string(&env_file_path) {
-                for line in lines.lines() {
-                    if let Some((key, value)) = line.split_once('=') {
-                        // Task-specific env vars take precedence
-                        if !task_env.contains_key(key) {
-                            task_env.insert(key.to_string(), value.to_string());
-                        }
-                    }
-                }
-            }
-        }
+        // Task-specific env vars take precedence over its env files; secret
+        // references are resolved here and never written back
+        let task_env = secrets::resolve_env(
+            &Self::effective_env(&task.env, &task.env_files),
+            &self.secret_store(),
+        )?;
+
+        let mut task = task;
+        task.cgroup = self.prepare_cgroup(&task)?;
 
         // Start the process
-        match self
-            .process_manager
-            .start_task(&task, &task_env, &stdout_path, &stderr_path)
-        {
+        match self.process_manager.start_task_with_logs(
+            &task,
+            &task_env,
+            &stdout_path,
+            &stderr_path,
+            &retention,
+        ) {
             Ok(pid) => {
+                self.pending_exits.remove(&task.id);
                 let pid_start_time = self.process_manager.process_start_time(pid);
                 // Update task state
                 if let Some(task_mut) = self.find_task_mut(identifier) {
                     task_mut.set_status(TaskStatus::Running);
+                    task_mut.reset_health();
                     task_mut.set_pid(Some(pid));
                     task_mut.set_pid_start_time(pid_start_time);
+                    task_mut.cgroup = task.cgroup.clone();
                     task_mut.set_last_started();
                     task_mut.clear_suppress_restart();
                 }
@@ -421,6 +631,9 @@ impl TaskManager {
                 Ok(())
             }
             Err(e) => {
+                if let Some(path) = &task.cgroup {
+                    let _ = Cgroup::at(path).remove();
+                }
                 // Update task state to failed
                 if let Some(task_mut) = self.find_task_mut(identifier) {
                     task_mut.set_status(TaskStatus::Failed);
@@ -431,16 +644,100 @@ impl TaskManager {
         }
     }
 
+    /// Create the cgroup `task` runs in, with its limits applied; `None` falls back to
+    /// tracking the task by process group
+    fn prepare_cgroup(&self, task: &Task) -> Result<Option<String>> {
+        let config = self.config.load_daemon_config()?.cgroup;
+        let warn_unlimited = |reason: &str| {
+            if !task.cgroup_limits.is_empty() {
+                eprintln!(
+                    "⚠️  cgroup limits of task \"{}\" are not applied: {}",
+                    task.name, reason
+                );
+            }
+        };
+        let Some(root) = config.task_root() else {
+            warn_unlimited("no cgroup v2 hierarchy is available");
+            return Ok(None);
+        };
+        let cgroup = match Cgroup::create(&root, &task.id) {
+            Ok(cgroup) => cgroup,
+            Err(e) => {
+                warn_unlimited(&format!(
+                    "cannot create a cgroup in {}: {}",
+                    root.display(),
+                    e
+                ));
+                return Ok(None);
+            }
+        };
+
+        #[cfg(unix)]
+        if cgroup.is_populated() {
+            println!(
+                "⚠️  Killing processes left in {} by an earlier run",
+                cgroup.path().display()
+            );
+            let _ = cgroup.kill();
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+            while cgroup.is_populated() && std::time::Instant::now() < deadline {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+        }
+        if let Err(e) = cgroup.apply_limits(&task.cgroup_limits) {
+            warn_unlimited(&e.to_string());
+        }
+        Ok(Some(cgroup.path().to_string_lossy().to_string()))
+    }
+
+    /// Delete the cgroup of a finished run, unless processes are still left in it
+    fn release_cgroup(task: &mut Task) {
+        if let Some(path) = task.cgroup.take() {
+            let cgroup = Cgroup::at(path);
+            if !cgroup.is_populated() {
+                let _ = cgroup.remove();
+            }
+        }
+    }
+
     /// Stop a task
     pub fn stop_task(&mut self, identifier: &str) -> Result<()> {
-        let (task_name, task_id, pid, binary, pid_start_time) = {
+        let Some(mut stop) = self.begin_stop_task(identifier)? else {
+            return Ok(());
+        };
+        let result = stop.wait();
+        self.finish_stop(stop, result)
+    }
+
+    /// Send a running task SIGTERM without waiting for it to exit.
+    ///
+    /// Returns the stop still in progress: run [`StopInProgress::wait`] (it may run
+    /// on another thread) and pass the outcome to [`TaskManager::finish_stop`].
+    /// Returns `None` when the task needed no wait and has already been marked
+    /// stopped. Until the stop finishes the task is not started, probed or
+    /// marked as exited by this manager.
+    pub fn begin_stop_task(&mut self, identifier: &str) -> Result<Option<StopInProgress>> {
+        let (task_name, task_id, pid, binary, pid_start_time, cgroup) = {
             let task = self
                 .find_task(identifier)
                 .ok_or_else(|| HyperVError::TaskNotFound(identifier.to_string()))?;
 
+            if self.stopping.contains(&task.id) {
+                return Err(HyperVError::ProcessStop(format!(
+                    "Task \"{}\" is already being stopped",
+                    task.name
+                )));
+            }
             if task.status != TaskStatus::Running {
                 println!("ℹ️  Task \"{}\" is already stopped", task.name);
-                return Ok(());
+                // An exited task may still be waiting for its policy restart; cancel it
+                if task.status != TaskStatus::Stopped && !task.suppress_restart {
+                    if let Some(task) = self.find_task_mut(identifier) {
+                        task.suppress_restart = true;
+                    }
+                    self.save()?;
+                }
+                return Ok(None);
             }
             (
                 task.name.clone(),
@@ -448,6 +745,7 @@ impl TaskManager {
                 task.pid,
                 task.binary.clone(),
                 task.pid_start_time,
+                task.cgroup.clone().map(Cgroup::at),
             )
         };
 
@@ -458,14 +756,20 @@ impl TaskManager {
             ))
         })?;
 
+        let cgroup_path = cgroup
+            .as_ref()
+            .map(|cgroup| cgroup.path().to_string_lossy().to_string());
         let pid_running = self.process_manager.is_process_running(pid);
-        let group_running = self.process_manager.is_process_group_running(pid);
+        let group_running = self
+            .process_manager
+            .is_task_group_running(pid, cgroup_path.as_deref());
 
-        if !pid_running && !group_running {
+        let stop = if !pid_running && !group_running {
             println!(
                 "ℹ️  Process {} for task \"{}\" has already terminated",
                 pid, task_name
             );
+            None
         } else if pid_running {
             // Detect PID reuse before sending signals: refuse to kill if it doesn't match.
             if !self
@@ -479,37 +783,82 @@ impl TaskManager {
             }
 
             println!("🛑 Stopping task \"{}\" (PID: {})...", task_name, pid);
-            self.process_manager.stop_task(&task_id, pid)?;
-            // Defensive: only mark stopped if the PID is actually gone.
-            if self.process_manager.is_process_running(pid)
-                || self.process_manager.is_process_group_running(pid)
-            {
-                return Err(HyperVError::ProcessStop(format!(
-                    "Process {} for task \"{}\" did not terminate",
-                    pid, task_name
-                )));
-            }
+            self.process_manager
+                .begin_stop(&task_id, pid, cgroup.as_ref())?
         } else {
-            // The original PID is gone but the process group is still alive (e.g., task forked and exited).
-            // We can still stop the group by PGID (= original PID).
+            // The original PID is gone but the process group or cgroup is still alive (e.g., task
+            // forked and exited). We can still stop the group by PGID (= original PID).
             println!(
-                "⚠️  Task \"{}\" PID {} is gone but its process group is still running; stopping group...",
+                "⚠️  Task \"{}\" PID {} is gone but its process group or cgroup is still running; stopping them...",
                 task_name, pid
             );
-            self.process_manager.stop_task(&task_id, pid)?;
-            if self.process_manager.is_process_group_running(pid) {
-                return Err(HyperVError::ProcessStop(format!(
-                    "Process group {} for task \"{}\" did not terminate",
-                    pid, task_name
-                )));
+            self.process_manager
+                .begin_stop(&task_id, pid, cgroup.as_ref())?
+        };
+
+        match stop {
+            Some(stop) => {
+                // Keep the daemon from recording the exit it is causing as a crash
+                self.stopping.insert(task_id);
+                Ok(Some(stop))
+            }
+            None => {
+                self.mark_stopped(&task_id)?;
+                Ok(None)
             }
         }
+    }
 
-        if let Some(task) = self.find_task_mut(identifier) {
-            task.set_status(TaskStatus::Stopped);
-            task.suppress_restart = true;
-            task.clear_pid();
+    /// Complete a stop started with [`TaskManager::begin_stop_task`], given the
+    /// outcome of its [`StopInProgress::wait`]
+    pub fn finish_stop(&mut self, stop: StopInProgress, result: Result<()>) -> Result<()> {
+        let task_id = stop.task_id().to_string();
+        let pid = stop.pid();
+        self.stopping.remove(&task_id);
+        self.process_manager.reclaim(stop);
+        result?;
+
+        let Some(task) = self.tasks.iter().find(|t| t.id == task_id) else {
+            // Removed while it was being stopped
+            return Ok(());
+        };
+        if task.pid != Some(pid) {
+            return Ok(());
         }
+        // Defensive: only mark stopped if the PID is actually gone.
+        let pid_running = self.process_manager.is_process_running(pid)
+            && self
+                .process_manager
+                .pid_matches_identity(pid, &task.binary, task.pid_start_time);
+        if pid_running
+            || self
+                .process_manager
+                .is_task_group_running(pid, task.cgroup.as_deref())
+        {
+            return Err(HyperVError::ProcessStop(format!(
+                "Process {} for task \"{}\" did not terminate",
+                pid, task.name
+            )));
+        }
+
+        self.mark_stopped(&task_id)
+    }
+
+    /// Whether a stop of the task is still in progress
+    pub fn is_stopping(&self, task_id: &str) -> bool {
+        self.stopping.contains(task_id)
+    }
+
+    /// Record that a task was stopped on request, so its policy does not restart it
+    fn mark_stopped(&mut self, task_id: &str) -> Result<()> {
+        let Some(task) = self.tasks.iter_mut().find(|t| t.id == task_id) else {
+            return Ok(());
+        };
+        task.set_status(TaskStatus::Stopped);
+        task.suppress_restart = true;
+        Self::release_cgroup(task);
+        task.clear_pid();
+        let task_name = task.name.clone();
 
         self.save()?;
         self.save_running_tasks()?;
@@ -538,9 +887,7 @@ impl TaskManager {
         let task_index = self
             .tasks
             .iter()
-            .position(|t| {
-                t.name == identifier || t.id == identifier || t.id.starts_with(identifier)
-            })
+            .position(|t| t.matches(identifier))
             .ok_or_else(|| HyperVError::TaskNotFound(identifier.to_string()))?;
 
         // Check if task is running and stop it first
@@ -559,29 +906,47 @@ impl TaskManager {
     }
 
     /// Show task status
-    pub fn show_status(&mut self, identifier: Option<&str>) -> Result<()> {
+    pub fn show_status(&mut self, identifier: Option<&str>, show_secrets: bool) -> Result<()> {
         self.refresh_task_statuses()?;
+        Self::print_task_status(&self.tasks, identifier, show_secrets);
+        Ok(())
+    }
 
+    /// Print details for one task (or all tasks) as shown by `hyperV status`;
+    /// secrets are masked unless `show_secrets` is set
+    pub fn print_task_status(tasks: &[Task], identifier: Option<&str>, show_secrets: bool) {
+        let mut sampler = UsageSampler::new();
+        if tasks
+            .iter()
+            .any(|t| t.status == TaskStatus::Running && identifier.is_none_or(|id| t.matches(id)))
+        {
+            sampler.refresh_with_cpu();
+        }
+        let print = |task: &Task| {
+            task.print_details_with(show_secrets);
+            if let Some(usage) = sampler.measure(task) {
+                println!("Resource usage: {}", usage);
+            }
+        };
         match identifier {
             Some(id) => {
-                if let Some(task) = self.find_task(id) {
-                    task.print_details();
+                if let Some(task) = tasks.iter().find(|t| t.matches(id)) {
+                    print(task);
                 } else {
                     println!("❌ Task \"{}\" not found", id);
                 }
             }
             None => {
-                if self.tasks.is_empty() {
+                if tasks.is_empty() {
                     println!("No tasks configured.");
                 } else {
-                    for task in &self.tasks {
-                        task.print_details();
+                    for task in tasks {
+                        print(task);
                         println!("{}", "-".repeat(50));
                     }
                 }
             }
         }
-        Ok(())
     }
 
     /// Show task logs
@@ -590,6 +955,7 @@ impl TaskManager {
         identifier: &str,
         lines: usize,
         log_type: LogType,
+        filter: &LogFilter,
         follow: bool,
         summary: bool,
     ) -> Result<()> {
@@ -599,12 +965,244 @@ impl TaskManager {
 
         let stdout_path = self.config.stdout_log_path(&task.id);
         let stderr_path = self.config.stderr_log_path(&task.id);
+        let filter = filter.clone().with_field_map(self.log_json_fields(task)?);
+
+        LogManager::show_logs(
+            &stdout_path,
+            &stderr_path,
+            log_type,
+            lines,
+            &filter,
+            follow,
+            summary,
+        )
+    }
+
+    /// Tasks named on the command line, or every task, or those of one compose project
+    pub fn select_tasks(
+        &self,
+        identifiers: &[String],
+        all: bool,
+        project: Option<&str>,
+    ) -> Result<Vec<Task>> {
+        if all {
+            return Ok(self.tasks.clone());
+        }
+        if let Some(project) = project {
+            let tasks: Vec<Task> = self
+                .tasks
+                .iter()
+                .filter(|t| t.belongs_to_project(project))
+                .cloned()
+                .collect();
+            if tasks.is_empty() {
+                return Err(HyperVError::InvalidInput(format!(
+                    "no tasks belong to compose project {}",
+                    project
+                )));
+            }
+            return Ok(tasks);
+        }
+        identifiers
+            .iter()
+            .map(|identifier| {
+                self.find_task(identifier)
+                    .cloned()
+                    .ok_or_else(|| HyperVError::TaskNotFound(identifier.clone()))
+            })
+            .collect()
+    }
 
-        LogManager::show_logs(&stdout_path, &stderr_path, log_type, lines, follow, summary)
+    /// Show the logs of several tasks together, each line prefixed with its task name
+    pub fn show_task_logs(
+        &self,
+        tasks: &[Task],
+        lines: usize,
+        log_type: LogType,
+        filter: &LogFilter,
+        follow: bool,
+    ) -> Result<()> {
+        let logs = tasks
+            .iter()
+            .map(|task| {
+                Ok(TaskLogs {
+                    name: task.name.clone(),
+                    stdout_path: self.config.stdout_log_path(&task.id),
+                    stderr_path: self.config.stderr_log_path(&task.id),
+                    json_fields: self.log_json_fields(task)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        LogManager::show_task_logs(&logs, log_type, lines, filter, follow)
     }
 
-    /// Diagnose a task's binary
-    pub fn diagnose_task(&self, identifier: &str) -> Result<()> {
+    /// Log rotation and retention for `task`: its own settings over those in `daemon.yaml`
+    pub fn log_retention(&self, task: &Task) -> Result<LogRetention> {
+        let daemon_config = self.config.load_daemon_config()?;
+        Ok(task.log_settings.resolve(&daemon_config.logs))
+    }
+
+    /// Tasks whose logs are forwarded, with their targets: each task's own, or else
+    /// those in `daemon.yaml`
+    pub fn log_forwarding(&self) -> Result<Vec<ForwardedTask>> {
+        let daemon_config = self.config.load_daemon_config()?;
+        Ok(self
+            .tasks
+            .iter()
+            .filter_map(|task| {
+                let targets = task.log_settings.forward_targets(&daemon_config.logs);
+                (!targets.is_empty()).then(|| ForwardedTask {
+                    id: task.id.clone(),
+                    name: task.name.clone(),
+                    stdout_path: self.config.stdout_log_path(&task.id),
+                    stderr_path: self.config.stderr_log_path(&task.id),
+                    targets,
+                    field_map: task.log_settings.json.or(&daemon_config.logs.json),
+                })
+            })
+            .collect())
+    }
+
+    /// How JSON lines of `task` are read: its own mapping over the one in `daemon.yaml`
+    pub fn log_json_fields(&self, task: &Task) -> Result<JsonFieldMap> {
+        let daemon_config = self.config.load_daemon_config()?;
+        Ok(task.log_settings.json.or(&daemon_config.logs.json))
+    }
+
+    /// Delete rotated logs of `tasks` that are older than their `max_age_days` or beyond
+    /// their `max_archives`
+    pub fn prune_logs(&self, tasks: &[Task]) -> Result<()> {
+        let mut total_files = 0;
+        let mut total_bytes = 0;
+        for task in tasks {
+            let retention = self.log_retention(task)?;
+            for log_path in [
+                self.config.stdout_log_path(&task.id),
+                self.config.stderr_log_path(&task.id),
+            ] {
+                for (path, bytes) in LogManager::prune_archives(&log_path, &retention)? {
+                    println!("🗑️  {}: removed {}", task.name, path.display());
+                    total_files += 1;
+                    total_bytes += bytes;
+                }
+            }
+        }
+        if total_files == 0 {
+            println!("✅ No rotated logs to prune");
+        } else {
+            println!(
+                "✅ Removed {} rotated log file(s), {}",
+                total_files,
+                format_bytes(total_bytes)
+            );
+        }
+        Ok(())
+    }
+
+    /// Write a support bundle for a task to `output` (by default
+    /// `hyperv-bundle-<task>-<time>.tar.gz` in the current directory), with the last
+    /// `lines` lines of each log stream. Returns where it was written.
+    pub fn bundle_task(
+        &self,
+        identifier: &str,
+        lines: usize,
+        output: Option<&Path>,
+    ) -> Result<PathBuf> {
+        let task = self
+            .find_task(identifier)
+            .ok_or_else(|| HyperVError::TaskNotFound(identifier.to_string()))?;
+        let now = Utc::now();
+        let root = format!(
+            "hyperv-bundle-{}-{}",
+            task.name,
+            now.format("%Y%m%d-%H%M%S")
+        );
+        let output = output
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from(format!("{}.tar.gz", root)));
+        let stdout_path = self.config.stdout_log_path(&task.id);
+        let stderr_path = self.config.stderr_log_path(&task.id);
+
+        let mut bundle = SupportBundle::new(&root);
+        bundle.add("task.json", redacted_task_json(task)?);
+        bundle.add("history.txt", task_history(task));
+
+        let mut diagnosis = String::new();
+        let result = diagnose_binary_with(&task.binary, &mut |line| {
+            diagnosis.push_str(line);
+            diagnosis.push('\n');
+        });
+        if let Err(e) = result {
+            diagnosis.push_str(&format!("\nError: {}\n", e));
+        }
+        bundle.add("diagnose.txt", redact_sensitive_values(&diagnosis));
+
+        let summary = match self.log_json_fields(task).and_then(|field_map| {
+            LogManager::summarize_logs_with(&stdout_path, &stderr_path, LogType::Both, &field_map)
+        }) {
+            Ok(summary) => summary.format(),
+            Err(e) => format!("Could not summarize logs: {}\n", e),
+        };
+        bundle.add("summary.txt", summary);
+
+        for (path, stream, name) in [
+            (&stdout_path, LogStream::Stdout, "logs/stdout.log"),
+            (&stderr_path, LogStream::Stderr, "logs/stderr.log"),
+        ] {
+            let mut content = String::new();
+            for record in LogManager::read_records(path, stream, lines)? {
+                if let Some(time) = record.time {
+                    content.push_str(&LogRecord::prefix(time, record.stream));
+                }
+                content.push_str(&redact_sensitive_values(&record.message));
+                content.push('\n');
+            }
+            bundle.add(name, content);
+        }
+
+        let running = task.status == TaskStatus::Running
+            && task
+                .pid
+                .is_some_and(|pid| self.process_manager.is_process_running(pid));
+        bundle.add("processes.txt", process_tree(task, running));
+
+        let mut manifest = format!(
+            "hyperV {} support bundle\nTask: {} ({})\nCreated: {}\nHost: {} {}\nLog lines per stream: {}\n\nContents:\n",
+            env!("CARGO_PKG_VERSION"),
+            task.name,
+            task.id,
+            now.to_rfc3339(),
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            lines
+        );
+        for name in bundle.file_names() {
+            manifest.push_str(&format!("- {}\n", name));
+        }
+        bundle.add("manifest.txt", manifest);
+
+        bundle.write(&output)?;
+        Ok(output)
+    }
+
+    /// The secret store that `secret:NAME` env values are resolved from
+    pub fn secret_store(&self) -> SecretStore {
+        SecretStore::open(&self.config)
+    }
+
+    /// Names of the tasks whose env refers to the stored secret `name`
+    pub fn tasks_using_secret(&self, name: &str) -> Vec<String> {
+        let reference = SecretRef::Store(name.to_string()).to_string();
+        self.tasks
+            .iter()
+            .filter(|task| task.env.values().any(|value| *value == reference))
+            .map(|task| task.name.clone())
+            .collect()
+    }
+
+    /// Diagnose a task's binary; secrets in its configuration are masked unless
+    /// `show_secrets` is set
+    pub fn diagnose_task(&self, identifier: &str, show_secrets: bool) -> Result<()> {
         let task = self
             .find_task(identifier)
             .ok_or_else(|| HyperVError::TaskNotFound(identifier.to_string()))?;
@@ -612,63 +1210,207 @@ impl TaskManager {
         println!("🔍 Diagnosing task: {}", task.name);
         println!("---------------------------------------------------");
 
-        // Diagnose the binary
-        diagnose_binary(&task.binary)?;
+        // Diagnose the binary, then report env file problems even if it failed
+        let binary = diagnose_binary(&task.binary);
+        Self::diagnose_env_files(task);
+        #[cfg(unix)]
+        Self::diagnose_run_as(task);
+        binary?;
 
         // Show task configuration
         println!(
             "
 ⚙️  Task Configuration:"
         );
-        task.print_details();
+        task.print_details_with(show_secrets);
 
         Ok(())
     }
 
-    /// Check and restart failed tasks with auto-restart enabled
-    pub fn check_and_restart_tasks(&mut self) -> Result<()> {
-        use crate::constants::{MAX_RESTART_ATTEMPTS, RESTART_DELAY};
+    /// Report whether the task's user and groups exist, whether hyperV may switch to
+    /// them, and whether that user can reach the binary and working directory
+    #[cfg(unix)]
+    fn diagnose_run_as(task: &Task) {
+        use crate::privileges::RunAs;
+
+        let run_as = match RunAs::for_task(task) {
+            Ok(Some(run_as)) => run_as,
+            Ok(None) => return,
+            Err(e) => {
+                println!(
+                    "
+👤 Run as: {}",
+                    task.run_as_display()
+                );
+                println!("❌ {}", e);
+                return;
+            }
+        };
+        println!(
+            "
+👤 Run as: {}",
+            task.run_as_display()
+        );
+        let user = run_as.user_name();
+        println!(
+            "✅ User {} (uid {}, gid {}{})",
+            user,
+            run_as.uid,
+            run_as.gid,
+            if run_as.groups.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    ", groups {}",
+                    run_as
+                        .groups
+                        .iter()
+                        .map(|gid| gid.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                )
+            }
+        );
+        if let Err(e) = run_as.check_permitted() {
+            println!("❌ {}", e);
+        }
+
+        let binary = Path::new(&task.binary);
+        if !run_as.can_read(binary) {
+            println!("❌ {} cannot read {}", user, task.binary);
+        } else if !run_as.can_execute(binary) {
+            println!("❌ {} cannot execute {}", user, task.binary);
+        } else {
+            println!("✅ {} can read and execute {}", user, task.binary);
+        }
+        if let Some(workdir) = &task.workdir {
+            let dir = Path::new(workdir);
+            if run_as.can_read(dir) && run_as.can_execute(dir) {
+                println!("✅ {} can enter working directory {}", user, workdir);
+            } else {
+                println!("❌ {} cannot enter working directory {}", user, workdir);
+            }
+        }
+    }
+
+    /// Report unreadable env files and parse warnings with their line numbers
+    fn diagnose_env_files(task: &Task) {
+        if task.env_files.is_empty() {
+            return;
+        }
+        println!(
+            "
+📄 Env files:"
+        );
+        let mut defined = HashMap::new();
+        for path in &task.env_files {
+            let content = match fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(e) => {
+                    println!("❌ {}: {}", path, e);
+                    continue;
+                }
+            };
+            let file = dotenv::parse_with(&content, &defined);
+            if file.warnings.is_empty() {
+                println!("✅ {} ({} variables)", path, file.entries.len());
+            } else {
+                println!(
+                    "⚠️  {} ({} variables, {} warnings)",
+                    path,
+                    file.entries.len(),
+                    file.warnings.len()
+                );
+                for warning in &file.warnings {
+                    println!("   {}", warning);
+                }
+            }
+            defined.extend(file.entries);
+        }
+    }
+
+    /// Start tasks with the `always` restart policy that were stopped manually.
+    ///
+    /// Called once when the daemon starts: unlike `unless-stopped`, an `always` task
+    /// comes back after a daemon restart even if it was stopped by hand. Tasks that
+    /// were never started are left alone.
+    pub fn start_always_tasks(&mut self) -> Result<()> {
+        let _ = self.refresh_task_statuses();
+
+        let to_start: Vec<String> = self
+            .tasks
+            .iter()
+            .filter(|t| {
+                t.restart_policy == RestartPolicy::Always
+                    && t.status != TaskStatus::Running
+                    && t.last_started.is_some()
+            })
+            .map(|t| t.name.clone())
+            .collect();
+
+        for task_name in to_start {
+            println!(
+                "🔄 Starting task \"{}\" (restart policy: always)",
+                task_name
+            );
+            if let Err(e) = self.start_task(&task_name) {
+                println!("❌ Failed to start task \"{}\": {}", task_name, e);
+            }
+        }
+
+        Ok(())
+    }
 
+    /// How long until the earliest pending policy restart is due, if any.
+    /// The daemon uses this to wake up for a restart instead of sleeping through its backoff.
+    pub fn next_restart_in(&self) -> Option<std::time::Duration> {
+        let now = chrono::Utc::now();
+        self.tasks
+            .iter()
+            .filter_map(Task::next_restart_at)
+            .min()
+            .map(|due| (due - now).to_std().unwrap_or_default())
+    }
+
+    /// Restart exited tasks whose restart policy asks for it and whose backoff delay has passed
+    pub fn check_and_restart_tasks(&mut self) -> Result<()> {
         // Reload tasks from disk to pick up external changes (like suppression on stop)
-        if let Ok(content) = fs::read_to_string(&self.config.tasks_file)
-            && let Ok(tasks_on_disk) = serde_json::from_str::<Vec<Task>>(&content)
-        {
+        if let Ok(tasks_on_disk) = Self::read_tasks_file(&self.config.tasks_file) {
             self.tasks = tasks_on_disk;
         }
 
+        let now = chrono::Utc::now();
         let tasks_to_restart: Vec<String> = self
             .tasks
             .iter()
-            .filter(|task| {
-                task.auto_restart
-                    && !task.suppress_restart
-                    && task.status == TaskStatus::Failed
-                    && task.restart_count < MAX_RESTART_ATTEMPTS
-            })
+            .filter(|task| task.next_restart_at().is_some_and(|due| due <= now))
             .map(|task| task.id.clone())
             .collect();
 
         for task_id in tasks_to_restart {
             if let Some(task) = self.tasks.iter_mut().find(|t| t.id == task_id) {
                 println!(
-                    "🔄 Auto-restarting failed task: {} (attempt {}/{})",
+                    "🔄 Auto-restarting {} task: {} (attempt {}/{})",
+                    if task.status == TaskStatus::Exited {
+                        "exited"
+                    } else {
+                        "failed"
+                    },
                     task.name,
                     task.restart_count + 1,
-                    MAX_RESTART_ATTEMPTS
+                    task.restart_backoff.max_attempts
                 );
 
                 task.increment_restart_count();
                 let task_name = task.name.clone();
                 self.save()?;
 
-                // Small delay before restart
-                std::thread::sleep(RESTART_DELAY);
-
                 if let Err(e) = self.start_task(&task_name) {
                     println!("❌ Failed to auto-restart task \"{}\": {}", task_name, e);
-                    // Mark as failed again if restart fails
+                    // Mark as failed again if restart fails; the next attempt backs off from now
                     if let Some(task_mut) = self.find_task_mut(&task_name) {
                         task_mut.set_status(TaskStatus::Failed);
+                        task_mut.last_exit_at = Some(chrono::Utc::now().to_rfc3339());
                     }
                     self.save()?;
                 } else {
@@ -680,16 +1422,165 @@ impl TaskManager {
         Ok(())
     }
 
+    /// Store the result of a health probe. Results for a run that has since ended are
+    /// ignored. Returns the task if this result made it unhealthy.
+    pub fn record_health_result(&mut self, probe: &HealthProbeResult) -> Result<Option<Task>> {
+        let _lock_file = self.lock_tasks_for_update()?;
+        let now = chrono::Utc::now();
+
+        let Some(task) = self.tasks.iter_mut().find(|t| {
+            t.id == probe.task_id
+                && t.status == TaskStatus::Running
+                && t.pid == Some(probe.pid)
+                && !self.stopping.contains(&t.id)
+        }) else {
+            return Ok(None);
+        };
+        let Some(check) = task.health_check.clone() else {
+            return Ok(None);
+        };
+
+        let in_start_period = task
+            .last_started
+            .as_deref()
+            .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
+            .is_some_and(|started| {
+                now.signed_duration_since(started)
+                    < chrono::Duration::seconds(check.start_period_secs as i64)
+            });
+
+        let health = task
+            .health
+            .get_or_insert_with(crate::health::HealthState::starting);
+        let previous = health.status;
+        let became_unhealthy = health.record(&check, &probe.result, in_start_period, now);
+        if previous == HealthStatus::Unhealthy && health.status == HealthStatus::Healthy {
+            println!("💚 Task \"{}\" is healthy again", task.name);
+        }
+        let snapshot = became_unhealthy.then(|| task.clone());
+
+        self.save_unlocked()?;
+        Ok(snapshot)
+    }
+
+    /// Restart a task whose health checks keep failing. Follows the task's restart
+    /// policy and attempt limit like a crash would.
+    pub fn restart_unhealthy_task(&mut self, task_id: &str) -> Result<()> {
+        match self.claim_unhealthy_restart(task_id)? {
+            Some(task_name) => self.restart_task(&task_name),
+            None => Ok(()),
+        }
+    }
+
+    /// Decide whether an unhealthy task gets restarted and, if so, count the attempt.
+    /// Returns the name of the task to restart.
+    pub fn claim_unhealthy_restart(&mut self, task_id: &str) -> Result<Option<String>> {
+        self.load()?;
+        if self.stopping.contains(task_id) {
+            return Ok(None);
+        }
+        let Some(task) = self.tasks.iter_mut().find(|t| t.id == task_id) else {
+            return Ok(None);
+        };
+        if task.restart_policy == RestartPolicy::Never || task.suppress_restart {
+            println!(
+                "⚠️  Task \"{}\" is unhealthy (restart policy: {}); leaving it running",
+                task.name, task.restart_policy
+            );
+            return Ok(None);
+        }
+        if task.restart_count >= task.restart_backoff.max_attempts {
+            println!(
+                "⚠️  Task \"{}\" is unhealthy but has used all {} restart attempts",
+                task.name, task.restart_backoff.max_attempts
+            );
+            return Ok(None);
+        }
+
+        println!(
+            "🩺 Restarting unhealthy task: {} (attempt {}/{})",
+            task.name,
+            task.restart_count + 1,
+            task.restart_backoff.max_attempts
+        );
+        task.increment_restart_count();
+        let task_name = task.name.clone();
+        self.save()?;
+        Ok(Some(task_name))
+    }
+
+    /// Record a watchdog breach in the event history of the run that caused it.
+    /// Returns the task when that run is still the current one.
+    pub fn record_watchdog_breach(&mut self, breach: &WatchdogBreach) -> Result<Option<Task>> {
+        let _lock_file = self.lock_tasks_for_update()?;
+
+        let Some(task) = self.tasks.iter_mut().find(|t| {
+            t.id == breach.task_id
+                && t.status == TaskStatus::Running
+                && t.pid == Some(breach.pid)
+                && !self.stopping.contains(&t.id)
+        }) else {
+            return Ok(None);
+        };
+        task.record_event(format!("watchdog: {} ({})", breach.kind, breach.action));
+        let snapshot = task.clone();
+
+        self.save_unlocked()?;
+        Ok(Some(snapshot))
+    }
+
+    /// Restart a task that went over a watchdog threshold with the `restart` action.
+    /// The restart counts towards the task's attempt limit like a crash would.
+    pub fn restart_runaway_task(&mut self, task_id: &str) -> Result<()> {
+        match self.claim_runaway_restart(task_id)? {
+            Some(task_name) => self.restart_task(&task_name),
+            None => Ok(()),
+        }
+    }
+
+    /// Decide whether a runaway task gets restarted and, if so, count the attempt.
+    /// Returns the name of the task to restart.
+    pub fn claim_runaway_restart(&mut self, task_id: &str) -> Result<Option<String>> {
+        self.load()?;
+        if self.stopping.contains(task_id) {
+            return Ok(None);
+        }
+        let Some(task) = self.tasks.iter_mut().find(|t| t.id == task_id) else {
+            return Ok(None);
+        };
+        if task.restart_count >= task.restart_backoff.max_attempts {
+            println!(
+                "⚠️  Task \"{}\" went over its watchdog limits but has used all {} restart attempts",
+                task.name, task.restart_backoff.max_attempts
+            );
+            return Ok(None);
+        }
+
+        println!(
+            "🐕 Restarting runaway task: {} (attempt {}/{})",
+            task.name,
+            task.restart_count + 1,
+            task.restart_backoff.max_attempts
+        );
+        task.increment_restart_count();
+        let task_name = task.name.clone();
+        self.save()?;
+        Ok(Some(task_name))
+    }
+
     /// Refresh task statuses by checking if running processes are still alive
     pub fn refresh_task_statuses(&mut self) -> Result<()> {
         let mut updated = false;
 
         for task in &mut self.tasks {
             if task.status == TaskStatus::Running
+                && !self.stopping.contains(&task.id)
                 && let Some(pid) = task.pid
             {
                 let pid_running = self.process_manager.is_process_running(pid);
-                let group_running = self.process_manager.is_process_group_running(pid);
+                let group_running = self
+                    .process_manager
+                    .is_task_group_running(pid, task.cgroup.as_deref());
                 let matches = !pid_running
                     || self.process_manager.pid_matches_identity(
                         pid,
@@ -698,7 +1589,18 @@ impl TaskManager {
                     );
                 if (!pid_running && !group_running) || !matches {
                     // Process has terminated, update status
-                    task.set_status(TaskStatus::Failed);
+                    let exit = self
+                        .pending_exits
+                        .remove(&task.id)
+                        .filter(|exit| exit.pid == pid);
+                    task.record_exit(
+                        exit.and_then(|e| e.code),
+                        exit.and_then(|e| e.signal),
+                        exit.and_then(|e| e.cpu_time),
+                    );
+                    let status = Self::status_after_exit(task);
+                    task.set_status(status);
+                    Self::release_cgroup(task);
                     task.clear_pid();
                     updated = true;
                 } else if pid_running && task.pid_start_time.is_none() {
@@ -735,9 +1637,12 @@ impl TaskManager {
         &self.tasks
     }
 
-    /// Get the number of tasks with auto-restart enabled
+    /// Get the number of tasks with a restart policy other than `never`
     pub fn tasks_with_autorestart_count(&self) -> usize {
-        self.tasks.iter().filter(|t| t.auto_restart).count()
+        self.tasks
+            .iter()
+            .filter(|t| t.restart_policy != RestartPolicy::Never)
+            .count()
     }
 
     /// Clean up zombie processes and update task states
@@ -748,23 +1653,25 @@ impl TaskManager {
     /// Clean up zombie processes, update task states, and return tasks that failed in this pass.
     pub fn cleanup_with_events(&mut self) -> Result<Vec<Task>> {
         // Reload tasks from disk to incorporate external updates (e.g., stop suppression)
-        if let Ok(content) = fs::read_to_string(&self.config.tasks_file)
-            && let Ok(tasks_on_disk) = serde_json::from_str::<Vec<Task>>(&content)
-        {
+        if let Ok(tasks_on_disk) = Self::read_tasks_file(&self.config.tasks_file) {
             self.tasks = tasks_on_disk;
         }
 
-        let exit_codes = self.process_manager.cleanup_zombies();
+        self.pending_exits
+            .extend(self.process_manager.cleanup_zombies());
 
         // Update task states for processes that are no longer running
         let mut changed = false;
         let mut failed_tasks = Vec::new();
         for task in &mut self.tasks {
             if task.status == TaskStatus::Running
+                && !self.stopping.contains(&task.id)
                 && let Some(pid) = task.pid
             {
                 let pid_running = self.process_manager.is_process_running(pid);
-                let group_running = self.process_manager.is_process_group_running(pid);
+                let group_running = self
+                    .process_manager
+                    .is_task_group_running(pid, task.cgroup.as_deref());
                 let matches = !pid_running
                     || self.process_manager.pid_matches_identity(
                         pid,
@@ -772,23 +1679,83 @@ impl TaskManager {
                         task.pid_start_time,
                     );
                 if (!pid_running && !group_running) || !matches {
-                    // Check if we have an exit code for this task
-                    if let Some(&exit_code) = exit_codes.get(&task.id) {
-                        task.set_exit_code(Some(exit_code));
-                        println!("ℹ️  Task \"{}\" exited with code {}", task.name, exit_code);
+                    // Only children spawned by this process have a known exit status
+                    let exit = self
+                        .pending_exits
+                        .remove(&task.id)
+                        .filter(|exit| exit.pid == pid);
+                    match exit {
+                        Some(ProcessExit {
+                            code: Some(code), ..
+                        }) => {
+                            println!("ℹ️  Task \"{}\" exited with code {}", task.name, code);
+                        }
+                        Some(ProcessExit {
+                            signal: Some(signal),
+                            cpu_time,
+                            ..
+                        }) => match task.limits.violation(signal, cpu_time) {
+                            Some(reason) => println!(
+                                "ℹ️  Task \"{}\" was killed by signal {}: {}",
+                                task.name, signal, reason
+                            ),
+                            None => println!(
+                                "ℹ️  Task \"{}\" was killed by signal {}",
+                                task.name, signal
+                            ),
+                        },
+                        _ => {}
                     }
+                    task.record_exit(
+                        exit.and_then(|e| e.code),
+                        exit.and_then(|e| e.signal),
+                        exit.and_then(|e| e.cpu_time),
+                    );
 
-                    task.set_status(TaskStatus::Failed);
+                    let status = Self::status_after_exit(task);
+                    task.set_status(status);
+                    Self::release_cgroup(task);
                     task.clear_pid();
-                    failed_tasks.push(task.clone());
-                    changed = true;
-                } else if pid_running && task.pid_start_time.is_none() {
-                    task.set_pid_start_time(self.process_manager.process_start_time(pid));
+                    if task.status == TaskStatus::Failed {
+                        failed_tasks.push(task.clone());
+                    }
                     changed = true;
+                } else {
+                    if pid_running && task.pid_start_time.is_none() {
+                        task.set_pid_start_time(self.process_manager.process_start_time(pid));
+                        changed = true;
+                    }
+                    if task.reset_restarts_if_stable(chrono::Utc::now()) {
+                        println!(
+                            "ℹ️  Task \"{}\" has been stable; restart count reset",
+                            task.name
+                        );
+                        changed = true;
+                    }
+                }
+            } else if task.status != TaskStatus::Running
+                && let Some(exit) = self.pending_exits.remove(&task.id)
+            {
+                // Another hyperV process already noticed the exit (e.g. `hyperV status`),
+                // but only we could collect the exit status of our child.
+                task.last_exit_code = exit.code;
+                task.last_exit_signal = exit.signal;
+                task.last_exit_cpu_secs = exit.cpu_time.map(|d| d.as_secs_f64());
+                if task.status == TaskStatus::Failed && task.exit_succeeded() {
+                    task.set_status(TaskStatus::Exited);
                 }
+                if task.status == TaskStatus::Failed {
+                    failed_tasks.push(task.clone());
+                }
+                changed = true;
             }
         }
 
+        // Forget exits of tasks that were removed in the meantime
+        let tasks = &self.tasks;
+        self.pending_exits
+            .retain(|task_id, _| tasks.iter().any(|t| t.id == *task_id));
+
         if changed {
             self.save()?;
             self.save_running_tasks()?;
@@ -797,9 +1764,11 @@ impl TaskManager {
         Ok(failed_tasks)
     }
 
-    /// Whether any task has auto-restart enabled
+    /// Whether any task has a restart policy other than `never`
     pub fn any_autorestart_enabled(&self) -> bool {
-        self.tasks.iter().any(|t| t.auto_restart)
+        self.tasks
+            .iter()
+            .any(|t| t.restart_policy != RestartPolicy::Never)
     }
 }
 
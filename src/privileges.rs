@@ -0,0 +1,274 @@
+//! Running tasks as another user and group
+//!
+//! A task with `user`, `group` or `supplementary_groups` set switches to that identity
+//! in the child process, after fork and before exec. Names are looked up in the
+//! system user and group databases; numeric IDs are accepted as well. Switching to
+//! another identity requires hyperV itself to run as root.
+
+use crate::error::{HyperVError, Result};
+use crate::task::Task;
+use std::ffi::{CStr, CString};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::Command;
+
+/// An entry of the user database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserEntry {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+}
+
+/// Look up a user by name, or by numeric ID
+pub fn lookup_user(user: &str) -> Result<Option<UserEntry>> {
+    let name = CString::new(user)
+        .map_err(|_| HyperVError::InvalidInput(format!("invalid user name \"{}\"", user)))?;
+    if let Some(entry) = read_passwd(|pwd, buf, len, result| unsafe {
+        libc::getpwnam_r(name.as_ptr(), pwd, buf, len, result)
+    })? {
+        return Ok(Some(entry));
+    }
+    match user.parse::<u32>() {
+        Ok(uid) => read_passwd(|pwd, buf, len, result| unsafe {
+            libc::getpwuid_r(uid, pwd, buf, len, result)
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Look up a group ID by name, or accept a numeric ID
+pub fn lookup_group(group: &str) -> Result<Option<u32>> {
+    let name = CString::new(group)
+        .map_err(|_| HyperVError::InvalidInput(format!("invalid group name \"{}\"", group)))?;
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+    loop {
+        let mut entry: libc::group = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::group = std::ptr::null_mut();
+        let code = unsafe {
+            libc::getgrnam_r(
+                name.as_ptr(),
+                &mut entry,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        if code == libc::ERANGE && buffer.len() < 1024 * 1024 {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        if code != 0 {
+            return Err(HyperVError::Io(std::io::Error::from_raw_os_error(code)));
+        }
+        if !result.is_null() {
+            return Ok(Some(entry.gr_gid));
+        }
+        return Ok(group.parse().ok());
+    }
+}
+
+/// Call a `getpw*_r` function with a growing buffer
+fn read_passwd(
+    lookup: impl Fn(
+        *mut libc::passwd,
+        *mut libc::c_char,
+        libc::size_t,
+        *mut *mut libc::passwd,
+    ) -> libc::c_int,
+) -> Result<Option<UserEntry>> {
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+    loop {
+        let mut entry: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result: *mut libc::passwd = std::ptr::null_mut();
+        let code = lookup(&mut entry, buffer.as_mut_ptr(), buffer.len(), &mut result);
+        if code == libc::ERANGE && buffer.len() < 1024 * 1024 {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        if code != 0 {
+            return Err(HyperVError::Io(std::io::Error::from_raw_os_error(code)));
+        }
+        if result.is_null() {
+            return Ok(None);
+        }
+        let text = |ptr: *const libc::c_char| {
+            if ptr.is_null() {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(ptr) }
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        };
+        return Ok(Some(UserEntry {
+            name: text(entry.pw_name),
+            uid: entry.pw_uid,
+            gid: entry.pw_gid,
+            home: text(entry.pw_dir),
+        }));
+    }
+}
+
+/// Identity a task process switches to before exec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunAs {
+    pub uid: u32,
+    pub gid: u32,
+    /// Supplementary groups; only the listed ones, the user's memberships are not added
+    pub groups: Vec<u32>,
+    /// The user's database entry, absent for a numeric ID without one
+    pub user: Option<UserEntry>,
+}
+
+impl RunAs {
+    /// The identity `task` runs as; `None` when it runs as the invoking user
+    pub fn for_task(task: &Task) -> Result<Option<Self>> {
+        Self::resolve(
+            task.user.as_deref(),
+            task.group.as_deref(),
+            &task.supplementary_groups,
+        )
+    }
+
+    /// Look up `user`, `group` and `supplementary_groups`; `None` when all are unset
+    pub fn resolve(
+        user: Option<&str>,
+        group: Option<&str>,
+        supplementary_groups: &[String],
+    ) -> Result<Option<Self>> {
+        if user.is_none() && group.is_none() && supplementary_groups.is_empty() {
+            return Ok(None);
+        }
+        let group_id = |group: &str| {
+            lookup_group(group)?.ok_or_else(|| HyperVError::GroupNotFound(group.to_string()))
+        };
+
+        let (uid, entry) = match user {
+            Some(name) => match lookup_user(name)? {
+                Some(entry) => (entry.uid, Some(entry)),
+                None => match name.parse::<u32>() {
+                    Ok(uid) => (uid, None),
+                    Err(_) => return Err(HyperVError::UserNotFound(name.to_string())),
+                },
+            },
+            None => (unsafe { libc::geteuid() }, None),
+        };
+        let gid = match (group, &entry) {
+            (Some(group), _) => group_id(group)?,
+            (None, Some(entry)) => entry.gid,
+            (None, None) if user.is_some() => {
+                return Err(HyperVError::InvalidInput(format!(
+                    "user {} has no entry in the user database; set a group as well",
+                    uid
+                )));
+            }
+            (None, None) => unsafe { libc::getegid() },
+        };
+        let groups = supplementary_groups
+            .iter()
+            .map(|group| group_id(group))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(RunAs {
+            uid,
+            gid,
+            groups,
+            user: entry,
+        }))
+    }
+
+    /// Name of the user, or its numeric ID
+    pub fn user_name(&self) -> String {
+        self.user
+            .as_ref()
+            .map_or_else(|| self.uid.to_string(), |entry| entry.name.clone())
+    }
+
+    /// Fail unless this process may switch to the identity: only root can change it
+    pub fn check_permitted(&self) -> Result<()> {
+        let (euid, egid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        if euid == 0 || (self.uid == euid && self.gid == egid && self.groups.is_empty()) {
+            return Ok(());
+        }
+        Err(HyperVError::PrivilegeDrop(format!(
+            "hyperV runs as uid {}; only root can start tasks as user {} (uid {}, gid {})",
+            euid,
+            self.user_name(),
+            self.uid,
+            self.gid
+        )))
+    }
+
+    /// Make `cmd` switch to this identity before exec, and point `HOME`, `USER` and
+    /// `LOGNAME` at the user unless `env` sets them
+    pub fn apply_to(&self, cmd: &mut Command, env: &std::collections::HashMap<String, String>) {
+        use std::os::unix::process::CommandExt;
+
+        if let Some(entry) = &self.user {
+            for (key, value) in [
+                ("HOME", &entry.home),
+                ("USER", &entry.name),
+                ("LOGNAME", &entry.name),
+            ] {
+                if !env.contains_key(key) {
+                    cmd.env(key, value);
+                }
+            }
+        }
+
+        let (uid, gid, groups) = (self.uid, self.gid, self.groups.clone());
+        let switch = unsafe { libc::geteuid() } == 0;
+        // Only async-signal-safe calls between fork and exec
+        unsafe {
+            cmd.pre_exec(move || {
+                if !switch {
+                    return Ok(());
+                }
+                if libc::setgroups(groups.len() as _, groups.as_ptr() as *const _) != 0
+                    || libc::setgid(gid) != 0
+                    || libc::setuid(uid) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    /// Whether the identity may read `path` (and search every directory above it)
+    pub fn can_read(&self, path: &Path) -> bool {
+        self.can_reach(path) && self.permits(path, 0o4)
+    }
+
+    /// Whether the identity may execute `path`, or enter it if it is a directory
+    pub fn can_execute(&self, path: &Path) -> bool {
+        self.can_reach(path) && self.permits(path, 0o1)
+    }
+
+    /// Every ancestor directory of `path` is searchable
+    fn can_reach(&self, path: &Path) -> bool {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        path.ancestors().skip(1).all(|dir| self.permits(dir, 0o1))
+    }
+
+    /// Whether the permission bits of `path` grant `bits` (4 read, 2 write, 1 execute)
+    fn permits(&self, path: &Path, bits: u32) -> bool {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return false;
+        };
+        let mode = metadata.mode();
+        if self.uid == 0 {
+            // Root reads anything, but executes files only with some execute bit set
+            return bits & 0o1 == 0 || metadata.is_dir() || mode & 0o111 != 0;
+        }
+        let class = if metadata.uid() == self.uid {
+            mode >> 6
+        } else if metadata.gid() == self.gid || self.groups.contains(&metadata.gid()) {
+            mode >> 3
+        } else {
+            mode
+        };
+        class & bits == bits
+    }
+}
@@ -2,23 +2,58 @@
 //!
 //! Handles process spawning, monitoring, and termination with proper signal handling.
 
+use crate::cgroup::Cgroup;
 use crate::constants::SHUTDOWN_TIMEOUT;
 use crate::error::{HyperVError, Result};
+use crate::logs::{LogRetention, LogStream};
 use crate::task::Task;
-use std::collections::HashMap;
-#[cfg(unix)]
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::thread;
 use std::time::{Duration, Instant};
 
+/// How a supervised child process ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessExit {
+    /// PID of the process that exited
+    pub pid: u32,
+    /// Exit code, when the process exited normally
+    pub code: Option<i32>,
+    /// Terminating signal, when the process was killed by one
+    pub signal: Option<i32>,
+    /// CPU time the process used, when it could be read before it was reaped
+    pub cpu_time: Option<Duration>,
+}
+
+impl ProcessExit {
+    fn from_status(pid: u32, status: std::process::ExitStatus, cpu_time: Option<Duration>) -> Self {
+        #[cfg(unix)]
+        let signal = {
+            use std::os::unix::process::ExitStatusExt;
+            status.signal()
+        };
+        #[cfg(not(unix))]
+        let signal = None;
+
+        Self {
+            pid,
+            code: status.code(),
+            signal,
+            cpu_time,
+        }
+    }
+}
+
 /// Process manager for handling running tasks
 pub struct ProcessManager {
     /// Currently running processes
     running_processes: HashMap<String, Child>,
+    /// hyperV executable used to capture task output through pipes; without one,
+    /// tasks write straight to their log files
+    log_writer: Option<PathBuf>,
 }
 
 impl ProcessManager {
@@ -26,7 +61,80 @@ impl ProcessManager {
     pub fn new() -> Self {
         Self {
             running_processes: HashMap::new(),
+            log_writer: None,
+        }
+    }
+
+    /// Capture task output through pipes read by `hyperV capture-log` processes
+    /// started from `executable`, so logs are rotated while the task runs
+    pub fn set_log_writer(&mut self, executable: PathBuf) {
+        self.log_writer = Some(executable);
+    }
+
+    /// Where a task's stdout or stderr should go: a pipe into a log writer process, or
+    /// the log file itself when no writer is configured or it cannot be started
+    fn log_output(
+        &self,
+        log_path: &Path,
+        stream: LogStream,
+        retention: &LogRetention,
+    ) -> Result<Stdio> {
+        if let Some(executable) = &self.log_writer {
+            match Self::spawn_log_writer(executable, log_path, stream, retention) {
+                Ok(stdio) => return Ok(stdio),
+                Err(e) => eprintln!(
+                    "⚠️  Cannot capture {} through a pipe ({}); writing to it directly",
+                    log_path.display(),
+                    e
+                ),
+            }
+        }
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)
+            .map_err(HyperVError::Io)?;
+        Ok(Stdio::from(file))
+    }
+
+    /// Start a log writer reading from a new pipe and return the pipe's writing end.
+    ///
+    /// The writer runs in its own process group, so it outlives both the command that
+    /// started the task and signals sent to the task's group, and exits once every
+    /// process holding the writing end has closed it.
+    fn spawn_log_writer(
+        executable: &Path,
+        log_path: &Path,
+        stream: LogStream,
+        retention: &LogRetention,
+    ) -> std::io::Result<Stdio> {
+        let (reader, writer) = std::io::pipe()?;
+        let mut cmd = Command::new(executable);
+        cmd.arg("capture-log")
+            .arg(log_path)
+            .args(["--stream", stream.as_str()])
+            .arg(format!("--max-size={}", retention.max_size))
+            .arg(format!("--max-archives={}", retention.max_archives));
+        if let Some(max_age_days) = retention.max_age_days {
+            cmd.arg(format!("--max-age-days={}", max_age_days));
+        }
+        if !retention.compress {
+            cmd.arg("--no-compress");
+        }
+        cmd.stdin(reader)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
         }
+        let mut child = cmd.spawn()?;
+        // Reap the writer when it exits; a short-lived CLI leaves that to init instead
+        thread::spawn(move || {
+            let _ = child.wait();
+        });
+        Ok(Stdio::from(writer))
     }
 
     fn is_pid_running(pid: u32) -> bool {
@@ -38,7 +146,8 @@ impl ProcessManager {
             use libc::kill;
             let rc = unsafe { kill(pid as i32, 0) };
             if rc == 0 {
-                return true;
+                // An exited child that nobody has reaped yet still answers kill(0).
+                return !Self::is_zombie(pid);
             }
             let err = std::io::Error::last_os_error();
             !matches!(err.raw_os_error(), Some(libc::ESRCH))
@@ -59,7 +168,7 @@ impl ProcessManager {
             use libc::kill;
             let rc = unsafe { kill(-(pgid as i32), 0) };
             if rc == 0 {
-                return true;
+                return Self::group_has_live_member(pgid);
             }
             let err = std::io::Error::last_os_error();
             !matches!(err.raw_os_error(), Some(libc::ESRCH))
@@ -72,6 +181,93 @@ impl ProcessManager {
         }
     }
 
+    /// Read a process' state letter and process group from `/proc/<pid>/stat`.
+    #[cfg(target_os = "linux")]
+    fn proc_stat(pid: u32) -> Option<(char, u32)> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        // The command name is wrapped in parentheses and may itself contain spaces or ')'.
+        let rest = &stat[stat.rfind(')')? + 1..];
+        let mut fields = rest.split_whitespace();
+        let state = fields.next()?.chars().next()?;
+        let _ppid = fields.next()?;
+        let pgrp = fields.next()?.parse().ok()?;
+        Some((state, pgrp))
+    }
+
+    /// User plus system CPU time of a process that has exited but was not reaped yet
+    fn zombie_cpu_time(pid: u32) -> Option<Duration> {
+        #[cfg(target_os = "linux")]
+        {
+            let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            let rest = &stat[stat.rfind(')')? + 1..];
+            let fields: Vec<&str> = rest.split_whitespace().collect();
+            // Fields after the command name start at `state`; utime and stime follow 11 later
+            if fields.first()? != &"Z" {
+                return None;
+            }
+            let utime: u64 = fields.get(11)?.parse().ok()?;
+            let stime: u64 = fields.get(12)?.parse().ok()?;
+            let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+            if ticks_per_sec <= 0 {
+                return None;
+            }
+            Some(Duration::from_secs_f64(
+                (utime + stime) as f64 / ticks_per_sec as f64,
+            ))
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = pid;
+            None
+        }
+    }
+
+    #[cfg(unix)]
+    fn is_zombie(pid: u32) -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            Self::proc_stat(pid).is_some_and(|(state, _)| state == 'Z')
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            use sysinfo::{Pid, ProcessStatus, System};
+            let mut system = System::new();
+            let pid = Pid::from_u32(pid);
+            system.refresh_process(pid)
+                && system
+                    .process(pid)
+                    .is_some_and(|p| p.status() == ProcessStatus::Zombie)
+        }
+    }
+
+    /// Whether a signalable process group contains at least one process that is not a zombie.
+    #[cfg(unix)]
+    fn group_has_live_member(pgid: u32) -> bool {
+        #[cfg(target_os = "linux")]
+        {
+            // Fast path: a live leader means a live group.
+            if !Self::is_zombie(pgid) && Self::proc_stat(pgid).is_some() {
+                return true;
+            }
+            let Ok(entries) = fs::read_dir("/proc") else {
+                return true;
+            };
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+                .filter_map(Self::proc_stat)
+                .any(|(state, pgrp)| pgrp == pgid && state != 'Z')
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = pgid;
+            true
+        }
+    }
+
     #[cfg(unix)]
     fn process_group_id(pid: u32) -> Option<u32> {
         let pgid = unsafe { libc::getpgid(pid as i32) };
@@ -110,6 +306,12 @@ impl ProcessManager {
         Self::is_pgid_running(pgid)
     }
 
+    /// Whether anything of a task is left: its process group (usually the task's
+    /// initial PID), or any process in its cgroup
+    pub fn is_task_group_running(&self, pgid: u32, cgroup: Option<&str>) -> bool {
+        Self::is_pgid_running(pgid) || cgroup.is_some_and(|path| Cgroup::at(path).is_populated())
+    }
+
     /// Best-effort process start time used to detect PID reuse.
     pub fn process_start_time(&self, pid: u32) -> Option<u64> {
         use sysinfo::{Pid, System};
@@ -190,13 +392,31 @@ impl ProcessManager {
         actual.is_some_and(|p| p == expected)
     }
 
-    /// Start a task process
+    /// Start a task process with the default log rotation
     pub fn start_task(
         &mut self,
         task: &Task,
         task_env: &HashMap<String, String>,
         stdout_log: &Path,
         stderr_log: &Path,
+    ) -> Result<u32> {
+        self.start_task_with_logs(
+            task,
+            task_env,
+            stdout_log,
+            stderr_log,
+            &LogRetention::default(),
+        )
+    }
+
+    /// Start a task process whose logs are rotated according to `retention`
+    pub fn start_task_with_logs(
+        &mut self,
+        task: &Task,
+        task_env: &HashMap<String, String>,
+        stdout_log: &Path,
+        stderr_log: &Path,
+        retention: &LogRetention,
     ) -> Result<u32> {
         // Validate the binary before starting
         self.validate_binary(&task.binary)?;
@@ -215,21 +435,9 @@ impl ProcessManager {
             cmd.current_dir(workdir);
         }
 
-        // Setup log files
-        let stdout_file = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(stdout_log)
-            .map_err(HyperVError::Io)?;
-
-        let stderr_file = fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(stderr_log)
-            .map_err(HyperVError::Io)?;
-
-        cmd.stdout(Stdio::from(stdout_file));
-        cmd.stderr(Stdio::from(stderr_file));
+        // Setup log output
+        cmd.stdout(self.log_output(stdout_log, LogStream::Stdout, retention)?);
+        cmd.stderr(self.log_output(stderr_log, LogStream::Stderr, retention)?);
 
         // Create process group for proper signal handling
         #[cfg(unix)]
@@ -238,10 +446,49 @@ impl ProcessManager {
             cmd.process_group(0);
         }
 
+        // Join the task's cgroup while the child still may write to it
+        if let Some(path) = &task.cgroup {
+            Cgroup::at(path)
+                .add_to(&mut cmd)
+                .map_err(|e| HyperVError::ProcessStart(task.binary.clone(), e.to_string()))?;
+        }
+
+        // Apply resource limits while the child may still raise hard limits
+        task.limits.check_permitted()?;
+        task.limits.apply_to(&mut cmd);
+
+        // Switch to the task's user and group between fork and exec
+        #[cfg(unix)]
+        let run_as = crate::privileges::RunAs::for_task(task)?;
+        #[cfg(unix)]
+        if let Some(run_as) = &run_as {
+            run_as.check_permitted()?;
+            run_as.apply_to(&mut cmd, task_env);
+        }
+        #[cfg(not(unix))]
+        if task.user.is_some() || task.group.is_some() || !task.supplementary_groups.is_empty() {
+            return Err(HyperVError::PrivilegeDrop(
+                "running tasks as another user is only supported on Unix".to_string(),
+            ));
+        }
+
         // Spawn the process
-        let child = cmd
-            .spawn()
-            .map_err(|e| HyperVError::ProcessStart(task.binary.clone(), e.to_string()))?;
+        let child = cmd.spawn().map_err(|e| {
+            #[cfg(unix)]
+            if let Some(run_as) = &run_as
+                && e.raw_os_error() == Some(libc::EPERM)
+            {
+                return HyperVError::PrivilegeDrop(format!(
+                    "cannot run {} as user {} (uid {}, gid {}): {}",
+                    task.binary,
+                    run_as.user_name(),
+                    run_as.uid,
+                    run_as.gid,
+                    e
+                ));
+            }
+            HyperVError::ProcessStart(task.binary.clone(), e.to_string())
+        })?;
 
         let pid = child.id();
         self.running_processes.insert(task.id.clone(), child);
@@ -251,111 +498,89 @@ impl ProcessManager {
 
     /// Stop a task process gracefully
     pub fn stop_task(&mut self, task_id: &str, pid: u32) -> Result<()> {
-        // Take ownership of the Child so we can poll/reap without borrowing self.
-        // If the process doesn't actually terminate, we reinsert it.
-        let mut child = self.running_processes.remove(task_id);
-
-        let mut wait_for_exit = |timeout: Duration| -> bool {
-            let start = Instant::now();
-            while start.elapsed() < timeout {
-                if let Some(c) = child.as_mut() {
-                    // try_wait() reaps the child if it exited.
-                    let exited = matches!(c.try_wait(), Ok(Some(_)));
-                    if exited {
-                        child.take();
-                        return true;
-                    }
-                }
-
-                if !Self::is_pid_running(pid) && !Self::is_pgid_running(pid) {
-                    // If the OS no longer reports it running, best-effort reap to avoid zombies.
-                    if let Some(mut c) = child.take() {
-                        let _ = c.try_wait();
-                        let _ = c.wait();
-                    }
-                    return true;
-                }
+        self.stop_task_with_cgroup(task_id, pid, None)
+    }
 
-                thread::sleep(Duration::from_millis(100));
-            }
+    /// Stop a task process gracefully, together with every process in its cgroup
+    pub fn stop_task_with_cgroup(
+        &mut self,
+        task_id: &str,
+        pid: u32,
+        cgroup: Option<&Cgroup>,
+    ) -> Result<()> {
+        let Some(mut stop) = self.begin_stop(task_id, pid, cgroup)? else {
+            return Ok(());
+        };
+        let result = stop.wait();
+        self.reclaim(stop);
+        result
+    }
 
-            // One final check at the boundary.
-            if let Some(c) = child.as_mut() {
-                let exited = matches!(c.try_wait(), Ok(Some(_)));
-                if exited {
-                    child.take();
-                    return true;
-                }
-            }
-            !Self::is_pid_running(pid) && !Self::is_pgid_running(pid)
+    /// Send SIGTERM to a task and return the stop still in progress, or `None`
+    /// when the task is already gone.
+    ///
+    /// The returned [`StopInProgress`] owns the child handle, so the wait for
+    /// the task to exit can run on another thread; hand it back through
+    /// [`ProcessManager::reclaim`] afterwards.
+    pub fn begin_stop(
+        &mut self,
+        task_id: &str,
+        pid: u32,
+        cgroup: Option<&Cgroup>,
+    ) -> Result<Option<StopInProgress>> {
+        // Take ownership of the Child so we can poll/reap without borrowing self.
+        // If the process doesn't actually terminate, it is reinserted by reclaim().
+        let mut stop = StopInProgress {
+            task_id: task_id.to_string(),
+            pid,
+            cgroup: cgroup.cloned(),
+            child: self.running_processes.remove(task_id),
+            watched_pids: vec![pid],
+            watched_pgids: [pid].into_iter().collect(),
         };
 
         // First check if the process is actually running
-        if !Self::is_pid_running(pid) && !Self::is_pgid_running(pid) {
+        if !stop.root_running() {
             println!("ℹ️  Process {} is already stopped", pid);
             // Best-effort reap any tracked child to avoid zombies.
-            if let Some(mut c) = child.take() {
-                let _ = c.try_wait();
-                let _ = c.wait();
-            }
-            return Ok(());
+            stop.reap();
+            return Ok(None);
         }
 
         // First try graceful shutdown with SIGTERM
         #[cfg(unix)]
         {
-            use libc::{SIGKILL, SIGTERM, kill};
-
-            let descendant_pids = Self::descendant_pids(pid);
-            let mut watched_pids = descendant_pids.clone();
-            watched_pids.push(pid);
-
-            let mut watched_pgids = HashSet::new();
-            watched_pgids.insert(pid);
+            use libc::SIGTERM;
+
+            // cgroup membership is exact; walking the process table misses processes
+            // that were reparented
+            let descendant_pids: Vec<u32> = match cgroup {
+                Some(cgroup) => cgroup
+                    .members()
+                    .into_iter()
+                    .filter(|member| *member != pid)
+                    .collect(),
+                None => Self::descendant_pids(pid),
+            };
             for child_pid in &descendant_pids {
                 if let Some(pgid) = Self::process_group_id(*child_pid) {
-                    watched_pgids.insert(pgid);
+                    stop.watched_pgids.insert(pgid);
                 }
             }
-
-            let all_stopped = |watched_pids: &[u32], watched_pgids: &HashSet<u32>| {
-                !watched_pids.iter().any(|pid| Self::is_pid_running(*pid))
-                    && !watched_pgids
-                        .iter()
-                        .any(|pgid| Self::is_pgid_running(*pgid))
-            };
-
-            let send_signal = |signal| {
-                let mut sent = false;
-                for pgid in &watched_pgids {
-                    if unsafe { kill(-(*pgid as i32), signal) } == 0 {
-                        sent = true;
-                    }
-                }
-                for watched_pid in &watched_pids {
-                    if Self::is_pid_running(*watched_pid)
-                        && unsafe { kill(*watched_pid as i32, signal) } == 0
-                    {
-                        sent = true;
-                    }
-                }
-                sent
-            };
+            stop.watched_pids.extend(descendant_pids);
 
             // Try to send SIGTERM to the process group first
             println!("🛑 Sending SIGTERM to process group {}", pid);
-            if !send_signal(SIGTERM) {
+            if !stop.send_signal(SIGTERM) {
                 // Check if the process died between our checks
-                if all_stopped(&watched_pids, &watched_pgids) {
+                if stop.all_stopped() {
                     println!("ℹ️  Process {} terminated during stop attempt", pid);
-                    if let Some(mut c) = child.take() {
-                        let _ = c.try_wait();
-                        let _ = c.wait();
-                    }
-                    return Ok(());
+                    stop.reap();
+                    return Ok(None);
                 }
 
                 let errno = std::io::Error::last_os_error();
+                self.reclaim(stop);
                 return Err(HyperVError::ProcessStop(format!(
                     "Failed to send SIGTERM to process {} or its children (errno: {})",
                     pid, errno
@@ -366,49 +591,13 @@ impl ProcessManager {
                 "⏳ Waiting {} seconds for graceful shutdown...",
                 SHUTDOWN_TIMEOUT.as_secs()
             );
-
-            if !wait_for_exit(SHUTDOWN_TIMEOUT) {
-                println!("💀 Process still running, sending SIGKILL...");
-
-                if !send_signal(SIGKILL) {
-                    // Check if the process died during our attempts
-                    if all_stopped(&watched_pids, &watched_pgids) {
-                        println!("ℹ️  Process {} terminated during kill attempt", pid);
-                        if let Some(mut c) = child.take() {
-                            let _ = c.try_wait();
-                            let _ = c.wait();
-                        }
-                        return Ok(());
-                    }
-
-                    let errno = std::io::Error::last_os_error();
-                    if let Some(c) = child.take() {
-                        self.running_processes.insert(task_id.to_string(), c);
-                    }
-                    return Err(HyperVError::ProcessStop(format!(
-                        "Failed to kill process {} or its children (errno: {})",
-                        pid, errno
-                    )));
-                }
-
-                // Give it a chance to actually terminate after SIGKILL.
-                let kill_timeout = Duration::from_secs(2);
-                if !wait_for_exit(kill_timeout) || !all_stopped(&watched_pids, &watched_pgids) {
-                    if let Some(c) = child.take() {
-                        self.running_processes.insert(task_id.to_string(), c);
-                    }
-                    return Err(HyperVError::ProcessStop(format!(
-                        "Process {} or one of its children did not terminate after SIGKILL",
-                        pid
-                    )));
-                }
-            }
+            Ok(Some(stop))
         }
 
         #[cfg(not(unix))]
         {
             // On non-Unix systems, try to terminate the child process
-            if let Some(mut c) = child.take() {
+            if let Some(mut c) = stop.child.take() {
                 let _ = c.kill();
                 let _ = c.wait();
             } else if Self::is_pid_running(pid) {
@@ -417,15 +606,15 @@ impl ProcessManager {
                     pid
                 )));
             }
+            Ok(None)
         }
+    }
 
-        // If we still have a tracked child here, ensure it's reaped before dropping.
-        if let Some(mut c) = child.take() {
-            let _ = c.try_wait();
-            let _ = c.wait();
+    /// Take back the child handle of a stop that did not reap its process
+    pub fn reclaim(&mut self, mut stop: StopInProgress) {
+        if let Some(child) = stop.child.take() {
+            self.running_processes.insert(stop.task_id.clone(), child);
         }
-
-        Ok(())
     }
 
     /// Validate that a binary file exists and is executable
@@ -499,18 +688,21 @@ impl ProcessManager {
         self.running_processes.len()
     }
 
-    /// Clean up zombie processes and update their exit codes
-    pub fn cleanup_zombies(&mut self) -> HashMap<String, i32> {
+    /// Clean up zombie processes and collect how each of them exited
+    pub fn cleanup_zombies(&mut self) -> HashMap<String, ProcessExit> {
         let mut to_remove = Vec::new();
-        let mut exit_codes = HashMap::new();
+        let mut exits = HashMap::new();
 
         for (task_id, child) in &mut self.running_processes {
+            // Reaping discards the usage of the process, so read it while it is a zombie
+            let cpu_time = Self::zombie_cpu_time(child.id());
             match child.try_wait() {
                 Ok(Some(status)) => {
                     to_remove.push(task_id.clone());
-                    if let Some(code) = status.code() {
-                        exit_codes.insert(task_id.clone(), code);
-                    }
+                    exits.insert(
+                        task_id.clone(),
+                        ProcessExit::from_status(child.id(), status, cpu_time),
+                    );
                 }
                 Ok(None) => { /* Still running */ }
                 Err(e) => {
@@ -523,7 +715,7 @@ impl ProcessManager {
         for task_id in to_remove {
             self.running_processes.remove(&task_id);
         }
-        exit_codes
+        exits
     }
 }
 
@@ -533,30 +725,203 @@ impl Default for ProcessManager {
     }
 }
 
+/// A task that has been asked to stop and is being given time to exit
+///
+/// Created by [`ProcessManager::begin_stop`] after SIGTERM was sent. It owns
+/// everything the wait needs, so [`StopInProgress::wait`] may run off the
+/// thread that owns the `ProcessManager`.
+#[derive(Debug)]
+pub struct StopInProgress {
+    task_id: String,
+    pid: u32,
+    cgroup: Option<Cgroup>,
+    child: Option<Child>,
+    watched_pids: Vec<u32>,
+    watched_pgids: HashSet<u32>,
+}
+
+impl StopInProgress {
+    /// ID of the task being stopped
+    pub fn task_id(&self) -> &str {
+        &self.task_id
+    }
+
+    /// PID of the task's main process
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Wait `SHUTDOWN_TIMEOUT` for the task to exit, then kill it
+    pub fn wait(&mut self) -> Result<()> {
+        #[cfg(unix)]
+        {
+            use libc::SIGKILL;
+
+            if !self.wait_for_exit(SHUTDOWN_TIMEOUT) {
+                let killed = match self.cgroup.clone() {
+                    Some(cgroup) => {
+                        println!(
+                            "💀 Process still running, killing cgroup {}...",
+                            cgroup.path().display()
+                        );
+                        let killed = cgroup.kill().is_ok();
+                        self.send_signal(SIGKILL) || killed
+                    }
+                    None => {
+                        println!("💀 Process still running, sending SIGKILL...");
+                        self.send_signal(SIGKILL)
+                    }
+                };
+
+                if !killed {
+                    // Check if the process died during our attempts
+                    if self.all_stopped() {
+                        println!("ℹ️  Process {} terminated during kill attempt", self.pid);
+                        self.reap();
+                        return Ok(());
+                    }
+
+                    let errno = std::io::Error::last_os_error();
+                    return Err(HyperVError::ProcessStop(format!(
+                        "Failed to kill process {} or its children (errno: {})",
+                        self.pid, errno
+                    )));
+                }
+
+                // Give it a chance to actually terminate after SIGKILL.
+                let kill_timeout = Duration::from_secs(2);
+                if !self.wait_for_exit(kill_timeout) || !self.all_stopped() {
+                    return Err(HyperVError::ProcessStop(format!(
+                        "Process {} or one of its children did not terminate after SIGKILL",
+                        self.pid
+                    )));
+                }
+            }
+        }
+
+        // If we still have a tracked child here, ensure it's reaped before dropping.
+        self.reap();
+        Ok(())
+    }
+
+    fn cgroup_running(&self) -> bool {
+        self.cgroup.as_ref().is_some_and(Cgroup::is_populated)
+    }
+
+    /// Whether the task's own process, process group or cgroup is still alive
+    fn root_running(&self) -> bool {
+        ProcessManager::is_pid_running(self.pid)
+            || ProcessManager::is_pgid_running(self.pid)
+            || self.cgroup_running()
+    }
+
+    fn all_stopped(&self) -> bool {
+        !self
+            .watched_pids
+            .iter()
+            .any(|pid| ProcessManager::is_pid_running(*pid))
+            && !self
+                .watched_pgids
+                .iter()
+                .any(|pgid| ProcessManager::is_pgid_running(*pgid))
+            && !self.cgroup_running()
+    }
+
+    /// Best-effort reap of the tracked child to avoid zombies
+    fn reap(&mut self) {
+        if let Some(mut c) = self.child.take() {
+            let _ = c.try_wait();
+            let _ = c.wait();
+        }
+    }
+
+    #[cfg(unix)]
+    fn send_signal(&self, signal: i32) -> bool {
+        use libc::kill;
+
+        let mut sent = false;
+        for pgid in &self.watched_pgids {
+            if unsafe { kill(-(*pgid as i32), signal) } == 0 {
+                sent = true;
+            }
+        }
+        for watched_pid in &self.watched_pids {
+            if ProcessManager::is_pid_running(*watched_pid)
+                && unsafe { kill(*watched_pid as i32, signal) } == 0
+            {
+                sent = true;
+            }
+        }
+        if let Some(cgroup) = &self.cgroup
+            && cgroup.signal(signal)
+        {
+            sent = true;
+        }
+        sent
+    }
+
+    #[cfg(unix)]
+    fn wait_for_exit(&mut self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if let Some(c) = self.child.as_mut() {
+                // try_wait() reaps the child if it exited. Other members of its
+                // process group may still be shutting down, so keep watching the group.
+                let exited = matches!(c.try_wait(), Ok(Some(_)));
+                if exited {
+                    self.child.take();
+                }
+            }
+
+            if !self.root_running() {
+                // If the OS no longer reports it running, best-effort reap to avoid zombies.
+                self.reap();
+                return true;
+            }
+
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        // One final check at the boundary.
+        if let Some(c) = self.child.as_mut() {
+            let exited = matches!(c.try_wait(), Ok(Some(_)));
+            if exited {
+                self.child.take();
+            }
+        }
+        !self.root_running()
+    }
+}
+
 /// Diagnose issues with a binary file
 pub fn diagnose_binary(binary_path: &str) -> Result<()> {
+    diagnose_binary_with(binary_path, &mut |line| println!("{}", line))
+}
+
+/// [`diagnose_binary`], passing each line of the report to `report`
+pub fn diagnose_binary_with(binary_path: &str, report: &mut dyn FnMut(&str)) -> Result<()> {
     let path = Path::new(binary_path);
 
-    println!("🔍 Diagnosing binary: {}", binary_path);
-    println!();
+    report(&format!("🔍 Diagnosing binary: {}", binary_path));
+    report("");
 
     // Check file existence
     if !path.exists() {
-        println!("❌ File does not exist");
+        report("❌ File does not exist");
         return Err(HyperVError::BinaryNotFound(binary_path.to_string()));
     }
-    println!("✅ File exists");
+    report("✅ File exists");
 
     // Check file type
     let metadata = std::fs::metadata(path).map_err(HyperVError::Io)?;
 
     if metadata.is_dir() {
-        println!("❌ Path points to a directory, not a file");
+        report("❌ Path points to a directory, not a file");
         return Err(HyperVError::InvalidBinary(
             "Path is a directory".to_string(),
         ));
     }
-    println!("✅ Is a file");
+    report("✅ Is a file");
 
     // Check permissions
     #[cfg(unix)]
@@ -565,14 +930,14 @@ pub fn diagnose_binary(binary_path: &str) -> Result<()> {
         let permissions = metadata.permissions();
         let mode = permissions.mode();
 
-        println!("📋 File permissions: {:o}", mode & 0o777);
+        report(&format!("📋 File permissions: {:o}", mode & 0o777));
 
         if mode & 0o111 == 0 {
-            println!("❌ File is not executable");
-            println!("💡 Fix with: chmod +x {}", binary_path);
+            report("❌ File is not executable");
+            report(&format!("💡 Fix with: chmod +x {}", binary_path));
             return Err(HyperVError::BinaryNotExecutable(binary_path.to_string()));
         }
-        println!("✅ File is executable");
+        report("✅ File is executable");
     }
 
     // Analyze file content
@@ -582,7 +947,7 @@ pub fn diagnose_binary(binary_path: &str) -> Result<()> {
     let bytes_read = file.read(&mut buffer).unwrap_or(0);
 
     if bytes_read == 0 {
-        println!("❌ File is empty");
+        report("❌ File is empty");
         return Err(HyperVError::InvalidBinary("File is empty".to_string()));
     }
 
@@ -593,49 +958,49 @@ pub fn diagnose_binary(binary_path: &str) -> Result<()> {
         .any(|&b| b == 0 || (!b.is_ascii() && b != b'\n' && b != b'\r' && b != b'\t'));
 
     if is_binary {
-        println!("✅ Detected binary file");
+        report("✅ Detected binary file");
 
         // Check for common binary formats
         if bytes_read >= 4 {
             match &buffer[0..4] {
-                [0x7f, b'E', b'L', b'F'] => println!("📋 Format: ELF executable (Linux)"),
+                [0x7f, b'E', b'L', b'F'] => report("📋 Format: ELF executable (Linux)"),
                 [0xcf, 0xfa, 0xed, 0xfe] | [0xce, 0xfa, 0xed, 0xfe] => {
-                    println!("📋 Format: Mach-O executable (macOS)")
+                    report("📋 Format: Mach-O executable (macOS)")
                 }
-                [b'M', b'Z', _, _] => println!("📋 Format: PE executable (Windows)"),
-                _ => println!("📋 Format: Unknown binary format"),
+                [b'M', b'Z', _, _] => report("📋 Format: PE executable (Windows)"),
+                _ => report("📋 Format: Unknown binary format"),
             }
         }
     } else {
-        println!("📋 Detected text file (script)");
+        report("📋 Detected text file (script)");
 
         // Check for shebang
         if bytes_read >= 2 && buffer[0] == 0x23 && buffer[1] == 0x21 {
             let shebang_content = String::from_utf8_lossy(&buffer[..bytes_read.min(256)]);
             let shebang_line = shebang_content.lines().next().unwrap_or("").trim();
 
-            println!("✅ Has shebang: {}", shebang_line);
+            report(&format!("✅ Has shebang: {}", shebang_line));
 
             // Validate interpreter
             if let Some(interpreter) = shebang_line.strip_prefix("#!") {
                 let interpreter = interpreter.split_whitespace().next().unwrap_or("");
                 if !interpreter.is_empty() {
                     if Path::new(interpreter).exists() {
-                        println!("✅ Interpreter exists: {}", interpreter);
+                        report(&format!("✅ Interpreter exists: {}", interpreter));
                     } else {
-                        println!("❌ Interpreter not found: {}", interpreter);
-                        println!("💡 Install the interpreter or fix the shebang line");
+                        report(&format!("❌ Interpreter not found: {}", interpreter));
+                        report("💡 Install the interpreter or fix the shebang line");
                         return Err(HyperVError::InterpreterNotFound(interpreter.to_string()));
                     }
                 }
             }
         } else {
-            println!("❌ No shebang found");
-            println!("💡 Add a shebang line like '#!/bin/bash' as the first line");
+            report("❌ No shebang found");
+            report("💡 Add a shebang line like '#!/bin/bash' as the first line");
         }
     }
 
-    println!();
-    println!("🎯 Diagnosis complete - binary appears valid");
+    report("");
+    report("🎯 Diagnosis complete - binary appears valid");
     Ok(())
 }
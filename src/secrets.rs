@@ -0,0 +1,306 @@
+//! Secret references and the local secret store
+//!
+//! A task env value of the form `secret:NAME`, `secret:file:PATH` or `secret:env:VAR`
+//! is stored in `tasks.json` as written and only resolved when the task is spawned.
+//! Named secrets live in `secrets.json` in the configuration directory; each value is
+//! encrypted with AES-256-GCM under the key in `secrets.key` (or the file named by
+//! `HYPERV_SECRET_KEY_FILE`), which is created on the first `hyperV secret set`.
+
+use crate::config::{Config, write_private};
+use crate::error::{HyperVError, Result};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Prefix that marks an env value as a secret reference
+pub const SECRET_REF_PREFIX: &str = "secret:";
+
+/// Length of the AES-256-GCM nonce stored in front of each ciphertext
+const NONCE_LEN: usize = 12;
+
+/// Where the value of a secret reference comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretRef {
+    /// `secret:NAME`: an entry in the local secret store
+    Store(String),
+    /// `secret:file:PATH`: the contents of a file, without trailing newlines
+    File(PathBuf),
+    /// `secret:env:VAR`: a variable in the environment of the process spawning the task
+    Env(String),
+}
+
+impl SecretRef {
+    /// Parse `value` as a secret reference; plain values give `None`
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        let Some(reference) = value.strip_prefix(SECRET_REF_PREFIX) else {
+            return Ok(None);
+        };
+        let invalid = |reason: &str| {
+            HyperVError::InvalidInput(format!(
+                "invalid secret reference \"{}\": {}",
+                value, reason
+            ))
+        };
+        let parsed = if let Some(path) = reference.strip_prefix("file:") {
+            if path.is_empty() {
+                return Err(invalid("expected secret:file:PATH"));
+            }
+            SecretRef::File(PathBuf::from(path))
+        } else if let Some(var) = reference.strip_prefix("env:") {
+            if var.is_empty() || var.contains('=') {
+                return Err(invalid("expected secret:env:VAR"));
+            }
+            SecretRef::Env(var.to_string())
+        } else {
+            validate_name(reference)
+                .map_err(|_| invalid("expected secret:NAME, secret:file:PATH or secret:env:VAR"))?;
+            SecretRef::Store(reference.to_string())
+        };
+        Ok(Some(parsed))
+    }
+
+    /// Read the value this reference points to
+    pub fn resolve(&self, store: &SecretStore) -> Result<String> {
+        match self {
+            SecretRef::Store(name) => store.get(name),
+            SecretRef::File(path) => fs::read_to_string(path)
+                .map(|content| content.trim_end_matches(['\r', '\n']).to_string())
+                .map_err(|e| HyperVError::Secret(format!("cannot read {}: {}", path.display(), e))),
+            SecretRef::Env(var) => std::env::var(var)
+                .map_err(|_| HyperVError::Secret(format!("host variable {} is not set", var))),
+        }
+    }
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretRef::Store(name) => write!(f, "{}{}", SECRET_REF_PREFIX, name),
+            SecretRef::File(path) => write!(f, "{}file:{}", SECRET_REF_PREFIX, path.display()),
+            SecretRef::Env(var) => write!(f, "{}env:{}", SECRET_REF_PREFIX, var),
+        }
+    }
+}
+
+/// Whether an env value is a secret reference rather than a plain value
+pub fn is_secret_ref(value: &str) -> bool {
+    value.starts_with(SECRET_REF_PREFIX)
+}
+
+/// Check that every secret reference in `env` is well-formed
+pub fn validate_env(env: &HashMap<String, String>) -> Result<()> {
+    for (key, value) in env {
+        SecretRef::parse(value).map_err(|e| match e {
+            HyperVError::InvalidInput(msg) => {
+                HyperVError::InvalidInput(format!("{}: {}", key, msg))
+            }
+            other => other,
+        })?;
+    }
+    Ok(())
+}
+
+/// Replace the secret references in `env` by their values
+pub fn resolve_env(
+    env: &HashMap<String, String>,
+    store: &SecretStore,
+) -> Result<HashMap<String, String>> {
+    let mut resolved = HashMap::with_capacity(env.len());
+    for (key, value) in env {
+        let value = match SecretRef::parse(value)? {
+            Some(reference) => reference.resolve(store).map_err(|e| match e {
+                HyperVError::Secret(msg) => {
+                    HyperVError::Secret(format!("{} ({}): {}", key, reference, msg))
+                }
+                other => other,
+            })?,
+            None => value.clone(),
+        };
+        resolved.insert(key.clone(), value);
+    }
+    Ok(resolved)
+}
+
+/// Secret names: letters, digits, `_`, `-` and `.`
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        return Err(HyperVError::InvalidInput(format!(
+            "invalid secret name \"{}\" (use letters, digits, '_', '-' and '.')",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Contents of `secrets.json`: hex-encoded nonce and ciphertext per secret name
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoreFile {
+    #[serde(default)]
+    secrets: BTreeMap<String, String>,
+}
+
+/// Encrypted secrets kept in the configuration directory
+#[derive(Debug, Clone)]
+pub struct SecretStore {
+    path: PathBuf,
+    key_path: PathBuf,
+}
+
+impl SecretStore {
+    /// The store at `path`, encrypted with the key in `key_path`
+    pub fn new(path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            key_path: key_path.into(),
+        }
+    }
+
+    /// The store of this configuration directory
+    pub fn open(config: &Config) -> Self {
+        let key_path = std::env::var_os("HYPERV_SECRET_KEY_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| config.secret_key_path());
+        Self::new(config.secrets_path(), key_path)
+    }
+
+    /// Names of the stored secrets, sorted
+    pub fn names(&self) -> Result<Vec<String>> {
+        Ok(self.read()?.secrets.into_keys().collect())
+    }
+
+    /// Decrypt the secret `name`
+    pub fn get(&self, name: &str) -> Result<String> {
+        let store = self.read()?;
+        let Some(encoded) = store.secrets.get(name) else {
+            return Err(HyperVError::Secret(format!(
+                "secret \"{}\" is not in the store (add it with `hyperV secret set {}`)",
+                name, name
+            )));
+        };
+        let cipher = self.cipher(false)?;
+        let sealed = decode_hex(encoded)
+            .filter(|sealed| sealed.len() > NONCE_LEN)
+            .ok_or_else(|| {
+                HyperVError::Secret(format!(
+                    "secret \"{}\" in {} is corrupted",
+                    name,
+                    self.path.display()
+                ))
+            })?;
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: name.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                HyperVError::Secret(format!(
+                    "cannot decrypt secret \"{}\" with the key in {}",
+                    name,
+                    self.key_path.display()
+                ))
+            })?;
+        String::from_utf8(plaintext)
+            .map_err(|_| HyperVError::Secret(format!("secret \"{}\" is not valid UTF-8", name)))
+    }
+
+    /// Encrypt and store `value` as `name`, creating the key on first use
+    pub fn set(&self, name: &str, value: &str) -> Result<()> {
+        validate_name(name)?;
+        let cipher = self.cipher(true)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value.as_bytes(),
+                    aad: name.as_bytes(),
+                },
+            )
+            .map_err(|_| HyperVError::Secret(format!("cannot encrypt secret \"{}\"", name)))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+
+        let mut store = self.read()?;
+        store.secrets.insert(name.to_string(), encode_hex(&sealed));
+        self.write(&store)
+    }
+
+    /// Delete the secret `name`; false when there was none
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let mut store = self.read()?;
+        if store.secrets.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.write(&store)?;
+        Ok(true)
+    }
+
+    fn read(&self) -> Result<StoreFile> {
+        if !self.path.exists() {
+            return Ok(StoreFile::default());
+        }
+        let content = fs::read_to_string(&self.path).map_err(HyperVError::Io)?;
+        serde_json::from_str(&content)
+            .map_err(|e| HyperVError::Secret(format!("{}: {}", self.path.display(), e)))
+    }
+
+    fn write(&self, store: &StoreFile) -> Result<()> {
+        let json = serde_json::to_string_pretty(store).map_err(HyperVError::Json)?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        write_private(&tmp_path, json.as_bytes())?;
+        fs::rename(&tmp_path, &self.path).map_err(HyperVError::Io)
+    }
+
+    /// Load the key file, generating it when `create` is set and it does not exist
+    fn cipher(&self, create: bool) -> Result<Aes256Gcm> {
+        if !self.key_path.exists() {
+            if !create {
+                return Err(HyperVError::Secret(format!(
+                    "secret key file {} is missing",
+                    self.key_path.display()
+                )));
+            }
+            let key = Aes256Gcm::generate_key(&mut OsRng);
+            if let Some(parent) = self.key_path.parent() {
+                fs::create_dir_all(parent).map_err(HyperVError::Io)?;
+            }
+            write_private(&self.key_path, format!("{}\n", encode_hex(&key)).as_bytes())?;
+        }
+        let content = fs::read_to_string(&self.key_path).map_err(HyperVError::Io)?;
+        let key = decode_hex(content.trim())
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| {
+                HyperVError::Secret(format!(
+                    "secret key file {} does not hold a 256-bit hex key",
+                    self.key_path.display()
+                ))
+            })?;
+        Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
@@ -1,5 +1,18 @@
+use crate::cgroup::CgroupLimits;
+use crate::constants::{
+    MAX_RESTART_ATTEMPTS, MAX_RESTART_DELAY, MAX_TASK_EVENTS, RESTART_BACKOFF_MULTIPLIER,
+    RESTART_DELAY, RESTART_STABLE_AFTER,
+};
+use crate::error::HyperVError;
+use crate::health::{HealthCheck, HealthState};
+use crate::limits::ResourceLimits;
+use crate::logs::{LogSettings, is_sensitive_name, redact_sensitive_values};
+use crate::secrets::is_secret_ref;
+use crate::watchdog::Watchdog;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 
 /// Task status enumeration
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -7,6 +20,8 @@ pub enum TaskStatus {
     Stopped,
     Running,
     Failed,
+    /// The process exited on its own with a success exit code
+    Exited,
 }
 
 impl TaskStatus {
@@ -16,6 +31,7 @@ impl TaskStatus {
             TaskStatus::Stopped => "🔴 Stopped",
             TaskStatus::Running => "🟢 Running",
             TaskStatus::Failed => "🟡 Failed",
+            TaskStatus::Exited => "⚪ Exited",
         }
     }
 }
@@ -26,6 +42,179 @@ impl std::fmt::Display for TaskStatus {
     }
 }
 
+/// When the daemon should restart a task whose process has exited
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Never restart automatically
+    #[default]
+    Never,
+    /// Restart only when the process exits with a non-success code or is killed
+    OnFailure,
+    /// Restart after every exit; a manually stopped task comes back when the daemon restarts
+    Always,
+    /// Restart after every exit unless the task was stopped manually
+    UnlessStopped,
+}
+
+impl RestartPolicy {
+    /// Whether a run that ended with the given outcome should be restarted
+    pub fn should_restart(&self, exit_succeeded: bool) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !exit_succeeded,
+            RestartPolicy::Always | RestartPolicy::UnlessStopped => true,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RestartPolicy::Never => "never",
+            RestartPolicy::OnFailure => "on-failure",
+            RestartPolicy::Always => "always",
+            RestartPolicy::UnlessStopped => "unless-stopped",
+        }
+    }
+}
+
+impl std::fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for RestartPolicy {
+    type Err = HyperVError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "never" | "no" => Ok(RestartPolicy::Never),
+            "on-failure" => Ok(RestartPolicy::OnFailure),
+            "always" => Ok(RestartPolicy::Always),
+            "unless-stopped" => Ok(RestartPolicy::UnlessStopped),
+            _ => Err(HyperVError::InvalidInput(format!(
+                "Unknown restart policy '{}' (expected always, on-failure, unless-stopped or never)",
+                s
+            ))),
+        }
+    }
+}
+
+/// How quickly the daemon retries a task that keeps exiting
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RestartBackoff {
+    /// Delay before the first restart attempt
+    pub initial_delay_secs: u64,
+    /// Factor applied to the delay after each attempt
+    pub multiplier: f64,
+    /// Upper bound for the delay between attempts
+    pub max_delay_secs: u64,
+    /// Give up after this many consecutive restarts
+    pub max_attempts: u32,
+    /// A run lasting this long resets the restart count
+    pub stable_after_secs: u64,
+}
+
+impl Default for RestartBackoff {
+    fn default() -> Self {
+        Self {
+            initial_delay_secs: RESTART_DELAY.as_secs(),
+            multiplier: RESTART_BACKOFF_MULTIPLIER,
+            max_delay_secs: MAX_RESTART_DELAY.as_secs(),
+            max_attempts: MAX_RESTART_ATTEMPTS,
+            stable_after_secs: RESTART_STABLE_AFTER.as_secs(),
+        }
+    }
+}
+
+/// Longest restart delay a task may be configured with: one year
+const MAX_BACKOFF_DELAY_SECS: u64 = 365 * 24 * 60 * 60;
+
+impl RestartBackoff {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        for (key, secs) in [
+            ("initial_delay_secs", self.initial_delay_secs),
+            ("max_delay_secs", self.max_delay_secs),
+        ] {
+            if secs > MAX_BACKOFF_DELAY_SECS {
+                return Err(format!(
+                    "{} must be at most {} (one year), got {}",
+                    key, MAX_BACKOFF_DELAY_SECS, secs
+                ));
+            }
+        }
+        if !self.multiplier.is_finite() {
+            return Err(format!(
+                "multiplier must be a finite number, got {}",
+                self.multiplier
+            ));
+        }
+        Ok(())
+    }
+
+    /// Delay to wait before restart number `attempt + 1`
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(attempt.min(64) as i32);
+        let secs = (self.initial_delay_secs as f64 * factor).min(self.max_delay_secs as f64);
+        // A cap near `u64::MAX` seconds does not fit a `Duration` once it is a float
+        Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX)
+    }
+
+    pub fn stable_after(&self) -> Duration {
+        Duration::from_secs(self.stable_after_secs)
+    }
+}
+
+/// Something notable that happened to a task, kept in its event history
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TaskEvent {
+    /// RFC 3339 timestamp
+    pub at: String,
+    pub message: String,
+}
+
+/// Optional per-task settings shared by `hyperV new` and compose services
+#[derive(Debug, Clone, Default)]
+pub struct TaskOptions {
+    pub restart_policy: RestartPolicy,
+    /// Exit codes besides 0 that count as a successful exit
+    pub success_exit_codes: Vec<i32>,
+    pub restart_backoff: RestartBackoff,
+    pub health_check: Option<HealthCheck>,
+    pub watchdog: Option<Watchdog>,
+    /// `.env` files read when the task starts
+    pub env_files: Vec<String>,
+    /// Variables masked in output besides those whose names look secret
+    pub secret_env: Vec<String>,
+    /// User, group and supplementary groups the process runs as
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub supplementary_groups: Vec<String>,
+    pub limits: ResourceLimits,
+    pub cgroup_limits: CgroupLimits,
+    pub log_settings: LogSettings,
+}
+
+impl TaskOptions {
+    /// Apply these settings to an existing task
+    pub fn apply_to(&self, task: &mut Task) {
+        task.env_files = self.env_files.clone();
+        task.secret_env = self.secret_env.clone();
+        task.user = self.user.clone();
+        task.group = self.group.clone();
+        task.supplementary_groups = self.supplementary_groups.clone();
+        task.limits = self.limits.clone();
+        task.cgroup_limits = self.cgroup_limits.clone();
+        task.set_restart_policy(self.restart_policy);
+        task.success_exit_codes = self.success_exit_codes.clone();
+        task.restart_backoff = self.restart_backoff.clone();
+        task.health_check = self.health_check.clone();
+        task.watchdog = self.watchdog.clone();
+        task.log_settings = self.log_settings.clone();
+    }
+}
+
 /// Task configuration and state
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Task {
@@ -34,8 +223,49 @@ pub struct Task {
     pub binary: String,
     pub args: Vec<String>,
     pub env: HashMap<String, String>,
+    /// `.env` files read on every start, in order; values in `env` take precedence
+    #[serde(default)]
+    pub env_files: Vec<String>,
+    /// Variables whose values `status` and `diagnose` mask even though their names
+    /// do not look secret
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secret_env: Vec<String>,
+    /// User (name or UID) the process runs as; unset runs as the invoking user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Primary group (name or GID); defaults to the user's primary group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Supplementary groups (names or GIDs) of the process
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub supplementary_groups: Vec<String>,
+    /// Resource limits applied with `setrlimit` before exec
+    #[serde(default, skip_serializing_if = "ResourceLimits::is_empty")]
+    pub limits: ResourceLimits,
+    /// `memory.max`, `cpu.max` and `pids.max` of the task's cgroup
+    #[serde(default, skip_serializing_if = "CgroupLimits::is_empty")]
+    pub cgroup_limits: CgroupLimits,
     pub workdir: Option<String>,
+    /// Legacy on/off switch kept in sync with `restart_policy` for older task files
     pub auto_restart: bool,
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+    /// Exit codes besides 0 that count as a successful exit
+    #[serde(default)]
+    pub success_exit_codes: Vec<i32>,
+    #[serde(default)]
+    pub restart_backoff: RestartBackoff,
+    #[serde(default)]
+    pub health_check: Option<HealthCheck>,
+    /// Memory and CPU thresholds checked by the daemon
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watchdog: Option<Watchdog>,
+    /// Log rotation and retention; unset fields use the defaults in `daemon.yaml`
+    #[serde(default, skip_serializing_if = "LogSettings::is_empty")]
+    pub log_settings: LogSettings,
+    /// Health of the current run; only present while running with a health check
+    #[serde(default)]
+    pub health: Option<HealthState>,
     pub status: TaskStatus,
     pub created_at: String,
     pub pid: Option<u32>,
@@ -43,13 +273,37 @@ pub struct Task {
     /// sysinfo's start_time() for the process at the moment we spawned it.
     #[serde(default)]
     pub pid_start_time: Option<u64>,
+    /// cgroup v2 directory of the current run; unset when it is tracked by process group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<String>,
     pub stdout_log_path: Option<String>,
     pub stderr_log_path: Option<String>,
     pub last_started: Option<String>,
     pub restart_count: u32,
     pub last_exit_code: Option<i32>,
+    /// Signal that terminated the last run, when it was killed by one
+    #[serde(default)]
+    pub last_exit_signal: Option<i32>,
+    /// When the last run was observed to exit
+    #[serde(default)]
+    pub last_exit_at: Option<String>,
+    /// CPU time the last run used, in seconds, when the daemon reaped it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_exit_cpu_secs: Option<f64>,
     #[serde(default)]
     pub suppress_restart: bool,
+    /// Compose file that created this task, if any
+    #[serde(default)]
+    pub compose_file: Option<String>,
+    /// Compose project that owns this task, if any
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Service key in the compose file (the task name is namespaced by project)
+    #[serde(default)]
+    pub service: Option<String>,
+    /// Recent events, oldest first; at most `MAX_TASK_EVENTS` are kept
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<TaskEvent>,
 }
 
 impl Task {
@@ -72,26 +326,155 @@ impl Task {
             binary,
             args,
             env,
+            env_files: Vec::new(),
+            secret_env: Vec::new(),
+            user: None,
+            group: None,
+            supplementary_groups: Vec::new(),
+            limits: ResourceLimits::default(),
+            cgroup_limits: CgroupLimits::default(),
             workdir,
             auto_restart,
+            restart_policy: if auto_restart {
+                RestartPolicy::OnFailure
+            } else {
+                RestartPolicy::Never
+            },
+            success_exit_codes: Vec::new(),
+            restart_backoff: RestartBackoff::default(),
+            health_check: None,
+            watchdog: None,
+            log_settings: LogSettings::default(),
+            health: None,
             status: TaskStatus::Stopped,
             created_at: chrono::Utc::now().to_rfc3339(),
             pid: None,
             pid_start_time: None,
+            cgroup: None,
             stdout_log_path,
             stderr_log_path,
             last_started: None,
             restart_count: 0,
             last_exit_code: None,
+            last_exit_signal: None,
+            last_exit_at: None,
+            last_exit_cpu_secs: None,
             suppress_restart: false,
+            compose_file: None,
+            project: None,
+            service: None,
+            events: Vec::new(),
         }
     }
 
-    /// Set task status
+    /// Whether this task was created by the given compose project
+    pub fn belongs_to_project(&self, project: &str) -> bool {
+        self.project.as_deref() == Some(project)
+    }
+
+    /// Whether this task is addressed by `identifier` (name, ID, or partial ID)
+    pub fn matches(&self, identifier: &str) -> bool {
+        self.name == identifier || self.id == identifier || self.id.starts_with(identifier)
+    }
+
+    /// Map task files written before restart policies existed: `auto_restart: true`
+    /// without a policy means "restart on failure".
+    pub fn migrate_legacy_restart(&mut self) {
+        if self.auto_restart && self.restart_policy == RestartPolicy::Never {
+            self.restart_policy = RestartPolicy::OnFailure;
+        }
+    }
+
+    /// Task files written before `env_files` existed read `.env` from the working
+    /// directory implicitly; record that file explicitly instead.
+    pub fn migrate_legacy_env_file(&mut self) {
+        if self.env_files.is_empty()
+            && let Some(path) = crate::dotenv::workdir_env_file(self.workdir.as_deref())
+        {
+            self.env_files.push(path);
+        }
+    }
+
+    /// Set the restart policy, keeping the legacy `auto_restart` flag consistent
+    pub fn set_restart_policy(&mut self, policy: RestartPolicy) {
+        self.restart_policy = policy;
+        self.auto_restart = policy != RestartPolicy::Never;
+    }
+
+    /// Whether the last run ended with a success exit code
+    pub fn exit_succeeded(&self) -> bool {
+        self.last_exit_signal.is_none()
+            && self
+                .last_exit_code
+                .is_some_and(|code| code == 0 || self.success_exit_codes.contains(&code))
+    }
+
+    /// Whether the restart policy asks for the last run to be restarted
+    pub fn wants_restart(&self) -> bool {
+        matches!(self.status, TaskStatus::Failed | TaskStatus::Exited)
+            && !self.suppress_restart
+            && self.restart_policy.should_restart(self.exit_succeeded())
+    }
+
+    /// When the pending policy restart becomes due. Exits recorded before this field
+    /// existed are due immediately.
+    pub fn next_restart_at(&self) -> Option<DateTime<Utc>> {
+        if !self.wants_restart() || self.restart_count >= self.restart_backoff.max_attempts {
+            return None;
+        }
+        let exited_at = self
+            .last_exit_at
+            .as_deref()
+            .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+            .map(|ts| ts.with_timezone(&Utc));
+        let delay = self.restart_backoff.delay_for(self.restart_count);
+        Some(match exited_at {
+            Some(exited_at) => chrono::Duration::from_std(delay)
+                .ok()
+                .and_then(|delay| exited_at.checked_add_signed(delay))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            None => DateTime::<Utc>::MIN_UTC,
+        })
+    }
+
+    /// Forget earlier restarts once the current run has lasted the stability window.
+    /// Returns whether the count was reset.
+    pub fn reset_restarts_if_stable(&mut self, now: DateTime<Utc>) -> bool {
+        if self.restart_count == 0 {
+            return false;
+        }
+        let Some(started) = self
+            .last_started
+            .as_deref()
+            .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+        else {
+            return false;
+        };
+        let ran_for = now.signed_duration_since(started.with_timezone(&Utc));
+        if ran_for
+            .to_std()
+            .is_ok_and(|ran| ran >= self.restart_backoff.stable_after())
+        {
+            self.restart_count = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Set task status. Health results belong to a run, so they are dropped when the
+    /// task stops running.
     pub fn set_status(&mut self, status: TaskStatus) {
+        if status != TaskStatus::Running {
+            self.health = None;
+        }
         self.status = status;
     }
 
+    /// Start tracking health for a new run
+    pub fn reset_health(&mut self) {
+        self.health = self.health_check.as_ref().map(|_| HealthState::starting());
+    }
+
     /// Set task PID
     pub fn set_pid(&mut self, pid: Option<u32>) {
         self.pid = pid;
@@ -128,42 +511,226 @@ impl Task {
         self.last_exit_code = exit_code;
     }
 
-    /// Print detailed task information
+    /// Record how and when the last run ended, and the CPU time it used if known
+    pub fn record_exit(
+        &mut self,
+        exit_code: Option<i32>,
+        signal: Option<i32>,
+        cpu_time: Option<std::time::Duration>,
+    ) {
+        let now = Utc::now();
+        // A run that outlived the stability window starts a fresh series of restarts
+        self.reset_restarts_if_stable(now);
+        self.last_exit_code = exit_code;
+        self.last_exit_signal = signal;
+        self.last_exit_cpu_secs = cpu_time.map(|d| d.as_secs_f64());
+        self.last_exit_at = Some(now.to_rfc3339());
+    }
+
+    /// Add `message` to the event history, dropping the oldest events over the limit
+    pub fn record_event(&mut self, message: impl Into<String>) {
+        self.events.push(TaskEvent {
+            at: Utc::now().to_rfc3339(),
+            message: message.into(),
+        });
+        let excess = self.events.len().saturating_sub(MAX_TASK_EVENTS);
+        self.events.drain(..excess);
+    }
+
+    /// Whether the value of environment variable `key` is a secret: it is listed in
+    /// `secret_env` or its name contains a word like `PASSWORD`, `TOKEN` or `KEY`
+    pub fn is_secret_env(&self, key: &str) -> bool {
+        self.secret_env.iter().any(|name| name == key) || is_sensitive_name(key)
+    }
+
+    /// The configured user, group and supplementary groups, e.g.
+    /// `user www-data, group web, supplementary groups ssl-cert`
+    pub fn run_as_display(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(user) = &self.user {
+            parts.push(format!("user {}", user));
+        }
+        if let Some(group) = &self.group {
+            parts.push(format!("group {}", group));
+        }
+        if !self.supplementary_groups.is_empty() {
+            parts.push(format!(
+                "supplementary groups {}",
+                self.supplementary_groups.join(", ")
+            ));
+        }
+        parts.join(", ")
+    }
+
+    /// The signal that ended the last run, with the resource limit that explains it,
+    /// e.g. `24 (CPU time limit of 60s reached)`
+    pub fn exit_signal_display(&self) -> Option<String> {
+        let signal = self.last_exit_signal?;
+        let cpu_used = self
+            .last_exit_cpu_secs
+            .and_then(|secs| std::time::Duration::try_from_secs_f64(secs).ok());
+        Some(match self.limits.violation(signal, cpu_used) {
+            Some(reason) => format!("{} ({})", signal, reason),
+            None => signal.to_string(),
+        })
+    }
+
+    /// Print detailed task information with secrets masked
     pub fn print_details(&self) {
+        self.print_details_with(false);
+    }
+
+    /// Print detailed task information; unless `show_secrets` is set, secret
+    /// environment values and `key=value` secrets in arguments are masked
+    pub fn print_details_with(&self, show_secrets: bool) {
+        let redact = |text: &str| {
+            if show_secrets {
+                text.to_string()
+            } else {
+                redact_sensitive_values(text)
+            }
+        };
+        let args: Vec<String> = self.args.iter().map(|arg| redact(arg)).collect();
+
         println!("Task: {}", self.name);
         println!("ID: {}", self.id);
         println!("Binary: {}", self.binary);
-        println!("Args: {:?}", self.args);
+        println!("Args: {:?}", args);
         println!("Status: {}", self.status);
 
         if let Some(pid) = self.pid {
             println!("PID: {}", pid);
         }
 
+        if let Some(check) = &self.health_check {
+            println!(
+                "Health check: {} every {}s (timeout {}s, retries {}, start period {}s)",
+                redact(&check.probe.to_string()),
+                check.interval_secs,
+                check.timeout_secs,
+                check.retries,
+                check.start_period_secs
+            );
+        }
+
+        if let Some(health) = &self.health {
+            println!(
+                "Health: {} (consecutive failures: {})",
+                health.status, health.consecutive_failures
+            );
+            if let Some(error) = &health.last_error {
+                println!("Last health check error: {}", redact(error));
+            }
+        }
+
+        if let Some(watchdog) = &self.watchdog {
+            println!("Watchdog: {}", watchdog);
+        }
+
         if let Some(exit_code) = self.last_exit_code {
             println!("Last exit code: {}", exit_code);
         }
 
+        if let Some(signal) = self.exit_signal_display() {
+            println!("Last exit signal: {}", signal);
+        }
+
+        if let Some(last_exit_at) = &self.last_exit_at {
+            println!("Last exited: {}", last_exit_at);
+        }
+
         println!(
-            "Auto-restart: {} (restarts: {})",
-            self.auto_restart, self.restart_count
+            "Restart policy: {} (restarts: {})",
+            self.restart_policy, self.restart_count
         );
 
+        if !self.success_exit_codes.is_empty() {
+            println!("Success exit codes: 0, {:?}", self.success_exit_codes);
+        }
+
+        if self.restart_policy != RestartPolicy::Never {
+            let backoff = &self.restart_backoff;
+            println!(
+                "Restart backoff: {}s x{} up to {}s, max {} attempts, stable after {}s",
+                backoff.initial_delay_secs,
+                backoff.multiplier,
+                backoff.max_delay_secs,
+                backoff.max_attempts,
+                backoff.stable_after_secs
+            );
+        }
+
+        if let Some(next_restart) = self.next_restart_at()
+            && self.last_exit_at.is_some()
+        {
+            println!("Next restart: {}", next_restart.to_rfc3339());
+        }
+
         if let Some(workdir) = &self.workdir {
             println!("Working directory: {}", workdir);
         }
 
+        if self.user.is_some() || self.group.is_some() || !self.supplementary_groups.is_empty() {
+            println!("Run as: {}", self.run_as_display());
+        }
+
+        if !self.limits.is_empty() {
+            println!("Resource limits: {}", self.limits);
+        }
+
+        if !self.cgroup_limits.is_empty() {
+            println!("cgroup limits: {}", self.cgroup_limits);
+        }
+
+        if let Some(cgroup) = &self.cgroup {
+            println!("cgroup: {}", cgroup);
+        }
+
         if !self.env.is_empty() {
             println!("Environment variables:");
-            for (key, value) in &self.env {
-                println!("  {}={}", key, value);
+            let mut env: Vec<_> = self.env.iter().collect();
+            env.sort();
+            for (key, value) in env {
+                // References such as `secret:db_password` are safe to show
+                if show_secrets || !self.is_secret_env(key) || is_secret_ref(value) {
+                    println!("  {}={}", key, value);
+                } else {
+                    println!("  {}=[REDACTED]", key);
+                }
             }
         }
 
+        if !self.env_files.is_empty() {
+            println!("Env files: {}", self.env_files.join(", "));
+        }
+
+        if !self.log_settings.is_empty() {
+            println!("Logs: {}", self.log_settings);
+        }
+
         println!("Created: {}", self.created_at);
 
+        if let Some(project) = &self.project {
+            println!(
+                "Compose project: {} (service: {})",
+                project,
+                self.service.as_deref().unwrap_or("-")
+            );
+        }
+
+        if let Some(compose_file) = &self.compose_file {
+            println!("Compose file: {}", compose_file);
+        }
+
         if let Some(last_started) = &self.last_started {
             println!("Last started: {}", last_started);
         }
+
+        if !self.events.is_empty() {
+            println!("Recent events:");
+            for event in &self.events {
+                println!("  {} {}", event.at, event.message);
+            }
+        }
     }
 }
@@ -0,0 +1,187 @@
+//! Resource usage of task process trees
+//!
+//! A task is often a wrapper (a shell script, a launcher) around the process that
+//! does the work, so usage is added up over the whole process tree: every member of
+//! the task's cgroup, or else the task's process and all of its descendants. `list`,
+//! `status`, `top` and the watchdog all measure it this way.
+
+use crate::cgroup::Cgroup;
+use crate::task::{Task, TaskStatus};
+use std::collections::HashMap;
+use std::time::Duration;
+use sysinfo::{Pid, System};
+
+/// Usage of one task's process tree
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TaskUsage {
+    pub processes: usize,
+    /// Resident memory in bytes
+    pub memory_bytes: u64,
+    /// CPU usage since the previous refresh, in percent of one core
+    pub cpu_percent: f64,
+    pub threads: usize,
+    /// Open file descriptors; `None` where they cannot be counted
+    pub open_fds: Option<usize>,
+    /// How long the oldest process of the tree has been running
+    pub uptime: Duration,
+}
+
+impl TaskUsage {
+    /// Usage of the processes `pids` in `system`; PIDs that are gone are skipped
+    pub fn of_pids(system: &System, pids: &[u32]) -> Self {
+        let mut usage = TaskUsage::default();
+        // Unknown as soon as one process's descriptors cannot be read
+        let mut fds = Some(0);
+        for process in pids
+            .iter()
+            .filter_map(|pid| system.process(Pid::from_u32(*pid)))
+        {
+            usage.processes += 1;
+            usage.memory_bytes += process.memory();
+            usage.cpu_percent += process.cpu_usage() as f64;
+            // The main thread is not listed among the tasks of a process
+            usage.threads += 1 + process.tasks().map_or(0, |tasks| tasks.len());
+            usage.uptime = usage.uptime.max(Duration::from_secs(process.run_time()));
+            fds = fds
+                .zip(open_fds(process.pid().as_u32()))
+                .map(|(total, count)| total + count);
+        }
+        usage.open_fds = fds;
+        usage
+    }
+
+    pub fn memory_mb(&self) -> u64 {
+        self.memory_bytes / (1024 * 1024)
+    }
+}
+
+impl std::fmt::Display for TaskUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}, {} {}, memory {:.1} MB, CPU {:.1}%",
+            self.processes,
+            if self.processes == 1 {
+                "process"
+            } else {
+                "processes"
+            },
+            self.threads,
+            if self.threads == 1 {
+                "thread"
+            } else {
+                "threads"
+            },
+            self.memory_bytes as f64 / (1024.0 * 1024.0),
+            self.cpu_percent
+        )?;
+        if let Some(fds) = self.open_fds {
+            write!(f, ", {} open files", fds)?;
+        }
+        write!(f, ", up {}", format_uptime(self.uptime))
+    }
+}
+
+/// Process table shared by the views that show task usage
+#[derive(Default)]
+pub struct UsageSampler {
+    system: System,
+}
+
+impl UsageSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the process table again; CPU usage is measured since the previous refresh
+    pub fn refresh(&mut self) {
+        self.system.refresh_processes();
+    }
+
+    /// Refresh twice, a short moment apart, so a one-off view has CPU usage to show
+    pub fn refresh_with_cpu(&mut self) {
+        self.refresh();
+        std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
+        self.refresh();
+    }
+
+    /// Usage of a running task's process tree; `None` when none of its processes is left
+    pub fn measure(&self, task: &Task) -> Option<TaskUsage> {
+        if task.status != TaskStatus::Running {
+            return None;
+        }
+        let usage = TaskUsage::of_pids(&self.system, &task_tree_pids(&self.system, task));
+        (usage.processes > 0).then_some(usage)
+    }
+}
+
+/// Processes of a running task: every member of its cgroup, or else its PID and all
+/// of its descendants
+pub fn task_tree_pids(system: &System, task: &Task) -> Vec<u32> {
+    let members = task
+        .cgroup
+        .as_ref()
+        .map(|path| Cgroup::at(path).members())
+        .unwrap_or_default();
+    if !members.is_empty() {
+        return members;
+    }
+    task.pid
+        .map(|pid| process_tree_pids(system, pid))
+        .unwrap_or_default()
+}
+
+/// `root` and every process below it, from the parent links in `system`
+pub fn process_tree_pids(system: &System, root: u32) -> Vec<u32> {
+    let mut children: HashMap<Pid, Vec<Pid>> = HashMap::new();
+    // Threads are listed as children of their process on Linux; they are not processes
+    for (pid, process) in system.processes() {
+        if process.thread_kind().is_none()
+            && let Some(parent) = process.parent()
+        {
+            children.entry(parent).or_default().push(*pid);
+        }
+    }
+    let mut pids = Vec::new();
+    let mut pending = vec![Pid::from_u32(root)];
+    while let Some(pid) = pending.pop() {
+        pids.push(pid.as_u32());
+        if let Some(below) = children.get(&pid) {
+            pending.extend(below.iter().copied().filter(|p| p.as_u32() != root));
+        }
+    }
+    pids
+}
+
+/// Open file descriptors of `pid`, from `/proc/<pid>/fd`
+#[cfg(target_os = "linux")]
+fn open_fds(pid: u32) -> Option<usize> {
+    std::fs::read_dir(format!("/proc/{}/fd", pid))
+        .ok()
+        .map(|entries| entries.count())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_fds(_pid: u32) -> Option<usize> {
+    None
+}
+
+/// A duration the way `top` shows it, e.g. `3d 4h`, `2h 5m`, `7m 12s` or `42s`
+pub fn format_uptime(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (
+        secs / 86_400,
+        secs % 86_400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
@@ -0,0 +1,329 @@
+//! Memory and CPU watchdog for running tasks
+//!
+//! The daemon samples every task that has a watchdog on each pass of its main loop:
+//! the resident memory of the whole process tree, and its CPU usage averaged over a
+//! window. A task that goes over a threshold gets the configured action (a warning,
+//! an alert, or a restart) once; the breach is reported again only after usage has
+//! dropped back below the threshold. Samples are process-local like the health check
+//! schedule, so restarting the daemon starts fresh windows.
+
+use crate::task::{Task, TaskStatus};
+use crate::usage::UsageSampler;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+const DEFAULT_CPU_WINDOW_SECS: u64 = 60;
+
+fn default_cpu_window_secs() -> u64 {
+    DEFAULT_CPU_WINDOW_SECS
+}
+
+/// What the daemon does when a task goes over a watchdog threshold
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchdogAction {
+    /// Log a warning
+    #[default]
+    Warn,
+    /// Log a warning and send an alert
+    Alert,
+    /// Log a warning and restart the task
+    Restart,
+}
+
+impl WatchdogAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WatchdogAction::Warn => "warn",
+            WatchdogAction::Alert => "alert",
+            WatchdogAction::Restart => "restart",
+        }
+    }
+}
+
+impl std::fmt::Display for WatchdogAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for WatchdogAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "warn" => Ok(WatchdogAction::Warn),
+            "alert" => Ok(WatchdogAction::Alert),
+            "restart" => Ok(WatchdogAction::Restart),
+            _ => Err(format!(
+                "unknown watchdog action '{}' (expected warn, alert or restart)",
+                s
+            )),
+        }
+    }
+}
+
+/// Watchdog thresholds of a task
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Watchdog {
+    /// Resident memory of the whole process tree in MB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory_mb: Option<u64>,
+    /// CPU usage of the whole process tree in percent of one core, e.g. 150 for
+    /// one and a half cores
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu_percent: Option<f64>,
+    /// Seconds the CPU usage is averaged over
+    #[serde(default = "default_cpu_window_secs")]
+    pub cpu_window_secs: u64,
+    #[serde(default)]
+    pub action: WatchdogAction,
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self {
+            max_memory_mb: None,
+            max_cpu_percent: None,
+            cpu_window_secs: DEFAULT_CPU_WINDOW_SECS,
+            action: WatchdogAction::default(),
+        }
+    }
+}
+
+impl Watchdog {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.max_memory_mb.is_none() && self.max_cpu_percent.is_none() {
+            return Err("watchdog needs max_memory_mb or max_cpu_percent".to_string());
+        }
+        if self.max_memory_mb == Some(0) {
+            return Err("max_memory_mb must be greater than 0".to_string());
+        }
+        if let Some(percent) = self.max_cpu_percent
+            && !(percent.is_finite() && percent > 0.0)
+        {
+            return Err(format!(
+                "max_cpu_percent must be greater than 0, got {}",
+                percent
+            ));
+        }
+        if self.cpu_window_secs == 0 {
+            return Err("cpu_window_secs must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn cpu_window(&self) -> Duration {
+        Duration::from_secs(self.cpu_window_secs)
+    }
+}
+
+impl std::fmt::Display for Watchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(mb) = self.max_memory_mb {
+            parts.push(format!("memory {} MB", mb));
+        }
+        if let Some(percent) = self.max_cpu_percent {
+            parts.push(format!("CPU {}% over {}s", percent, self.cpu_window_secs));
+        }
+        write!(f, "{} (action: {})", parts.join(", "), self.action)
+    }
+}
+
+/// Usage of a task's process tree at one point in time
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UsageSample {
+    pub memory_mb: u64,
+    pub cpu_percent: f64,
+}
+
+/// Which threshold a task went over
+#[derive(Clone, Debug, PartialEq)]
+pub enum BreachKind {
+    Memory {
+        used_mb: u64,
+        limit_mb: u64,
+    },
+    Cpu {
+        percent: f64,
+        limit: f64,
+        window_secs: u64,
+    },
+}
+
+impl std::fmt::Display for BreachKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BreachKind::Memory { used_mb, limit_mb } => {
+                write!(
+                    f,
+                    "memory {} MB is above the limit of {} MB",
+                    used_mb, limit_mb
+                )
+            }
+            BreachKind::Cpu {
+                percent,
+                limit,
+                window_secs,
+            } => write!(
+                f,
+                "CPU {:.1}% over {}s is above the limit of {}%",
+                percent, window_secs, limit
+            ),
+        }
+    }
+}
+
+/// A task that went over one of its thresholds
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchdogBreach {
+    pub task_id: String,
+    pub task_name: String,
+    /// PID of the run that went over the threshold
+    pub pid: u32,
+    pub kind: BreachKind,
+    pub action: WatchdogAction,
+}
+
+/// Samples and breach state of one run of a task
+#[derive(Debug)]
+struct TaskSamples {
+    pid: u32,
+    since: Instant,
+    cpu: VecDeque<(Instant, f64)>,
+    memory_breached: bool,
+    cpu_breached: bool,
+}
+
+impl TaskSamples {
+    fn new(pid: u32, now: Instant) -> Self {
+        Self {
+            pid,
+            since: now,
+            cpu: VecDeque::new(),
+            memory_breached: false,
+            cpu_breached: false,
+        }
+    }
+}
+
+/// Daemon-side watchdog state
+#[derive(Default)]
+pub struct WatchdogMonitor {
+    sampler: UsageSampler,
+    samples: HashMap<String, TaskSamples>,
+}
+
+impl WatchdogMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sample every running task that has a watchdog and return the new breaches
+    pub fn check(&mut self, tasks: &[Task], now: Instant) -> Vec<WatchdogBreach> {
+        let watched: Vec<(&Task, &Watchdog, u32)> = tasks
+            .iter()
+            .filter(|task| task.status == TaskStatus::Running)
+            .filter_map(|task| Some((task, task.watchdog.as_ref()?, task.pid?)))
+            .collect();
+        if !watched.is_empty() {
+            self.sampler.refresh();
+        }
+
+        let mut breaches = Vec::new();
+        for (task, watchdog, pid) in &watched {
+            let Some(usage) = self.sampler.measure(task) else {
+                continue;
+            };
+            let sample = UsageSample {
+                memory_mb: usage.memory_mb(),
+                cpu_percent: usage.cpu_percent,
+            };
+            breaches.extend(
+                self.record(&task.id, *pid, watchdog, sample, now)
+                    .into_iter()
+                    .map(|kind| WatchdogBreach {
+                        task_id: task.id.clone(),
+                        task_name: task.name.clone(),
+                        pid: *pid,
+                        kind,
+                        action: watchdog.action,
+                    }),
+            );
+        }
+
+        let active: HashSet<&String> = watched.iter().map(|(task, _, _)| &task.id).collect();
+        self.samples.retain(|task_id, _| active.contains(task_id));
+        breaches
+    }
+
+    /// Record one sample of run `pid` of a task and return the thresholds it has just
+    /// gone over. A new PID starts a fresh window.
+    pub fn record(
+        &mut self,
+        task_id: &str,
+        pid: u32,
+        watchdog: &Watchdog,
+        sample: UsageSample,
+        now: Instant,
+    ) -> Vec<BreachKind> {
+        let fresh = self
+            .samples
+            .get(task_id)
+            .is_none_or(|state| state.pid != pid);
+        if fresh {
+            self.samples
+                .insert(task_id.to_string(), TaskSamples::new(pid, now));
+        }
+        let state = self.samples.get_mut(task_id).expect("just inserted");
+
+        let mut breaches = Vec::new();
+        if let Some(limit_mb) = watchdog.max_memory_mb {
+            let over = sample.memory_mb > limit_mb;
+            if over && !state.memory_breached {
+                breaches.push(BreachKind::Memory {
+                    used_mb: sample.memory_mb,
+                    limit_mb,
+                });
+            }
+            state.memory_breached = over;
+        }
+
+        if let Some(limit) = watchdog.max_cpu_percent {
+            let window = watchdog.cpu_window();
+            // CPU usage is measured since the previous sample, so the first sample of
+            // a run only starts its window
+            if !fresh {
+                state.cpu.push_back((now, sample.cpu_percent));
+            }
+            while state
+                .cpu
+                .front()
+                .is_some_and(|(at, _)| now.duration_since(*at) > window)
+            {
+                state.cpu.pop_front();
+            }
+            // Only a run that has been sampled for a whole window can be over the limit
+            let average = (now.duration_since(state.since) >= window && !state.cpu.is_empty())
+                .then(|| {
+                    state.cpu.iter().map(|(_, percent)| percent).sum::<f64>()
+                        / state.cpu.len() as f64
+                });
+            let over = average.is_some_and(|average| average > limit);
+            if over && !state.cpu_breached {
+                breaches.push(BreachKind::Cpu {
+                    percent: average.unwrap_or_default(),
+                    limit,
+                    window_secs: watchdog.cpu_window_secs,
+                });
+            }
+            // Stay quiet until the average is known to be back under the limit
+            if average.is_some() {
+                state.cpu_breached = over;
+            }
+        }
+        breaches
+    }
+}
@@ -1,6 +1,7 @@
 use chrono::{Duration, TimeZone, Utc};
-use hyperV::alerts::{AlertReason, AlertTracker};
+use hyperV::alerts::{AlertReason, AlertTracker, format_alert_message};
 use hyperV::constants::MAX_RESTART_ATTEMPTS;
+use hyperV::watchdog::{BreachKind, WatchdogAction, WatchdogBreach};
 use hyperV::{Task, TaskStatus};
 use std::collections::HashMap;
 
@@ -128,3 +129,70 @@ fn alerts_when_restart_attempts_are_exhausted() {
             .is_some()
     );
 }
+
+#[test]
+fn watchdog_breach_alerts_once_per_cooldown() {
+    let mut tracker = AlertTracker::new();
+    let mut task = task_with_state("task-4", "worker", 0, None);
+    task.set_status(TaskStatus::Running);
+    let breach = WatchdogBreach {
+        task_id: task.id.clone(),
+        task_name: task.name.clone(),
+        pid: 42,
+        kind: BreachKind::Memory {
+            used_mb: 612,
+            limit_mb: 512,
+        },
+        action: WatchdogAction::Alert,
+    };
+    let now = Utc.with_ymd_and_hms(2026, 5, 21, 10, 0, 0).unwrap();
+
+    let alert = tracker
+        .record_watchdog(&task, &breach, now)
+        .expect("first breach should alert");
+    assert_eq!(
+        alert.reason,
+        AlertReason::Watchdog {
+            breach: "memory 612 MB is above the limit of 512 MB".to_string(),
+        }
+    );
+    assert!(
+        format_alert_message(&alert)
+            .contains("Reason: watchdog: memory 612 MB is above the limit of 512 MB")
+    );
+
+    assert!(
+        tracker
+            .record_watchdog(&task, &breach, now + Duration::minutes(10))
+            .is_none()
+    );
+    assert!(
+        tracker
+            .record_watchdog(&task, &breach, now + Duration::minutes(31))
+            .is_some()
+    );
+}
+
+#[test]
+fn unhealthy_task_alerts_once_per_cooldown_and_counts_as_crash() {
+    let mut tracker = AlertTracker::new();
+    let task = task_with_state("task-3", "web", 1, None);
+    let now = Utc.with_ymd_and_hms(2026, 5, 21, 10, 0, 0).unwrap();
+
+    let alert = tracker
+        .record_unhealthy(&task, now)
+        .expect("first unhealthy report should alert");
+    assert_eq!(alert.reason, AlertReason::Unhealthy { failures: 0 });
+
+    // The second health restart inside the window is a crash loop
+    let alert = tracker
+        .record_unhealthy(&task, now + Duration::minutes(1))
+        .expect("crash loop should alert");
+    assert!(matches!(alert.reason, AlertReason::CrashLoop { .. }));
+
+    assert!(
+        tracker
+            .record_unhealthy(&task, now + Duration::minutes(2))
+            .is_none()
+    );
+}
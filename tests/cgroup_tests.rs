@@ -0,0 +1,152 @@
+use hyperV::cgroup::{Cgroup, CgroupConfig, CgroupLimits};
+use tempfile::TempDir;
+
+#[test]
+fn parses_cgroup_limits() {
+    let limits: CgroupLimits =
+        serde_yml::from_str("memory_max: 512MB\ncpu_max: 0.5\npids_max: 100\n").unwrap();
+    assert_eq!(
+        limits,
+        CgroupLimits {
+            memory_max: Some(512 * 1024 * 1024),
+            cpu_max: Some(0.5),
+            pids_max: Some(100),
+        }
+    );
+    assert_eq!(limits.to_string(), "memory 512.0 MB, CPUs 0.5, pids 100");
+    assert!(limits.validate().is_ok());
+    assert!(CgroupLimits::default().is_empty());
+
+    let too_little_cpu = CgroupLimits {
+        cpu_max: Some(0.001),
+        ..CgroupLimits::default()
+    };
+    assert!(too_little_cpu.validate().unwrap_err().contains("cpu_max"));
+}
+
+#[test]
+fn cgroups_can_be_disabled_or_rooted_elsewhere() {
+    let config: CgroupConfig = serde_yml::from_str("enabled: false\n").unwrap();
+    assert_eq!(config.task_root(), None);
+
+    let config: CgroupConfig = serde_yml::from_str("root: /sys/fs/cgroup/services\n").unwrap();
+    assert!(config.enabled);
+    assert_eq!(
+        config.task_root().as_deref(),
+        Some(std::path::Path::new("/sys/fs/cgroup/services"))
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn default_root_is_below_the_own_cgroup() {
+    let Some(root) = CgroupConfig::default().task_root() else {
+        return;
+    };
+    assert_eq!(root.file_name().unwrap(), "hyperv");
+    let cgroups = std::fs::read_to_string("/proc/self/cgroup").unwrap();
+    let Some(own) = cgroups.lines().find_map(|line| line.strip_prefix("0::")) else {
+        return;
+    };
+    // Root may write to its own cgroup
+    if unsafe { libc::geteuid() } == 0 {
+        assert!(
+            root.parent()
+                .unwrap()
+                .ends_with(own.trim_start_matches('/')),
+            "{} is not below {}",
+            root.display(),
+            own
+        );
+    }
+}
+
+#[test]
+fn applies_limits_to_interface_files() {
+    let temp = TempDir::new().unwrap();
+    let path = temp.path().join("hyperv").join("task");
+    std::fs::create_dir_all(&path).unwrap();
+    for file in ["memory.max", "cpu.max", "pids.max"] {
+        std::fs::write(path.join(file), "stale").unwrap();
+    }
+    let read = |file: &str| std::fs::read_to_string(path.join(file)).unwrap();
+
+    let cgroup = Cgroup::at(&path);
+    cgroup
+        .apply_limits(&CgroupLimits {
+            memory_max: Some(512 * 1024 * 1024),
+            cpu_max: Some(0.5),
+            pids_max: None,
+        })
+        .unwrap();
+    assert_eq!(read("memory.max"), "536870912");
+    assert_eq!(read("cpu.max"), "50000 100000");
+    assert_eq!(read("pids.max"), "max");
+
+    // Removed limits are reset
+    cgroup.apply_limits(&CgroupLimits::default()).unwrap();
+    assert_eq!(read("memory.max"), "max");
+    assert_eq!(read("cpu.max"), "max 100000");
+
+    // A limit whose controller is not available is an error
+    std::fs::remove_file(path.join("pids.max")).unwrap();
+    let err = cgroup
+        .apply_limits(&CgroupLimits {
+            pids_max: Some(10),
+            ..CgroupLimits::default()
+        })
+        .unwrap_err();
+    assert!(err.to_string().contains("pids controller"), "{}", err);
+}
+
+#[test]
+fn refuses_to_create_cgroups_outside_the_hierarchy() {
+    let temp = TempDir::new().unwrap();
+    let root = temp.path().join("hyperv");
+    assert!(Cgroup::create(&root, "task").is_err());
+    assert!(!root.exists());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn tracks_and_kills_every_member() {
+    use std::os::unix::process::CommandExt;
+
+    let Some(root) = CgroupConfig::default().task_root() else {
+        return;
+    };
+    let Ok(cgroup) = Cgroup::create(&root, &format!("cgroup-test-{}", std::process::id())) else {
+        // No writable cgroup v2 hierarchy here
+        return;
+    };
+
+    let mut cmd = std::process::Command::new("/bin/sh");
+    cmd.args(["-c", "sleep 30 & sleep 30"]).process_group(0);
+    cgroup.add_to(&mut cmd).unwrap();
+    let mut child = cmd.spawn().unwrap();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while cgroup.members().len() < 3 {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "{:?}",
+            cgroup.members()
+        );
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    assert!(cgroup.members().contains(&child.id()));
+    assert!(cgroup.is_populated());
+
+    cgroup.kill().unwrap();
+    child.wait().unwrap();
+    while cgroup.is_populated() {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "{:?}",
+            cgroup.members()
+        );
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    cgroup.remove().unwrap();
+    assert!(!cgroup.exists());
+}
@@ -99,6 +99,58 @@ fn test_lifecycle() {
         .stdout(predicate::str::contains("No tasks configured"));
 }
 
+#[cfg(unix)]
+#[test]
+fn test_usage_adds_up_the_children_of_a_wrapper() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+
+    // Like tests/surreal.sh without exec: the real work runs in children of the shell
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "wrapper",
+            "--binary",
+            sh_bin,
+            "--args",
+            "-c",
+            "sleep 30 & sleep 30 & wait",
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["start", "wrapper"])
+        .assert()
+        .success();
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    hyperv_cmd(&temp)
+        .args(&["status", "wrapper"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Resource usage: 3 processes, 3 threads, memory",
+        ));
+    hyperv_cmd(&temp)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("CPU%"))
+        .stdout(predicate::str::contains("THREADS"));
+    hyperv_cmd(&temp)
+        .args(&["top", "--iterations", "2", "--interval", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("PROCS").count(2))
+        .stdout(predicate::str::is_match(r"wrapper\s+\S+ Running\s+3\s").unwrap());
+
+    hyperv_cmd(&temp)
+        .args(&["stop", "wrapper"])
+        .assert()
+        .success();
+}
+
 #[test]
 fn test_persistence() {
     let temp = TempDir::new().unwrap();
@@ -152,6 +204,306 @@ fn test_duplicate_task() {
         .failure(); // Should exit non-zero
 }
 
+#[test]
+fn test_restart_policy_option() {
+    let temp = TempDir::new().unwrap();
+    let ls_bin = bin_path("/bin/ls", "/usr/bin/ls");
+
+    hyperv_cmd(&temp)
+        .args(&["new", "--name", "bad-policy", "--binary", ls_bin])
+        .args(&["--restart", "sometimes"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown restart policy"));
+
+    hyperv_cmd(&temp)
+        .args(&["new", "--name", "policy-task", "--binary", ls_bin])
+        .args(&["--restart", "unless-stopped", "--success-exit-code", "2"])
+        .assert()
+        .success();
+
+    hyperv_cmd(&temp)
+        .args(&["status", "policy-task"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Restart policy: unless-stopped"))
+        .stdout(predicate::str::contains("Success exit codes: 0, [2]"));
+}
+
+#[test]
+fn test_health_check_options() {
+    let temp = TempDir::new().unwrap();
+    let ls_bin = bin_path("/bin/ls", "/usr/bin/ls");
+
+    hyperv_cmd(&temp)
+        .args(&["new", "--name", "both", "--binary", ls_bin])
+        .args(&[
+            "--health-http",
+            "http://127.0.0.1:1/",
+            "--health-tcp",
+            "127.0.0.1:1",
+        ])
+        .assert()
+        .failure();
+
+    hyperv_cmd(&temp)
+        .args(&["new", "--name", "web", "--binary", ls_bin])
+        .args(&["--health-http", "http://127.0.0.1:8080/health"])
+        .args(&["--health-interval", "30", "--health-retries", "4"])
+        .assert()
+        .success();
+
+    hyperv_cmd(&temp)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("HEALTH"));
+
+    hyperv_cmd(&temp)
+        .args(&["status", "web"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Health check: http GET http://127.0.0.1:8080/health (expect 200) every 30s",
+        ))
+        .stdout(predicate::str::contains("retries 4"));
+}
+
+#[test]
+fn test_up_starts_services_after_dependencies() {
+    let temp = TempDir::new().unwrap();
+    let marker = temp.path().join("migrated");
+    let compose = temp.path().join("hyperv.yaml");
+    std::fs::write(
+        &compose,
+        format!(
+            r#"
+name: demo
+services:
+  app:
+    binary: /bin/sh
+    args: ["-c", "test -f {marker} && exec sleep 30"]
+    depends_on:
+      migrate:
+        condition: completed_successfully
+  migrate:
+    binary: /bin/sh
+    args: ["-c", "sleep 0.3; touch {marker}"]
+"#,
+            marker = marker.display()
+        ),
+    )
+    .unwrap();
+    let compose = compose.to_string_lossy().to_string();
+
+    hyperv_cmd(&temp)
+        .args(&["up", "--file", &compose, "--start"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Waiting for \"demo_migrate\" to be completed_successfully before starting \"demo_app\"",
+        ));
+
+    hyperv_cmd(&temp)
+        .args(&["status", "demo_app"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Running"));
+
+    hyperv_cmd(&temp)
+        .args(&["down", "--file", &compose])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_up_plan_restart_and_orphans() {
+    let temp = TempDir::new().unwrap();
+    let sleep_bin = bin_path("/bin/sleep", "/usr/bin/sleep");
+    let compose = temp.path().join("hyperv.yaml");
+    let compose_arg = compose.to_string_lossy().to_string();
+    let write_compose = |api_args: &str, extra: &str| {
+        std::fs::write(
+            &compose,
+            format!(
+                "name: shop\nservices:\n  api:\n    binary: {sleep_bin}\n    args: [\"{api_args}\"]\n{extra}"
+            ),
+        )
+        .unwrap();
+    };
+
+    write_compose(
+        "30",
+        &format!("  old:\n    binary: {sleep_bin}\n    args: [\"30\"]\n"),
+    );
+    hyperv_cmd(&temp)
+        .args(&["up", "--file", &compose_arg, "--start"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("+ create     shop_api"))
+        .stdout(predicate::str::contains("+ create     shop_old"));
+
+    hyperv_cmd(&temp)
+        .args(&["up", "--file", &compose_arg, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("= unchanged  shop_api"))
+        .stdout(predicate::str::contains("No changes."));
+
+    write_compose(
+        "40",
+        &format!("  new:\n    binary: {sleep_bin}\n    args: [\"30\"]\n"),
+    );
+    hyperv_cmd(&temp)
+        .args(&["up", "--file", &compose_arg, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("~ update     shop_api"))
+        .stdout(predicate::str::contains(r#"args: ["30"] -> ["40"]"#))
+        .stdout(predicate::str::contains("+ create     shop_new"))
+        .stdout(predicate::str::contains("- orphaned   shop_old"));
+
+    // A dry run changes nothing
+    hyperv_cmd(&temp)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("shop_new").not());
+
+    hyperv_cmd(&temp)
+        .args(&["up", "--file", &compose_arg, "--remove-orphans"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Configuration of \"shop_api\" changed; restarting",
+        ));
+
+    hyperv_cmd(&temp)
+        .args(&["status", "shop_api"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Running"))
+        .stdout(predicate::str::contains(r#"Args: ["40"]"#));
+    hyperv_cmd(&temp)
+        .args(&["status", "shop_old"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("not found"));
+
+    hyperv_cmd(&temp)
+        .args(&["down", "--file", &compose_arg])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_compose_projects_are_isolated() {
+    let temp = TempDir::new().unwrap();
+    let ls_bin = bin_path("/bin/ls", "/usr/bin/ls");
+
+    // Same service name in two projects; project names come from the directories
+    let mut files = Vec::new();
+    for project in ["alpha", "beta"] {
+        let dir = temp.path().join(project);
+        std::fs::create_dir(&dir).unwrap();
+        let file = dir.join("hyperv.yaml");
+        std::fs::write(
+            &file,
+            format!("services:\n  worker:\n    binary: {ls_bin}\n"),
+        )
+        .unwrap();
+        let file = file.to_string_lossy().to_string();
+        hyperv_cmd(&temp)
+            .args(&["up", "--file", &file])
+            .assert()
+            .success();
+        files.push(file);
+    }
+
+    // A hand-made task that collides with a project task name is left alone
+    hyperv_cmd(&temp)
+        .args(&["new", "--name", "worker", "--binary", ls_bin])
+        .assert()
+        .success();
+
+    hyperv_cmd(&temp)
+        .args(&["list", "--project", "alpha"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("alpha_worker"))
+        .stdout(predicate::str::contains("beta_worker").not());
+
+    hyperv_cmd(&temp)
+        .args(&["down", "--file", &files[0]])
+        .assert()
+        .success();
+
+    hyperv_cmd(&temp)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("alpha_worker").not())
+        .stdout(predicate::str::contains("beta_worker"))
+        .stdout(predicate::str::contains("worker "));
+
+    hyperv_cmd(&temp)
+        .args(&["status", "beta_worker"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Compose project: beta (service: worker)",
+        ));
+}
+
+#[test]
+fn test_up_refuses_to_adopt_foreign_task() {
+    let temp = TempDir::new().unwrap();
+    let ls_bin = bin_path("/bin/ls", "/usr/bin/ls");
+    let file = temp.path().join("hyperv.yaml");
+    std::fs::write(
+        &file,
+        format!("name: web\nservices:\n  api:\n    binary: {ls_bin}\n"),
+    )
+    .unwrap();
+
+    hyperv_cmd(&temp)
+        .args(&["new", "--name", "web_api", "--binary", ls_bin])
+        .assert()
+        .success();
+
+    hyperv_cmd(&temp)
+        .args(&["up", "--file", &file.to_string_lossy()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "does not belong to compose project",
+        ));
+}
+
+#[test]
+fn test_new_rejects_absurd_restart_delays() {
+    let temp = TempDir::new().unwrap();
+    let true_bin = bin_path("/bin/true", "/usr/bin/true");
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "patient",
+            "--binary",
+            true_bin,
+            "--restart-delay",
+            "20000000000000",
+            "--restart-max-delay",
+            "18446744073709551615",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "initial_delay_secs must be at most",
+        ));
+}
+
 #[test]
 fn test_long_running() {
     let temp = TempDir::new().unwrap();
@@ -298,3 +650,1455 @@ fn test_daemon_locking() {
         let _ = child.try_wait();
     }
 }
+
+struct DaemonGuard {
+    child: std::process::Child,
+}
+
+impl Drop for DaemonGuard {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn spawn_daemon(temp: &TempDir) -> DaemonGuard {
+    let child = std::process::Command::new(assert_cmd::cargo::cargo_bin("hyperV"))
+        .arg("daemon")
+        .env("HYPERV_CONFIG_DIR", temp.path())
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let socket = temp.path().join("daemon.sock");
+    let start = std::time::Instant::now();
+    while !socket.exists() && start.elapsed() < std::time::Duration::from_secs(5) {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert!(socket.exists(), "daemon did not open its control socket");
+
+    DaemonGuard { child }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_supervised_task_records_exit_code() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    let script = temp.path().join("exit3.sh");
+    std::fs::write(&script, "#!/bin/sh\nsleep 0.2\nexit 3\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let _daemon = spawn_daemon(&temp);
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "exiter",
+            "--binary",
+            script.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    hyperv_cmd(&temp)
+        .args(&["start", "exiter"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("supervised by daemon"));
+
+    // The daemon reaps its own children on every loop iteration.
+    let start = std::time::Instant::now();
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["status", "exiter"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        if stdout.contains("Last exit code: 3") {
+            assert!(stdout.contains("Last exited:"));
+            break;
+        }
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(15),
+            "exit code was never recorded:\n{stdout}"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(250));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_resource_limits_apply_to_the_task() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let _daemon = spawn_daemon(&temp);
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "limited",
+            "--binary",
+            sh_bin,
+            "--limit-open-files",
+            "64",
+            "--limit-core-size",
+            "0",
+            "--args",
+            "-c",
+            "echo \"nofile=$(ulimit -n) core=$(ulimit -c)\"",
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["status", "limited"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Resource limits: open files 64, core size 0 B",
+        ));
+    hyperv_cmd(&temp)
+        .args(&["start", "limited"])
+        .assert()
+        .success();
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "spinner",
+            "--binary",
+            sh_bin,
+            "--limit-cpu-time",
+            "1",
+            "--args",
+            "-c",
+            "while :; do :; done",
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["start", "spinner"])
+        .assert()
+        .success();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(15);
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["logs", "limited"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.contains("nofile=") {
+            assert!(stdout.contains("nofile=64 core=0"), "{}", stdout);
+            break;
+        }
+        assert!(
+            std::time::Instant::now() < deadline,
+            "no output: {}",
+            stdout
+        );
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["status", "spinner"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        if stdout.contains("Last exit signal:") {
+            assert!(
+                stdout.contains("Last exit signal: 24 (CPU time limit of 1s reached)"),
+                "{stdout}"
+            );
+            break;
+        }
+        assert!(
+            std::time::Instant::now() < deadline,
+            "exit signal was never recorded:\n{stdout}"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(250));
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_cpu_limit_explains_the_kill_of_a_task_ignoring_sigxcpu() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let _daemon = spawn_daemon(&temp);
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "stubborn-spinner",
+            "--binary",
+            sh_bin,
+            "--limit-cpu-time",
+            "1",
+            "--args",
+            "-c",
+            "trap '' XCPU; while :; do :; done",
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["start", "stubborn-spinner"])
+        .assert()
+        .success();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(15);
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["status", "stubborn-spinner"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        if stdout.contains("Last exit signal:") {
+            assert!(
+                stdout.contains(
+                    "Last exit signal: 9 (CPU time limit of 1s likely reached; SIGXCPU was ignored)"
+                ),
+                "{stdout}"
+            );
+            break;
+        }
+        assert!(
+            std::time::Instant::now() < deadline,
+            "exit signal was never recorded:\n{stdout}"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(250));
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_cgroup_keeps_track_of_double_forked_daemons() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let pid_file = temp.path().join("daemon.pid");
+
+    // The daemon leaves the process group with setsid and the task's own PID exits
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "forker",
+            "--binary",
+            sh_bin,
+            "--args",
+            "-c",
+            &format!(
+                "setsid sh -c 'echo $$ > {}; exec sleep 300' & sleep 0.5",
+                pid_file.display()
+            ),
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["start", "forker"])
+        .assert()
+        .success();
+
+    let output = hyperv_cmd(&temp)
+        .args(&["status", "forker"])
+        .output()
+        .unwrap();
+    if !String::from_utf8_lossy(&output.stdout).contains("cgroup: ") {
+        // No writable cgroup v2 hierarchy: tasks are tracked by process group
+        return;
+    }
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !pid_file.exists()
+        || std::fs::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .is_empty()
+    {
+        assert!(std::time::Instant::now() < deadline, "daemon did not start");
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    let daemon_pid: i32 = std::fs::read_to_string(&pid_file)
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    // The task's PID is gone, but the daemon in its cgroup keeps it running
+    hyperv_cmd(&temp)
+        .args(&["status", "forker"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Running"));
+    hyperv_cmd(&temp)
+        .args(&["stop", "forker"])
+        .assert()
+        .success();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while unsafe { libc::kill(daemon_pid, 0) } == 0
+        && !std::fs::read_to_string(format!("/proc/{}/stat", daemon_pid))
+            .is_ok_and(|stat| stat.contains(") Z"))
+    {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "daemon {} survived stop",
+            daemon_pid
+        );
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    hyperv_cmd(&temp)
+        .args(&["status", "forker"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Stopped"))
+        .stdout(predicate::str::contains("cgroup: ").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_watchdog_restarts_a_task_whose_children_spin() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let _daemon = spawn_daemon(&temp);
+
+    // The task itself only waits; the CPU is used by its child
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "runaway",
+            "--binary",
+            sh_bin,
+            "--watchdog-cpu",
+            "50",
+            "--watchdog-cpu-window",
+            "1",
+            "--watchdog-action",
+            "restart",
+            "--args",
+            "-c",
+            "sh -c 'while :; do :; done' & wait",
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["status", "runaway"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Watchdog: CPU 50% over 1s (action: restart)",
+        ));
+    hyperv_cmd(&temp)
+        .args(&["start", "runaway"])
+        .assert()
+        .success();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(30);
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["status", "runaway"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        if stdout.contains("(restarts: 1)") {
+            assert!(stdout.contains("Recent events:"), "{stdout}");
+            assert!(stdout.contains("watchdog: CPU"), "{stdout}");
+            assert!(
+                stdout.contains("is above the limit of 50% (restart)"),
+                "{stdout}"
+            );
+            break;
+        }
+        assert!(
+            std::time::Instant::now() < deadline,
+            "the watchdog never restarted the task:\n{stdout}"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(250));
+    }
+
+    hyperv_cmd(&temp)
+        .args(&["stop", "runaway"])
+        .assert()
+        .success();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_commands_are_served_by_running_daemon() {
+    let temp = TempDir::new().unwrap();
+    let logger = abs_repo_path("tests/logger.sh");
+    let _daemon = spawn_daemon(&temp);
+
+    hyperv_cmd(&temp)
+        .args(&["new", "--name", "served", "--binary", &logger])
+        .assert()
+        .success();
+
+    hyperv_cmd(&temp)
+        .args(&["start", "served"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("supervised by daemon"));
+
+    hyperv_cmd(&temp)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("served"))
+        .stdout(predicate::str::contains("Running"));
+
+    hyperv_cmd(&temp)
+        .args(&["stop", "served"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Task \"served\" stopped"));
+
+    hyperv_cmd(&temp)
+        .args(&["status", "served"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Stopped"));
+
+    hyperv_cmd(&temp)
+        .args(&["status", "missing"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("not found"));
+
+    hyperv_cmd(&temp)
+        .arg("reload")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("reloaded 1 tasks"));
+
+    hyperv_cmd(&temp)
+        .args(&["start", "missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Task 'missing' not found"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_keeps_serving_while_a_task_stops() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let _daemon = spawn_daemon(&temp);
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "stubborn",
+            "--binary",
+            sh_bin,
+            "--args",
+            "-c",
+            "trap '' TERM; while :; do sleep 0.1; done",
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["start", "stubborn"])
+        .assert()
+        .success();
+
+    // The task ignores SIGTERM, so the stop only ends with SIGKILL after the grace period
+    let started = std::time::Instant::now();
+    let stop = std::process::Command::new(assert_cmd::cargo::cargo_bin("hyperV"))
+        .args(["stop", "stubborn"])
+        .env("HYPERV_CONFIG_DIR", temp.path())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    hyperv_cmd(&temp)
+        .args(&["status", "stubborn"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Running"));
+    hyperv_cmd(&temp)
+        .args(&["start", "stubborn"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is still being stopped"));
+    assert!(
+        started.elapsed() < std::time::Duration::from_millis(1500),
+        "requests waited for the stop"
+    );
+
+    let output = stop.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Task \"stubborn\" stopped"));
+    assert!(started.elapsed() >= std::time::Duration::from_secs(2));
+    hyperv_cmd(&temp)
+        .args(&["status", "stubborn"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Stopped"));
+}
+
+#[test]
+fn test_reload_without_daemon() {
+    let temp = TempDir::new().unwrap();
+    hyperv_cmd(&temp)
+        .arg("reload")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No daemon is running"));
+}
+
+#[test]
+fn test_up_print_config_resolves_variables() {
+    let temp = TempDir::new().unwrap();
+    let compose = temp.path().join("hyperv.yaml");
+    let compose_arg = compose.to_string_lossy().to_string();
+    std::fs::write(temp.path().join(".env"), "APP_ROOT=/srv/app\n").unwrap();
+    std::fs::write(
+        &compose,
+        "name: demo\nservices:\n  api:\n    binary: ${APP_ROOT}/bin/api\n    env:\n      TOKEN: ${API_TOKEN:?export API_TOKEN}\n",
+    )
+    .unwrap();
+
+    hyperv_cmd(&temp)
+        .args(&["up", "--file", &compose_arg, "--print-config"])
+        .env("API_TOKEN", "s3cret")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("binary: /srv/app/bin/api"))
+        .stdout(predicate::str::contains("TOKEN: s3cret"))
+        .stdout(predicate::str::contains("null").not());
+
+    hyperv_cmd(&temp)
+        .args(&["up", "--file", &compose_arg, "--print-config"])
+        .env_remove("API_TOKEN")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("env.TOKEN"))
+        .stderr(predicate::str::contains(
+            "required variable API_TOKEN is not set: export API_TOKEN",
+        ));
+
+    // Nothing is created when only printing the configuration
+    hyperv_cmd(&temp)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No tasks configured."));
+}
+
+#[test]
+fn test_env_file_option_and_diagnose_warnings() {
+    let temp = TempDir::new().unwrap();
+    let true_bin = bin_path("/bin/true", "/usr/bin/true");
+    let env_file = temp.path().join("app.env");
+    std::fs::write(&env_file, "PORT=8000\nexport DEBUG=1\noops\n").unwrap();
+    let env_file_arg = env_file.to_string_lossy().to_string();
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "envy",
+            "--binary",
+            true_bin,
+            "--env-file",
+            &env_file_arg,
+        ])
+        .assert()
+        .success();
+
+    hyperv_cmd(&temp)
+        .args(&["diagnose", "envy"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Env files:"))
+        .stdout(predicate::str::contains("2 variables, 1 warnings"))
+        .stdout(predicate::str::contains(
+            "line 3: expected KEY=VALUE, found \"oops\"",
+        ));
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "missing",
+            "--binary",
+            true_bin,
+            "--env-file",
+            "does-not-exist.env",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("EnvFileNotFound"));
+}
+
+#[test]
+fn test_secret_references_resolve_at_start() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let out = temp.path().join("seen");
+    let script = format!("echo \"$DB_PASSWORD\" > {}", out.display());
+
+    hyperv_cmd(&temp)
+        .args(&["secret", "set", "db_password"])
+        .write_stdin("hunter2\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Secret \"db_password\" stored"));
+    hyperv_cmd(&temp)
+        .args(&["secret", "get", "db_password"])
+        .assert()
+        .success()
+        .stdout("hunter2\n");
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "db",
+            "--binary",
+            sh_bin,
+            "--env",
+            "DB_PASSWORD=secret:db_password",
+            "--args",
+            "-c",
+            &script,
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["secret", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("db_password (used by: db)"));
+    hyperv_cmd(&temp)
+        .args(&["status", "db"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("DB_PASSWORD=secret:db_password"));
+
+    hyperv_cmd(&temp).args(&["start", "db"]).assert().success();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while std::fs::read_to_string(&out).unwrap_or_default() != "hunter2\n" {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "secret never resolved"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    for file in ["tasks.json", "secrets.json", "secrets.key"] {
+        let path = temp.path().join(file);
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("hunter2"));
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600, "{}", file);
+    }
+
+    hyperv_cmd(&temp)
+        .args(&["secret", "rm", "db_password"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Still referenced by: db"));
+    hyperv_cmd(&temp)
+        .args(&["start", "db"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "DB_PASSWORD (secret:db_password): secret \\\"db_password\\\" is not in the store",
+        ));
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "bad",
+            "--binary",
+            sh_bin,
+            "--env",
+            "X=secret:",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid secret reference"));
+}
+
+#[test]
+fn test_run_as_user_and_group() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let id = |args: &[&str]| {
+        let output = std::process::Command::new("id")
+            .args(args)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "ghost",
+            "--binary",
+            sh_bin,
+            "--user",
+            "hyperv-no-such-user",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("UserNotFound"));
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "worker",
+            "--binary",
+            sh_bin,
+            "--user",
+            "nobody",
+            "--supplementary-group",
+            "0",
+            "--args",
+            "-c",
+            "echo \"uid=$(id -u) gid=$(id -g) groups=$(id -G) home=$HOME\"",
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["status", "worker"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Run as: user nobody, supplementary groups 0",
+        ));
+    hyperv_cmd(&temp)
+        .args(&["diagnose", "worker"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Run as: user nobody"))
+        .stdout(predicate::str::contains(format!(
+            "nobody can read and execute {}",
+            sh_bin
+        )));
+
+    if id(&["-u"]) != "0" {
+        hyperv_cmd(&temp)
+            .args(&["start", "worker"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("PrivilegeDrop"))
+            .stderr(predicate::str::contains("only root can start tasks"));
+        return;
+    }
+
+    hyperv_cmd(&temp)
+        .args(&["start", "worker"])
+        .assert()
+        .success();
+    let expected = format!(
+        "uid={} gid={} groups={} 0",
+        id(&["-u", "nobody"]),
+        id(&["-g", "nobody"]),
+        id(&["-g", "nobody"])
+    );
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["logs", "worker"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.contains("uid=") {
+            assert!(stdout.contains(&expected), "{}", stdout);
+            assert!(!stdout.contains("home=/root"), "{}", stdout);
+            break;
+        }
+        assert!(
+            std::time::Instant::now() < deadline,
+            "no output: {}",
+            stdout
+        );
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_status_and_diagnose_mask_secrets() {
+    let temp = TempDir::new().unwrap();
+    let true_bin = bin_path("/bin/true", "/usr/bin/true");
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "db",
+            "--binary",
+            true_bin,
+            "--env",
+            "SURREAL_PASSWORD=hunter2",
+            "--env",
+            "Gemini__ApiKey=abc123",
+            "--env",
+            "DSN=postgres://app:s3cret@db/app",
+            "--env",
+            "PORT=8000",
+            "--secret-env",
+            "DSN",
+            "--args",
+            "--token=tok-42",
+            "--verbose",
+        ])
+        .assert()
+        .success();
+
+    for command in ["status", "diagnose"] {
+        hyperv_cmd(&temp)
+            .args(&[command, "db"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("SURREAL_PASSWORD=[REDACTED]"))
+            .stdout(predicate::str::contains("Gemini__ApiKey=[REDACTED]"))
+            .stdout(predicate::str::contains("DSN=[REDACTED]"))
+            .stdout(predicate::str::contains("PORT=8000"))
+            .stdout(predicate::str::contains(
+                r#"["--token=[REDACTED]", "--verbose"]"#,
+            ))
+            .stdout(predicate::str::contains("hunter2").not())
+            .stdout(predicate::str::contains("abc123").not())
+            .stdout(predicate::str::contains("s3cret").not())
+            .stdout(predicate::str::contains("tok-42").not());
+
+        hyperv_cmd(&temp)
+            .args(&[command, "db", "--show-secrets"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("SURREAL_PASSWORD=hunter2"))
+            .stdout(predicate::str::contains("Gemini__ApiKey=abc123"))
+            .stdout(predicate::str::contains("DSN=postgres://app:s3cret@db/app"))
+            .stdout(predicate::str::contains("--token=tok-42"));
+    }
+}
+
+#[test]
+fn test_chatty_task_log_rotates_while_running() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    // A little over the 10MB limit once each line is stamped, then stay alive
+    let script = "head -c 8000000 /dev/zero | tr '\\0' x | fold -w 99; echo after-flood; sleep 30";
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new", "--name", "chatty", "--binary", sh_bin, "--args", "-c", script,
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["start", "chatty"])
+        .assert()
+        .success();
+
+    let log_dir = std::fs::read_dir(temp.path().join("logs"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let archive = log_dir.join("stdout.log.1.gz");
+    let stdout_log = log_dir.join("stdout.log");
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(20);
+    while !(archive.exists()
+        && std::fs::read_to_string(&stdout_log).is_ok_and(|s| s.contains("after-flood")))
+    {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "log was not rotated while the task was running"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    hyperv_cmd(&temp)
+        .args(&["status", "chatty"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Running"));
+    assert!(std::fs::metadata(&stdout_log).unwrap().len() < 1024 * 1024);
+
+    hyperv_cmd(&temp)
+        .args(&["stop", "chatty"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn test_logs_both_interleaves_streams() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let script = "echo first; sleep 0.2; echo second >&2; sleep 0.2; echo third";
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new", "--name", "talker", "--binary", sh_bin, "--args", "-c", script,
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["start", "talker"])
+        .assert()
+        .success();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["logs", "talker", "--log-type", "both"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if let (Some(first), Some(second), Some(third)) = (
+            stdout.find("[OUT] first"),
+            stdout.find("[ERR] second"),
+            stdout.find("[OUT] third"),
+        ) {
+            assert!(first < second && second < third, "{}", stdout);
+            break;
+        }
+        assert!(
+            std::time::Instant::now() < deadline,
+            "lines never showed up: {}",
+            stdout
+        );
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_logs_filters() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let script = "echo 'INFO ready'; echo 'ERROR disk full' >&2; echo 'WARN low memory' >&2";
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new", "--name", "filtered", "--binary", sh_bin, "--args", "-c", script,
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["start", "filtered"])
+        .assert()
+        .success();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["logs", "filtered", "--log-type", "both"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.contains("ready") && stdout.contains("low memory") {
+            break;
+        }
+        assert!(std::time::Instant::now() < deadline, "{}", stdout);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    hyperv_cmd(&temp)
+        .args(&["logs", "filtered", "-t", "both", "--level", "error"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("disk full"))
+        .stdout(predicate::str::contains("ready").not())
+        .stdout(predicate::str::contains("low memory").not());
+    hyperv_cmd(&temp)
+        .args(&["logs", "filtered", "-t", "both", "--grep", "mem(ory)?$"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("low memory"))
+        .stdout(predicate::str::contains("disk full").not());
+    hyperv_cmd(&temp)
+        .args(&["logs", "filtered", "-t", "both", "--since", "1h"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ready"));
+    hyperv_cmd(&temp)
+        .args(&["logs", "filtered", "--since", "2999-01-01T00:00:00Z"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No matching log lines."));
+    hyperv_cmd(&temp)
+        .args(&["logs", "filtered", "--since", "soon"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid time"));
+}
+
+#[test]
+fn test_logs_json_field_filter() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let script = r#"echo '{"severity":"ERROR","msg":"charge failed","request_id":"abc"}'; echo '{"severity":"INFO","msg":"charged","request_id":"def"}'"#;
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "payments",
+            "--binary",
+            sh_bin,
+            "--log-json",
+            "level=severity",
+            "--args",
+            "-c",
+            script,
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["status", "payments"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("JSON fields level=severity"));
+    hyperv_cmd(&temp)
+        .args(&["start", "payments"])
+        .assert()
+        .success();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["logs", "payments"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.contains("charge failed") && stdout.contains("charged") {
+            break;
+        }
+        assert!(std::time::Instant::now() < deadline, "{}", stdout);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    hyperv_cmd(&temp)
+        .args(&["logs", "payments", "--json-field", "request_id=abc"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("charge failed"))
+        .stdout(predicate::str::contains("\"charged\"").not());
+    hyperv_cmd(&temp)
+        .args(&["logs", "payments", "--level", "error"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("charge failed"))
+        .stdout(predicate::str::contains("\"charged\"").not());
+    hyperv_cmd(&temp)
+        .args(&["logs", "payments", "--json-field", "request_id"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected KEY=VALUE"));
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "bad",
+            "--binary",
+            sh_bin,
+            "--log-json",
+            "lvl=x",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid JSON field mapping"));
+}
+
+#[test]
+fn test_log_forwarding_settings() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "shipped",
+            "--binary",
+            sh_bin,
+            "--log-forward",
+            "udp://127.0.0.1:5140",
+            "--log-forward",
+            "http://127.0.0.1:9000/ingest",
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "local",
+            "--binary",
+            sh_bin,
+            "--no-log-forward",
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "forwarded to udp://127.0.0.1:5140, http://127.0.0.1:9000/ingest",
+        ))
+        .stdout(predicate::str::contains("not forwarded"));
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "bad",
+            "--binary",
+            sh_bin,
+            "--log-forward",
+            "syslog.local",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid log forwarding target"));
+}
+
+#[test]
+fn test_bundle_collects_redacted_diagnostics() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let script = "echo 'ready api_key=sk-live-123'; echo 'ERROR upstream down' >&2; exec sleep 30";
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "bundled",
+            "--binary",
+            sh_bin,
+            "--env",
+            "DATABASE_PASSWORD=hunter2",
+            "--env",
+            "LOG_LEVEL=debug",
+            "--args",
+            "-c",
+            script,
+            "--token=abc123",
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["start", "bundled"])
+        .assert()
+        .success();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["logs", "bundled", "-t", "both"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.contains("ready") && stdout.contains("upstream down") {
+            break;
+        }
+        assert!(std::time::Instant::now() < deadline, "{}", stdout);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    let bundle_path = temp.path().join("bundle.tar.gz");
+    hyperv_cmd(&temp)
+        .args(&["bundle", "bundled", "--output"])
+        .arg(&bundle_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Support bundle"));
+    let _ = hyperv_cmd(&temp).args(&["stop", "bundled"]).assert();
+
+    let mut files = std::collections::HashMap::new();
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(
+        std::fs::File::open(&bundle_path).unwrap(),
+    ));
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().to_string_lossy().to_string();
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut entry, &mut content).unwrap();
+        let (root, name) = path.split_once('/').unwrap();
+        assert!(root.starts_with("hyperv-bundle-bundled-"), "{}", root);
+        files.insert(name.to_string(), content);
+    }
+
+    for name in [
+        "manifest.txt",
+        "task.json",
+        "history.txt",
+        "diagnose.txt",
+        "summary.txt",
+        "logs/stdout.log",
+        "logs/stderr.log",
+        "processes.txt",
+    ] {
+        assert!(files.contains_key(name), "missing {}", name);
+    }
+    for (name, content) in &files {
+        for secret in ["hunter2", "abc123", "sk-live-123"] {
+            assert!(!content.contains(secret), "{} leaks {}", name, secret);
+        }
+    }
+    let task: serde_json::Value = serde_json::from_str(&files["task.json"]).unwrap();
+    assert_eq!(task["env"]["DATABASE_PASSWORD"], "[REDACTED]");
+    assert_eq!(task["env"]["LOG_LEVEL"], "debug");
+    assert!(files["logs/stdout.log"].contains("ready api_key=[REDACTED]"));
+    assert!(files["logs/stderr.log"].contains("stderr ERROR upstream down"));
+    assert!(files["summary.txt"].contains("=== LOG SUMMARY ==="));
+    assert!(files["diagnose.txt"].contains("File exists"));
+    assert!(files["history.txt"].contains("Restart count: 0"));
+    assert!(
+        files["processes.txt"].contains("sleep 30"),
+        "{}",
+        files["processes.txt"]
+    );
+}
+
+#[test]
+fn test_logs_follow_several_tasks_with_prefixes() {
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    for (name, script) in [
+        (
+            "alpha",
+            "echo alpha-started; while true; do echo alpha-tick; sleep 0.2; done",
+        ),
+        (
+            "beta-worker",
+            "while true; do echo beta-tick >&2; sleep 0.2; done",
+        ),
+    ] {
+        hyperv_cmd(&temp)
+            .args(&[
+                "new", "--name", name, "--binary", sh_bin, "--args", "-c", script,
+            ])
+            .assert()
+            .success();
+        hyperv_cmd(&temp).args(&["start", name]).assert().success();
+    }
+
+    let mut follower = std::process::Command::new(assert_cmd::cargo::cargo_bin("hyperV"))
+        .env("HYPERV_CONFIG_DIR", temp.path())
+        .args(&["logs", "-f", "-t", "both", "alpha", "beta-worker"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let output = Arc::new(Mutex::new(String::new()));
+    let mut stdout = follower.stdout.take().unwrap();
+    let reader_output = Arc::clone(&output);
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        while let Ok(read) = stdout.read(&mut buffer) {
+            if read == 0 {
+                break;
+            }
+            reader_output
+                .lock()
+                .unwrap()
+                .push_str(&String::from_utf8_lossy(&buffer[..read]));
+        }
+    });
+    let wait_for = |wanted: &str| {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        loop {
+            let text = output.lock().unwrap().clone();
+            if let Some(following) = text.split("Following").nth(1)
+                && following.contains(wanted)
+            {
+                return;
+            }
+            assert!(
+                std::time::Instant::now() < deadline,
+                "{:?} never followed: {}",
+                wanted,
+                text
+            );
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    };
+
+    wait_for("alpha       | ");
+    wait_for("beta-worker | ");
+    wait_for("[ERR] beta-tick");
+
+    // A restarted task keeps being followed
+    hyperv_cmd(&temp)
+        .args(&["restart", "alpha"])
+        .assert()
+        .success();
+    wait_for("alpha-started");
+
+    follower.kill().unwrap();
+    let _ = follower.wait();
+    for name in ["alpha", "beta-worker"] {
+        hyperv_cmd(&temp).args(&["stop", name]).assert().success();
+    }
+}
+
+#[test]
+fn test_logs_for_a_compose_project() {
+    let temp = TempDir::new().unwrap();
+    let compose = temp.path().join("compose.yaml");
+    std::fs::write(
+        &compose,
+        "name: shop\nservices:\n  web:\n    binary: /bin/sh\n    args: [\"-c\", \"echo web-ready\"]\n  db:\n    binary: /bin/sh\n    args: [\"-c\", \"echo db-ready\"]\n",
+    )
+    .unwrap();
+    hyperv_cmd(&temp)
+        .args(&["up", "--file", compose.to_str().unwrap(), "--start"])
+        .assert()
+        .success();
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    loop {
+        let output = hyperv_cmd(&temp)
+            .args(&["logs", "--project", "shop"])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.contains("shop_web | ")
+            && stdout.contains("web-ready")
+            && stdout.contains("shop_db  | ")
+            && stdout.contains("db-ready")
+        {
+            break;
+        }
+        assert!(std::time::Instant::now() < deadline, "{}", stdout);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+
+    hyperv_cmd(&temp)
+        .args(&["logs", "--project", "nope"])
+        .assert()
+        .failure();
+    hyperv_cmd(&temp)
+        .args(&["logs", "--all", "--summary"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_per_task_log_settings_and_prune() {
+    let temp = TempDir::new().unwrap();
+    let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+    let script =
+        "i=0; while [ $i -lt 200 ]; do echo line-$i; i=$((i+1)); done; echo flood-done; sleep 30";
+
+    hyperv_cmd(&temp)
+        .args(&[
+            "new",
+            "--name",
+            "small",
+            "--binary",
+            sh_bin,
+            "--log-max-size",
+            "2K",
+            "--log-max-archives",
+            "2",
+            "--log-no-compress",
+            "--args",
+            "-c",
+            script,
+        ])
+        .assert()
+        .success();
+    hyperv_cmd(&temp)
+        .args(&["status", "small"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Logs: max size 2.0 KB, 2 archives, uncompressed",
+        ));
+    hyperv_cmd(&temp)
+        .args(&["start", "small"])
+        .assert()
+        .success();
+
+    let log_dir = std::fs::read_dir(temp.path().join("logs"))
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !std::fs::read_to_string(log_dir.join("stdout.log"))
+        .is_ok_and(|s| s.contains("flood-done"))
+    {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "task never finished writing"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    hyperv_cmd(&temp)
+        .args(&["stop", "small"])
+        .assert()
+        .success();
+
+    // Only two plain archives are kept, each close to the configured size
+    assert!(log_dir.join("stdout.log.1").exists());
+    assert!(log_dir.join("stdout.log.2").exists());
+    assert!(!log_dir.join("stdout.log.3").exists());
+    assert!(!log_dir.join("stdout.log.1.gz").exists());
+    assert!(
+        std::fs::metadata(log_dir.join("stdout.log.1"))
+            .unwrap()
+            .len()
+            <= 2048
+    );
+
+    // The daemon config sets retention for tasks that don't set their own
+    std::fs::write(
+        temp.path().join("daemon.yaml"),
+        "logs:\n  max_age_days: 1\n",
+    )
+    .unwrap();
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 24 * 60 * 60);
+    std::fs::File::options()
+        .write(true)
+        .open(log_dir.join("stdout.log.2"))
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+    hyperv_cmd(&temp)
+        .args(&["logs", "--prune"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("stdout.log.2"))
+        .stdout(predicate::str::contains("Removed 1 rotated log file(s)"));
+    assert!(log_dir.join("stdout.log.1").exists());
+    assert!(!log_dir.join("stdout.log.2").exists());
+
+    hyperv_cmd(&temp)
+        .args(&["logs", "--prune", "small"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No rotated logs to prune"));
+
+    std::fs::write(temp.path().join("daemon.yaml"), "logs:\n  max_size: 0\n").unwrap();
+    hyperv_cmd(&temp)
+        .args(&["logs", "--prune"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("max_size must be greater than 0"));
+}
@@ -0,0 +1,520 @@
+use hyperV::compose::{ComposeFile, DependencyCondition};
+use hyperV::interpolation::interpolate_str;
+use hyperV::{Task, TaskStatus};
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn compose_from_yaml(yaml: &str) -> ComposeFile {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("hyperv.yaml");
+    std::fs::write(&path, yaml).unwrap();
+    ComposeFile::from_path(&path).unwrap()
+}
+
+fn task(name: &str, status: TaskStatus) -> Task {
+    let mut task = Task::new(
+        name.to_string(),
+        name.to_string(),
+        "/bin/true".to_string(),
+        Vec::new(),
+        HashMap::new(),
+        None,
+        false,
+        None,
+        None,
+    );
+    task.set_status(status);
+    task
+}
+
+#[test]
+fn startup_order_puts_dependencies_first() {
+    let compose = compose_from_yaml(
+        r#"
+services:
+  web:
+    binary: /bin/true
+    depends_on:
+      api:
+        condition: healthy
+  api:
+    binary: /bin/true
+    depends_on: [db, migrate]
+  migrate:
+    binary: /bin/true
+    depends_on:
+      db:
+        condition: service_started
+  db:
+    binary: /bin/true
+  cache:
+    binary: /bin/true
+"#,
+    );
+
+    assert_eq!(
+        compose.services["web"].depends_on["api"],
+        DependencyCondition::Healthy
+    );
+    assert_eq!(
+        compose.services["api"].depends_on["db"],
+        DependencyCondition::Started
+    );
+    assert_eq!(
+        compose.startup_order().unwrap(),
+        vec!["cache", "db", "migrate", "api", "web"]
+    );
+}
+
+#[test]
+fn dependency_cycle_is_reported() {
+    let compose = compose_from_yaml(
+        r#"
+services:
+  a:
+    binary: /bin/true
+    depends_on: [b]
+  b:
+    binary: /bin/true
+    depends_on: [c]
+  c:
+    binary: /bin/true
+    depends_on: [a]
+  d:
+    binary: /bin/true
+"#,
+    );
+
+    let err = compose.startup_order().unwrap_err().to_string();
+    assert!(
+        err.contains("Dependency cycle between services: a -> b -> c -> a"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn unknown_dependency_is_reported() {
+    let compose = compose_from_yaml(
+        r#"
+services:
+  api:
+    binary: /bin/true
+    depends_on: [database]
+"#,
+    );
+
+    let err = compose.startup_order().unwrap_err().to_string();
+    assert!(
+        err.contains("Service \"api\" depends on unknown service \"database\""),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn dependency_conditions() {
+    let started = DependencyCondition::Started;
+    assert!(
+        !started
+            .is_satisfied_by(&task("db", TaskStatus::Stopped))
+            .unwrap()
+    );
+    assert!(
+        started
+            .is_satisfied_by(&task("db", TaskStatus::Running))
+            .unwrap()
+    );
+    assert!(
+        started
+            .is_satisfied_by(&task("db", TaskStatus::Failed))
+            .is_err()
+    );
+
+    let completed = DependencyCondition::CompletedSuccessfully;
+    assert!(
+        !completed
+            .is_satisfied_by(&task("job", TaskStatus::Running))
+            .unwrap()
+    );
+    assert!(
+        completed
+            .is_satisfied_by(&task("job", TaskStatus::Exited))
+            .unwrap()
+    );
+    let mut failed = task("job", TaskStatus::Failed);
+    failed.last_exit_code = Some(2);
+    let err = completed.is_satisfied_by(&failed).unwrap_err().to_string();
+    assert!(err.contains("exit code: 2"), "{}", err);
+
+    // Without a health check a task can never become healthy
+    let healthy = DependencyCondition::Healthy;
+    assert!(
+        healthy
+            .is_satisfied_by(&task("api", TaskStatus::Running))
+            .is_err()
+    );
+}
+
+#[test]
+fn project_name_comes_from_name_key_or_directory() {
+    let dir = TempDir::new().unwrap();
+    let project_dir = dir.path().join("My Shop");
+    std::fs::create_dir(&project_dir).unwrap();
+    let path = project_dir.join("hyperv.yaml");
+
+    std::fs::write(&path, "services:\n  worker:\n    binary: /bin/true\n").unwrap();
+    let compose = ComposeFile::from_path(&path).unwrap();
+    assert_eq!(compose.project_name(), "my-shop");
+    assert_eq!(compose.task_name("worker"), "my-shop_worker");
+
+    std::fs::write(
+        &path,
+        "name: shop\nservices:\n  worker:\n    binary: /bin/true\n",
+    )
+    .unwrap();
+    let compose = ComposeFile::from_path(&path).unwrap();
+    assert_eq!(compose.task_name("worker"), "shop_worker");
+}
+
+fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn interpolation_forms() {
+    let env = vars(&[("HOME_DIR", "/home/me"), ("EMPTY", "")]);
+    let cases = [
+        ("${HOME_DIR}/app", "/home/me/app"),
+        ("$HOME_DIR/app", "/home/me/app"),
+        ("${MISSING:-fallback}", "fallback"),
+        ("${EMPTY:-fallback}", "fallback"),
+        ("${EMPTY-fallback}", ""),
+        ("${MISSING:-${HOME_DIR}}", "/home/me"),
+        ("price: $$5", "price: $5"),
+        ("$5", "$5"),
+    ];
+    for (input, expected) in cases {
+        let (output, unset) = interpolate_str(input, &env).unwrap();
+        assert_eq!(output, expected, "input {:?}", input);
+        assert!(unset.is_empty(), "input {:?}", input);
+    }
+
+    let (output, unset) = interpolate_str("a${MISSING}b", &env).unwrap();
+    assert_eq!(output, "ab");
+    assert_eq!(unset, vec!["MISSING".to_string()]);
+
+    let err = interpolate_str("${TOKEN:?set TOKEN first}", &env).unwrap_err();
+    assert_eq!(err, "required variable TOKEN is not set: set TOKEN first");
+    let err = interpolate_str("${EMPTY:?}", &env).unwrap_err();
+    assert_eq!(err, "required variable EMPTY is empty");
+    assert!(interpolate_str("${EMPTY?}", &env).is_ok());
+    assert!(interpolate_str("${UNCLOSED", &env).is_err());
+    assert!(interpolate_str("${1BAD}", &env).is_err());
+}
+
+#[test]
+fn compose_file_interpolates_from_environment_and_project_env() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(
+        dir.path().join(".env"),
+        "# project defaults\nAPP_ROOT=/srv/app\nexport PORT=8000\nTOKEN=from-dotenv\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("api.env"), "LOG_LEVEL=debug\nPORT=1\n").unwrap();
+    let path = dir.path().join("hyperv.yaml");
+    std::fs::write(
+        &path,
+        r#"
+services:
+  api:
+    binary: ${APP_ROOT}/bin/api
+    args: ["--port", "${PORT}"]
+    workdir: ${APP_ROOT}
+    env_file: api.env
+    env:
+      PORT: "${PORT}"
+      TOKEN: ${TOKEN}
+"#,
+    )
+    .unwrap();
+
+    let compose =
+        ComposeFile::from_path_with_vars(&path, vars(&[("TOKEN", "from-shell")])).unwrap();
+    let api = &compose.services["api"];
+    assert_eq!(api.binary, "/srv/app/bin/api");
+    assert_eq!(api.args, vec!["--port", "8000"]);
+    assert_eq!(api.workdir.as_deref(), Some("/srv/app"));
+    // The invoking environment wins over .env, explicit env wins over env_file
+    assert_eq!(api.env["TOKEN"], "from-shell");
+    assert_eq!(api.env["PORT"], "8000");
+    assert_eq!(api.env["LOG_LEVEL"], "debug");
+
+    let rendered = compose.to_yaml().unwrap();
+    assert!(rendered.contains("binary: /srv/app/bin/api"));
+    assert!(rendered.contains("LOG_LEVEL: debug"));
+    let reparsed: ComposeFile = serde_yml::from_str(&rendered).unwrap();
+    assert_eq!(reparsed.services["api"].env, api.env);
+}
+
+#[test]
+fn unquoted_substitutions_can_be_booleans_and_numbers() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("hyperv.yaml");
+    std::fs::write(
+        &path,
+        r#"
+services:
+  api:
+    binary: /bin/true
+    auto_restart: ${AUTO_RESTART:-true}
+    restart_backoff:
+      max_attempts: $MAX_ATTEMPTS
+    env:
+      PORT: ${PORT}
+      DEBUG: "${AUTO_RESTART:-true}"
+    args: [$PORT, "v${PORT}"]
+"#,
+    )
+    .unwrap();
+
+    let compose =
+        ComposeFile::from_path_with_vars(&path, vars(&[("MAX_ATTEMPTS", "7"), ("PORT", "8080")]))
+            .unwrap();
+    let api = &compose.services["api"];
+    assert!(api.auto_restart);
+    assert_eq!(api.restart_backoff.max_attempts, 7);
+    // Fields that expect a string still take the value as written
+    assert_eq!(api.env["PORT"], "8080");
+    assert_eq!(api.env["DEBUG"], "true");
+    assert_eq!(api.args, vec!["8080", "v8080"]);
+
+    let err = ComposeFile::from_path_with_vars(&path, vars(&[("MAX_ATTEMPTS", "many")]))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("max_attempts"), "{}", err);
+}
+
+#[test]
+fn interpolation_errors_name_service_and_key() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("hyperv.yaml");
+    std::fs::write(
+        &path,
+        r#"
+services:
+  api:
+    binary: /bin/true
+    env:
+      DB_PASSWORD: ${DB_PASSWORD:?needed by the API}
+"#,
+    )
+    .unwrap();
+    let err = ComposeFile::from_path_with_vars(&path, HashMap::new())
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("Service \"api\", key \"env.DB_PASSWORD\""),
+        "{}",
+        err
+    );
+    assert!(err.contains("required variable DB_PASSWORD is not set: needed by the API"));
+
+    std::fs::write(
+        &path,
+        "services:\n  api:\n    binary: /bin/true\n    env_file: [missing.env]\n",
+    )
+    .unwrap();
+    let err = ComposeFile::from_path_with_vars(&path, HashMap::new())
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Service \"api\", key \"env_file\""), "{}", err);
+}
+
+#[test]
+fn services_carry_log_settings() {
+    let compose = compose_from_yaml(
+        r#"
+services:
+  api:
+    binary: /bin/true
+    logs:
+      max_size: 100MB
+      max_archives: 20
+      max_age_days: 30
+      json:
+        level: severity
+        trace_id: span.trace
+      forward:
+        - tcp://logs.internal:601
+  worker:
+    binary: /bin/true
+"#,
+    );
+    let api = compose.services["api"].task_options().log_settings;
+    assert_eq!(api.max_size, Some(100 * 1024 * 1024));
+    assert_eq!(api.max_archives, Some(20));
+    assert_eq!(api.max_age_days, Some(30));
+    assert_eq!(api.compress, None);
+    assert_eq!(api.json.level.as_deref(), Some("severity"));
+    assert_eq!(api.json.trace_id.as_deref(), Some("span.trace"));
+    assert_eq!(api.json.message, None);
+    assert_eq!(
+        api.forward_targets(&Default::default())[0].to_string(),
+        "tcp://logs.internal:601"
+    );
+    assert!(
+        compose.services["worker"]
+            .task_options()
+            .log_settings
+            .is_empty()
+    );
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("hyperv.yaml");
+    std::fs::write(
+        &path,
+        "services:\n  api:\n    binary: /bin/true\n    logs:\n      max_archives: 0\n",
+    )
+    .unwrap();
+    let error = ComposeFile::from_path(&path).unwrap_err().to_string();
+    assert!(
+        error.contains("Service \"api\", key \"logs\": max_archives must be greater than 0"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn services_mark_secret_env() {
+    let compose = compose_from_yaml(
+        r#"
+services:
+  api:
+    binary: /bin/true
+    env:
+      DSN: postgres://app:s3cret@db/app
+      DB_PASSWORD: hunter2
+      PORT: "8000"
+    secret_env: [DSN]
+"#,
+    );
+    let mut api = task("api", TaskStatus::Stopped);
+    compose.services["api"].task_options().apply_to(&mut api);
+    assert_eq!(api.secret_env, ["DSN"]);
+    assert!(api.is_secret_env("DSN"));
+    assert!(api.is_secret_env("DB_PASSWORD"));
+    assert!(!api.is_secret_env("PORT"));
+}
+
+#[test]
+fn services_run_as_a_user_and_groups() {
+    let compose = compose_from_yaml(
+        r#"
+services:
+  web:
+    binary: /bin/true
+    user: www-data
+    group: web
+    group_add: [ssl-cert, "1001"]
+"#,
+    );
+    let options = compose.services["web"].task_options();
+    assert_eq!(options.user.as_deref(), Some("www-data"));
+    assert_eq!(options.group.as_deref(), Some("web"));
+    assert_eq!(options.supplementary_groups, ["ssl-cert", "1001"]);
+
+    let mut web = task("web", TaskStatus::Stopped);
+    options.apply_to(&mut web);
+    assert_eq!(
+        web.run_as_display(),
+        "user www-data, group web, supplementary groups ssl-cert, 1001"
+    );
+}
+
+#[test]
+fn services_set_resource_limits() {
+    let compose = compose_from_yaml(
+        r#"
+services:
+  worker:
+    binary: /bin/true
+    limits:
+      open_files: 4096
+      address_space: 512MB
+      core_size: 0
+"#,
+    );
+    let options = compose.services["worker"].task_options();
+    assert_eq!(options.limits.open_files, Some(4096));
+    assert_eq!(options.limits.address_space, Some(512 * 1024 * 1024));
+    assert_eq!(options.limits.core_size, Some(0));
+    assert_eq!(options.limits.cpu_time, None);
+
+    let mut worker = task("worker", TaskStatus::Stopped);
+    options.apply_to(&mut worker);
+    assert_eq!(
+        worker.limits.to_string(),
+        "open files 4096, address space 512.0 MB, core size 0 B"
+    );
+}
+
+#[test]
+fn services_set_cgroup_limits() {
+    let compose = compose_from_yaml(
+        r#"
+services:
+  worker:
+    binary: /bin/true
+    cgroup:
+      memory_max: 1GB
+      cpu_max: 1.5
+      pids_max: 200
+"#,
+    );
+    let options = compose.services["worker"].task_options();
+    assert_eq!(options.cgroup_limits.memory_max, Some(1024 * 1024 * 1024));
+    assert_eq!(options.cgroup_limits.cpu_max, Some(1.5));
+    assert_eq!(options.cgroup_limits.pids_max, Some(200));
+}
+
+#[test]
+fn services_set_a_watchdog() {
+    let compose = compose_from_yaml(
+        r#"
+services:
+  worker:
+    binary: /bin/true
+    watchdog:
+      max_memory_mb: 512
+      max_cpu_percent: 150
+      cpu_window_secs: 30
+      action: alert
+"#,
+    );
+    let watchdog = compose.services["worker"]
+        .task_options()
+        .watchdog
+        .expect("watchdog is set");
+    assert_eq!(watchdog.max_memory_mb, Some(512));
+    assert_eq!(watchdog.max_cpu_percent, Some(150.0));
+    assert_eq!(watchdog.cpu_window_secs, 30);
+    assert_eq!(watchdog.action, hyperV::watchdog::WatchdogAction::Alert);
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("hyperv.yaml");
+    std::fs::write(
+        &path,
+        "services:\n  worker:\n    binary: /bin/true\n    watchdog:\n      action: restart\n",
+    )
+    .unwrap();
+    let err = ComposeFile::from_path(&path).unwrap_err().to_string();
+    assert!(
+        err.contains("Service \"worker\", key \"watchdog\""),
+        "{}",
+        err
+    );
+}
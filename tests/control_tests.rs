@@ -0,0 +1,61 @@
+use hyperV::control::{
+    ControlEnvelope, ControlRequest, ControlResponse, PROTOCOL_VERSION, parse_request,
+};
+
+#[test]
+fn request_envelope_round_trips_with_version() {
+    let envelope = ControlEnvelope {
+        version: PROTOCOL_VERSION,
+        request: ControlRequest::Stop {
+            task: "api".to_string(),
+        },
+    };
+
+    let line = serde_json::to_string(&envelope).unwrap();
+    assert!(line.contains(r#""version":1"#));
+    assert!(line.contains(r#""command":"stop""#));
+
+    assert_eq!(
+        parse_request(&line).unwrap(),
+        ControlRequest::Stop {
+            task: "api".to_string()
+        }
+    );
+}
+
+#[test]
+fn parses_requests_without_arguments() {
+    assert_eq!(
+        parse_request(r#"{"version":1,"command":"list"}"#).unwrap(),
+        ControlRequest::List
+    );
+    assert_eq!(
+        parse_request(r#"{"version":1,"command":"status","task":null}"#).unwrap(),
+        ControlRequest::Status { task: None }
+    );
+}
+
+#[test]
+fn rejects_unknown_or_missing_protocol_version() {
+    let err = parse_request(r#"{"version":99,"command":"list"}"#).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("Unsupported control protocol version 99")
+    );
+
+    let err = parse_request(r#"{"command":"list"}"#).unwrap_err();
+    assert!(err.to_string().contains("<missing>"));
+}
+
+#[test]
+fn rejects_unknown_commands() {
+    assert!(parse_request(r#"{"version":1,"command":"explode"}"#).is_err());
+}
+
+#[test]
+fn failure_response_becomes_error_with_daemon_message() {
+    let err = ControlResponse::failure("Task 'api' not found")
+        .into_result()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Task 'api' not found");
+}
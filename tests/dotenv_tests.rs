@@ -0,0 +1,85 @@
+use hyperV::dotenv::{self, Warning};
+use std::collections::HashMap;
+
+fn value(file: &dotenv::EnvFile, key: &str) -> Option<String> {
+    file.to_map().get(key).cloned()
+}
+
+#[test]
+fn comments_export_and_quoting() {
+    let file = dotenv::parse(
+        r#"
+# a comment=x
+export PORT=8000
+HOST = localhost   # trailing comment
+EMPTY=
+HASH=abc#def
+SINGLE='literal $PORT \n'
+DOUBLE="tab\there \"quoted\" \$PORT"
+URL=http://${HOST}:${PORT}/
+QUOTED_URL="${HOST}:$PORT"
+"#,
+    );
+    assert!(file.warnings.is_empty(), "{:?}", file.warnings);
+    assert_eq!(value(&file, "# a comment"), None);
+    assert_eq!(value(&file, "PORT").as_deref(), Some("8000"));
+    assert_eq!(value(&file, "HOST").as_deref(), Some("localhost"));
+    assert_eq!(value(&file, "EMPTY").as_deref(), Some(""));
+    assert_eq!(value(&file, "HASH").as_deref(), Some("abc#def"));
+    assert_eq!(value(&file, "SINGLE").as_deref(), Some("literal $PORT \\n"));
+    assert_eq!(
+        value(&file, "DOUBLE").as_deref(),
+        Some("tab\there \"quoted\" $PORT")
+    );
+    assert_eq!(
+        value(&file, "URL").as_deref(),
+        Some("http://localhost:8000/")
+    );
+    assert_eq!(
+        value(&file, "QUOTED_URL").as_deref(),
+        Some("localhost:8000")
+    );
+}
+
+#[test]
+fn multiline_and_escaped_newlines() {
+    let file = dotenv::parse("CERT=\"line one\nline two\"\nESCAPED=\"a\\nb\"\nAFTER=1\n");
+    assert!(file.warnings.is_empty(), "{:?}", file.warnings);
+    assert_eq!(value(&file, "CERT").as_deref(), Some("line one\nline two"));
+    assert_eq!(value(&file, "ESCAPED").as_deref(), Some("a\nb"));
+    assert_eq!(value(&file, "AFTER").as_deref(), Some("1"));
+}
+
+#[test]
+fn earlier_keys_and_defined_vars_are_expanded() {
+    let defined: HashMap<String, String> = [("BASE".to_string(), "/srv".to_string())]
+        .into_iter()
+        .collect();
+    let file = dotenv::parse_with("DATA=${BASE}/data\nLOGS=${DATA}/logs\n", &defined);
+    assert_eq!(value(&file, "LOGS").as_deref(), Some("/srv/data/logs"));
+}
+
+#[test]
+fn problems_are_reported_with_line_numbers() {
+    let file = dotenv::parse(
+        "GOOD=1\nnot a variable\n1BAD=x\nGOOD=2\nREF=${LATER}\nLATER=x\nOPEN=\"never closed\n",
+    );
+    assert_eq!(value(&file, "GOOD").as_deref(), Some("2"));
+    assert_eq!(value(&file, "REF").as_deref(), Some(""));
+    assert_eq!(value(&file, "OPEN"), None);
+
+    let lines: Vec<usize> = file.warnings.iter().map(|w| w.line).collect();
+    assert_eq!(lines, vec![2, 3, 4, 5, 7]);
+    assert_eq!(
+        file.warnings[0],
+        Warning {
+            line: 2,
+            message: "expected KEY=VALUE, found \"not a variable\"".to_string(),
+        }
+    );
+    assert_eq!(
+        file.warnings[2].to_string(),
+        "line 4: GOOD is already set on line 1; this value wins"
+    );
+    assert!(file.warnings[4].message.contains("unterminated"));
+}
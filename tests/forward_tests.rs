@@ -0,0 +1,358 @@
+use hyperV::forward::{
+    ForwardTarget, ForwardTransport, ForwardedLine, ForwardedTask, LogForwarder,
+};
+use hyperV::logs::{JsonFieldMap, LogRecord, LogSettings, LogStream};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Accept HTTP requests on `listener` and pass on their bodies
+fn http_collector(listener: TcpListener) -> Receiver<String> {
+    http_collector_answering(listener, &[])
+}
+
+/// Like `http_collector`, answering the first requests with `statuses` and the rest
+/// with `200 OK`
+fn http_collector_answering(
+    listener: TcpListener,
+    statuses: &'static [&'static str],
+) -> Receiver<String> {
+    let (sender, bodies) = mpsc::channel();
+    let requests = Arc::new(AtomicUsize::new(0));
+    thread::spawn(move || {
+        for connection in listener.incoming() {
+            let Ok(connection) = connection else { return };
+            let sender = sender.clone();
+            let requests = Arc::clone(&requests);
+            thread::spawn(move || {
+                let mut reader = BufReader::new(connection.try_clone().unwrap());
+                let mut writer = connection;
+                loop {
+                    let mut content_length = 0;
+                    let mut line = String::new();
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':')
+                            && name.eq_ignore_ascii_case("content-length")
+                        {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    let request = requests.fetch_add(1, Ordering::SeqCst);
+                    let status = statuses.get(request).copied().unwrap_or("200 OK");
+                    write!(writer, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+                    let _ = sender.send(String::from_utf8(body).unwrap());
+                }
+            });
+        }
+    });
+    bodies
+}
+
+/// Receive from `messages` until `count` lines arrived
+fn collect_lines(messages: &Receiver<String>, count: usize) -> Vec<String> {
+    let deadline = Instant::now() + Duration::from_secs(15);
+    let mut lines = Vec::new();
+    while lines.len() < count {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match messages.recv_timeout(left) {
+            Ok(message) => lines.extend(message.lines().map(str::to_string)),
+            Err(_) => panic!("received only {:?}", lines),
+        }
+    }
+    lines
+}
+
+fn forwarded_task(dir: &Path, targets: &[&str]) -> ForwardedTask {
+    ForwardedTask {
+        id: "task-1".to_string(),
+        name: "api".to_string(),
+        stdout_path: dir.join("stdout.log"),
+        stderr_path: dir.join("stderr.log"),
+        targets: targets.iter().map(|t| t.parse().unwrap()).collect(),
+        field_map: JsonFieldMap::default(),
+    }
+}
+
+fn append(path: &Path, content: &str) {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    file.write_all(content.as_bytes()).unwrap();
+}
+
+#[test]
+fn parses_forwarding_targets() {
+    let target: ForwardTarget = "udp://logs.internal".parse().unwrap();
+    assert_eq!(
+        target.transport,
+        ForwardTransport::SyslogUdp("logs.internal:514".to_string())
+    );
+    assert_eq!(target.facility, 1);
+
+    let target: ForwardTarget = "tcp://127.0.0.1:6514?facility=local3".parse().unwrap();
+    assert_eq!(
+        target.transport,
+        ForwardTransport::SyslogTcp("127.0.0.1:6514".to_string())
+    );
+    assert_eq!(target.facility, 19);
+    assert_eq!(target.to_string(), "tcp://127.0.0.1:6514?facility=local3");
+
+    let target: ForwardTarget = "unix:///dev/log".parse().unwrap();
+    assert_eq!(
+        target.transport,
+        ForwardTransport::SyslogUnix("/dev/log".into())
+    );
+    let target: ForwardTarget = "https://collector.example/ingest?token=x".parse().unwrap();
+    assert_eq!(
+        target.transport,
+        ForwardTransport::Http("https://collector.example/ingest?token=x".to_string())
+    );
+
+    for invalid in [
+        "logs.internal:514",
+        "ftp://logs.internal",
+        "udp://",
+        "unix://dev/log",
+        "udp://host?facility=local9",
+        "tcp://host?tls=1",
+    ] {
+        let error = invalid.parse::<ForwardTarget>().unwrap_err().to_string();
+        assert!(error.contains("invalid log forwarding target"), "{}", error);
+    }
+
+    let settings: LogSettings =
+        serde_yml::from_str("forward:\n  - udp://127.0.0.1:514\n  - http://127.0.0.1:9000/logs\n")
+            .unwrap();
+    assert_eq!(settings.forward_targets(&LogSettings::default()).len(), 2);
+    assert!(
+        serde_yml::to_string(&settings)
+            .unwrap()
+            .contains("- http://127.0.0.1:9000/logs")
+    );
+    assert!(serde_yml::from_str::<LogSettings>("forward: [udp:514]").is_err());
+
+    // An empty list turns off forwarding set up for every task
+    let disabled: LogSettings = serde_yml::from_str("forward: []").unwrap();
+    assert!(disabled.forward_targets(&settings).is_empty());
+    assert_eq!(
+        LogSettings::default().forward_targets(&settings),
+        settings.forward_targets(&LogSettings::default())
+    );
+}
+
+#[test]
+fn formats_rfc5424_and_json_lines() {
+    let record = LogRecord::parse(
+        "2026-05-21T07:00:00.123Z stderr ERROR disk full",
+        LogStream::Stderr,
+    );
+    let line = ForwardedLine::new("web 1", "api", record, &JsonFieldMap::default());
+    assert_eq!(line.level, Some("error"));
+    // local0 (16) * 8 + err (3)
+    assert_eq!(
+        line.to_syslog(16),
+        "<131>1 2026-05-21T07:00:00.123Z web1 api - stderr - ERROR disk full"
+    );
+    let json: serde_json::Value = serde_json::from_str(&line.to_json()).unwrap();
+    assert_eq!(json["task"], "api");
+    assert_eq!(json["stream"], "stderr");
+    assert_eq!(json["level"], "error");
+    assert_eq!(json["message"], "ERROR disk full");
+    assert_eq!(json["time"], "2026-05-21T07:00:00.123Z");
+
+    // Lines without a level: informational on stdout, errors on stderr
+    let plain = |stream| {
+        ForwardedLine::new(
+            "host",
+            "api",
+            LogRecord::parse("hello", stream),
+            &JsonFieldMap::default(),
+        )
+    };
+    assert_eq!(plain(LogStream::Stdout).severity(), 6);
+    assert_eq!(plain(LogStream::Stderr).severity(), 3);
+    let warning = ForwardedLine::new(
+        "host",
+        "api",
+        LogRecord::parse(r#"{"level":"warn","msg":"slow"}"#, LogStream::Stderr),
+        &JsonFieldMap::default(),
+    );
+    assert_eq!(warning.severity(), 4);
+}
+
+#[test]
+fn forwards_new_lines_to_syslog_and_http_collectors() {
+    let temp = TempDir::new().unwrap();
+
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let udp_address = udp.local_addr().unwrap();
+    let (udp_sender, udp_messages) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = [0; 65536];
+        while let Ok(len) = udp.recv(&mut buffer) {
+            let _ = udp_sender.send(String::from_utf8_lossy(&buffer[..len]).to_string());
+        }
+    });
+
+    let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+    let tcp_address = tcp.local_addr().unwrap();
+    let (tcp_sender, tcp_messages) = mpsc::channel();
+    thread::spawn(move || {
+        let (connection, _) = tcp.accept().unwrap();
+        let mut reader = BufReader::new(connection);
+        loop {
+            // Octet counting: "<length> <message>"
+            let mut length = Vec::new();
+            if reader.read_until(b' ', &mut length).unwrap_or(0) == 0 {
+                return;
+            }
+            let length: usize = String::from_utf8_lossy(&length).trim().parse().unwrap();
+            let mut message = vec![0; length];
+            reader.read_exact(&mut message).unwrap();
+            let _ = tcp_sender.send(String::from_utf8(message).unwrap());
+        }
+    });
+
+    let http = TcpListener::bind("127.0.0.1:0").unwrap();
+    let http_url = format!("http://{}/ingest", http.local_addr().unwrap());
+    let http_bodies = http_collector(http);
+
+    let mut forwarder = LogForwarder::start();
+    let task = forwarded_task(
+        temp.path(),
+        &[
+            &format!("udp://{}", udp_address),
+            &format!("tcp://{}?facility=daemon", tcp_address),
+            &http_url,
+        ],
+    );
+    assert!(forwarder.update(vec![task.clone()]));
+    assert!(!forwarder.update(vec![task.clone()]));
+
+    append(
+        &task.stdout_path,
+        "2026-05-21T07:00:00.000Z stdout listening on :8080\n",
+    );
+    append(
+        &task.stderr_path,
+        "2026-05-21T07:00:01.000Z stderr ERROR upstream refused\n",
+    );
+
+    let mut udp_lines = collect_lines(&udp_messages, 2);
+    udp_lines.sort();
+    assert!(
+        udp_lines[0].starts_with("<11>1 2026-05-21T07:00:01.000Z "),
+        "{:?}",
+        udp_lines
+    );
+    assert!(udp_lines[0].ends_with(" api - stderr - ERROR upstream refused"));
+    assert!(udp_lines[1].starts_with("<14>1 2026-05-21T07:00:00.000Z "));
+    assert!(udp_lines[1].ends_with(" api - stdout - listening on :8080"));
+
+    let mut tcp_lines = collect_lines(&tcp_messages, 2);
+    tcp_lines.sort();
+    // daemon (3) * 8 + err (3), and + info (6)
+    assert!(tcp_lines[0].starts_with("<27>1 "), "{:?}", tcp_lines);
+    assert!(tcp_lines[1].starts_with("<30>1 "), "{:?}", tcp_lines);
+
+    let http_lines = collect_lines(&http_bodies, 2);
+    let mut messages: Vec<String> = http_lines
+        .iter()
+        .map(|line| {
+            let json: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(json["task"], "api");
+            json["message"].as_str().unwrap().to_string()
+        })
+        .collect();
+    messages.sort();
+    assert_eq!(messages, ["ERROR upstream refused", "listening on :8080"]);
+}
+
+#[test]
+fn keeps_lines_until_the_collector_is_reachable() {
+    let temp = TempDir::new().unwrap();
+    // Find a free port, then leave it closed for now
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let task = forwarded_task(temp.path(), &[&format!("http://{}/ingest", address)]);
+
+    let mut forwarder = LogForwarder::start();
+    forwarder.update(vec![task.clone()]);
+    for index in 0..250 {
+        append(&task.stdout_path, &format!("line {}\n", index));
+    }
+    thread::sleep(Duration::from_millis(1500));
+
+    let bodies = http_collector(TcpListener::bind(address).unwrap());
+    let lines = collect_lines(&bodies, 250);
+    let messages: Vec<String> = lines
+        .iter()
+        .map(|line| {
+            let json: serde_json::Value = serde_json::from_str(line).unwrap();
+            json["message"].as_str().unwrap().to_string()
+        })
+        .collect();
+    let expected: Vec<String> = (0..250).map(|index| format!("line {}", index)).collect();
+    assert_eq!(messages, expected);
+}
+
+#[test]
+fn drops_batches_the_collector_rejects() {
+    let temp = TempDir::new().unwrap();
+    let http = TcpListener::bind("127.0.0.1:0").unwrap();
+    let task = forwarded_task(
+        temp.path(),
+        &[&format!("http://{}/ingest", http.local_addr().unwrap())],
+    );
+    let bodies = http_collector_answering(http, &["400 Bad Request"]);
+
+    let mut forwarder = LogForwarder::start();
+    forwarder.update(vec![task.clone()]);
+    append(&task.stdout_path, "malformed\n");
+    assert!(collect_lines(&bodies, 1)[0].contains("malformed"));
+
+    append(&task.stdout_path, "fine\n");
+    let lines = collect_lines(&bodies, 1);
+    assert_eq!(lines.len(), 1, "{:?}", lines);
+    assert!(lines[0].contains("fine"), "{:?}", lines);
+}
+
+#[test]
+fn truncates_long_udp_messages_at_a_character_boundary() {
+    let temp = TempDir::new().unwrap();
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let task = forwarded_task(
+        temp.path(),
+        &[&format!("udp://{}", udp.local_addr().unwrap())],
+    );
+
+    let mut forwarder = LogForwarder::start();
+    forwarder.update(vec![task.clone()]);
+    append(&task.stdout_path, &format!("{}\n", "é".repeat(40_000)));
+
+    udp.set_read_timeout(Some(Duration::from_secs(15))).unwrap();
+    let mut buffer = vec![0; 65536];
+    let len = udp.recv(&mut buffer).unwrap();
+    assert!(len <= 60_000, "{}", len);
+    let message = String::from_utf8(buffer[..len].to_vec()).unwrap();
+    assert!(message.ends_with('é'));
+}
@@ -0,0 +1,282 @@
+use chrono::Utc;
+use hyperV::compose::ComposeFile;
+use hyperV::health::{
+    HealthCheck, HealthProbe, HealthScheduler, HealthState, HealthStatus, run_probe,
+};
+use hyperV::secrets::SecretStore;
+use hyperV::{Task, TaskStatus};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+fn running_task_with_check(id: &str, pid: u32, check: HealthCheck) -> Task {
+    let mut task = Task::new(
+        id.to_string(),
+        id.to_string(),
+        "/bin/true".to_string(),
+        Vec::new(),
+        HashMap::new(),
+        None,
+        false,
+        None,
+        None,
+    );
+    task.health_check = Some(check);
+    task.set_status(TaskStatus::Running);
+    task.set_pid(Some(pid));
+    task
+}
+
+fn secret_store(temp: &TempDir) -> SecretStore {
+    SecretStore::new(temp.path().join("secrets.json"), temp.path().join("key"))
+}
+
+/// Answer every connection with the given HTTP status line
+fn serve_status(status: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+        }
+    });
+    format!("http://{}/health", addr)
+}
+
+#[test]
+fn http_probe_compares_status() {
+    let url = serve_status("503 Service Unavailable");
+    let expect_ok = HealthProbe::Http {
+        url: url.clone(),
+        expected_status: 200,
+    };
+    let err = run_probe(&expect_ok, TIMEOUT, None, &HashMap::new()).unwrap_err();
+    assert!(err.contains("503"), "{}", err);
+
+    let expect_503 = HealthProbe::Http {
+        url,
+        expected_status: 503,
+    };
+    assert!(run_probe(&expect_503, TIMEOUT, None, &HashMap::new()).is_ok());
+}
+
+#[test]
+fn tcp_probe_requires_listener() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let probe = HealthProbe::Tcp {
+        address: address.clone(),
+    };
+    assert!(run_probe(&probe, TIMEOUT, None, &HashMap::new()).is_ok());
+
+    drop(listener);
+    assert!(run_probe(&probe, TIMEOUT, None, &HashMap::new()).is_err());
+}
+
+#[test]
+fn tcp_probe_gives_up_at_its_timeout() {
+    // Name resolution and connecting share the timeout
+    let timeout = Duration::from_millis(300);
+    for address in ["localhost:1", "hyperv-probe.invalid:80"] {
+        let probe = HealthProbe::Tcp {
+            address: address.to_string(),
+        };
+        let start = Instant::now();
+        assert!(run_probe(&probe, timeout, None, &HashMap::new()).is_err());
+        assert!(
+            start.elapsed() < timeout + Duration::from_millis(200),
+            "{} took {:?}",
+            address,
+            start.elapsed()
+        );
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_probe_checks_exit_code_and_timeout() {
+    let sh = |script: &str| vec!["/bin/sh".to_string(), "-c".to_string(), script.to_string()];
+    let mut env = HashMap::new();
+    env.insert("PROBE_CODE".to_string(), "3".to_string());
+
+    let probe = HealthProbe::Exec {
+        command: sh("exit $PROBE_CODE"),
+        expected_exit_code: 3,
+    };
+    assert!(run_probe(&probe, TIMEOUT, None, &env).is_ok());
+
+    let probe = HealthProbe::Exec {
+        command: sh("exit $PROBE_CODE"),
+        expected_exit_code: 0,
+    };
+    let err = run_probe(&probe, TIMEOUT, None, &env).unwrap_err();
+    assert!(err.contains("code 3"), "{}", err);
+
+    let probe = HealthProbe::Exec {
+        command: sh("sleep 5"),
+        expected_exit_code: 0,
+    };
+    let start = Instant::now();
+    let err = run_probe(&probe, Duration::from_secs(1), None, &env).unwrap_err();
+    assert!(err.contains("timed out"), "{}", err);
+    assert!(start.elapsed() < Duration::from_secs(4));
+}
+
+#[test]
+fn consecutive_failures_make_task_unhealthy_after_retries() {
+    let mut check = HealthCheck::new(HealthProbe::Tcp {
+        address: "127.0.0.1:1".to_string(),
+    });
+    check.retries = 2;
+    let mut state = HealthState::starting();
+    let failed = Err("refused".to_string());
+    let now = Utc::now();
+
+    // Failures during the start period are not counted
+    assert!(!state.record(&check, &failed, true, now));
+    assert_eq!(state.consecutive_failures, 0);
+    assert_eq!(state.status, HealthStatus::Starting);
+
+    assert!(!state.record(&check, &failed, false, now));
+    assert!(state.record(&check, &failed, false, now));
+    assert_eq!(state.status, HealthStatus::Unhealthy);
+    // Only the transition is reported
+    assert!(!state.record(&check, &failed, false, now));
+
+    assert!(!state.record(&check, &Ok(()), false, now));
+    assert_eq!(state.status, HealthStatus::Healthy);
+    assert_eq!(state.consecutive_failures, 0);
+    assert!(state.last_error.is_none());
+}
+
+#[test]
+fn scheduler_waits_for_interval_and_in_flight_probe() {
+    let mut check = HealthCheck::new(HealthProbe::Tcp {
+        address: "127.0.0.1:1".to_string(),
+    });
+    check.interval_secs = 10;
+    let tasks = vec![running_task_with_check("api", 4242, check)];
+    let temp = TempDir::new().unwrap();
+    let mut scheduler = HealthScheduler::new(secret_store(&temp));
+    let now = Instant::now();
+
+    assert_eq!(scheduler.due_jobs(&tasks, now).len(), 1);
+    // Still in flight
+    assert!(
+        scheduler
+            .due_jobs(&tasks, now + Duration::from_secs(11))
+            .is_empty()
+    );
+    scheduler.finished("api");
+    assert!(
+        scheduler
+            .due_jobs(&tasks, now + Duration::from_secs(5))
+            .is_empty()
+    );
+    assert_eq!(
+        scheduler
+            .due_jobs(&tasks, now + Duration::from_secs(11))
+            .len(),
+        1
+    );
+
+    // A new run (new PID) is probed right away
+    scheduler.finished("api");
+    let mut restarted = tasks.clone();
+    restarted[0].set_pid(Some(4343));
+    assert_eq!(
+        scheduler
+            .due_jobs(&restarted, now + Duration::from_secs(12))
+            .len(),
+        1
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn exec_probe_sees_env_files_and_resolved_secrets() {
+    let temp = TempDir::new().unwrap();
+    let store = secret_store(&temp);
+    store.set("db_password", "s3cret").unwrap();
+    let env_file = temp.path().join("probe.env");
+    std::fs::write(&env_file, "DB_USER=app\n").unwrap();
+
+    let check = HealthCheck::new(HealthProbe::Exec {
+        command: vec![
+            "/bin/sh".to_string(),
+            "-c".to_string(),
+            r#"test "$DB_USER:$PGPASSWORD" = "app:s3cret""#.to_string(),
+        ],
+        expected_exit_code: 0,
+    });
+    let mut task = running_task_with_check("db", 4242, check);
+    task.env
+        .insert("PGPASSWORD".to_string(), "secret:db_password".to_string());
+    task.env_files = vec![env_file.to_string_lossy().to_string()];
+
+    let mut scheduler = HealthScheduler::new(store.clone());
+    let job = scheduler
+        .due_jobs(&[task.clone()], Instant::now())
+        .remove(0);
+    let result = job.run().result;
+    assert!(result.is_ok(), "{:?}", result);
+
+    // A secret that cannot be resolved fails the probe
+    task.env
+        .insert("PGPASSWORD".to_string(), "secret:missing".to_string());
+    let mut scheduler = HealthScheduler::new(store);
+    let job = scheduler.due_jobs(&[task], Instant::now()).remove(0);
+    let err = job.run().result.unwrap_err();
+    assert!(err.contains("missing"), "{}", err);
+}
+
+#[test]
+fn compose_service_accepts_health_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("hyperv.yaml");
+    std::fs::write(
+        &path,
+        r#"
+services:
+  api:
+    binary: /bin/true
+    health_check:
+      type: http
+      url: http://127.0.0.1:8080/health
+      interval_secs: 15
+      retries: 5
+  db:
+    binary: /bin/true
+    health_check:
+      type: tcp
+      address: 127.0.0.1:5432
+"#,
+    )
+    .unwrap();
+
+    let compose = ComposeFile::from_path(&path).unwrap();
+    let api = compose.services["api"].health_check.clone().unwrap();
+    assert_eq!(
+        api.probe,
+        HealthProbe::Http {
+            url: "http://127.0.0.1:8080/health".to_string(),
+            expected_status: 200,
+        }
+    );
+    assert_eq!(api.interval_secs, 15);
+    assert_eq!(api.retries, 5);
+    assert_eq!(api.timeout_secs, 5);
+
+    let db = compose.services["db"].health_check.clone().unwrap();
+    assert_eq!(db.retries, 3);
+}
@@ -0,0 +1,84 @@
+use hyperV::limits::ResourceLimits;
+
+#[test]
+fn parses_limits_with_sizes_and_skips_unset_ones() {
+    let limits: ResourceLimits =
+        serde_yml::from_str("open_files: 1024\naddress_space: 2GB\ncore_size: 0\ncpu_time: 60\n")
+            .unwrap();
+    assert_eq!(
+        limits,
+        ResourceLimits {
+            open_files: Some(1024),
+            address_space: Some(2 * 1024 * 1024 * 1024),
+            core_size: Some(0),
+            processes: None,
+            cpu_time: Some(60),
+        }
+    );
+    assert_eq!(
+        limits.to_string(),
+        "open files 1024, address space 2048.0 MB, core size 0 B, CPU time 60s"
+    );
+
+    let json = serde_json::to_value(&limits).unwrap();
+    assert!(json.get("processes").is_none());
+    let round_trip: ResourceLimits = serde_json::from_value(json).unwrap();
+    assert_eq!(round_trip, limits);
+
+    assert!(ResourceLimits::default().is_empty());
+    assert!(!limits.is_empty());
+}
+
+#[test]
+fn rejects_limits_that_prevent_any_start() {
+    let limits = ResourceLimits {
+        open_files: Some(0),
+        ..ResourceLimits::default()
+    };
+    assert!(limits.validate().unwrap_err().contains("open_files"));
+
+    // A core size of 0 only turns core dumps off
+    let no_cores = ResourceLimits {
+        core_size: Some(0),
+        ..ResourceLimits::default()
+    };
+    assert!(no_cores.validate().is_ok());
+}
+
+#[cfg(unix)]
+#[test]
+fn explains_signals_caused_by_limits() {
+    let limits = ResourceLimits {
+        address_space: Some(64 * 1024 * 1024),
+        cpu_time: Some(5),
+        ..ResourceLimits::default()
+    };
+    assert_eq!(
+        limits.violation(libc::SIGXCPU, None).as_deref(),
+        Some("CPU time limit of 5s reached")
+    );
+    assert!(
+        limits
+            .violation(libc::SIGSEGV, None)
+            .unwrap()
+            .contains("address space limit of 64.0 MB")
+    );
+    assert_eq!(limits.violation(libc::SIGTERM, None), None);
+    assert_eq!(
+        ResourceLimits::default().violation(libc::SIGXCPU, None),
+        None
+    );
+
+    // SIGKILL at the hard limit, one second after an ignored SIGXCPU
+    assert_eq!(
+        limits
+            .violation(libc::SIGKILL, Some(std::time::Duration::from_millis(6010)))
+            .as_deref(),
+        Some("CPU time limit of 5s likely reached; SIGXCPU was ignored")
+    );
+    assert_eq!(
+        limits.violation(libc::SIGKILL, Some(std::time::Duration::from_millis(300))),
+        None
+    );
+    assert_eq!(limits.violation(libc::SIGKILL, None), None);
+}
@@ -1,5 +1,9 @@
 use hyperV::constants::{MAX_LOG_ARCHIVES, MAX_LOG_SIZE};
-use hyperV::logs::{LogManager, LogType};
+use hyperV::logs::{
+    JsonFieldMap, LogFilter, LogManager, LogRecord, LogRetention, LogSettings, LogStream, LogType,
+    RotatingLogWriter, is_sensitive_name, merge_records, parse_size, parse_time_bound,
+    redact_sensitive_values,
+};
 use std::fs;
 use tempfile::TempDir;
 
@@ -85,3 +89,541 @@ fn summarizes_logs_with_counts_repeated_messages_and_redaction() {
     assert!(!formatted.contains("hunter2"));
     assert!(!formatted.contains("abc123"));
 }
+
+fn read_gzip(path: &std::path::Path) -> String {
+    use std::io::Read;
+    let mut content = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(path).unwrap())
+        .read_to_string(&mut content)
+        .unwrap();
+    content
+}
+
+/// Hands out input in small reads, like a pipe fed by a chatty process
+struct Trickle<'a>(&'a [u8]);
+
+impl std::io::Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.0.len().min(buf.len()).min(7);
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn capture_rotates_while_writing_without_losing_lines() {
+    let temp = TempDir::new().unwrap();
+    let log_path = temp.path().join("stdout.log");
+    let input: String = (0..200).map(|i| format!("line {:04}\n", i)).collect();
+
+    // Stamped lines are about 40 bytes, so a 250-byte limit rotates every few lines
+    LogManager::capture(
+        Trickle(input.as_bytes()),
+        &log_path,
+        LogStream::Stdout,
+        LogRetention {
+            max_size: 250,
+            max_archives: 3,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let mut archives = Vec::new();
+    for index in 1..=3 {
+        let archive = temp.path().join(format!("stdout.log.{index}.gz"));
+        archives.push(read_gzip(&archive));
+    }
+    assert!(!temp.path().join("stdout.log.4.gz").exists());
+    assert!(!temp.path().join("stdout.log.rotating").exists());
+
+    // Every file holds whole stamped lines, and the newest ones are all still there in order
+    let current = fs::read_to_string(&log_path).unwrap();
+    let mut kept: String = archives.iter().rev().cloned().collect();
+    kept.push_str(&current);
+    let messages: String = kept
+        .lines()
+        .map(|line| {
+            let record = LogRecord::parse(line, LogStream::Stderr);
+            assert!(record.time.is_some());
+            assert_eq!(record.stream, LogStream::Stdout);
+            format!("{}\n", record.message)
+        })
+        .collect();
+    assert!(messages.ends_with("line 0199\n"));
+    assert!(input.ends_with(&messages));
+    for file in archives.iter().chain([&current]) {
+        assert!(file.is_empty() || file.ends_with('\n'));
+        assert!(file.len() <= 250 + 100);
+    }
+}
+
+#[test]
+fn rotating_writer_splits_at_line_breaks() {
+    let temp = TempDir::new().unwrap();
+    let log_path = temp.path().join("stderr.log");
+    let retention = LogRetention {
+        max_size: 8,
+        max_archives: 2,
+        ..Default::default()
+    };
+    let mut writer = RotatingLogWriter::open(&log_path, retention).unwrap();
+
+    // Crossing the limit mid-line rotates after the last complete line that fits
+    writer.write(b"aaaa\nbbbb\ncc").unwrap();
+    assert_eq!(
+        fs::read_to_string(&log_path).unwrap(),
+        "bbbb\ncc",
+        "a line that does not fit starts the next file"
+    );
+    // A line that outgrows the file is kept whole, then the file is rotated
+    writer.write(b"c").unwrap();
+    writer.write(b"c\n").unwrap();
+    writer.write(b"dd\n").unwrap();
+    writer.finish().unwrap();
+
+    assert_eq!(read_gzip(&temp.path().join("stderr.log.2.gz")), "aaaa\n");
+    assert_eq!(
+        read_gzip(&temp.path().join("stderr.log.1.gz")),
+        "bbbb\ncccc\n"
+    );
+    assert_eq!(fs::read_to_string(&log_path).unwrap(), "dd\n");
+}
+
+#[test]
+fn archives_stay_readable_while_they_are_compressed() {
+    let temp = TempDir::new().unwrap();
+    let log_path = temp.path().join("stdout.log");
+    let retention = LogRetention {
+        max_size: 8,
+        max_archives: 2,
+        ..Default::default()
+    };
+    let read = || -> Vec<String> {
+        LogManager::read_records(&log_path, LogStream::Stdout, 10)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.message)
+            .collect()
+    };
+
+    let mut writer = RotatingLogWriter::open(&log_path, retention).unwrap();
+    writer.write(b"aaaa\nbbbb\n").unwrap();
+    // Wherever the compression of the first archive is, its lines are there once
+    assert_eq!(read(), vec!["aaaa", "bbbb"]);
+    writer.finish().unwrap();
+    assert_eq!(read(), vec!["aaaa", "bbbb"]);
+    assert!(!temp.path().join("stdout.log.1").exists());
+
+    // Both the plain archive and its finished gzip are left when compression is cut short
+    fs::write(temp.path().join("stdout.log.1"), "aaaa\n").unwrap();
+    assert_eq!(read(), vec!["aaaa", "bbbb"]);
+}
+
+fn write_gzip(path: &std::path::Path, content: &str) {
+    use std::io::Write;
+    let mut encoder =
+        flate2::write::GzEncoder::new(fs::File::create(path).unwrap(), Default::default());
+    encoder.write_all(content.as_bytes()).unwrap();
+    encoder.finish().unwrap();
+}
+
+#[test]
+fn parses_stamped_and_plain_lines() {
+    let record = LogRecord::parse(
+        "2026-05-21T07:00:00.123Z stderr disk almost full",
+        LogStream::Stdout,
+    );
+    assert_eq!(
+        record.time.unwrap().to_rfc3339(),
+        "2026-05-21T07:00:00.123+00:00"
+    );
+    assert_eq!(record.stream, LogStream::Stderr);
+    assert_eq!(record.message, "disk almost full");
+    assert!(record.display(true).ends_with("] [ERR] disk almost full"));
+
+    let empty = LogRecord::parse("2026-05-21T07:00:00.123Z stdout", LogStream::Stdout);
+    assert_eq!(empty.message, "");
+
+    let plain = LogRecord::parse("2026 was a good year", LogStream::Stdout);
+    assert_eq!(plain.time, None);
+    assert_eq!(plain.message, "2026 was a good year");
+    assert_eq!(plain.display(true), "[OUT] 2026 was a good year");
+}
+
+#[test]
+fn merges_streams_chronologically_across_archives() {
+    let temp = TempDir::new().unwrap();
+    let stdout_path = temp.path().join("stdout.log");
+    let stderr_path = temp.path().join("stderr.log");
+
+    write_gzip(
+        &temp.path().join("stdout.log.1.gz"),
+        "2026-05-21T07:00:01.000Z stdout one\n2026-05-21T07:00:03.000Z stdout three\n",
+    );
+    fs::write(
+        &stdout_path,
+        "2026-05-21T07:00:05.000Z stdout five\nplain continuation\n",
+    )
+    .unwrap();
+    fs::write(
+        &stderr_path,
+        "2026-05-21T07:00:02.000Z stderr two\n2026-05-21T07:00:04.000Z stderr four\n2026-05-21T07:00:06.000Z stderr six\n",
+    )
+    .unwrap();
+
+    let stdout = LogManager::read_records(&stdout_path, LogStream::Stdout, 4).unwrap();
+    assert_eq!(stdout.len(), 4);
+    assert_eq!(stdout[0].message, "one");
+
+    let merged = merge_records(
+        stdout,
+        LogManager::read_records(&stderr_path, LogStream::Stderr, 4).unwrap(),
+    );
+    let messages: Vec<&str> = merged.iter().map(|r| r.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "one",
+            "two",
+            "three",
+            "four",
+            "five",
+            "plain continuation",
+            "six"
+        ]
+    );
+    assert_eq!(merged[5].stream, LogStream::Stdout);
+}
+
+#[test]
+fn summary_reports_when_errors_happened() {
+    let temp = TempDir::new().unwrap();
+    let stdout_path = temp.path().join("stdout.log");
+    let stderr_path = temp.path().join("stderr.log");
+    fs::write(&stdout_path, "").unwrap();
+    fs::write(
+        &stderr_path,
+        "2026-05-21T07:00:02.000Z stderr ERROR second\n",
+    )
+    .unwrap();
+    write_gzip(
+        &temp.path().join("stderr.log.1.gz"),
+        "2026-05-21T07:00:01.000Z stderr ERROR first\n",
+    );
+
+    let formatted = LogManager::summarize_logs(&stdout_path, &stderr_path, LogType::Both)
+        .unwrap()
+        .format();
+    let first = formatted.find("ERROR first").unwrap();
+    let second = formatted.find("ERROR second").unwrap();
+    assert!(first < second, "{}", formatted);
+    assert!(formatted.contains("2026-05-2"));
+}
+
+#[test]
+fn summary_groups_structured_errors_by_logger_and_type() {
+    let temp = TempDir::new().unwrap();
+    let stdout_path = temp.path().join("stdout.log");
+    let stderr_path = temp.path().join("stderr.log");
+    let lines = [
+        // Go zap
+        r#"{"level":"error","ts":1779346800.5,"logger":"db","msg":"query failed","error":"context deadline exceeded"}"#,
+        r#"{"level":"info","ts":1779346801.0,"logger":"http","msg":"listening"}"#,
+        // Rust tracing-subscriber
+        r#"{"timestamp":"2026-05-21T07:00:02.000Z","level":"ERROR","fields":{"message":"pool exhausted","error_type":"PoolTimeout"},"target":"db"}"#,
+        r#"{"timestamp":"2026-05-21T07:00:03.000Z","level":"WARN","fields":{"message":"slow request"},"target":"api::handlers"}"#,
+        // Serilog compact JSON; Information events have no @l
+        r#"{"@t":"2026-05-21T07:00:04.000Z","@mt":"Order {Id} failed","@l":"Error","@x":"System.TimeoutException: The operation has timed out.\n   at Api.Orders","SourceContext":"Api.Orders","Id":7}"#,
+        r#"{"@t":"2026-05-21T07:00:05.000Z","@mt":"Started","SourceContext":"Api.Program"}"#,
+    ];
+    fs::write(&stdout_path, lines.join("\n") + "\n").unwrap();
+    fs::write(&stderr_path, "").unwrap();
+
+    let summary = LogManager::summarize_logs(&stdout_path, &stderr_path, LogType::Both).unwrap();
+    assert_eq!(summary.error_count, 3);
+    assert_eq!(summary.warning_count, 1);
+    assert_eq!(summary.info_count, 2);
+
+    let formatted = summary.format();
+    assert!(
+        formatted.contains("Errors by logger:\n- 2x db\n- 1x Api.Orders\n"),
+        "{}",
+        formatted
+    );
+    assert!(formatted.contains("- 1x PoolTimeout"), "{}", formatted);
+    assert!(
+        formatted.contains("- 1x System.TimeoutException"),
+        "{}",
+        formatted
+    );
+    assert!(formatted.contains("ERROR query failed"), "{}", formatted);
+    assert!(
+        formatted.contains("ERROR Order {Id} failed"),
+        "{}",
+        formatted
+    );
+}
+
+#[test]
+fn json_field_filters_follow_the_task_field_mapping() {
+    let temp = TempDir::new().unwrap();
+    let log_path = temp.path().join("stdout.log");
+    fs::write(
+        &log_path,
+        concat!(
+            r#"{"severity":"ERROR","text":"payment declined","span":{"trace":"t-1"},"request_id":"abc"}"#,
+            "\n",
+            r#"{"severity":"INFO","text":"payment accepted","span":{"trace":"t-2"},"request_id":"def","retry":false}"#,
+            "\n",
+            "plain line mentioning request_id=abc\n",
+        ),
+    )
+    .unwrap();
+    let messages = |filter: &LogFilter| -> Vec<String> {
+        LogManager::search_records(&log_path, LogStream::Stdout, filter, 10)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.message)
+            .collect()
+    };
+    let with_fields = |fields: &[&str]| {
+        let fields: Vec<String> = fields.iter().map(|f| f.to_string()).collect();
+        LogFilter::new(None, None, None, None)
+            .unwrap()
+            .with_json_fields(&fields)
+            .unwrap()
+    };
+
+    let by_request = messages(&with_fields(&["request_id=abc"]));
+    assert_eq!(by_request.len(), 1);
+    assert!(by_request[0].contains("payment declined"));
+    assert_eq!(
+        messages(&with_fields(&["span.trace=t-2", "retry=false"])).len(),
+        1
+    );
+    assert!(messages(&with_fields(&["request_id=abc", "retry=false"])).is_empty());
+
+    // Well-known names need the mapping when a logger uses its own keys
+    assert!(messages(&with_fields(&["trace_id=t-1"])).is_empty());
+    let mut field_map = JsonFieldMap::default();
+    field_map.set("level=severity").unwrap();
+    field_map.set("trace_id=span.trace").unwrap();
+    field_map.set("message=text").unwrap();
+    let mapped = with_fields(&["trace_id=t-1", "message=payment declined"])
+        .with_field_map(field_map.clone());
+    assert_eq!(messages(&mapped).len(), 1);
+
+    let errors = LogFilter::new(None, None, None, Some("error"))
+        .unwrap()
+        .with_field_map(field_map.clone());
+    let errors = messages(&errors);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("payment declined"));
+
+    assert!(field_map.set("severity=level").is_err());
+    assert!(field_map.set("level").is_err());
+    assert!(
+        with_fields(&[])
+            .with_json_fields(&["=x".to_string()])
+            .is_err()
+    );
+}
+
+#[test]
+fn parses_absolute_and_relative_time_bounds() {
+    let now = chrono::DateTime::parse_from_rfc3339("2026-05-21T12:00:00Z")
+        .unwrap()
+        .with_timezone(&chrono::Utc);
+    let at = |input: &str| parse_time_bound(input, now).unwrap().to_rfc3339();
+
+    assert_eq!(at("2026-05-21T09:30:00+02:00"), "2026-05-21T07:30:00+00:00");
+    assert_eq!(at("15m"), "2026-05-21T11:45:00+00:00");
+    assert_eq!(at("1h30m"), "2026-05-21T10:30:00+00:00");
+    assert_eq!(at("2d"), "2026-05-19T12:00:00+00:00");
+    for invalid in ["", "15", "m", "15x", "yesterday"] {
+        assert!(parse_time_bound(invalid, now).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn filters_search_archives_and_the_current_file() {
+    let temp = TempDir::new().unwrap();
+    let log_path = temp.path().join("stderr.log");
+    write_gzip(
+        &temp.path().join("stderr.log.2.gz"),
+        "2026-05-21T07:00:01.000Z stderr ERROR db timeout\n",
+    );
+    write_gzip(
+        &temp.path().join("stderr.log.1.gz"),
+        "2026-05-21T07:00:02.000Z stderr WARN slow query\n2026-05-21T07:00:03.000Z stderr INFO db reconnected\n",
+    );
+    fs::write(
+        &log_path,
+        "2026-05-21T07:00:04.000Z stderr {\"level\":\"error\",\"msg\":\"db gone\"}\n  at db.rs:10\n2026-05-21T07:00:05.000Z stderr done\n",
+    )
+    .unwrap();
+    let search = |filter: LogFilter, count: usize| -> Vec<String> {
+        LogManager::search_records(&log_path, LogStream::Stderr, &filter, count)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.message)
+            .collect()
+    };
+
+    let grep = LogFilter::new(None, None, Some("^(ERROR )?db"), None).unwrap();
+    assert_eq!(search(grep.clone(), 10), vec!["ERROR db timeout"]);
+    let grep = LogFilter::new(None, None, Some("db"), None).unwrap();
+    assert_eq!(
+        search(grep, 2),
+        vec!["{\"level\":\"error\",\"msg\":\"db gone\"}", "  at db.rs:10"]
+    );
+
+    let warnings = LogFilter::new(None, None, None, Some("warn")).unwrap();
+    assert_eq!(
+        search(warnings, 10),
+        vec![
+            "ERROR db timeout",
+            "WARN slow query",
+            "{\"level\":\"error\",\"msg\":\"db gone\"}"
+        ]
+    );
+
+    // The continuation line has no time of its own and goes with the record before it
+    let window = LogFilter::new(
+        Some("2026-05-21T07:00:03Z"),
+        Some("2026-05-21T07:00:04Z"),
+        None,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        search(window, 10),
+        vec![
+            "INFO db reconnected",
+            "{\"level\":\"error\",\"msg\":\"db gone\"}",
+            "  at db.rs:10"
+        ]
+    );
+
+    assert!(LogFilter::new(None, None, Some("("), None).is_err());
+    assert!(LogFilter::new(None, None, None, Some("loud")).is_err());
+}
+
+#[test]
+fn log_settings_fall_back_to_daemon_defaults() {
+    let task: LogSettings = serde_yml::from_str("max_size: 50MB\ncompress: false\n").unwrap();
+    let daemon: LogSettings =
+        serde_yml::from_str("max_size: 1048576\nmax_archives: 10\nmax_age_days: 14\n").unwrap();
+
+    assert_eq!(
+        task.resolve(&daemon),
+        LogRetention {
+            max_size: 50 * 1024 * 1024,
+            max_archives: 10,
+            max_age_days: Some(14),
+            compress: false,
+        }
+    );
+    assert_eq!(
+        LogSettings::default().resolve(&LogSettings::default()),
+        LogRetention::default()
+    );
+
+    assert_eq!(parse_size("512K").unwrap(), 512 * 1024);
+    assert_eq!(parse_size("1 GiB").unwrap(), 1024 * 1024 * 1024);
+    assert!(parse_size("12 parsecs").is_err());
+    assert!(serde_yml::from_str::<LogSettings>("max_size: lots").is_err());
+    let zero: LogSettings = serde_yml::from_str("max_archives: 0").unwrap();
+    assert!(zero.validate().is_err());
+}
+
+#[test]
+fn uncompressed_rotation_and_pruning_by_count_and_age() {
+    let temp = TempDir::new().unwrap();
+    let log_path = temp.path().join("stdout.log");
+    let retention = LogRetention {
+        max_size: 16,
+        max_archives: 2,
+        max_age_days: Some(7),
+        compress: false,
+    };
+
+    let mut writer = RotatingLogWriter::open(&log_path, retention).unwrap();
+    for line in 1..=8 {
+        writer
+            .write(format!("line-{:02}\n", line).as_bytes())
+            .unwrap();
+    }
+    writer.finish().unwrap();
+
+    // Plain archives, newest first, bounded by max_archives
+    assert_eq!(
+        fs::read_to_string(temp.path().join("stdout.log.1")).unwrap(),
+        "line-05\nline-06\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp.path().join("stdout.log.2")).unwrap(),
+        "line-03\nline-04\n"
+    );
+    assert!(!temp.path().join("stdout.log.3").exists());
+    assert!(!temp.path().join("stdout.log.1.gz").exists());
+
+    // Readers see plain and compressed archives alike
+    write_gzip(&temp.path().join("stdout.log.3.gz"), "ancient\n");
+    let records = LogManager::read_records(&log_path, LogStream::Stdout, 10).unwrap();
+    let messages: Vec<&str> = records.iter().map(|r| r.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "ancient", "line-03", "line-04", "line-05", "line-06", "line-07", "line-08"
+        ]
+    );
+
+    // Beyond max_archives, or older than max_age_days
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(8 * 24 * 60 * 60);
+    fs::File::options()
+        .write(true)
+        .open(temp.path().join("stdout.log.2"))
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+    let removed: Vec<String> = LogManager::prune_archives(&log_path, &retention)
+        .unwrap()
+        .into_iter()
+        .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(removed, vec!["stdout.log.2", "stdout.log.3.gz"]);
+    assert!(temp.path().join("stdout.log.1").exists());
+    assert!(log_path.exists());
+}
+
+#[test]
+fn recognizes_secret_variable_names() {
+    for name in [
+        "DATABASE_PASSWORD",
+        "GITHUB_API_KEY",
+        "api-key",
+        "STRIPE_SECRET",
+        "auth.token",
+        "APIKEY",
+        "Gemini__ApiKey",
+        "surrealPassword",
+    ] {
+        assert!(is_sensitive_name(name), "{}", name);
+    }
+    for name in [
+        "MONKEY",
+        "KEYBOARD_LAYOUT",
+        "TOKENIZER_PATH_X",
+        "LOG_LEVEL",
+        "MonkeyBusiness",
+    ] {
+        assert!(!is_sensitive_name(name), "{}", name);
+    }
+    assert_eq!(
+        redact_sensitive_values("--token=abc123 --name api"),
+        "--token=[REDACTED] --name api"
+    );
+}
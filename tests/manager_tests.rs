@@ -1,3 +1,6 @@
+use hyperV::compose::{ComposeFile, PlanAction};
+use hyperV::health::{HealthCheck, HealthProbe, HealthProbeResult, HealthStatus};
+use hyperV::task::{RestartBackoff, RestartPolicy, TaskOptions};
 use hyperV::{Task, TaskManager, TaskStatus};
 use std::sync::{Mutex, OnceLock};
 use tempfile::TempDir;
@@ -110,3 +113,530 @@ fn exhausted_auto_restart_task_is_not_restarted_again() {
         assert!(tasks[0].pid.is_none());
     });
 }
+
+fn create_with_policy(
+    manager: &mut TaskManager,
+    name: &str,
+    policy: RestartPolicy,
+    success_exit_codes: Vec<i32>,
+) {
+    let sleep_bin = bin_path("/bin/sleep", "/usr/bin/sleep");
+    manager
+        .create_task_with_options(
+            name.to_string(),
+            sleep_bin.to_string(),
+            vec!["30".to_string()],
+            Vec::new(),
+            None,
+            TaskOptions {
+                restart_policy: policy,
+                success_exit_codes,
+                ..TaskOptions::default()
+            },
+        )
+        .unwrap();
+}
+
+fn stop_all(manager: &mut TaskManager) {
+    let names: Vec<String> = manager.tasks().iter().map(|t| t.name.clone()).collect();
+    for name in names {
+        let _ = manager.stop_task(&name);
+    }
+}
+
+#[test]
+fn legacy_auto_restart_task_loads_with_on_failure_policy() {
+    with_temp_config(|temp| {
+        let true_bin = bin_path("/bin/true", "/usr/bin/true");
+        let mut manager = TaskManager::new().unwrap();
+        manager
+            .create_task(
+                "legacy".to_string(),
+                true_bin.to_string(),
+                Vec::new(),
+                Vec::new(),
+                None,
+                true,
+            )
+            .unwrap();
+
+        // Rewrite the file the way versions without restart policies stored it
+        let tasks_path = temp.path().join("tasks.json");
+        let mut tasks: Vec<serde_json::Value> =
+            serde_json::from_str(&std::fs::read_to_string(&tasks_path).unwrap()).unwrap();
+        let task = tasks[0].as_object_mut().unwrap();
+        task.remove("restart_policy");
+        task.remove("success_exit_codes");
+        std::fs::write(&tasks_path, serde_json::to_string_pretty(&tasks).unwrap()).unwrap();
+
+        let manager = TaskManager::new().unwrap();
+        let task = manager.find_task("legacy").unwrap();
+        assert_eq!(task.restart_policy, RestartPolicy::OnFailure);
+        assert!(task.success_exit_codes.is_empty());
+        assert!(manager.any_autorestart_enabled());
+    });
+}
+
+#[test]
+fn on_failure_policy_honours_success_exit_codes() {
+    with_temp_config(|temp| {
+        let mut manager = TaskManager::new().unwrap();
+        create_with_policy(&mut manager, "graceful", RestartPolicy::OnFailure, vec![3]);
+        create_with_policy(&mut manager, "crashed", RestartPolicy::OnFailure, vec![3]);
+
+        let tasks_path = temp.path().join("tasks.json");
+        let mut tasks = read_tasks(temp);
+        tasks[0].status = TaskStatus::Exited;
+        tasks[0].last_exit_code = Some(3);
+        tasks[1].status = TaskStatus::Failed;
+        tasks[1].last_exit_code = Some(1);
+        std::fs::write(&tasks_path, serde_json::to_string_pretty(&tasks).unwrap()).unwrap();
+
+        manager.check_and_restart_tasks().unwrap();
+
+        let graceful = manager.find_task("graceful").unwrap();
+        assert_eq!(graceful.status, TaskStatus::Exited);
+        assert_eq!(graceful.restart_count, 0);
+        let crashed = manager.find_task("crashed").unwrap();
+        assert_eq!(crashed.status, TaskStatus::Running);
+        assert_eq!(crashed.restart_count, 1);
+
+        stop_all(&mut manager);
+    });
+}
+
+#[test]
+fn always_policy_restarts_after_success_exit() {
+    with_temp_config(|temp| {
+        let mut manager = TaskManager::new().unwrap();
+        create_with_policy(&mut manager, "worker", RestartPolicy::Always, Vec::new());
+
+        let tasks_path = temp.path().join("tasks.json");
+        let mut tasks = read_tasks(temp);
+        tasks[0].status = TaskStatus::Exited;
+        tasks[0].last_exit_code = Some(0);
+        std::fs::write(&tasks_path, serde_json::to_string_pretty(&tasks).unwrap()).unwrap();
+
+        manager.check_and_restart_tasks().unwrap();
+        assert_eq!(
+            manager.find_task("worker").unwrap().status,
+            TaskStatus::Running
+        );
+
+        stop_all(&mut manager);
+    });
+}
+
+#[test]
+fn daemon_startup_resumes_always_but_not_unless_stopped_tasks() {
+    with_temp_config(|_temp| {
+        let mut manager = TaskManager::new().unwrap();
+        create_with_policy(&mut manager, "always", RestartPolicy::Always, Vec::new());
+        create_with_policy(
+            &mut manager,
+            "unless",
+            RestartPolicy::UnlessStopped,
+            Vec::new(),
+        );
+        create_with_policy(&mut manager, "never-run", RestartPolicy::Always, Vec::new());
+
+        for name in ["always", "unless"] {
+            manager.start_task(name).unwrap();
+            manager.stop_task(name).unwrap();
+        }
+
+        // Manual stops must survive the regular restart check
+        manager.check_and_restart_tasks().unwrap();
+        assert_eq!(
+            manager.find_task("unless").unwrap().status,
+            TaskStatus::Stopped
+        );
+        assert_eq!(
+            manager.find_task("always").unwrap().status,
+            TaskStatus::Stopped
+        );
+
+        manager.start_always_tasks().unwrap();
+        assert_eq!(
+            manager.find_task("always").unwrap().status,
+            TaskStatus::Running
+        );
+        assert_eq!(
+            manager.find_task("unless").unwrap().status,
+            TaskStatus::Stopped
+        );
+        assert_eq!(
+            manager.find_task("never-run").unwrap().status,
+            TaskStatus::Stopped
+        );
+
+        stop_all(&mut manager);
+    });
+}
+
+#[test]
+fn restart_backoff_grows_and_is_capped() {
+    let backoff = RestartBackoff {
+        initial_delay_secs: 2,
+        multiplier: 3.0,
+        max_delay_secs: 30,
+        ..RestartBackoff::default()
+    };
+    let delays: Vec<u64> = (0..5).map(|n| backoff.delay_for(n).as_secs()).collect();
+    assert_eq!(delays, vec![2, 6, 18, 30, 30]);
+
+    let unbounded = RestartBackoff {
+        initial_delay_secs: u64::MAX,
+        multiplier: f64::INFINITY,
+        max_delay_secs: u64::MAX,
+        ..RestartBackoff::default()
+    };
+    assert_eq!(unbounded.delay_for(3), std::time::Duration::MAX);
+    assert!(
+        unbounded
+            .validate()
+            .unwrap_err()
+            .contains("initial_delay_secs")
+    );
+    assert!(RestartBackoff::default().validate().is_ok());
+}
+
+#[test]
+fn huge_backoff_postpones_the_restart_without_overflowing() {
+    let mut task = Task::new(
+        "id".to_string(),
+        "patient".to_string(),
+        "/bin/false".to_string(),
+        Vec::new(),
+        Default::default(),
+        None,
+        true,
+        None,
+        None,
+    );
+    // Written by a version that did not validate the backoff
+    task.restart_backoff.initial_delay_secs = 20_000_000_000_000;
+    task.restart_backoff.max_delay_secs = u64::MAX;
+    task.set_status(TaskStatus::Failed);
+    task.record_exit(Some(1), None, None);
+
+    assert_eq!(
+        task.next_restart_at(),
+        Some(chrono::DateTime::<chrono::Utc>::MAX_UTC)
+    );
+}
+
+#[test]
+fn failed_task_waits_for_its_backoff_before_restarting() {
+    with_temp_config(|temp| {
+        let mut manager = TaskManager::new().unwrap();
+        create_with_policy(&mut manager, "flaky", RestartPolicy::OnFailure, Vec::new());
+
+        let tasks_path = temp.path().join("tasks.json");
+        let mut tasks = read_tasks(temp);
+        tasks[0].status = TaskStatus::Failed;
+        tasks[0].last_exit_code = Some(1);
+        tasks[0].restart_count = 2;
+        tasks[0].restart_backoff.initial_delay_secs = 10;
+        tasks[0].last_exit_at = Some(chrono::Utc::now().to_rfc3339());
+        std::fs::write(&tasks_path, serde_json::to_string_pretty(&tasks).unwrap()).unwrap();
+
+        manager.check_and_restart_tasks().unwrap();
+        let task = manager.find_task("flaky").unwrap();
+        assert_eq!(task.status, TaskStatus::Failed);
+        assert_eq!(task.restart_count, 2);
+        // Third attempt: 10s * 2^2
+        let wait = manager.next_restart_in().unwrap();
+        assert!(wait > std::time::Duration::from_secs(35), "{:?}", wait);
+
+        let mut tasks = read_tasks(temp);
+        tasks[0].last_exit_at =
+            Some((chrono::Utc::now() - chrono::Duration::seconds(41)).to_rfc3339());
+        std::fs::write(&tasks_path, serde_json::to_string_pretty(&tasks).unwrap()).unwrap();
+
+        manager.check_and_restart_tasks().unwrap();
+        let task = manager.find_task("flaky").unwrap();
+        assert_eq!(task.status, TaskStatus::Running);
+        assert_eq!(task.restart_count, 3);
+
+        stop_all(&mut manager);
+    });
+}
+
+#[test]
+fn stable_run_resets_restart_count() {
+    let mut task = Task::new(
+        "id".to_string(),
+        "stable".to_string(),
+        "/bin/true".to_string(),
+        Vec::new(),
+        Default::default(),
+        None,
+        true,
+        None,
+        None,
+    );
+    task.restart_count = 4;
+    task.restart_backoff.stable_after_secs = 60;
+
+    task.last_started = Some((chrono::Utc::now() - chrono::Duration::seconds(10)).to_rfc3339());
+    task.record_exit(Some(1), None, None);
+    assert_eq!(task.restart_count, 4);
+
+    task.last_started = Some((chrono::Utc::now() - chrono::Duration::seconds(90)).to_rfc3339());
+    task.record_exit(Some(1), None, None);
+    assert_eq!(task.restart_count, 0);
+}
+
+#[test]
+fn unhealthy_task_is_restarted_per_policy() {
+    with_temp_config(|_temp| {
+        let mut manager = TaskManager::new().unwrap();
+        let sleep_bin = bin_path("/bin/sleep", "/usr/bin/sleep");
+        let mut check = HealthCheck::new(HealthProbe::Tcp {
+            address: "127.0.0.1:1".to_string(),
+        });
+        check.retries = 2;
+        manager
+            .create_task_with_options(
+                "web".to_string(),
+                sleep_bin.to_string(),
+                vec!["30".to_string()],
+                Vec::new(),
+                None,
+                TaskOptions {
+                    restart_policy: RestartPolicy::OnFailure,
+                    health_check: Some(check),
+                    ..TaskOptions::default()
+                },
+            )
+            .unwrap();
+        manager.start_task("web").unwrap();
+
+        let task = manager.find_task("web").unwrap().clone();
+        assert_eq!(task.health.as_ref().unwrap().status, HealthStatus::Starting);
+        let failed = HealthProbeResult {
+            task_id: task.id.clone(),
+            pid: task.pid.unwrap(),
+            result: Err("connection refused".to_string()),
+        };
+
+        assert!(manager.record_health_result(&failed).unwrap().is_none());
+        let unhealthy = manager
+            .record_health_result(&failed)
+            .unwrap()
+            .expect("second failure should make the task unhealthy");
+        assert_eq!(
+            unhealthy.health.as_ref().unwrap().status,
+            HealthStatus::Unhealthy
+        );
+
+        manager.restart_unhealthy_task(&task.id).unwrap();
+        let restarted = manager.find_task("web").unwrap();
+        assert_eq!(restarted.status, TaskStatus::Running);
+        assert_ne!(restarted.pid, task.pid);
+        assert_eq!(restarted.restart_count, 1);
+        assert_eq!(
+            restarted.health.as_ref().unwrap().status,
+            HealthStatus::Starting
+        );
+
+        // Results from the previous run are ignored
+        assert!(manager.record_health_result(&failed).unwrap().is_none());
+
+        stop_all(&mut manager);
+    });
+}
+
+#[test]
+fn env_files_are_read_at_start_and_task_env_wins() {
+    with_temp_config(|temp| {
+        let sh_bin = bin_path("/bin/sh", "/usr/bin/sh");
+        let workdir = temp.path().join("app");
+        std::fs::create_dir(&workdir).unwrap();
+        let env_file = workdir.join(".env");
+        std::fs::write(&env_file, "# settings\nGREETING=hello\nNAME=file\n").unwrap();
+
+        let mut manager = TaskManager::new().unwrap();
+        manager
+            .create_task_with_options(
+                "env".to_string(),
+                sh_bin.to_string(),
+                vec![
+                    "-c".to_string(),
+                    "echo \"$GREETING $NAME\" > out.txt".to_string(),
+                ],
+                vec!["NAME=task".to_string()],
+                Some(workdir.to_string_lossy().to_string()),
+                TaskOptions::default(),
+            )
+            .unwrap();
+
+        // The workdir .env is recorded explicitly, not copied into the task
+        let task = manager.find_task("env").unwrap();
+        assert_eq!(task.env_files, vec![env_file.to_string_lossy().to_string()]);
+        assert!(!task.env.contains_key("GREETING"));
+
+        // Edits to the file apply on the next start
+        std::fs::write(&env_file, "GREETING=hi\n").unwrap();
+        manager.start_task("env").unwrap();
+        let out = workdir.join("out.txt");
+        for _ in 0..100 {
+            if std::fs::read_to_string(&out).is_ok_and(|s| s.ends_with('\n')) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "hi task\n");
+
+        std::fs::remove_file(&env_file).unwrap();
+        manager.refresh_task_statuses().unwrap();
+        let err = manager.start_task("env").unwrap_err().to_string();
+        assert!(err.contains("Env file not found"), "{}", err);
+    });
+}
+
+#[test]
+fn legacy_task_records_its_workdir_env_file() {
+    with_temp_config(|temp| {
+        let true_bin = bin_path("/bin/true", "/usr/bin/true");
+        let mut manager = TaskManager::new().unwrap();
+        manager
+            .create_task(
+                "legacy".to_string(),
+                true_bin.to_string(),
+                Vec::new(),
+                Vec::new(),
+                Some(temp.path().to_string_lossy().to_string()),
+                false,
+            )
+            .unwrap();
+        assert!(manager.find_task("legacy").unwrap().env_files.is_empty());
+
+        // Older versions read workdir/.env implicitly and had no env_files key
+        std::fs::write(temp.path().join(".env"), "A=1\n").unwrap();
+        let tasks_path = temp.path().join("tasks.json");
+        let mut tasks: Vec<serde_json::Value> =
+            serde_json::from_str(&std::fs::read_to_string(&tasks_path).unwrap()).unwrap();
+        tasks[0].as_object_mut().unwrap().remove("env_files");
+        std::fs::write(&tasks_path, serde_json::to_string_pretty(&tasks).unwrap()).unwrap();
+
+        let manager = TaskManager::new().unwrap();
+        let task = manager.find_task("legacy").unwrap();
+        assert_eq!(
+            task.env_files,
+            vec![temp.path().join(".env").to_string_lossy().to_string()]
+        );
+    });
+}
+
+#[test]
+fn up_adopts_tasks_created_before_names_were_namespaced() {
+    with_temp_config(|temp| {
+        let true_bin = bin_path("/bin/true", "/usr/bin/true");
+        let project_dir = temp.path().join("shop");
+        std::fs::create_dir(&project_dir).unwrap();
+        let compose_path = project_dir.join("hyperv.yaml");
+        std::fs::write(
+            &compose_path,
+            format!("services:\n  api:\n    binary: {}\n", true_bin),
+        )
+        .unwrap();
+        let compose = ComposeFile::from_path(&compose_path).unwrap();
+
+        let mut manager = TaskManager::new().unwrap();
+        manager
+            .create_task(
+                "api".to_string(),
+                true_bin.to_string(),
+                Vec::new(),
+                Vec::new(),
+                None,
+                false,
+            )
+            .unwrap();
+        // Rewrite the file the way versions without projects stored compose tasks
+        let tasks_path = temp.path().join("tasks.json");
+        let mut tasks: Vec<serde_json::Value> =
+            serde_json::from_str(&std::fs::read_to_string(&tasks_path).unwrap()).unwrap();
+        tasks[0].as_object_mut().unwrap().insert(
+            "compose_file".to_string(),
+            compose_path
+                .canonicalize()
+                .unwrap()
+                .to_string_lossy()
+                .into(),
+        );
+        std::fs::write(&tasks_path, serde_json::to_string_pretty(&tasks).unwrap()).unwrap();
+        let id = read_tasks(temp)[0].id.clone();
+
+        let mut manager = TaskManager::new().unwrap();
+        let plan = manager.plan_compose(&compose).unwrap();
+        assert_eq!(plan.entries.len(), 1, "{:?}", plan.entries);
+        assert_eq!(plan.entries[0].name, "shop_api");
+        let PlanAction::Update(changes) = &plan.entries[0].action else {
+            panic!("expected an update, got {:?}", plan.entries[0].action);
+        };
+        assert_eq!(changes[0].field, "name");
+        assert_eq!(changes[0].old, "\"api\"");
+        assert_eq!(plan.orphans().count(), 0);
+
+        manager.up_from_compose(&compose).unwrap();
+        let tasks = read_tasks(temp);
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, id);
+        assert_eq!(tasks[0].name, "shop_api");
+        assert!(tasks[0].belongs_to_project("shop"));
+        assert_eq!(tasks[0].service.as_deref(), Some("api"));
+        assert!(!manager.plan_compose(&compose).unwrap().has_changes());
+    });
+}
+
+#[test]
+fn up_adopts_service_tasks_from_before_compose_files_were_recorded() {
+    with_temp_config(|temp| {
+        let true_bin = bin_path("/bin/true", "/usr/bin/true");
+        let sleep_bin = bin_path("/bin/sleep", "/usr/bin/sleep");
+        let project_dir = temp.path().join("shop");
+        std::fs::create_dir(&project_dir).unwrap();
+        let compose_path = project_dir.join("hyperv.yaml");
+        std::fs::write(
+            &compose_path,
+            format!(
+                "services:\n  api:\n    binary: {}\n  worker:\n    binary: {}\n",
+                true_bin, true_bin
+            ),
+        )
+        .unwrap();
+        let compose = ComposeFile::from_path(&compose_path).unwrap();
+
+        // The first `up` created service tasks with plain `create_task` calls
+        let mut manager = TaskManager::new().unwrap();
+        for (name, binary) in [("api", true_bin), ("worker", sleep_bin)] {
+            manager
+                .create_task(
+                    name.to_string(),
+                    binary.to_string(),
+                    Vec::new(),
+                    Vec::new(),
+                    None,
+                    false,
+                )
+                .unwrap();
+        }
+        let api_id = read_tasks(temp)[0].id.clone();
+
+        let mut manager = TaskManager::new().unwrap();
+        manager.up_from_compose(&compose).unwrap();
+        let tasks = read_tasks(temp);
+        assert_eq!(tasks.len(), 3, "{:?}", tasks);
+        let api = tasks.iter().find(|t| t.id == api_id).unwrap();
+        assert_eq!(api.name, "shop_api");
+        assert!(api.belongs_to_project("shop"));
+
+        // A task that runs something else only shares the service's name
+        let worker = tasks.iter().find(|t| t.name == "worker").unwrap();
+        assert!(worker.project.is_none());
+        assert!(tasks.iter().any(|t| t.name == "shop_worker"));
+    });
+}
@@ -0,0 +1,75 @@
+use hyperV::HyperVError;
+use hyperV::privileges::{RunAs, lookup_group, lookup_user};
+use std::os::unix::fs::PermissionsExt;
+use tempfile::TempDir;
+
+fn chmod(path: &std::path::Path, mode: u32) {
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+}
+
+#[test]
+fn looks_up_users_and_groups_by_name_or_id() {
+    let root = lookup_user("root").unwrap().unwrap();
+    assert_eq!((root.uid, root.gid), (0, 0));
+    assert_eq!(lookup_user("0").unwrap().unwrap().name, root.name);
+    assert_eq!(lookup_user("hyperv-no-such-user").unwrap(), None);
+
+    let root_group = lookup_group(&root.gid.to_string()).unwrap();
+    assert_eq!(root_group, Some(0));
+    assert_eq!(lookup_group("hyperv-no-such-group").unwrap(), None);
+    // Numeric IDs need no database entry
+    assert_eq!(lookup_group("424242").unwrap(), Some(424242));
+}
+
+#[test]
+fn resolves_the_identity_a_task_runs_as() {
+    assert_eq!(RunAs::resolve(None, None, &[]).unwrap(), None);
+
+    let run_as = RunAs::resolve(Some("root"), None, &["0".to_string()])
+        .unwrap()
+        .unwrap();
+    assert_eq!((run_as.uid, run_as.gid, run_as.groups), (0, 0, vec![0]));
+
+    let numeric = RunAs::resolve(Some("424242"), Some("424243"), &[])
+        .unwrap()
+        .unwrap();
+    assert_eq!((numeric.uid, numeric.gid), (424242, 424243));
+    assert_eq!(numeric.user_name(), "424242");
+    // Without an entry there is no primary group to fall back to
+    assert!(RunAs::resolve(Some("424242"), None, &[]).is_err());
+
+    assert!(matches!(
+        RunAs::resolve(Some("hyperv-no-such-user"), None, &[]),
+        Err(HyperVError::UserNotFound(user)) if user == "hyperv-no-such-user"
+    ));
+    assert!(matches!(
+        RunAs::resolve(Some("root"), None, &["hyperv-no-such-group".to_string()]),
+        Err(HyperVError::GroupNotFound(_))
+    ));
+}
+
+#[test]
+fn checks_file_access_for_the_target_user() {
+    let temp = TempDir::new().unwrap();
+    chmod(temp.path(), 0o755);
+    let binary = temp.path().join("app");
+    std::fs::write(&binary, "#!/bin/sh\n").unwrap();
+    let stranger = RunAs::resolve(Some("424242"), Some("424242"), &[])
+        .unwrap()
+        .unwrap();
+
+    chmod(&binary, 0o700);
+    assert!(!stranger.can_read(&binary));
+    assert!(!stranger.can_execute(&binary));
+    chmod(&binary, 0o744);
+    assert!(stranger.can_read(&binary));
+    assert!(!stranger.can_execute(&binary));
+    chmod(&binary, 0o755);
+    assert!(stranger.can_execute(&binary));
+
+    // A directory on the way that the user cannot search hides the file
+    chmod(temp.path(), 0o700);
+    assert!(!stranger.can_read(&binary));
+    assert!(!stranger.can_execute(temp.path()));
+    chmod(temp.path(), 0o755);
+}
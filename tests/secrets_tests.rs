@@ -0,0 +1,140 @@
+use hyperV::secrets::{SecretRef, SecretStore, resolve_env, validate_env};
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn mode(path: &Path) -> u32 {
+    std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+#[test]
+fn parses_secret_references() {
+    assert_eq!(
+        SecretRef::parse("secret:db_password").unwrap(),
+        Some(SecretRef::Store("db_password".to_string()))
+    );
+    assert_eq!(
+        SecretRef::parse("secret:file:/run/secrets/db").unwrap(),
+        Some(SecretRef::File("/run/secrets/db".into()))
+    );
+    assert_eq!(
+        SecretRef::parse("secret:env:DB_PASSWORD").unwrap(),
+        Some(SecretRef::Env("DB_PASSWORD".to_string()))
+    );
+    // Plain values, including SQLite-style `file:` URLs, are not references
+    assert_eq!(SecretRef::parse("file:dev.db").unwrap(), None);
+    assert_eq!(SecretRef::parse("hunter2").unwrap(), None);
+    assert_eq!(
+        SecretRef::parse("secret:file:/run/secrets/db")
+            .unwrap()
+            .unwrap()
+            .to_string(),
+        "secret:file:/run/secrets/db"
+    );
+
+    for invalid in ["secret:", "secret:two words", "secret:file:", "secret:env:"] {
+        let error = SecretRef::parse(invalid).unwrap_err().to_string();
+        assert!(error.contains("invalid secret reference"), "{}", error);
+    }
+    let env = HashMap::from([("DB".to_string(), "secret:a/b".to_string())]);
+    assert!(validate_env(&env).unwrap_err().to_string().contains("DB: "));
+}
+
+#[test]
+fn store_encrypts_values_with_a_private_key_file() {
+    let temp = TempDir::new().unwrap();
+    let store_path = temp.path().join("secrets.json");
+    let key_path = temp.path().join("secrets.key");
+    let store = SecretStore::new(&store_path, &key_path);
+
+    assert!(store.names().unwrap().is_empty());
+    store.set("db_password", "hunter2").unwrap();
+    store.set("api.token", "sk-live-123").unwrap();
+    assert_eq!(store.get("db_password").unwrap(), "hunter2");
+    assert_eq!(store.names().unwrap(), ["api.token", "db_password"]);
+
+    let content = std::fs::read_to_string(&store_path).unwrap();
+    assert!(!content.contains("hunter2"));
+    assert!(!content.contains("sk-live-123"));
+    assert_eq!(mode(&store_path), 0o600);
+    assert_eq!(mode(&key_path), 0o600);
+
+    // Values are bound to their names: swapping ciphertexts does not decrypt
+    let mut file: serde_json::Value = serde_json::from_str(&content).unwrap();
+    let secrets = file["secrets"].as_object_mut().unwrap();
+    let token = secrets["api.token"].clone();
+    secrets.insert("db_password".to_string(), token);
+    std::fs::write(&store_path, file.to_string()).unwrap();
+    assert!(
+        store
+            .get("db_password")
+            .unwrap_err()
+            .to_string()
+            .contains("cannot decrypt")
+    );
+
+    // Another key cannot read the store
+    let other = SecretStore::new(&store_path, temp.path().join("other.key"));
+    assert!(
+        other
+            .get("api.token")
+            .unwrap_err()
+            .to_string()
+            .contains("is missing")
+    );
+    other.set("x", "y").unwrap();
+    assert!(
+        other
+            .get("api.token")
+            .unwrap_err()
+            .to_string()
+            .contains("cannot decrypt")
+    );
+
+    assert!(store.remove("api.token").unwrap());
+    assert!(!store.remove("api.token").unwrap());
+    assert!(
+        store
+            .get("api.token")
+            .unwrap_err()
+            .to_string()
+            .contains("is not in the store")
+    );
+    assert!(store.set("bad name", "x").is_err());
+}
+
+#[test]
+fn resolves_references_from_the_store_files_and_host_environment() {
+    let temp = TempDir::new().unwrap();
+    let store = SecretStore::new(temp.path().join("secrets.json"), temp.path().join("key"));
+    store.set("db_password", "hunter2").unwrap();
+    let secret_file = temp.path().join("api_key");
+    std::fs::write(&secret_file, "abc123\n").unwrap();
+
+    let env = HashMap::from([
+        ("DB_PASSWORD".to_string(), "secret:db_password".to_string()),
+        (
+            "API_KEY".to_string(),
+            format!("secret:file:{}", secret_file.display()),
+        ),
+        ("HOST_PATH".to_string(), "secret:env:PATH".to_string()),
+        ("PORT".to_string(), "8000".to_string()),
+    ]);
+    let resolved = resolve_env(&env, &store).unwrap();
+    assert_eq!(resolved["DB_PASSWORD"], "hunter2");
+    assert_eq!(resolved["API_KEY"], "abc123");
+    assert_eq!(resolved["HOST_PATH"], std::env::var("PATH").unwrap());
+    assert_eq!(resolved["PORT"], "8000");
+
+    let missing = HashMap::from([(
+        "TOKEN".to_string(),
+        "secret:env:HYPERV_TEST_UNSET_VARIABLE".to_string(),
+    )]);
+    let error = resolve_env(&missing, &store).unwrap_err().to_string();
+    assert!(
+        error.contains("TOKEN (secret:env:HYPERV_TEST_UNSET_VARIABLE): host variable"),
+        "{}",
+        error
+    );
+}
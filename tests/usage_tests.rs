@@ -0,0 +1,89 @@
+use hyperV::usage::{TaskUsage, UsageSampler, format_uptime, process_tree_pids};
+use hyperV::{Task, TaskStatus};
+use std::collections::HashMap;
+use std::time::Duration;
+use sysinfo::System;
+
+#[test]
+fn formats_uptime_like_top() {
+    assert_eq!(format_uptime(Duration::from_secs(42)), "42s");
+    assert_eq!(format_uptime(Duration::from_secs(7 * 60 + 12)), "7m 12s");
+    assert_eq!(
+        format_uptime(Duration::from_secs(2 * 3600 + 5 * 60 + 9)),
+        "2h 5m"
+    );
+    assert_eq!(
+        format_uptime(Duration::from_secs(3 * 86_400 + 4 * 3600)),
+        "3d 4h"
+    );
+}
+
+#[test]
+fn usage_display_lists_every_figure() {
+    let usage = TaskUsage {
+        processes: 2,
+        memory_bytes: 1536 * 1024 * 1024,
+        cpu_percent: 12.5,
+        threads: 24,
+        open_fds: Some(57),
+        uptime: Duration::from_secs(3 * 3600 + 120),
+    };
+    assert_eq!(
+        usage.to_string(),
+        "2 processes, 24 threads, memory 1536.0 MB, CPU 12.5%, 57 open files, up 3h 2m"
+    );
+    assert_eq!(usage.memory_mb(), 1536);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn task_usage_covers_the_children_of_a_wrapper() {
+    // A wrapper script whose real work happens in a child, like tests/surreal.sh
+    let mut wrapper = std::process::Command::new("/bin/sh")
+        .args(["-c", "sleep 30 & wait"])
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+
+    let mut task = Task::new(
+        "usage".to_string(),
+        "usage".to_string(),
+        "/bin/sh".to_string(),
+        Vec::new(),
+        HashMap::new(),
+        None,
+        false,
+        None,
+        None,
+    );
+    task.set_pid(Some(wrapper.id()));
+    let mut sampler = UsageSampler::new();
+    sampler.refresh();
+    let stopped = sampler.measure(&task);
+    task.set_status(TaskStatus::Running);
+    let usage = sampler.measure(&task);
+
+    let mut system = System::new();
+    system.refresh_processes();
+    let pids = process_tree_pids(&system, wrapper.id());
+    let own = process_tree_pids(&system, std::process::id());
+    let _ = wrapper.kill();
+    let _ = wrapper.wait();
+
+    assert!(stopped.is_none());
+    let usage = usage.expect("the wrapper is running");
+    assert_eq!(usage.processes, 2);
+    assert_eq!(usage.threads, 2);
+    assert!(usage.memory_bytes > 0);
+    assert!(usage.open_fds.is_some_and(|fds| fds >= 3), "{usage:?}");
+
+    assert_eq!(pids[0], wrapper.id());
+    assert_eq!(pids.len(), 2, "{pids:?}");
+    // Threads of the test harness are not processes of their own
+    assert!(own.contains(&wrapper.id()));
+    assert!(own.iter().all(|pid| {
+        system
+            .process(sysinfo::Pid::from_u32(*pid))
+            .is_some_and(|p| p.thread_kind().is_none())
+    }));
+}
@@ -0,0 +1,169 @@
+use hyperV::watchdog::{BreachKind, UsageSample, Watchdog, WatchdogAction, WatchdogMonitor};
+use std::time::{Duration, Instant};
+
+fn sample(memory_mb: u64, cpu_percent: f64) -> UsageSample {
+    UsageSample {
+        memory_mb,
+        cpu_percent,
+    }
+}
+
+#[test]
+fn parses_watchdog_with_defaults() {
+    let watchdog: Watchdog = serde_yml::from_str("max_memory_mb: 512\naction: restart\n").unwrap();
+    assert_eq!(
+        watchdog,
+        Watchdog {
+            max_memory_mb: Some(512),
+            max_cpu_percent: None,
+            cpu_window_secs: 60,
+            action: WatchdogAction::Restart,
+        }
+    );
+    assert_eq!(watchdog.to_string(), "memory 512 MB (action: restart)");
+    assert!(watchdog.validate().is_ok());
+
+    let watchdog: Watchdog = serde_yml::from_str("max_cpu_percent: 90\n").unwrap();
+    assert_eq!(watchdog.action, WatchdogAction::Warn);
+    assert_eq!(watchdog.to_string(), "CPU 90% over 60s (action: warn)");
+
+    assert!(serde_yml::from_str::<Watchdog>("max_memory_mb: 1\naction: kill\n").is_err());
+    assert!("ALERT".parse::<WatchdogAction>().is_ok());
+}
+
+#[test]
+fn rejects_watchdogs_that_can_never_fire() {
+    let cases = [
+        (
+            Watchdog::default(),
+            "needs max_memory_mb or max_cpu_percent",
+        ),
+        (
+            Watchdog {
+                max_memory_mb: Some(0),
+                ..Watchdog::default()
+            },
+            "max_memory_mb must be greater than 0",
+        ),
+        (
+            Watchdog {
+                max_cpu_percent: Some(f64::NAN),
+                ..Watchdog::default()
+            },
+            "max_cpu_percent must be greater than 0",
+        ),
+        (
+            Watchdog {
+                max_cpu_percent: Some(50.0),
+                cpu_window_secs: 0,
+                ..Watchdog::default()
+            },
+            "cpu_window_secs must be greater than 0",
+        ),
+    ];
+    for (watchdog, expected) in cases {
+        let err = watchdog.validate().unwrap_err();
+        assert!(err.contains(expected), "{err}");
+    }
+}
+
+#[test]
+fn memory_breach_fires_once_until_usage_drops() {
+    let watchdog = Watchdog {
+        max_memory_mb: Some(100),
+        ..Watchdog::default()
+    };
+    let mut monitor = WatchdogMonitor::new();
+    let start = Instant::now();
+    let at = |secs| start + Duration::from_secs(secs);
+
+    assert!(
+        monitor
+            .record("task", 10, &watchdog, sample(50, 0.0), at(0))
+            .is_empty()
+    );
+    assert_eq!(
+        monitor.record("task", 10, &watchdog, sample(150, 0.0), at(5)),
+        vec![BreachKind::Memory {
+            used_mb: 150,
+            limit_mb: 100
+        }]
+    );
+    assert!(
+        monitor
+            .record("task", 10, &watchdog, sample(200, 0.0), at(10))
+            .is_empty()
+    );
+    assert!(
+        monitor
+            .record("task", 10, &watchdog, sample(80, 0.0), at(15))
+            .is_empty()
+    );
+    assert_eq!(
+        monitor.record("task", 10, &watchdog, sample(120, 0.0), at(20)),
+        vec![BreachKind::Memory {
+            used_mb: 120,
+            limit_mb: 100
+        }]
+    );
+
+    // A restarted task is a new run and can breach again right away
+    assert_eq!(
+        monitor
+            .record("task", 11, &watchdog, sample(120, 0.0), at(25))
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn cpu_breach_needs_the_average_over_a_whole_window() {
+    let watchdog = Watchdog {
+        max_cpu_percent: Some(80.0),
+        cpu_window_secs: 10,
+        ..Watchdog::default()
+    };
+    let mut monitor = WatchdogMonitor::new();
+    let start = Instant::now();
+    let at = |secs| start + Duration::from_secs(secs);
+
+    // The first sample of a run is only a baseline, and a short spike is not enough
+    for secs in [0, 5] {
+        assert!(
+            monitor
+                .record("task", 10, &watchdog, sample(0, 100.0), at(secs))
+                .is_empty()
+        );
+    }
+    assert_eq!(
+        monitor.record("task", 10, &watchdog, sample(0, 90.0), at(10)),
+        vec![BreachKind::Cpu {
+            percent: 95.0,
+            limit: 80.0,
+            window_secs: 10
+        }]
+    );
+    assert!(
+        monitor
+            .record("task", 10, &watchdog, sample(0, 100.0), at(15))
+            .is_empty()
+    );
+
+    // Samples older than the window no longer count
+    assert!(
+        monitor
+            .record("task", 10, &watchdog, sample(0, 10.0), at(20))
+            .is_empty()
+    );
+    assert!(
+        monitor
+            .record("task", 10, &watchdog, sample(0, 10.0), at(30))
+            .is_empty()
+    );
+    assert_eq!(
+        monitor
+            .record("task", 10, &watchdog, sample(0, 200.0), at(35))
+            .len(),
+        1
+    );
+}